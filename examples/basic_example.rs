@@ -1,6 +1,6 @@
 //! Basic example demonstrating Opus codec encoding and decoding
 
-use opus_codec::{Application, Bitrate, Channels, Decoder, Encoder, SampleRate};
+use opus_codec::{Application, Bitrate, Channels, Decoder, Encoder, MAX_PACKET_SIZE, SampleRate};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Opus Codec Basic Example");
@@ -39,7 +39,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("✓ Generated {} samples of test audio", input_pcm.len());
 
     // Encode the audio
-    let mut output = vec![0u8; 4000]; // Output buffer
+    let mut output = vec![0u8; MAX_PACKET_SIZE]; // Output buffer
     let encoded_size = encoder.encode(&input_pcm, &mut output)?;
     println!(
         "✓ Encoded {} bytes (compression ratio: {:.2})",
@@ -0,0 +1,212 @@
+//! Voice-activity heuristics: decode-side active-speaker indication and send-side
+//! silence checks
+
+use crate::decoder::Decoder;
+use crate::error::Result;
+use crate::frame::Sample;
+use crate::stats::FrameLevel;
+
+/// Packets at or below this size carry no coded audio (DTX / comfort noise).
+const DTX_PACKET_MAX_BYTES: usize = 2;
+
+/// Tuning knobs for [`VoiceActivity`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct VoiceActivityConfig {
+    /// Frame RMS level (dBFS) below which a frame is never considered speech.
+    pub energy_threshold_dbfs: f32,
+    /// Extra headroom (dB) above the threshold at which unpitched frames count as speech.
+    pub unvoiced_margin_db: f32,
+    /// Number of frames the flag stays raised after the last detected speech frame.
+    pub hangover_frames: u32,
+}
+
+impl Default for VoiceActivityConfig {
+    fn default() -> Self {
+        Self {
+            energy_threshold_dbfs: -45.0,
+            unvoiced_margin_db: 12.0,
+            hangover_frames: 8,
+        }
+    }
+}
+
+/// Rough per-frame speech/no-speech flag derived from decoder state.
+///
+/// Combines the decoder pitch estimate, the decoded frame energy and DTX
+/// observations (tiny packets or concealed frames). It is meant for
+/// active-speaker indication, not as a replacement for a real VAD.
+#[derive(Debug, Clone)]
+pub struct VoiceActivity {
+    config: VoiceActivityConfig,
+    hangover_left: u32,
+    active: bool,
+}
+
+impl VoiceActivity {
+    /// Create a detector with the given tuning.
+    #[must_use]
+    pub const fn new(config: VoiceActivityConfig) -> Self {
+        Self {
+            config,
+            hangover_left: 0,
+            active: false,
+        }
+    }
+
+    /// Update the flag after `decoder` decoded `packet` into `pcm` (16-bit).
+    ///
+    /// `packet` is the input passed to the decode call (empty for PLC).
+    ///
+    /// # Errors
+    /// Returns [`crate::Error::InvalidState`] if the decoder is invalid, or a mapped libopus error
+    /// when querying the pitch fails.
+    pub fn observe(&mut self, decoder: &mut Decoder, packet: &[u8], pcm: &[i16]) -> Result<bool> {
+        let level = rms_dbfs(pcm.iter().map(|&s| f32::from(s) / 32768.0), pcm.len());
+        self.update(decoder, packet, level)
+    }
+
+    /// Update the flag after `decoder` decoded `packet` into `pcm` (`f32`).
+    ///
+    /// # Errors
+    /// Returns [`crate::Error::InvalidState`] if the decoder is invalid, or a mapped libopus error
+    /// when querying the pitch fails.
+    pub fn observe_float(
+        &mut self,
+        decoder: &mut Decoder,
+        packet: &[u8],
+        pcm: &[f32],
+    ) -> Result<bool> {
+        let level = rms_dbfs(pcm.iter().copied(), pcm.len());
+        self.update(decoder, packet, level)
+    }
+
+    /// Current speech flag.
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Clear any pending hangover.
+    pub fn reset(&mut self) {
+        self.hangover_left = 0;
+        self.active = false;
+    }
+
+    fn update(&mut self, decoder: &mut Decoder, packet: &[u8], level_dbfs: f32) -> Result<bool> {
+        let coded = packet.len() > DTX_PACKET_MAX_BYTES;
+        let speech = if coded && level_dbfs > self.config.energy_threshold_dbfs {
+            let voiced = decoder.get_pitch()? > 0;
+            voiced
+                || level_dbfs > self.config.energy_threshold_dbfs + self.config.unvoiced_margin_db
+        } else {
+            false
+        };
+
+        if speech {
+            self.hangover_left = self.config.hangover_frames;
+            self.active = true;
+        } else if self.hangover_left > 0 {
+            self.hangover_left -= 1;
+            self.active = true;
+        } else {
+            self.active = false;
+        }
+        Ok(self.active)
+    }
+}
+
+impl Default for VoiceActivity {
+    fn default() -> Self {
+        Self::new(VoiceActivityConfig::default())
+    }
+}
+
+/// Whether interleaved `pcm` is silent: its RMS level is below `threshold_dbfs`.
+///
+/// A cheap send-side check for muting logic when DTX is off; with DTX on,
+/// [`crate::Encoder::last_frame_was_silence`] reports the encoder's own decision.
+/// An empty frame counts as silent.
+#[must_use]
+pub fn is_silence<S: Sample>(pcm: &[S], threshold_dbfs: f32) -> bool {
+    FrameLevel::measure(pcm).rms_dbfs() < threshold_dbfs
+}
+
+/// RMS level of `len` normalized samples in dBFS (`-inf` for silence).
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn rms_dbfs(samples: impl Iterator<Item = f32>, len: usize) -> f32 {
+    if len == 0 {
+        return f32::NEG_INFINITY;
+    }
+    let sum: f32 = samples.map(|s| s * s).sum();
+    let mean = sum / len as f32;
+    if mean <= 0.0 {
+        return f32::NEG_INFINITY;
+    }
+    10.0 * mean.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+    use crate::types::{Application, Channels, SampleRate};
+
+    #[test]
+    fn tone_is_active_and_silence_releases_after_hangover() {
+        let sr = SampleRate::Hz48000;
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+        encoder.set_dtx(true).unwrap();
+        let mut decoder = Decoder::new(sr, Channels::Mono).unwrap();
+        let mut vad = VoiceActivity::new(VoiceActivityConfig {
+            hangover_frames: 2,
+            ..VoiceActivityConfig::default()
+        });
+
+        let tone: Vec<i16> = (0..960)
+            .map(|i| {
+                let t = f64::from(i) / 48_000.0;
+                (8_000.0 * (2.0 * std::f64::consts::PI * 220.0 * t).sin()) as i16
+            })
+            .collect();
+        let silence = vec![0i16; 960];
+        let mut packet = [0u8; 1500];
+        let mut pcm = vec![0i16; 960];
+
+        let mut active = false;
+        for _ in 0..10 {
+            let n = encoder.encode(&tone, &mut packet).unwrap();
+            decoder.decode(&packet[..n], &mut pcm, false).unwrap();
+            active = vad.observe(&mut decoder, &packet[..n], &pcm).unwrap();
+        }
+        assert!(active);
+
+        for _ in 0..40 {
+            let n = encoder.encode(&silence, &mut packet).unwrap();
+            decoder.decode(&packet[..n], &mut pcm, false).unwrap();
+            active = vad.observe(&mut decoder, &packet[..n], &pcm).unwrap();
+        }
+        assert!(!active);
+    }
+
+    #[test]
+    fn silence_check_against_threshold() {
+        let tone =
+            crate::testsignal::sine::<i16>(SampleRate::Hz48000, Channels::Mono, 440.0, 0.1, 960);
+        assert!(!is_silence(&tone, -40.0));
+        assert!(is_silence(&tone, -10.0));
+        assert!(is_silence(&[0f32; 960], -90.0));
+        assert!(is_silence::<f32>(&[], -90.0));
+
+        let mut encoder =
+            Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+        encoder.set_dtx(true).unwrap();
+        let mut packet = [0u8; 1500];
+        encoder.encode(&tone, &mut packet).unwrap();
+        assert!(!encoder.last_frame_was_silence().unwrap());
+        for _ in 0..20 {
+            encoder.encode(&[0; 960], &mut packet).unwrap();
+        }
+        assert!(encoder.last_frame_was_silence().unwrap());
+    }
+}
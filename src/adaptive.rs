@@ -0,0 +1,404 @@
+//! Encoder complexity control driven by measured CPU load
+
+use crate::constants::MAX_PACKET_SIZE;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::testsignal::pink_noise;
+use crate::types::{Application, Bandwidth, Bitrate, Channels, Complexity, FrameSize, SampleRate};
+use std::time::{Duration, Instant};
+
+/// Calibration encode run by [`Complexity::auto_tune`]; match it to the stream the
+/// application will actually encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoTuneConfig {
+    /// Input sample rate.
+    pub sample_rate: SampleRate,
+    /// Input channels.
+    pub channels: Channels,
+    /// Encoder application.
+    pub application: Application,
+    /// Frame duration encoded per call.
+    pub frame_size: FrameSize,
+    /// Target bitrate.
+    pub bitrate: Bitrate,
+    /// Frames timed at each complexity.
+    pub frames: u32,
+}
+
+impl Default for AutoTuneConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Stereo,
+            application: Application::Audio,
+            frame_size: FrameSize::Ms20,
+            bitrate: Bitrate::Auto,
+            frames: 50,
+        }
+    }
+}
+
+impl Complexity {
+    /// Pick the highest complexity whose encode time fits `target_budget` per frame on
+    /// this machine.
+    ///
+    /// Encodes `config.frames` frames of pink noise at each complexity from 10 downwards
+    /// and stops at the first one whose 90th-percentile frame time is within the budget,
+    /// so a fast machine only pays for one pass. If even complexity 0 is too slow, 0 is
+    /// returned. Meant to run once at startup; the result is only as representative as
+    /// the machine's load during the calibration.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `target_budget` is zero or `config.frames` is 0, and any
+    /// error from creating or configuring the encoder.
+    pub fn auto_tune(target_budget: Duration, config: AutoTuneConfig) -> Result<Self> {
+        if target_budget.is_zero() || config.frames == 0 {
+            return Err(Error::BadArg);
+        }
+        let frame = config.frame_size.samples(config.sample_rate);
+        let ch = config.channels.as_usize();
+        // One extra frame warms up the encoder and is not timed.
+        let pcm: Vec<f32> = pink_noise(
+            config.channels,
+            0.5,
+            frame * (config.frames as usize + 1),
+            0x0A75_70BE,
+        );
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let mut times = Vec::with_capacity(config.frames as usize);
+        for value in (1..=10).rev() {
+            let complexity = Self::new(value);
+            let mut encoder =
+                Encoder::new(config.sample_rate, config.channels, config.application)?;
+            encoder.set_complexity(complexity)?;
+            encoder.set_bitrate(config.bitrate)?;
+            times.clear();
+            for (i, input) in pcm.chunks_exact(frame * ch).enumerate() {
+                let started = Instant::now();
+                encoder.encode_float(input, &mut packet)?;
+                if i > 0 {
+                    times.push(started.elapsed());
+                }
+            }
+            times.sort_unstable();
+            if times[times.len() * 9 / 10] <= target_budget {
+                return Ok(complexity);
+            }
+        }
+        Ok(Self::new(0))
+    }
+}
+
+/// Tuning for [`ComplexityController`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexityControllerConfig {
+    /// Lowest complexity the controller steps down to.
+    pub min_complexity: Complexity,
+    /// Highest complexity the controller steps up to.
+    pub max_complexity: Complexity,
+    /// Encode time, as a fraction of the frame duration, above which a frame counts as overloaded.
+    pub high_load: f32,
+    /// Encode time fraction below which a frame counts as having headroom; keep it
+    /// well under `high_load` for hysteresis.
+    pub low_load: f32,
+    /// Consecutive overloaded frames before stepping down.
+    pub frames_to_step_down: u32,
+    /// Consecutive frames with headroom before stepping back up.
+    pub frames_to_step_up: u32,
+    /// Once at `min_complexity`, also narrow the encoder's maximum bandwidth down to this
+    /// bandwidth. `None` leaves bandwidth alone.
+    pub min_bandwidth: Option<Bandwidth>,
+}
+
+impl Default for ComplexityControllerConfig {
+    fn default() -> Self {
+        Self {
+            min_complexity: Complexity::new(0),
+            max_complexity: Complexity::new(10),
+            high_load: 0.5,
+            low_load: 0.2,
+            frames_to_step_down: 5,
+            frames_to_step_up: 100,
+            min_bandwidth: None,
+        }
+    }
+}
+
+/// Setting changed by [`ComplexityController::observe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    /// Complexity was set to this value.
+    Complexity(Complexity),
+    /// Maximum bandwidth was set to this value.
+    MaxBandwidth(Bandwidth),
+}
+
+/// Steps encoder complexity (and optionally bandwidth) down when encoding takes too large a
+/// share of the frame duration, and back up once there is headroom again.
+#[derive(Debug, Clone)]
+pub struct ComplexityController {
+    config: ComplexityControllerConfig,
+    complexity: Complexity,
+    max_bandwidth: Bandwidth,
+    overloaded: u32,
+    idle: u32,
+}
+
+impl ComplexityController {
+    /// Create a controller for an encoder currently running at `complexity` with no bandwidth cap.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the complexity or load bounds are inverted, a load bound is
+    /// not positive, or a frame count is zero.
+    pub fn new(config: ComplexityControllerConfig, complexity: Complexity) -> Result<Self> {
+        if config.min_complexity.value() > config.max_complexity.value()
+            || !(config.low_load > 0.0 && config.low_load < config.high_load)
+            || config.frames_to_step_down == 0
+            || config.frames_to_step_up == 0
+        {
+            return Err(Error::BadArg);
+        }
+        let clamped = complexity
+            .value()
+            .clamp(config.min_complexity.value(), config.max_complexity.value());
+        Ok(Self {
+            config,
+            complexity: Complexity::new(clamped),
+            max_bandwidth: Bandwidth::Fullband,
+            overloaded: 0,
+            idle: 0,
+        })
+    }
+
+    /// Complexity the controller last applied (or started with).
+    #[must_use]
+    pub const fn complexity(&self) -> Complexity {
+        self.complexity
+    }
+
+    /// Maximum bandwidth the controller last applied; [`Bandwidth::Fullband`] when uncapped.
+    #[must_use]
+    pub const fn max_bandwidth(&self) -> Bandwidth {
+        self.max_bandwidth
+    }
+
+    /// Record that encoding a frame of `frame_duration` took `elapsed`, adjusting `encoder`
+    /// when the load has stayed outside the thresholds long enough.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `frame_duration` is zero, or any error from the encoder setters.
+    pub fn observe(
+        &mut self,
+        encoder: &mut Encoder,
+        elapsed: Duration,
+        frame_duration: Duration,
+    ) -> Result<Option<Adjustment>> {
+        if frame_duration.is_zero() {
+            return Err(Error::BadArg);
+        }
+        let load = elapsed.as_secs_f32() / frame_duration.as_secs_f32();
+        if load > self.config.high_load {
+            self.overloaded += 1;
+            self.idle = 0;
+        } else if load < self.config.low_load {
+            self.idle += 1;
+            self.overloaded = 0;
+        } else {
+            self.overloaded = 0;
+            self.idle = 0;
+        }
+
+        let adjustment = if self.overloaded >= self.config.frames_to_step_down {
+            self.step_down()
+        } else if self.idle >= self.config.frames_to_step_up {
+            self.step_up()
+        } else {
+            return Ok(None);
+        };
+        self.overloaded = 0;
+        self.idle = 0;
+        match adjustment {
+            Some(Adjustment::Complexity(complexity)) => encoder.set_complexity(complexity)?,
+            Some(Adjustment::MaxBandwidth(bandwidth)) => encoder.set_max_bandwidth(bandwidth)?,
+            None => {}
+        }
+        Ok(adjustment)
+    }
+
+    /// Encode `input` with [`Encoder::encode`], timing the call and feeding it to [`Self::observe`].
+    ///
+    /// # Errors
+    /// Propagates errors from [`Encoder::encode`] and [`Self::observe`].
+    pub fn encode(
+        &mut self,
+        encoder: &mut Encoder,
+        input: &[i16],
+        output: &mut [u8],
+    ) -> Result<usize> {
+        let started = Instant::now();
+        let len = encoder.encode(input, output)?;
+        self.observe_call(encoder, started.elapsed(), input.len())?;
+        Ok(len)
+    }
+
+    /// Encode `input` with [`Encoder::encode_float`], timing the call and feeding it to
+    /// [`Self::observe`].
+    ///
+    /// # Errors
+    /// Propagates errors from [`Encoder::encode_float`] and [`Self::observe`].
+    pub fn encode_float(
+        &mut self,
+        encoder: &mut Encoder,
+        input: &[f32],
+        output: &mut [u8],
+    ) -> Result<usize> {
+        let started = Instant::now();
+        let len = encoder.encode_float(input, output)?;
+        self.observe_call(encoder, started.elapsed(), input.len())?;
+        Ok(len)
+    }
+
+    fn observe_call(
+        &mut self,
+        encoder: &mut Encoder,
+        elapsed: Duration,
+        input_len: usize,
+    ) -> Result<()> {
+        let samples = input_len / encoder.channels().as_usize();
+        let frame_duration = encoder.sample_rate().duration_of(samples);
+        self.observe(encoder, elapsed, frame_duration)?;
+        Ok(())
+    }
+
+    fn step_down(&mut self) -> Option<Adjustment> {
+        if self.complexity.value() > self.config.min_complexity.value() {
+            self.complexity = Complexity::new(self.complexity.value() - 1);
+            return Some(Adjustment::Complexity(self.complexity));
+        }
+        let floor = self.config.min_bandwidth?;
+        if self.max_bandwidth == floor {
+            return None;
+        }
+        self.max_bandwidth = narrower(self.max_bandwidth);
+        Some(Adjustment::MaxBandwidth(self.max_bandwidth))
+    }
+
+    fn step_up(&mut self) -> Option<Adjustment> {
+        if self.max_bandwidth != Bandwidth::Fullband {
+            self.max_bandwidth = wider(self.max_bandwidth);
+            return Some(Adjustment::MaxBandwidth(self.max_bandwidth));
+        }
+        if self.complexity.value() < self.config.max_complexity.value() {
+            self.complexity = Complexity::new(self.complexity.value() + 1);
+            return Some(Adjustment::Complexity(self.complexity));
+        }
+        None
+    }
+}
+
+const fn narrower(bandwidth: Bandwidth) -> Bandwidth {
+    match bandwidth {
+        Bandwidth::Fullband => Bandwidth::SuperWideband,
+        Bandwidth::SuperWideband => Bandwidth::Wideband,
+        Bandwidth::Wideband => Bandwidth::Mediumband,
+        Bandwidth::Mediumband | Bandwidth::Narrowband => Bandwidth::Narrowband,
+    }
+}
+
+const fn wider(bandwidth: Bandwidth) -> Bandwidth {
+    match bandwidth {
+        Bandwidth::Narrowband => Bandwidth::Mediumband,
+        Bandwidth::Mediumband => Bandwidth::Wideband,
+        Bandwidth::Wideband => Bandwidth::SuperWideband,
+        Bandwidth::SuperWideband | Bandwidth::Fullband => Bandwidth::Fullband,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_down_under_load_and_recovers_with_hysteresis() {
+        let mut encoder =
+            Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+        let config = ComplexityControllerConfig {
+            min_complexity: Complexity::new(8),
+            min_bandwidth: Some(Bandwidth::SuperWideband),
+            ..ComplexityControllerConfig::default()
+        };
+        let mut controller = ComplexityController::new(config, Complexity::new(9)).unwrap();
+        let frame = Duration::from_millis(20);
+        let mut feed = |controller: &mut ComplexityController, millis: u64, frames: u32| {
+            let mut last = None;
+            for _ in 0..frames {
+                if let Some(adjustment) = controller
+                    .observe(&mut encoder, Duration::from_millis(millis), frame)
+                    .unwrap()
+                {
+                    last = Some(adjustment);
+                }
+            }
+            last
+        };
+
+        assert_eq!(feed(&mut controller, 15, 4), None);
+        assert_eq!(
+            feed(&mut controller, 15, 1),
+            Some(Adjustment::Complexity(Complexity::new(8)))
+        );
+        assert_eq!(
+            feed(&mut controller, 15, 5),
+            Some(Adjustment::MaxBandwidth(Bandwidth::SuperWideband))
+        );
+        assert_eq!(feed(&mut controller, 15, 20), None);
+
+        // Between the thresholds nothing changes.
+        assert_eq!(feed(&mut controller, 6, 500), None);
+        assert_eq!(
+            feed(&mut controller, 1, 100),
+            Some(Adjustment::MaxBandwidth(Bandwidth::Fullband))
+        );
+        assert_eq!(
+            feed(&mut controller, 1, 100),
+            Some(Adjustment::Complexity(Complexity::new(9)))
+        );
+        assert_eq!(encoder.complexity().unwrap(), Complexity::new(9));
+        assert_eq!(encoder.max_bandwidth().unwrap(), Bandwidth::Fullband);
+
+        let inverted = ComplexityControllerConfig {
+            low_load: 0.9,
+            ..ComplexityControllerConfig::default()
+        };
+        assert!(ComplexityController::new(inverted, Complexity::new(5)).is_err());
+    }
+
+    #[test]
+    fn auto_tune_picks_highest_complexity_within_budget() {
+        let config = AutoTuneConfig {
+            channels: Channels::Mono,
+            frames: 3,
+            ..AutoTuneConfig::default()
+        };
+        assert_eq!(
+            Complexity::auto_tune(Duration::from_secs(1), config),
+            Ok(Complexity::new(10))
+        );
+        assert_eq!(
+            Complexity::auto_tune(Duration::from_nanos(1), config),
+            Ok(Complexity::new(0))
+        );
+        assert_eq!(
+            Complexity::auto_tune(Duration::ZERO, config),
+            Err(Error::BadArg)
+        );
+        let empty = AutoTuneConfig {
+            frames: 0,
+            ..config
+        };
+        assert_eq!(
+            Complexity::auto_tune(Duration::from_secs(1), empty),
+            Err(Error::BadArg)
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! `arbitrary::Arbitrary` implementations for types with invariants
+//!
+//! Plain enums and config structs derive `Arbitrary` next to their definitions;
+//! the types below construct values that respect the checks the codec applies.
+
+use crate::projection::{AmbisonicsLayout, MAX_AMBISONICS_ORDER, SessionParams};
+use crate::types::{Bitrate, Complexity, PacketLossPerc};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+impl<'a> Arbitrary<'a> for Complexity {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::new(u.int_in_range(0..=10)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PacketLossPerc {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::new(u.int_in_range(0..=100)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Bitrate {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=2)? {
+            0 => Self::Auto,
+            1 => Self::Max,
+            _ => Self::Custom(u.int_in_range(500..=512_000)?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AmbisonicsLayout {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let order = u.int_in_range(1..=MAX_AMBISONICS_ORDER)?;
+        Self::new(order, u.arbitrary()?).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SessionParams {
+    /// Layout-consistent stream counts with an arbitrary (but correctly sized) matrix.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let layout = AmbisonicsLayout::arbitrary(u)?;
+        let channels = layout.channels();
+        let mut params = Self {
+            channels,
+            streams: channels.div_ceil(2),
+            coupled: channels / 2,
+            matrix: Vec::new(),
+        };
+        params.matrix = u.bytes(params.expected_matrix_len())?.to_vec();
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multistream::Mapping;
+    use crate::packet::Toc;
+    use crate::types::{Application, Channels, SampleRate};
+
+    #[test]
+    fn generated_values_respect_invariants() {
+        let data: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..32 {
+            let bitrate = Bitrate::arbitrary(&mut u).unwrap();
+            if let Bitrate::Custom(bps) = bitrate {
+                assert!((500..=512_000).contains(&bps));
+            }
+            assert!(Complexity::arbitrary(&mut u).unwrap().value() <= 10);
+            let _: (Application, Channels, SampleRate, Toc) = u.arbitrary().unwrap();
+        }
+        let params = SessionParams::arbitrary(&mut u).unwrap();
+        assert!(params.validate().is_ok());
+        let _mapping: Mapping<'_> = u.arbitrary().unwrap();
+    }
+}
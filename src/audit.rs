@@ -0,0 +1,144 @@
+//! Bounded log of CTL mutations applied to a codec
+
+use crate::bindings::{
+    OPUS_SET_APPLICATION_REQUEST, OPUS_SET_BANDWIDTH_REQUEST, OPUS_SET_BITRATE_REQUEST,
+    OPUS_SET_COMPLEXITY_REQUEST, OPUS_SET_DNN_BLOB_REQUEST, OPUS_SET_DRED_DURATION_REQUEST,
+    OPUS_SET_DTX_REQUEST, OPUS_SET_EXPERT_FRAME_DURATION_REQUEST, OPUS_SET_FORCE_CHANNELS_REQUEST,
+    OPUS_SET_GAIN_REQUEST, OPUS_SET_INBAND_FEC_REQUEST, OPUS_SET_LSB_DEPTH_REQUEST,
+    OPUS_SET_MAX_BANDWIDTH_REQUEST, OPUS_SET_PACKET_LOSS_PERC_REQUEST,
+    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, OPUS_SET_PREDICTION_DISABLED_REQUEST,
+    OPUS_SET_SIGNAL_REQUEST, OPUS_SET_VBR_CONSTRAINT_REQUEST, OPUS_SET_VBR_REQUEST,
+};
+use crate::constants::OPUS_SET_FORCE_MODE_REQUEST;
+use crate::error::Error;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// Number of most recent CTL mutations a [`CtlAuditLog`] keeps.
+pub const CTL_AUDIT_LEN: usize = 256;
+
+/// One CTL mutation attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlAuditEntry {
+    /// Wall-clock time of the call.
+    pub at: SystemTime,
+    /// libopus CTL request code (e.g. `OPUS_SET_BITRATE_REQUEST`).
+    pub request: i32,
+    /// Value passed with the request.
+    pub value: i32,
+    /// `None` if the CTL succeeded, otherwise the error it returned.
+    pub error: Option<Error>,
+}
+
+impl CtlAuditEntry {
+    /// libopus name of the request (e.g. `"OPUS_SET_BITRATE"`), or `"UNKNOWN"`.
+    #[must_use]
+    pub fn request_name(&self) -> &'static str {
+        request_name(self.request)
+    }
+
+    /// Whether the CTL succeeded.
+    #[must_use]
+    pub const fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Ring of the last [`CTL_AUDIT_LEN`] CTL mutations, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct CtlAuditLog {
+    entries: VecDeque<CtlAuditEntry>,
+    dropped: u64,
+}
+
+impl CtlAuditLog {
+    /// Empty log.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Record a CTL call and its outcome, evicting the oldest entry when full.
+    pub fn record(&mut self, request: i32, value: i32, result: &Result<(), Error>) {
+        if self.entries.len() == CTL_AUDIT_LEN {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(CtlAuditEntry {
+            at: SystemTime::now(),
+            request,
+            value,
+            error: result.as_ref().err().cloned(),
+        });
+    }
+
+    /// Retained entries, oldest first.
+    #[must_use]
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &CtlAuditEntry> {
+        self.entries.iter()
+    }
+
+    /// Most recent entry for the request named `name` (see [`request_name`]), e.g.
+    /// `log.last("OPUS_SET_BITRATE")` to find out when the bitrate was last changed.
+    #[must_use]
+    pub fn last(&self, name: &str) -> Option<&CtlAuditEntry> {
+        self.entries.iter().rev().find(|e| e.request_name() == name)
+    }
+
+    /// Number of retained entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing was recorded (or everything was cleared).
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries evicted to stay within [`CTL_AUDIT_LEN`].
+    #[must_use]
+    pub const fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Forget all entries.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// libopus name of a `SET` CTL request, or `"UNKNOWN"`.
+#[must_use]
+pub fn request_name(request: i32) -> &'static str {
+    let Ok(request) = u32::try_from(request) else {
+        return "UNKNOWN";
+    };
+    match request {
+        OPUS_SET_APPLICATION_REQUEST => "OPUS_SET_APPLICATION",
+        OPUS_SET_BANDWIDTH_REQUEST => "OPUS_SET_BANDWIDTH",
+        OPUS_SET_BITRATE_REQUEST => "OPUS_SET_BITRATE",
+        OPUS_SET_COMPLEXITY_REQUEST => "OPUS_SET_COMPLEXITY",
+        OPUS_SET_DNN_BLOB_REQUEST => "OPUS_SET_DNN_BLOB",
+        OPUS_SET_DRED_DURATION_REQUEST => "OPUS_SET_DRED_DURATION",
+        OPUS_SET_DTX_REQUEST => "OPUS_SET_DTX",
+        OPUS_SET_EXPERT_FRAME_DURATION_REQUEST => "OPUS_SET_EXPERT_FRAME_DURATION",
+        OPUS_SET_FORCE_CHANNELS_REQUEST => "OPUS_SET_FORCE_CHANNELS",
+        OPUS_SET_FORCE_MODE_REQUEST => "OPUS_SET_FORCE_MODE",
+        OPUS_SET_GAIN_REQUEST => "OPUS_SET_GAIN",
+        OPUS_SET_INBAND_FEC_REQUEST => "OPUS_SET_INBAND_FEC",
+        OPUS_SET_LSB_DEPTH_REQUEST => "OPUS_SET_LSB_DEPTH",
+        OPUS_SET_MAX_BANDWIDTH_REQUEST => "OPUS_SET_MAX_BANDWIDTH",
+        OPUS_SET_PACKET_LOSS_PERC_REQUEST => "OPUS_SET_PACKET_LOSS_PERC",
+        OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST => "OPUS_SET_PHASE_INVERSION_DISABLED",
+        OPUS_SET_PREDICTION_DISABLED_REQUEST => "OPUS_SET_PREDICTION_DISABLED",
+        OPUS_SET_SIGNAL_REQUEST => "OPUS_SET_SIGNAL",
+        OPUS_SET_VBR_CONSTRAINT_REQUEST => "OPUS_SET_VBR_CONSTRAINT",
+        OPUS_SET_VBR_REQUEST => "OPUS_SET_VBR",
+        _ => "UNKNOWN",
+    }
+}
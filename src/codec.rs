@@ -0,0 +1,333 @@
+//! Codec-agnostic encoder and decoder traits
+//!
+//! [`AudioEncoder`] and [`AudioDecoder`] are object safe, so applications supporting
+//! several codecs can hold a `Box<dyn AudioEncoder>` and swap Opus in or out behind one
+//! interface. They are implemented by [`Encoder`]/[`Decoder`], the multistream
+//! [`MSEncoder`]/[`MSDecoder`] and the ambisonics [`ProjectionEncoder`]/[`ProjectionDecoder`],
+//! so generic code can target all three families. Where a trait method shares its name with an inherent
+//! one, the inherent method wins on the concrete type; call the trait version through
+//! `dyn` or fully qualified syntax.
+
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::multistream::{MSDecoder, MSEncoder};
+use crate::projection::{ProjectionDecoder, ProjectionEncoder};
+use crate::types::Bitrate;
+use std::time::Duration;
+
+/// Encodes one frame of interleaved PCM per call.
+pub trait AudioEncoder: Send {
+    /// Input sample rate in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// Interleaved input channels.
+    fn channels(&self) -> usize;
+
+    /// Encode one frame of interleaved i16 PCM into `out`, returning the packet length.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnevenChannels`] or another argument error if `pcm` is not a valid
+    /// frame, or any codec error.
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize>;
+
+    /// Encode one frame of interleaved f32 PCM into `out`, returning the packet length.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnevenChannels`] or another argument error if `pcm` is not a valid
+    /// frame, or any codec error.
+    fn encode_float(&mut self, pcm: &[f32], out: &mut [u8]) -> Result<usize>;
+
+    /// Set the target bitrate.
+    ///
+    /// # Errors
+    /// Returns any codec error, e.g. for an unsupported bitrate.
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<()>;
+
+    /// Algorithmic delay the encoder adds to the signal path.
+    ///
+    /// # Errors
+    /// Returns any codec error from querying the delay.
+    fn latency(&mut self) -> Result<Duration>;
+
+    /// Reset to the freshly created state, dropping any history.
+    ///
+    /// # Errors
+    /// Returns any codec error from resetting.
+    fn reset(&mut self) -> Result<()>;
+}
+
+/// Decodes one packet of audio per call into interleaved PCM.
+pub trait AudioDecoder: Send {
+    /// Output sample rate in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// Interleaved output channels.
+    fn channels(&self) -> usize;
+
+    /// Decode `packet` into `out`, returning samples per channel written. An empty
+    /// `packet` signals a lost packet and produces concealment sized by `out`.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnevenChannels`] if `out` is not a whole number of sample frames,
+    /// [`Error::InvalidPacket`] for a corrupt packet, or any codec error.
+    fn decode(&mut self, packet: &[u8], out: &mut [i16]) -> Result<usize>;
+
+    /// Float counterpart of [`Self::decode`].
+    ///
+    /// # Errors
+    /// Same as [`Self::decode`].
+    fn decode_float(&mut self, packet: &[u8], out: &mut [f32]) -> Result<usize>;
+
+    /// Reset to the freshly created state, dropping any history.
+    ///
+    /// # Errors
+    /// Returns any codec error from resetting.
+    fn reset(&mut self) -> Result<()>;
+}
+
+impl AudioEncoder for Encoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        self.channels().as_usize()
+    }
+
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize> {
+        self.encode(pcm, out)
+    }
+
+    fn encode_float(&mut self, pcm: &[f32], out: &mut [u8]) -> Result<usize> {
+        self.encode_float(pcm, out)
+    }
+
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<()> {
+        self.set_bitrate(bitrate)
+    }
+
+    fn latency(&mut self) -> Result<Duration> {
+        self.latency()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+impl AudioDecoder for Decoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        self.channels().as_usize()
+    }
+
+    fn decode(&mut self, packet: &[u8], out: &mut [i16]) -> Result<usize> {
+        self.decode(packet, out, false)
+    }
+
+    fn decode_float(&mut self, packet: &[u8], out: &mut [f32]) -> Result<usize> {
+        self.decode_float(packet, out, false)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+impl AudioEncoder for MSEncoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        usize::from(self.channels())
+    }
+
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize> {
+        let frame = frame_len(pcm.len(), usize::from(self.channels()))?;
+        self.encode(pcm, frame, out)
+    }
+
+    fn encode_float(&mut self, pcm: &[f32], out: &mut [u8]) -> Result<usize> {
+        let frame = frame_len(pcm.len(), usize::from(self.channels()))?;
+        self.encode_float(pcm, frame, out)
+    }
+
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<()> {
+        self.set_bitrate(bitrate)
+    }
+
+    fn latency(&mut self) -> Result<Duration> {
+        self.latency()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+impl AudioDecoder for MSDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        usize::from(self.channels())
+    }
+
+    fn decode(&mut self, packet: &[u8], out: &mut [i16]) -> Result<usize> {
+        let frame = frame_len(out.len(), usize::from(self.channels()))?;
+        self.decode(packet, out, frame, false)
+    }
+
+    fn decode_float(&mut self, packet: &[u8], out: &mut [f32]) -> Result<usize> {
+        let frame = frame_len(out.len(), usize::from(self.channels()))?;
+        self.decode_float(packet, out, frame, false)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+impl AudioEncoder for ProjectionEncoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        usize::from(self.channels())
+    }
+
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize> {
+        let frame = frame_len(pcm.len(), usize::from(self.channels()))?;
+        self.encode(pcm, frame, out)
+    }
+
+    fn encode_float(&mut self, pcm: &[f32], out: &mut [u8]) -> Result<usize> {
+        let frame = frame_len(pcm.len(), usize::from(self.channels()))?;
+        self.encode_float(pcm, frame, out)
+    }
+
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<()> {
+        self.set_bitrate(bitrate)
+    }
+
+    fn latency(&mut self) -> Result<Duration> {
+        self.latency()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+impl AudioDecoder for ProjectionDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate() as u32
+    }
+
+    fn channels(&self) -> usize {
+        usize::from(self.channels())
+    }
+
+    fn decode(&mut self, packet: &[u8], out: &mut [i16]) -> Result<usize> {
+        let frame = frame_len(out.len(), usize::from(self.channels()))?;
+        self.decode(packet, out, frame, false)
+    }
+
+    fn decode_float(&mut self, packet: &[u8], out: &mut [f32]) -> Result<usize> {
+        let frame = frame_len(out.len(), usize::from(self.channels()))?;
+        self.decode_float(packet, out, frame, false)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reset()
+    }
+}
+
+/// Samples per channel in an interleaved buffer of `len` samples.
+fn frame_len(len: usize, channels: usize) -> Result<usize> {
+    if channels == 0 {
+        return Err(Error::BadArg);
+    }
+    if !len.is_multiple_of(channels) {
+        return Err(Error::UnevenChannels { len, channels });
+    }
+    Ok(len / channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_PACKET_SIZE;
+    use crate::multistream::Mapping;
+    use crate::projection::AmbisonicsLayout;
+    use crate::types::{Application, Channels, SampleRate};
+
+    fn round_trip(encoder: &mut dyn AudioEncoder, decoder: &mut dyn AudioDecoder) {
+        let ch = encoder.channels();
+        assert_eq!(decoder.channels(), ch);
+        assert_eq!(encoder.sample_rate(), 48_000);
+        encoder.set_bitrate(Bitrate::Custom(64_000)).unwrap();
+        assert!(encoder.latency().unwrap() > Duration::ZERO);
+
+        let pcm: Vec<f32> = (0..960 * ch)
+            .map(|i| if i % 40 < 20 { 0.3 } else { -0.3 })
+            .collect();
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let len = encoder.encode_float(&pcm, &mut packet).unwrap();
+        let mut out = vec![0f32; 960 * ch];
+        assert_eq!(decoder.decode_float(&packet[..len], &mut out).unwrap(), 960);
+
+        let mut lost = vec![0i16; 960 * ch];
+        assert_eq!(decoder.decode(&[], &mut lost).unwrap(), 960);
+        assert_eq!(
+            encoder.encode(&vec![0; 960 * ch + 1], &mut packet),
+            Err(Error::UnevenChannels {
+                len: 960 * ch + 1,
+                channels: ch,
+            })
+        );
+        encoder.reset().unwrap();
+        decoder.reset().unwrap();
+    }
+
+    #[test]
+    fn boxed_codecs_share_one_interface() {
+        let mut codecs: Vec<(Box<dyn AudioEncoder>, Box<dyn AudioDecoder>)> = vec![(
+            Box::new(
+                Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap(),
+            ),
+            Box::new(Decoder::new(SampleRate::Hz48000, Channels::Stereo).unwrap()),
+        )];
+        let mapping = Mapping {
+            channels: 3,
+            streams: 2,
+            coupled_streams: 1,
+            mapping: &[0, 1, 2],
+        };
+        codecs.push((
+            Box::new(MSEncoder::new(SampleRate::Hz48000, Application::Audio, mapping).unwrap()),
+            Box::new(MSDecoder::new(SampleRate::Hz48000, mapping).unwrap()),
+        ));
+        let mut projection = ProjectionEncoder::new_ambisonics(
+            SampleRate::Hz48000,
+            AmbisonicsLayout::new(1, false).unwrap(),
+            Application::Audio,
+        )
+        .unwrap();
+        let params = projection.session_params().unwrap();
+        codecs.push((
+            Box::new(projection),
+            Box::new(ProjectionDecoder::from_params(SampleRate::Hz48000, &params).unwrap()),
+        ));
+        for (encoder, decoder) in &mut codecs {
+            round_trip(encoder.as_mut(), decoder.as_mut());
+        }
+    }
+}
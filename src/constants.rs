@@ -1,6 +1,7 @@
 //! Crate-wide constants and small helpers
 
 use crate::types::SampleRate;
+use std::time::Duration;
 
 /// Maximum samples per channel in a single Opus frame at 48 kHz.
 ///
@@ -10,6 +11,48 @@ pub const MAX_FRAME_SAMPLES_48KHZ: usize = 5760;
 /// Maximum packet duration in milliseconds.
 pub const MAX_PACKET_DURATION_MS: usize = 120;
 
+/// `OPUS_SET_FORCE_MODE` from libopus' private `opus_private.h`, which bindgen does not
+/// see.
+pub(crate) const OPUS_SET_FORCE_MODE_REQUEST: u32 = 11002;
+
+/// Largest compressed size of a single Opus frame in bytes (RFC 6716 section 3.2.1).
+pub const MAX_FRAME_BYTES: usize = 1275;
+
+/// Output buffer size that holds any single-stream packet of up to 60 ms, the longest
+/// [`FrameSize`](crate::FrameSize): three full frames plus the packet framing.
+///
+/// Use it instead of a guessed `vec![0u8; 4000]`; for longer or multistream packets see
+/// [`max_packet_size`] and [`max_multistream_packet_size`].
+pub const MAX_PACKET_SIZE: usize = max_packet_size(Duration::from_millis(60));
+
+/// Worst-case size in bytes of a single-stream packet carrying `frame` of audio.
+///
+/// The encoder codes one frame per 20 ms (rounded up) and each is at most
+/// [`MAX_FRAME_BYTES`]; framing adds a TOC byte plus at most two bytes of frame count
+/// and length per frame. Durations over 120 ms are treated as 120 ms.
+#[must_use]
+pub const fn max_packet_size(frame: Duration) -> usize {
+    let frames = frame.as_micros().div_ceil(20_000) as usize;
+    let frames = if frames == 0 {
+        1
+    } else if frames > 6 {
+        6
+    } else {
+        frames
+    };
+    frames * (MAX_FRAME_BYTES + 2) + 1
+}
+
+/// Worst-case size in bytes of a multistream packet with `streams` streams carrying
+/// `frame` of audio.
+///
+/// Every stream but the last uses self-delimiting framing, which adds up to two bytes.
+#[must_use]
+pub const fn max_multistream_packet_size(streams: u8, frame: Duration) -> usize {
+    let streams = streams as usize;
+    streams * max_packet_size(frame) + 2 * streams.saturating_sub(1)
+}
+
 /// Compute the maximum samples per channel for a frame at the given `sample_rate`.
 #[must_use]
 pub const fn max_frame_samples_for(sample_rate: SampleRate) -> usize {
@@ -17,3 +60,28 @@ pub const fn max_frame_samples_for(sample_rate: SampleRate) -> usize {
     // sample_rate.as_i32() is always positive given valid SampleRate enum values
     (MAX_FRAME_SAMPLES_48KHZ * (sample_rate as usize)) / 48_000
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_size_bounds() {
+        assert_eq!(MAX_PACKET_SIZE, 3 * 1275 + 7);
+        assert_eq!(max_packet_size(Duration::from_micros(2_500)), 1278);
+        assert_eq!(max_packet_size(Duration::from_millis(20)), 1278);
+        assert_eq!(max_packet_size(Duration::from_millis(40)), 2555);
+        assert_eq!(
+            max_packet_size(Duration::from_millis(120)),
+            max_packet_size(Duration::from_secs(1))
+        );
+        assert_eq!(
+            max_multistream_packet_size(1, Duration::from_millis(20)),
+            1278
+        );
+        assert_eq!(
+            max_multistream_packet_size(3, Duration::from_millis(20)),
+            3 * 1278 + 4
+        );
+    }
+}
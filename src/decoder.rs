@@ -1,27 +1,178 @@
 //! Opus decoder implementation with safe wrappers
 
-#[cfg(feature = "dred")]
+#[cfg(feature = "ctl-audit")]
+use crate::audit::CtlAuditLog;
 use crate::bindings::{
-    OPUS_GET_DRED_DURATION_REQUEST, OPUS_SET_DNN_BLOB_REQUEST, OPUS_SET_DRED_DURATION_REQUEST,
+    OPUS_GET_BANDWIDTH_REQUEST, OPUS_GET_COMPLEXITY_REQUEST, OPUS_GET_FINAL_RANGE_REQUEST,
+    OPUS_GET_GAIN_REQUEST, OPUS_GET_LAST_PACKET_DURATION_REQUEST,
+    OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST, OPUS_GET_PITCH_REQUEST,
+    OPUS_GET_SAMPLE_RATE_REQUEST, OPUS_RESET_STATE, OPUS_SET_COMPLEXITY_REQUEST,
+    OPUS_SET_GAIN_REQUEST, OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, OpusDecoder, opus_decode,
+    opus_decode_float, opus_decoder_create, opus_decoder_ctl, opus_decoder_destroy,
+    opus_decoder_get_nb_samples, opus_decoder_get_size, opus_decoder_init,
 };
+#[cfg(feature = "dred")]
 use crate::bindings::{
-    OPUS_GET_FINAL_RANGE_REQUEST, OPUS_GET_GAIN_REQUEST, OPUS_GET_LAST_PACKET_DURATION_REQUEST,
-    OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST, OPUS_GET_PITCH_REQUEST,
-    OPUS_GET_SAMPLE_RATE_REQUEST, OPUS_RESET_STATE, OPUS_SET_GAIN_REQUEST,
-    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, OpusDecoder, opus_decode, opus_decode_float,
-    opus_decoder_create, opus_decoder_ctl, opus_decoder_destroy, opus_decoder_get_nb_samples,
+    OPUS_GET_DRED_DURATION_REQUEST, OPUS_SET_DNN_BLOB_REQUEST, OPUS_SET_DRED_DURATION_REQUEST,
 };
 use crate::constants::max_frame_samples_for;
+#[cfg(feature = "dred")]
+use crate::dred::DnnBlob;
 use crate::error::{Error, Result};
-use crate::packet;
-use crate::types::{Bandwidth, Channels, SampleRate};
+use crate::frame::PcmFrame;
+use crate::packet::{self, Toc};
+use crate::stats::{ConcealmentStats, FrameLevel, RangeChain};
+#[cfg(feature = "timing")]
+use crate::stats::{TimingReservoir, TimingStats};
+use crate::types::{Bandwidth, Channels, CodecMode, Complexity, Q8Gain, SampleRate};
 use std::ptr;
+use std::time::Duration;
+#[cfg(feature = "timing")]
+use std::time::Instant;
+
+/// What the last successful decode produced, from [`Decoder::last_packet_info`] or
+/// [`MSDecoder::last_packet_info`](crate::MSDecoder::last_packet_info).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastPacketInfo {
+    /// Samples per channel written.
+    pub samples: usize,
+    /// Playback duration of those samples.
+    pub duration: Duration,
+    /// Coded bandwidth of the packet (the widest of its streams), or `None` when no
+    /// packet was given.
+    pub bandwidth: Option<Bandwidth>,
+    /// Channels coded in each elementary stream, in stream order; empty when no packet
+    /// was given.
+    pub stream_channels: Vec<Channels>,
+    /// Whether the output was packet-loss concealment or FEC recovery rather than a
+    /// normal decode of the packet.
+    pub concealed: bool,
+}
+
+impl LastPacketInfo {
+    /// Widest channel layout coded in the packet, which may differ from the decoder's
+    /// output layout, or `None` when no packet was given.
+    #[must_use]
+    pub fn coded_channels(&self) -> Option<Channels> {
+        self.stream_channels
+            .iter()
+            .copied()
+            .max_by_key(|ch| ch.as_usize())
+    }
+}
+
+/// How [`Decoder::recover_with_fec`] filled in a lost packet.
+///
+/// The output is `samples` long: the first `samples - fec_samples` are concealment and
+/// the trailing `fec_samples` were rebuilt from the next packet's in-band FEC (LBRR)
+/// data, which covers exactly one of its frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecRecovery {
+    /// Samples per channel written, the lost duration.
+    pub samples: usize,
+    /// Trailing samples per channel recovered from FEC; `0` when everything was
+    /// concealed.
+    pub fec_samples: usize,
+}
+
+/// Post-processing applied by [`Decoder::decode_float_processed`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeProcessing {
+    /// Linear gain applied to every sample.
+    pub gain: f32,
+    /// Soft-clip the scaled output to `[-1, 1]`. A decoder with
+    /// [`Decoder::set_soft_clip`] enabled clips either way.
+    pub soft_clip: bool,
+}
+
+impl Default for DecodeProcessing {
+    /// Unity gain, no clipping.
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            soft_clip: false,
+        }
+    }
+}
+
+/// Raw record behind [`LastPacketInfo`], refilled in place so decoding does not allocate.
+#[derive(Debug, Default)]
+pub(crate) struct LastDecode {
+    samples: usize,
+    concealed: bool,
+    tocs: Vec<Toc>,
+}
+
+impl LastDecode {
+    /// Record a successful decode of `input`, whose stream TOCs `read_tocs` fills in.
+    pub(crate) fn record(
+        slot: &mut Option<Self>,
+        input: &[u8],
+        fec: bool,
+        samples: usize,
+        read_tocs: impl FnOnce(&mut Vec<Toc>) -> Result<()>,
+    ) -> Result<()> {
+        let last = slot.get_or_insert_with(Self::default);
+        last.samples = samples;
+        last.concealed = input.is_empty() || fec;
+        last.tocs.clear();
+        if !input.is_empty() {
+            read_tocs(&mut last.tocs)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn info(&self, sample_rate: SampleRate) -> LastPacketInfo {
+        LastPacketInfo {
+            samples: self.samples,
+            duration: sample_rate.duration_of(self.samples),
+            bandwidth: self
+                .tocs
+                .iter()
+                .map(|toc| toc.bandwidth())
+                .reduce(|widest, bw| if bw.is_wider_than(widest) { bw } else { widest }),
+            stream_channels: self.tocs.iter().map(|toc| toc.channels()).collect(),
+            concealed: self.concealed,
+        }
+    }
+}
 
 /// Safe wrapper around a libopus `OpusDecoder`.
+///
+/// A decoder accepts mono and stereo packets alike, whatever its own channel count:
+/// libopus upmixes mono packets and downmixes stereo ones into the output layout, and keeps
+/// its history when a sender switches layouts, as a stereo encoder does at low bitrates.
+/// [`LastPacketInfo::coded_channels`] reports the layout of the last packet.
 pub struct Decoder {
     raw: *mut OpusDecoder,
+    /// Whether `raw` was allocated by libopus; state initialized in caller memory by
+    /// [`Self::init_in`] is not freed on drop.
+    owned: bool,
+    /// Bytes available for the libopus state, bounding what [`Self::reinit`] can reuse.
+    capacity: usize,
     sample_rate: SampleRate,
     channels: Channels,
+    /// Whether [`Self::decode_float`] soft-clips its output.
+    soft_clip: bool,
+    /// Per-channel soft-clip state, shared by every float decode that clips.
+    clip_mem: [f32; 2],
+    range_chain: Option<RangeChain>,
+    concealment: ConcealmentStats,
+    last_decode: Option<LastDecode>,
+    /// Mode of the last packet decoded; libopus cannot apply FEC after a CELT-only one.
+    last_mode: Option<CodecMode>,
+    level: Option<FrameLevel>,
+    /// Contiguous decode buffers for the strided decode methods.
+    i16_scratch: Vec<i16>,
+    f32_scratch: Vec<f32>,
+    #[cfg(feature = "timing")]
+    timing: TimingReservoir,
+    #[cfg(feature = "ctl-audit")]
+    ctl_audit: CtlAuditLog,
+    /// Blobs libopus may point into: the last one loaded by [`Self::set_dnn_blob`], plus
+    /// any earlier ones while a failed load leaves a model partly replaced.
+    #[cfg(feature = "dred")]
+    dnn_blobs: Vec<DnnBlob>,
 }
 
 unsafe impl Send for Decoder {}
@@ -37,6 +188,7 @@ impl Decoder {
         if !sample_rate.is_valid() {
             return Err(Error::BadArg);
         }
+        let capacity = Self::size(channels)?;
 
         let mut error = 0i32;
         let decoder = unsafe {
@@ -55,11 +207,83 @@ impl Decoder {
             return Err(Error::AllocFail);
         }
 
-        Ok(Self {
-            raw: decoder,
+        Ok(Self::wrap(decoder, true, capacity, sample_rate, channels))
+    }
+
+    /// Size in bytes of the libopus decoder state for `channels`, as needed by
+    /// [`Self::init_in`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InternalError`] if libopus reports an invalid size.
+    pub fn size(channels: Channels) -> Result<usize> {
+        let raw = unsafe { opus_decoder_get_size(channels.as_i32()) };
+        usize::try_from(raw)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or(Error::InternalError)
+    }
+
+    /// Initialize a decoder whose libopus state lives in caller-provided memory, e.g. an
+    /// arena, instead of being allocated by libopus.
+    ///
+    /// `buf` must hold at least [`Self::size`] bytes and be aligned to 8 bytes. Dropping
+    /// the decoder leaves `buf` alone; only the wrapper's own buffers are freed.
+    ///
+    /// # Safety
+    /// `buf` must outlive the returned decoder and must not be read, written or moved
+    /// while the decoder is alive.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for an invalid sample rate or a buffer that is too small or
+    /// misaligned, or a mapped libopus error if initialization fails.
+    pub unsafe fn init_in(
+        buf: &mut [u8],
+        sample_rate: SampleRate,
+        channels: Channels,
+    ) -> Result<Self> {
+        if !sample_rate.is_valid()
+            || buf.len() < Self::size(channels)?
+            || buf.as_ptr().align_offset(8) != 0
+        {
+            return Err(Error::BadArg);
+        }
+        let raw = buf.as_mut_ptr().cast::<OpusDecoder>();
+        let r = unsafe { opus_decoder_init(raw, sample_rate.as_i32(), channels.as_i32()) };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Ok(Self::wrap(raw, false, buf.len(), sample_rate, channels))
+    }
+
+    fn wrap(
+        raw: *mut OpusDecoder,
+        owned: bool,
+        capacity: usize,
+        sample_rate: SampleRate,
+        channels: Channels,
+    ) -> Self {
+        Self {
+            raw,
+            owned,
+            capacity,
             sample_rate,
             channels,
-        })
+            soft_clip: false,
+            clip_mem: [0.0; 2],
+            range_chain: None,
+            concealment: ConcealmentStats::default(),
+            last_decode: None,
+            last_mode: None,
+            level: None,
+            i16_scratch: Vec::new(),
+            f32_scratch: Vec::new(),
+            #[cfg(feature = "timing")]
+            timing: TimingReservoir::new(),
+            #[cfg(feature = "ctl-audit")]
+            ctl_audit: CtlAuditLog::new(),
+            #[cfg(feature = "dred")]
+            dnn_blobs: Vec::new(),
+        }
     }
 
     /// Decode a packet into 16-bit PCM.
@@ -69,9 +293,10 @@ impl Decoder {
     /// - `fec`: Enable in-band FEC if available.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle is invalid, [`Error::BadArg`]
-    /// for invalid buffer sizes or frame sizes, or a mapped libopus error via
-    /// [`Error::from_code`].
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for an output that is not a
+    /// valid frame, [`Error::BadArg`] for an oversized packet, or a mapped libopus error
+    /// via [`Error::from_code`].
     pub fn decode(&mut self, input: &[u8], output: &mut [i16], fec: bool) -> Result<usize> {
         // Errors: InvalidState, BadArg, or libopus error mapped.
         if self.raw.is_null() {
@@ -82,17 +307,7 @@ impl Decoder {
         if !input.is_empty() && input.len() > i32::MAX as usize {
             return Err(Error::BadArg);
         }
-        if output.is_empty() {
-            return Err(Error::BadArg);
-        }
-        if !output.len().is_multiple_of(self.channels.as_usize()) {
-            return Err(Error::BadArg);
-        }
-        let frame_size = output.len() / self.channels.as_usize();
-        let max_frame = max_frame_samples_for(self.sample_rate);
-        if frame_size == 0 || frame_size > max_frame {
-            return Err(Error::BadArg);
-        }
+        let frame_size = self.output_frames(output.len())?;
 
         let input_len_i32 = if input.is_empty() {
             0
@@ -101,6 +316,8 @@ impl Decoder {
         };
         let frame_size_i32 = i32::try_from(frame_size).map_err(|_| Error::BadArg)?;
 
+        #[cfg(feature = "timing")]
+        let started = Instant::now();
         let result = unsafe {
             opus_decode(
                 self.raw,
@@ -115,22 +332,30 @@ impl Decoder {
                 i32::from(fec),
             )
         };
+        #[cfg(feature = "timing")]
+        self.timing.record(started.elapsed());
 
         if result < 0 {
             return Err(Error::from_code(result));
         }
-
-        usize::try_from(result).map_err(|_| Error::InternalError)
+        let decoded = usize::try_from(result).map_err(|_| Error::InternalError)?;
+        self.record_decode(input, fec, decoded)?;
+        if let Some(level) = self.level.as_mut() {
+            *level = FrameLevel::measure(&output[..decoded * self.channels.as_usize()]);
+        }
+        Ok(decoded)
     }
 
     /// Decode a packet into `f32` PCM.
     ///
-    /// See [`Self::decode`] for parameter semantics.
+    /// See [`Self::decode`] for parameter semantics. When [`Self::set_soft_clip`] is
+    /// enabled, the output is soft-clipped to `[-1, 1]`.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle is invalid, [`Error::BadArg`]
-    /// for invalid buffer sizes or frame sizes, or a mapped libopus error via
-    /// [`Error::from_code`].
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for an output that is not a
+    /// valid frame, [`Error::BadArg`] for an oversized packet, or a mapped libopus error
+    /// via [`Error::from_code`].
     pub fn decode_float(&mut self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
@@ -140,17 +365,7 @@ impl Decoder {
         if !input.is_empty() && input.len() > i32::MAX as usize {
             return Err(Error::BadArg);
         }
-        if output.is_empty() {
-            return Err(Error::BadArg);
-        }
-        if !output.len().is_multiple_of(self.channels.as_usize()) {
-            return Err(Error::BadArg);
-        }
-        let frame_size = output.len() / self.channels.as_usize();
-        let max_frame = max_frame_samples_for(self.sample_rate);
-        if frame_size == 0 || frame_size > max_frame {
-            return Err(Error::BadArg);
-        }
+        let frame_size = self.output_frames(output.len())?;
 
         let input_len_i32 = if input.is_empty() {
             0
@@ -159,6 +374,8 @@ impl Decoder {
         };
         let frame_size_i32 = i32::try_from(frame_size).map_err(|_| Error::BadArg)?;
 
+        #[cfg(feature = "timing")]
+        let started = Instant::now();
         let result = unsafe {
             opus_decode_float(
                 self.raw,
@@ -173,12 +390,324 @@ impl Decoder {
                 i32::from(fec),
             )
         };
+        #[cfg(feature = "timing")]
+        self.timing.record(started.elapsed());
 
         if result < 0 {
             return Err(Error::from_code(result));
         }
+        let decoded = usize::try_from(result).map_err(|_| Error::InternalError)?;
+        self.record_decode(input, fec, decoded)?;
+        if self.soft_clip && decoded > 0 {
+            packet::soft_clip(output, decoded, self.channels.as_i32(), &mut self.clip_mem)?;
+        }
+        if let Some(level) = self.level.as_mut() {
+            *level = FrameLevel::measure(&output[..decoded * self.channels.as_usize()]);
+        }
+        Ok(decoded)
+    }
 
-        usize::try_from(result).map_err(|_| Error::InternalError)
+    /// Decode a packet into `f32` PCM as [`Self::decode_float`] does, then scale it and
+    /// optionally soft-clip it in one pass.
+    ///
+    /// The gain comes first, so a boost cannot push clipped output back over ±1.0. The
+    /// output is clipped when `processing` asks for it or [`Self::set_soft_clip`] is
+    /// enabled, using the same clip state as [`Self::decode_float`], so the two calls can
+    /// be mixed on one stream. An empty `packet` conceals a lost packet; use
+    /// [`Self::decode_float`] for FEC.
+    ///
+    /// # Errors
+    /// Same as [`Self::decode_float`].
+    pub fn decode_float_processed(
+        &mut self,
+        packet: &[u8],
+        output: &mut [f32],
+        processing: &DecodeProcessing,
+    ) -> Result<usize> {
+        // Clip after the gain rather than inside `decode_float`.
+        let auto_clip = std::mem::replace(&mut self.soft_clip, false);
+        let result = self.decode_float(packet, output, false);
+        self.soft_clip = auto_clip;
+        let decoded = result?;
+        for sample in &mut output[..decoded * self.channels.as_usize()] {
+            *sample *= processing.gain;
+        }
+        if (processing.soft_clip || self.soft_clip) && decoded > 0 {
+            packet::soft_clip(output, decoded, self.channels.as_i32(), &mut self.clip_mem)?;
+        }
+        Ok(decoded)
+    }
+
+    /// Decode a packet into a fixed-size interleaved `i16` array without heap allocation.
+    ///
+    /// `N` is the total interleaved sample count (`frame_size * channels`). The packet
+    /// (or PLC output for an empty `input`) must decode to exactly `N / channels` samples.
+    ///
+    /// # Errors
+    /// Returns [`Error::FrameLengthMismatch`] if the packet duration differs from `N`, plus
+    /// any error from [`Self::decode`], which also rejects an `N` that is not a valid frame.
+    pub fn decode_frame<const N: usize>(&mut self, input: &[u8], fec: bool) -> Result<[i16; N]> {
+        const { assert!(N > 0, "frame array must not be empty") };
+        let mut out = [0i16; N];
+        let decoded = self.decode(input, &mut out, fec)?;
+        if decoded * self.channels.as_usize() != N {
+            return Err(Error::FrameLengthMismatch {
+                expected: decoded * self.channels.as_usize(),
+                actual: N,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Decode a packet into a fixed-size interleaved `f32` array without heap allocation.
+    ///
+    /// See [`Self::decode_frame`] for the sizing rules.
+    ///
+    /// # Errors
+    /// Returns [`Error::FrameLengthMismatch`] if the packet duration differs from `N`, plus
+    /// any error from [`Self::decode_float`].
+    pub fn decode_frame_float<const N: usize>(
+        &mut self,
+        input: &[u8],
+        fec: bool,
+    ) -> Result<[f32; N]> {
+        const { assert!(N > 0, "frame array must not be empty") };
+        let mut out = [0f32; N];
+        let decoded = self.decode_float(input, &mut out, fec)?;
+        if decoded * self.channels.as_usize() != N {
+            return Err(Error::FrameLengthMismatch {
+                expected: decoded * self.channels.as_usize(),
+                actual: N,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Decode a packet into a newly allocated buffer of exactly the decoded length.
+    ///
+    /// The buffer is sized from the packet's duration at this decoder's rate, so callers
+    /// need not compute a frame size; an empty `input` conceals a frame as long as the
+    /// last decoded packet.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] when PLC is requested before any packet was decoded,
+    /// otherwise the same errors as [`Self::decode`].
+    pub fn decode_vec(&mut self, input: &[u8], fec: bool) -> Result<Vec<i16>> {
+        let mut samples = vec![0i16; self.frame_len_for(input)?];
+        let decoded = self.decode(input, &mut samples, fec)?;
+        samples.truncate(decoded * self.channels.as_usize());
+        Ok(samples)
+    }
+
+    /// Float counterpart of [`Self::decode_vec`].
+    ///
+    /// # Errors
+    /// See [`Self::decode_vec`].
+    pub fn decode_float_vec(&mut self, input: &[u8], fec: bool) -> Result<Vec<f32>> {
+        let mut samples = vec![0f32; self.frame_len_for(input)?];
+        let decoded = self.decode_float(input, &mut samples, fec)?;
+        samples.truncate(decoded * self.channels.as_usize());
+        Ok(samples)
+    }
+
+    /// Conceal one lost packet into the start of `out`, as long as the last decoded
+    /// packet (`OPUS_GET_LAST_PACKET_DURATION`), and return the samples per channel
+    /// written.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] before any packet was decoded or if `out` is shorter than
+    /// that duration, otherwise the same errors as [`Self::decode`].
+    pub fn conceal(&mut self, out: &mut [i16]) -> Result<usize> {
+        let len = self.frame_len_for(&[])?;
+        let out = out.get_mut(..len).ok_or(Error::BadArg)?;
+        self.decode(&[], out, false)
+    }
+
+    /// Conceal `ms` milliseconds of lost audio, e.g. a gap measured from timestamps.
+    ///
+    /// libopus conceals in 2.5 ms steps, so `ms` must be a multiple of 5; gaps longer
+    /// than the 120 ms a single decode call allows are concealed in several calls.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `ms` is zero or not a multiple of 5, otherwise the same
+    /// errors as [`Self::decode`].
+    pub fn conceal_ms(&mut self, ms: u32) -> Result<Vec<i16>> {
+        if ms == 0 || !ms.is_multiple_of(5) {
+            return Err(Error::BadArg);
+        }
+        let ch = self.channels.as_usize();
+        let per_channel = self.sample_rate as usize * ms as usize / 1000;
+        let mut samples = vec![0i16; per_channel * ch];
+        let max_chunk = max_frame_samples_for(self.sample_rate) * ch;
+        for chunk in samples.chunks_mut(max_chunk) {
+            self.decode(&[], chunk, false)?;
+        }
+        Ok(samples)
+    }
+
+    /// Fill in a lost packet of `lost_duration_samples` (per channel) from the in-band
+    /// FEC of `next_packet`, the packet that arrived after it, writing to the start of
+    /// `out`. Decode `next_packet` normally afterwards.
+    ///
+    /// FEC only covers the last frame of the lost span, and only when `next_packet`
+    /// carries LBRR data and the packet before the loss was not CELT-only; the rest, or
+    /// everything otherwise, is concealed. The returned [`FecRecovery`] says which
+    /// samples came from where.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `next_packet` is empty, the lost duration is zero or
+    /// not a multiple of 2.5 ms, or `out` is shorter than it; [`Error::InvalidPacket`] for
+    /// a malformed `next_packet`; otherwise the same errors as [`Self::decode`].
+    pub fn recover_with_fec(
+        &mut self,
+        next_packet: &[u8],
+        lost_duration_samples: usize,
+        out: &mut [i16],
+    ) -> Result<FecRecovery> {
+        let step = self.sample_rate as usize / 400;
+        if next_packet.is_empty()
+            || lost_duration_samples == 0
+            || !lost_duration_samples.is_multiple_of(step)
+        {
+            return Err(Error::BadArg);
+        }
+        let out = out
+            .get_mut(..lost_duration_samples * self.channels.as_usize())
+            .ok_or(Error::BadArg)?;
+        let frame = packet::packet_samples_per_frame(next_packet, self.sample_rate)?;
+        let usable = packet::packet_has_lbrr(next_packet)?
+            && frame <= lost_duration_samples
+            && self.last_mode != Some(CodecMode::CeltOnly);
+        if !usable {
+            let samples = self.decode(&[], out, false)?;
+            return Ok(FecRecovery {
+                samples,
+                fec_samples: 0,
+            });
+        }
+        let samples = self.decode(next_packet, out, true)?;
+        Ok(FecRecovery {
+            samples,
+            fec_samples: frame,
+        })
+    }
+
+    /// Decode a packet into a [`PcmFrame`] tagged with this decoder's rate and layout.
+    ///
+    /// The frame is sized from the packet; an empty `input` conceals a frame as long as
+    /// the last decoded packet.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] when PLC is requested before any packet was decoded,
+    /// otherwise the same errors as [`Self::decode`].
+    pub fn decode_pcm_frame(&mut self, input: &[u8], fec: bool) -> Result<PcmFrame<i16>> {
+        let samples = self.decode_vec(input, fec)?;
+        PcmFrame::new(samples, self.sample_rate, self.channels)
+    }
+
+    /// Decode a packet into a [`PcmFrame`] of `f32` samples.
+    ///
+    /// # Errors
+    /// See [`Self::decode_pcm_frame`].
+    pub fn decode_pcm_frame_float(&mut self, input: &[u8], fec: bool) -> Result<PcmFrame<f32>> {
+        let samples = self.decode_float_vec(input, fec)?;
+        PcmFrame::new(samples, self.sample_rate, self.channels)
+    }
+
+    /// Decode a packet into channels `offset..offset + channels` of an interleaved buffer
+    /// holding `stride` samples per frame, e.g. two channels of an 8-channel device
+    /// buffer. The other channels of `output` are left untouched.
+    ///
+    /// `output` holds `output.len() / stride` frames (a trailing partial frame is
+    /// ignored), which plays the role of the frame size in [`Self::decode`]. Decoding goes
+    /// through a scratch buffer owned by the decoder, so callers need no intermediate
+    /// buffer of their own.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the channels do not fit within `stride` or `output`
+    /// holds no complete frame, plus any error from [`Self::decode`].
+    pub fn decode_strided(
+        &mut self,
+        input: &[u8],
+        output: &mut [i16],
+        stride: usize,
+        offset: usize,
+        fec: bool,
+    ) -> Result<usize> {
+        let ch = self.channels.as_usize();
+        let frames = self.strided_frames(output.len(), stride, offset)?;
+        let mut scratch = std::mem::take(&mut self.i16_scratch);
+        scratch.resize(frames * ch, 0);
+        let result = self.decode(input, &mut scratch, fec);
+        if let Ok(decoded) = result {
+            scatter(&scratch[..decoded * ch], output, ch, stride, offset);
+        }
+        self.i16_scratch = scratch;
+        result
+    }
+
+    /// Float counterpart of [`Self::decode_strided`].
+    ///
+    /// # Errors
+    /// Same as [`Self::decode_strided`], with errors from [`Self::decode_float`].
+    pub fn decode_float_strided(
+        &mut self,
+        input: &[u8],
+        output: &mut [f32],
+        stride: usize,
+        offset: usize,
+        fec: bool,
+    ) -> Result<usize> {
+        let ch = self.channels.as_usize();
+        let frames = self.strided_frames(output.len(), stride, offset)?;
+        let mut scratch = std::mem::take(&mut self.f32_scratch);
+        scratch.resize(frames * ch, 0.0);
+        let result = self.decode_float(input, &mut scratch, fec);
+        if let Ok(decoded) = result {
+            scatter(&scratch[..decoded * ch], output, ch, stride, offset);
+        }
+        self.f32_scratch = scratch;
+        result
+    }
+
+    /// Samples per channel an interleaved output of `len` samples holds, checked against
+    /// the Opus frame limits.
+    fn output_frames(&self, len: usize) -> Result<usize> {
+        let channels = self.channels.as_usize();
+        if !len.is_multiple_of(channels) {
+            return Err(Error::UnevenChannels { len, channels });
+        }
+        let samples = len / channels;
+        if samples == 0 || samples > max_frame_samples_for(self.sample_rate) {
+            return Err(Error::InvalidFrameSize { samples });
+        }
+        Ok(samples)
+    }
+
+    /// Frames a strided buffer of `len` samples can take, capped at the longest frame.
+    fn strided_frames(&self, len: usize, stride: usize, offset: usize) -> Result<usize> {
+        let ch = self.channels.as_usize();
+        if offset.checked_add(ch).is_none_or(|end| end > stride) {
+            return Err(Error::BadArg);
+        }
+        let frames = len / stride;
+        if frames == 0 {
+            return Err(Error::BadArg);
+        }
+        Ok(frames.min(max_frame_samples_for(self.sample_rate)))
+    }
+
+    /// Interleaved output length needed to decode `input` (or conceal, when empty).
+    pub(crate) fn frame_len_for(&mut self, input: &[u8]) -> Result<usize> {
+        let per_channel = if input.is_empty() {
+            usize::try_from(self.get_last_packet_duration()?).map_err(|_| Error::InternalError)?
+        } else {
+            self.packet_samples(input)?
+        };
+        if per_channel == 0 {
+            return Err(Error::BadArg);
+        }
+        Ok(per_channel * self.channels.as_usize())
     }
 
     /// Return the number of samples (per channel) in an Opus `packet` at this decoder's rate.
@@ -205,6 +734,15 @@ impl Decoder {
         usize::try_from(result).map_err(|_| Error::InternalError)
     }
 
+    /// Interleaved `i16`/`f32` buffer length (samples per channel × channels) that
+    /// [`Self::decode`] needs for `packet` at this decoder's rate.
+    ///
+    /// # Errors
+    /// Same as [`Self::packet_samples`].
+    pub fn required_output_len(&self, packet: &[u8]) -> Result<usize> {
+        Ok(self.packet_samples(packet)? * self.channels.as_usize())
+    }
+
     /// Return the bandwidth encoded in an Opus `packet`.
     ///
     /// # Errors
@@ -250,7 +788,61 @@ impl Decoder {
         if result != 0 {
             return Err(Error::from_code(result));
         }
+        self.clip_mem = [0.0; 2];
+        self.concealment = ConcealmentStats::default();
+        self.last_decode = None;
+        self.last_mode = None;
+        if let Some(level) = self.level.as_mut() {
+            *level = FrameLevel::default();
+        }
+        #[cfg(feature = "timing")]
+        self.timing.clear();
+
+        Ok(())
+    }
 
+    /// Reinitialize for a new stream as if freshly created, reusing the libopus state
+    /// allocation and the wrapper's scratch buffers.
+    ///
+    /// Every CTL and wrapper option returns to its default. A mono decoder switched to
+    /// stereo needs a larger state: one allocated by libopus is then replaced, while one
+    /// in caller memory from [`Self::init_in`] must have been given room for it.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for an invalid sample rate or a caller-provided state too
+    /// small for `channels`, or any error from creating the decoder; after an error from
+    /// libopus the decoder is unusable and reports [`Error::InvalidState`].
+    pub fn reinit(&mut self, sample_rate: SampleRate, channels: Channels) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        if !sample_rate.is_valid() {
+            return Err(Error::BadArg);
+        }
+        if Self::size(channels)? > self.capacity {
+            if !self.owned {
+                return Err(Error::BadArg);
+            }
+            *self = Self::new(sample_rate, channels)?;
+            return Ok(());
+        }
+        let raw = std::mem::replace(&mut self.raw, ptr::null_mut());
+        let r = unsafe { opus_decoder_init(raw, sample_rate.as_i32(), channels.as_i32()) };
+        if r != 0 {
+            if self.owned {
+                unsafe { opus_decoder_destroy(raw) };
+            }
+            return Err(Error::from_code(r));
+        }
+        let scratch = (
+            std::mem::take(&mut self.i16_scratch),
+            std::mem::take(&mut self.f32_scratch),
+        );
+        // `wrap` starts without DNN blobs: init points the models back at the built-in
+        // weights or marks them unloaded, so libopus no longer reads the old ones.
+        let owned = std::mem::replace(&mut self.owned, false);
+        *self = Self::wrap(raw, owned, self.capacity, sample_rate, channels);
+        (self.i16_scratch, self.f32_scratch) = scratch;
         Ok(())
     }
 
@@ -266,6 +858,111 @@ impl Decoder {
         self.channels
     }
 
+    /// Run `opus_pcm_soft_clip` over every [`Self::decode_float`] output, keeping its
+    /// state across calls, so samples slightly over ±1.0 are smoothly limited instead of
+    /// clipping hard downstream. Enabling starts from fresh state.
+    pub fn set_soft_clip(&mut self, enabled: bool) {
+        self.soft_clip = enabled;
+        if enabled {
+            self.clip_mem = [0.0; 2];
+        }
+    }
+
+    /// Whether float output is soft-clipped.
+    #[must_use]
+    pub const fn soft_clip(&self) -> bool {
+        self.soft_clip
+    }
+
+    /// Start (from an empty chain) or stop chaining the `final_range` of every decoded
+    /// packet, for comparison with the sender's [`Encoder::range_chain`](crate::Encoder::range_chain).
+    ///
+    /// Only packets decoded normally are chained; concealment and FEC recovery are
+    /// skipped, so any loss leaves the chains unequal.
+    pub fn set_range_chain(&mut self, enabled: bool) {
+        self.range_chain = enabled.then(RangeChain::new);
+    }
+
+    /// Chain over the packets decoded since it was enabled. Not cleared by [`Self::reset`].
+    #[must_use]
+    pub const fn range_chain(&self) -> Option<RangeChain> {
+        self.range_chain
+    }
+
+    /// How many decode calls decoded, concealed or FEC-recovered a packet since creation or
+    /// the last [`Self::reset`].
+    #[must_use]
+    pub const fn concealment_stats(&self) -> ConcealmentStats {
+        self.concealment
+    }
+
+    /// Measure the peak and RMS level of every decoded frame (after soft clipping), so
+    /// meters can read them from [`Self::frame_level`] instead of rescanning the PCM.
+    pub fn set_metering(&mut self, enabled: bool) {
+        self.level = enabled.then(FrameLevel::default);
+    }
+
+    /// Level of the last decoded frame while metering is enabled; silent until the first
+    /// decode and after [`Self::reset`].
+    #[must_use]
+    pub const fn frame_level(&self) -> Option<FrameLevel> {
+        self.level
+    }
+
+    /// Duration, bandwidth, channels and concealment of the last successful decode, or
+    /// `None` before the first one and after [`Self::reset`].
+    #[must_use]
+    pub fn last_packet_info(&self) -> Option<LastPacketInfo> {
+        self.last_decode
+            .as_ref()
+            .map(|last| last.info(self.sample_rate))
+    }
+
+    /// Update the concealment counters, last-packet info and range chain after a
+    /// successful decode of `samples` per channel.
+    fn record_decode(&mut self, input: &[u8], fec: bool, samples: usize) -> Result<()> {
+        self.concealment.record(input, fec);
+        if !input.is_empty() {
+            self.last_mode = Some(Toc::of(input)?.mode());
+        }
+        LastDecode::record(&mut self.last_decode, input, fec, samples, |tocs| {
+            tocs.push(Toc::of(input)?);
+            Ok(())
+        })?;
+        if self.range_chain.is_none() || input.is_empty() || fec {
+            return Ok(());
+        }
+        let range = self.final_range()?;
+        if let Some(chain) = self.range_chain.as_mut() {
+            chain.push(range);
+        }
+        Ok(())
+    }
+
+    /// CTL mutations applied to this decoder, with their outcome.
+    #[cfg(feature = "ctl-audit")]
+    #[must_use]
+    pub const fn ctl_audit(&self) -> &CtlAuditLog {
+        &self.ctl_audit
+    }
+
+    /// Wall time spent in libopus decode calls, or `None` before the first call.
+    #[cfg(feature = "timing")]
+    #[must_use]
+    pub fn timing_stats(&self) -> Option<TimingStats> {
+        self.timing.stats()
+    }
+
+    /// Algorithmic delay added by decoding.
+    ///
+    /// libopus decoders add none of their own: the decoder-side delay is already
+    /// included in the encoder's [`Encoder::latency`](crate::Encoder::latency).
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub const fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+
     #[cfg_attr(not(feature = "dred"), allow(dead_code))]
     pub(crate) fn as_mut_ptr(&mut self) -> *mut OpusDecoder {
         self.raw
@@ -295,6 +992,27 @@ impl Decoder {
         self.get_int_ctl(OPUS_GET_LAST_PACKET_DURATION_REQUEST as i32)
     }
 
+    /// Bandwidth of the most recently decoded packet, read from the decoder state, or
+    /// `None` before the first packet (concealment keeps the previous value).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the decoder is invalid, a mapped libopus error,
+    /// or [`Error::InternalError`] for a value libopus does not define.
+    pub fn last_bandwidth(&mut self) -> Result<Option<Bandwidth>> {
+        let v = self.get_int_ctl(OPUS_GET_BANDWIDTH_REQUEST as i32)?;
+        if v == 0 || v == crate::bindings::OPUS_AUTO {
+            return Ok(None);
+        }
+        match u32::try_from(v).map_err(|_| Error::InternalError)? {
+            crate::bindings::OPUS_BANDWIDTH_NARROWBAND => Ok(Some(Bandwidth::Narrowband)),
+            crate::bindings::OPUS_BANDWIDTH_MEDIUMBAND => Ok(Some(Bandwidth::Mediumband)),
+            crate::bindings::OPUS_BANDWIDTH_WIDEBAND => Ok(Some(Bandwidth::Wideband)),
+            crate::bindings::OPUS_BANDWIDTH_SUPERWIDEBAND => Ok(Some(Bandwidth::SuperWideband)),
+            crate::bindings::OPUS_BANDWIDTH_FULLBAND => Ok(Some(Bandwidth::Fullband)),
+            _ => Err(Error::InternalError),
+        }
+    }
+
     /// Final RNG state after the last decode.
     ///
     /// # Errors
@@ -326,6 +1044,48 @@ impl Decoder {
         self.get_int_ctl(OPUS_GET_GAIN_REQUEST as i32)
     }
 
+    /// Set post-decode gain in decibels, rounded to the Q8 steps libopus uses.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for a gain outside [`Q8Gain`]'s range, otherwise the
+    /// errors of [`Self::set_gain`].
+    pub fn set_gain_db(&mut self, db: f32) -> Result<()> {
+        self.set_gain(i32::from(Q8Gain::from_db(db)?.value()))
+    }
+    /// Query post-decode gain in decibels.
+    ///
+    /// # Errors
+    /// Same as [`Self::gain`].
+    pub fn gain_db(&mut self) -> Result<f32> {
+        let q8 = i16::try_from(self.gain()?).map_err(|_| Error::InternalError)?;
+        Ok(Q8Gain::new(q8).db())
+    }
+
+    /// Set decoder complexity [0..=10] (libopus 1.5+). With a libopus built with deep
+    /// PLC or OSCE, 5 and above enable neural concealment and 6 and above speech
+    /// enhancement, at a higher CPU cost; otherwise the setting has no audible effect.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the decoder is invalid, or a mapped libopus error;
+    /// a system libopus older than 1.5 returns [`Error::Unimplemented`].
+    pub fn set_complexity(&mut self, complexity: Complexity) -> Result<()> {
+        self.simple_ctl(
+            OPUS_SET_COMPLEXITY_REQUEST as i32,
+            complexity.value() as i32,
+        )
+    }
+    /// Query decoder complexity.
+    ///
+    /// # Errors
+    /// Same as [`Self::set_complexity`], or [`Error::InternalError`] for a value out of range.
+    pub fn complexity(&mut self) -> Result<Complexity> {
+        let v = self.get_int_ctl(OPUS_GET_COMPLEXITY_REQUEST as i32)?;
+        match u32::try_from(v) {
+            Ok(v) if v <= 10 => Ok(Complexity::new(v)),
+            _ => Err(Error::InternalError),
+        }
+    }
+
     /// Returns true if phase inversion is disabled (CELT stereo decorrelation).
     ///
     /// # Errors
@@ -362,30 +1122,45 @@ impl Decoder {
         self.get_int_ctl(OPUS_GET_DRED_DURATION_REQUEST as i32)
     }
     #[cfg(feature = "dred")]
-    /// Set DNN blob for DRED (feature-gated; will error if unsupported).
+    /// Load the decoder's neural models (PLC, OSCE) from a DNN blob.
     ///
-    /// # Safety
-    /// Caller must ensure `ptr` is valid for reads as expected by libopus for the duration of the call
-    /// and points to a properly formatted DNN blob. Passing an invalid or dangling pointer is UB.
+    /// libopus points into the blob rather than copying the weights, so the decoder keeps
+    /// a reference to it until another blob loads successfully, or the decoder is dropped
+    /// or [reinitialized](Self::reinit).
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder is invalid, or a mapped libopus error.
-    pub unsafe fn set_dnn_blob(&mut self, ptr: *const u8, len: i32) -> Result<()> {
+    /// Returns [`Error::InvalidState`] if the decoder is invalid, or a mapped libopus error
+    /// for a malformed blob.
+    pub fn set_dnn_blob(&mut self, blob: &DnnBlob) -> Result<()> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
-        if ptr.is_null() || len <= 0 {
-            return Err(Error::BadArg);
-        }
-        let r = unsafe { opus_decoder_ctl(self.raw, OPUS_SET_DNN_BLOB_REQUEST as i32, ptr, len) };
+        self.dnn_blobs.push(blob.clone());
+        let r = unsafe {
+            opus_decoder_ctl(
+                self.raw,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                blob.as_bytes().as_ptr(),
+                blob.ctl_len(),
+            )
+        };
         if r != 0 {
             return Err(Error::from_code(r));
         }
+        // Every model now points into `blob`, so the earlier ones can go.
+        let last = self.dnn_blobs.len() - 1;
+        self.dnn_blobs.drain(..last);
         Ok(())
     }
 
     // --- internal helpers for CTLs ---
     fn simple_ctl(&mut self, req: i32, val: i32) -> Result<()> {
+        let result = self.simple_ctl_raw(req, val);
+        #[cfg(feature = "ctl-audit")]
+        self.ctl_audit.record(req, val, &result);
+        result
+    }
+    fn simple_ctl_raw(&mut self, req: i32, val: i32) -> Result<()> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
@@ -408,10 +1183,164 @@ impl Decoder {
     }
 }
 
+/// Copy interleaved `src` frames of `channels` samples into `dst` frames of `stride`
+/// samples, starting at channel `offset`.
+fn scatter<S: Copy>(src: &[S], dst: &mut [S], channels: usize, stride: usize, offset: usize) {
+    for (from, to) in src.chunks_exact(channels).zip(dst.chunks_exact_mut(stride)) {
+        to[offset..offset + channels].copy_from_slice(from);
+    }
+}
+
 impl Drop for Decoder {
     fn drop(&mut self) {
-        unsafe {
-            opus_decoder_destroy(self.raw);
+        if self.owned {
+            unsafe {
+                opus_decoder_destroy(self.raw);
+            }
+        }
+    }
+}
+
+/// Packets missing in a row beyond which [`StreamDecoder`] restarts instead of
+/// concealing the gap: one second of 20 ms packets.
+pub const DEFAULT_MAX_CONCEALED_PACKETS: u16 = 50;
+
+/// [`Decoder`] for a sequence-numbered packet stream such as RTP, turning arrivals and
+/// losses into contiguous PCM.
+///
+/// Sequence numbers are 16-bit and wrap. A packet that skips ahead conceals the gap
+/// first: every missing packet but the last by PLC, the last by [`Decoder::recover_with_fec`]
+/// from the arriving packet (itself PLC when it carries no FEC). A packet at or behind
+/// the expected number is a duplicate or arrived too late and is dropped. Missing
+/// packets are assumed to last as long as the previous one.
+pub struct StreamDecoder {
+    decoder: Decoder,
+    /// Sequence number of the next packet, once the first packet was decoded.
+    next_seq: Option<u16>,
+    max_concealed: u16,
+}
+
+impl StreamDecoder {
+    /// Wrap `decoder`, which should be fresh or [reset](Decoder::reset).
+    #[must_use]
+    pub const fn new(decoder: Decoder) -> Self {
+        Self {
+            decoder,
+            next_seq: None,
+            max_concealed: DEFAULT_MAX_CONCEALED_PACKETS,
+        }
+    }
+
+    /// Set how many missing packets in a row are concealed; a longer gap, e.g. after
+    /// the sender restarted, resets the decoder and resumes without filling it. The
+    /// [`Decoder::concealment_stats`] survive that reset and keep covering the stream.
+    pub const fn set_max_concealed(&mut self, packets: u16) {
+        self.max_concealed = packets;
+    }
+
+    /// Handle the event for packet `seq`, appending the PCM it produces to `out` and
+    /// returning the samples per channel appended.
+    ///
+    /// `Some(packet)` is an arrival. `None` reports the packet as lost now, e.g. when its
+    /// playout deadline passed, and conceals it (and any gap before it) by PLC at once;
+    /// FEC is then no longer possible for it. Events before the first packet and for
+    /// duplicate or late packets append nothing.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] for a malformed packet or any error from
+    /// [`Decoder::decode`]; `out` then holds the PCM produced before the failure and the
+    /// packet counts as consumed.
+    pub fn push(&mut self, seq: u16, packet: Option<&[u8]>, out: &mut Vec<i16>) -> Result<usize> {
+        let Some(expected) = self.next_seq else {
+            return match packet {
+                Some(packet) => {
+                    self.next_seq = Some(seq.wrapping_add(1));
+                    self.decode(packet, out)
+                }
+                None => Ok(0),
+            };
+        };
+        let missing = seq.wrapping_sub(expected);
+        if missing >= 0x8000 {
+            return Ok(0);
+        }
+        self.next_seq = Some(seq.wrapping_add(1));
+        let Some(packet) = packet else {
+            return self.conceal(missing + 1, out);
+        };
+        let mut produced = 0;
+        if missing > self.max_concealed {
+            let stats = self.decoder.concealment;
+            self.decoder.reset()?;
+            self.decoder.concealment = stats;
+        } else if missing > 0 {
+            produced += self.conceal(missing - 1, out)?;
+            let lost = self.lost_duration()?;
+            produced += self.append(out, lost, |decoder, pcm| {
+                Ok(decoder.recover_with_fec(packet, lost, pcm)?.samples)
+            })?;
+        }
+        Ok(produced + self.decode(packet, out)?)
+    }
+
+    /// Algorithmic delay of the wrapped decoder; see [`Decoder::latency`]. Packets are
+    /// decoded as they are pushed, so the sequence tracking adds none.
+    #[must_use]
+    pub const fn latency(&self) -> Duration {
+        self.decoder.latency()
+    }
+
+    /// The wrapped decoder, e.g. for its [`Decoder::concealment_stats`].
+    #[must_use]
+    pub const fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Unwrap the decoder.
+    #[must_use]
+    pub fn into_inner(self) -> Decoder {
+        self.decoder
+    }
+
+    fn decode(&mut self, packet: &[u8], out: &mut Vec<i16>) -> Result<usize> {
+        let samples = self.decoder.packet_samples(packet)?;
+        self.append(out, samples, |decoder, pcm| {
+            decoder.decode(packet, pcm, false)
+        })
+    }
+
+    /// Conceal `packets` lost packets by PLC, or skip them when there are more than the
+    /// configured maximum.
+    fn conceal(&mut self, packets: u16, out: &mut Vec<i16>) -> Result<usize> {
+        if packets > self.max_concealed {
+            return Ok(0);
+        }
+        let mut produced = 0;
+        for _ in 0..packets {
+            let lost = self.lost_duration()?;
+            produced += self.append(out, lost, |decoder, pcm| decoder.decode(&[], pcm, false))?;
         }
+        Ok(produced)
+    }
+
+    fn lost_duration(&mut self) -> Result<usize> {
+        usize::try_from(self.decoder.get_last_packet_duration()?).map_err(|_| Error::InternalError)
+    }
+
+    /// Run `decode` on `samples` per channel of room at the end of `out`, keeping only
+    /// what it wrote.
+    fn append(
+        &mut self,
+        out: &mut Vec<i16>,
+        samples: usize,
+        decode: impl FnOnce(&mut Decoder, &mut [i16]) -> Result<usize>,
+    ) -> Result<usize> {
+        let ch = self.decoder.channels.as_usize();
+        let start = out.len();
+        out.resize(start + samples * ch, 0);
+        let result = decode(&mut self.decoder, &mut out[start..]);
+        let written = *result.as_ref().unwrap_or(&0);
+        out.truncate(start + written * ch);
+        result
     }
 }
@@ -1,4 +1,5 @@
-//! Safe wrappers for libopus Deep Redundancy (DRED) decoder support.
+//! Safe wrappers for libopus Deep Redundancy (DRED) decoder support, and [`DnnBlob`]
+//! for loading external model weights into encoders and decoders.
 //! This module is available when the `dred` Cargo feature is enabled.
 
 use crate::bindings::{
@@ -11,6 +12,56 @@ use crate::constants::max_frame_samples_for;
 use crate::decoder::Decoder;
 use crate::error::{Error, Result};
 use crate::types::SampleRate;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Owned DNN weights for [`crate::Encoder::set_dnn_blob`] and
+/// [`crate::Decoder::set_dnn_blob`].
+///
+/// libopus keeps pointers into the blob instead of copying the weights, so each codec
+/// holds a reference to it for as long as it may use them. Cloning is cheap and shares
+/// the bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnnBlob {
+    data: Arc<[u8]>,
+}
+
+impl DnnBlob {
+    /// Copy a blob from `bytes`.
+    ///
+    /// The format is only checked when a codec loads the blob.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `bytes` is empty or longer than `i32::MAX`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() || i32::try_from(bytes.len()).is_err() {
+            return Err(Error::BadArg);
+        }
+        Ok(Self { data: bytes.into() })
+    }
+
+    /// Read a blob from the file at `path`, e.g. one written by libopus'
+    /// `write_lpcnet_weights` tool.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file cannot be read, otherwise the errors of
+    /// [`Self::from_bytes`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Raw blob bytes.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Length as passed to `OPUS_SET_DNN_BLOB`; checked to fit on construction.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub(crate) fn ctl_len(&self) -> i32 {
+        self.data.len() as i32
+    }
+}
 
 /// Managed handle for libopus `OpusDREDDecoder`.
 pub struct DredDecoder {
@@ -315,6 +366,18 @@ impl Drop for DredState {
 mod tests {
     use super::*;
 
+    #[test]
+    fn dnn_blob_rejects_empty_input() {
+        assert_eq!(DnnBlob::from_bytes(&[]), Err(Error::BadArg));
+        let blob = DnnBlob::from_bytes(&[1, 2, 3]).unwrap();
+        assert_eq!(blob.clone().as_bytes(), &[1, 2, 3]);
+        assert_eq!(blob.ctl_len(), 3);
+        assert!(matches!(
+            DnnBlob::from_file("/nonexistent/weights.bin"),
+            Err(Error::Io(_))
+        ));
+    }
+
     #[test]
     fn validate_pcm_frame_len_checks_arguments() {
         let pcm = vec![0i16; 4];
@@ -0,0 +1,316 @@
+//! Lightweight pre-processing stages to run in front of an encoder
+
+use crate::activity::rms_dbfs;
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use crate::types::{Channels, SampleRate};
+
+/// Tuning for [`Agc`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct AgcConfig {
+    /// Level (dBFS RMS) the stage steers speech towards.
+    pub target_rms_dbfs: f32,
+    /// Largest boost (dB) applied to quiet input.
+    pub max_gain_db: f32,
+    /// Time constant (ms) for reducing gain when input gets louder.
+    pub attack_ms: f32,
+    /// Time constant (ms) for raising gain when input gets quieter.
+    pub release_ms: f32,
+    /// Blocks quieter than this (dBFS RMS) are treated as background and keep the current gain.
+    pub noise_floor_dbfs: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            target_rms_dbfs: -20.0,
+            max_gain_db: 24.0,
+            attack_ms: 50.0,
+            release_ms: 1_500.0,
+            noise_floor_dbfs: -55.0,
+        }
+    }
+}
+
+/// Slow automatic gain control operating on whole encoder frames.
+///
+/// The gain is smoothed between frames and ramped across each frame, so output
+/// stays click-free; `i16` output saturates and `f32` output is clamped to `[-1, 1]`.
+#[derive(Debug, Clone)]
+pub struct Agc {
+    config: AgcConfig,
+    sample_rate: SampleRate,
+    channels: Channels,
+    gain_db: f32,
+}
+
+impl Agc {
+    /// Create an AGC stage for interleaved audio.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the time constants are not positive or `max_gain_db` is negative.
+    pub fn new(sample_rate: SampleRate, channels: Channels, config: AgcConfig) -> Result<Self> {
+        if config.attack_ms <= 0.0 || config.release_ms <= 0.0 || config.max_gain_db < 0.0 {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            config,
+            sample_rate,
+            channels,
+            gain_db: 0.0,
+        })
+    }
+
+    /// Apply gain to one frame of interleaved samples in place.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `pcm` is not a whole number of frames.
+    pub fn process<S: Sample>(&mut self, pcm: &mut [S]) -> Result<()> {
+        let channels = self.channels.as_usize();
+        if !pcm.len().is_multiple_of(channels) {
+            return Err(Error::BadArg);
+        }
+        if pcm.is_empty() {
+            return Ok(());
+        }
+        let level = rms_dbfs(pcm.iter().map(|s| s.to_f32()), pcm.len());
+        let start_gain = self.gain_db;
+        if level > self.config.noise_floor_dbfs {
+            let desired = (self.config.target_rms_dbfs - level)
+                .clamp(-self.config.max_gain_db, self.config.max_gain_db);
+            let tau = if desired < self.gain_db {
+                self.config.attack_ms
+            } else {
+                self.config.release_ms
+            };
+            let frames = pcm.len() / channels;
+            #[allow(clippy::cast_precision_loss)]
+            let block_ms = frames as f32 * 1_000.0 / self.sample_rate.as_i32() as f32;
+            let alpha = 1.0 - (-block_ms / tau).exp();
+            self.gain_db += alpha * (desired - self.gain_db);
+        }
+
+        let from = db_to_linear(start_gain);
+        let to = db_to_linear(self.gain_db);
+        let frames = pcm.len() / channels;
+        #[allow(clippy::cast_precision_loss)]
+        let step = (to - from) / frames as f32;
+        let mut gain = from;
+        for frame in pcm.chunks_exact_mut(channels) {
+            gain += step;
+            for sample in frame {
+                *sample = S::from_f32((sample.to_f32() * gain).clamp(-1.0, 1.0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Gain (dB) reached at the end of the last processed frame.
+    #[must_use]
+    pub const fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// Return to unity gain.
+    pub fn reset(&mut self) {
+        self.gain_db = 0.0;
+    }
+}
+
+/// Tuning for [`NoiseGate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct NoiseGateConfig {
+    /// Frame level (dBFS RMS) at which a closed gate opens.
+    pub open_threshold_dbfs: f32,
+    /// Frame level (dBFS RMS) below which an open gate starts closing; keep it under
+    /// `open_threshold_dbfs` for hysteresis.
+    pub close_threshold_dbfs: f32,
+    /// Time (ms) the gate stays open after the level drops below the close threshold.
+    pub hold_ms: f32,
+}
+
+impl Default for NoiseGateConfig {
+    fn default() -> Self {
+        Self {
+            open_threshold_dbfs: -45.0,
+            close_threshold_dbfs: -52.0,
+            hold_ms: 250.0,
+        }
+    }
+}
+
+/// Frame-based noise gate with hysteresis and hold time.
+///
+/// Closed frames are replaced by digital silence, which lets an encoder with DTX
+/// enabled switch to comfort-noise packets instead of coding background noise.
+#[derive(Debug, Clone)]
+pub struct NoiseGate {
+    config: NoiseGateConfig,
+    sample_rate: SampleRate,
+    channels: Channels,
+    open: bool,
+    hold_left_ms: f32,
+}
+
+impl NoiseGate {
+    /// Create a gate for interleaved audio; the gate starts closed.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the close threshold is above the open threshold or
+    /// `hold_ms` is negative.
+    pub fn new(
+        sample_rate: SampleRate,
+        channels: Channels,
+        config: NoiseGateConfig,
+    ) -> Result<Self> {
+        if config.close_threshold_dbfs > config.open_threshold_dbfs || config.hold_ms < 0.0 {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            config,
+            sample_rate,
+            channels,
+            open: false,
+            hold_left_ms: 0.0,
+        })
+    }
+
+    /// Gate one frame of interleaved samples in place, returning whether it was passed through.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `pcm` is not a whole number of frames.
+    pub fn process<S: Sample>(&mut self, pcm: &mut [S]) -> Result<bool> {
+        let channels = self.channels.as_usize();
+        if !pcm.len().is_multiple_of(channels) {
+            return Err(Error::BadArg);
+        }
+        let level = rms_dbfs(pcm.iter().map(|s| s.to_f32()), pcm.len());
+        #[allow(clippy::cast_precision_loss)]
+        let frame_ms = (pcm.len() / channels) as f32 * 1_000.0 / self.sample_rate.as_i32() as f32;
+
+        if level >= self.config.open_threshold_dbfs
+            || (self.open && level >= self.config.close_threshold_dbfs)
+        {
+            self.open = true;
+            self.hold_left_ms = self.config.hold_ms;
+        } else if self.open && self.hold_left_ms > 0.0 {
+            self.hold_left_ms -= frame_ms;
+        } else {
+            self.open = false;
+        }
+
+        if !self.open {
+            pcm.fill(S::default());
+        }
+        Ok(self.open)
+    }
+
+    /// Whether the last processed frame passed through the gate.
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Close the gate and clear the hold timer.
+    pub fn reset(&mut self) {
+        self.open = false;
+        self.hold_left_ms = 0.0;
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agc_raises_quiet_and_lowers_loud_input() {
+        let sr = SampleRate::Hz48000;
+        let tone = |amplitude: f32| -> Vec<f32> {
+            (0..960)
+                .map(|i| amplitude * (f64::from(i) as f32 * 0.05).sin())
+                .collect()
+        };
+
+        let mut agc = Agc::new(sr, Channels::Mono, AgcConfig::default()).unwrap();
+        for _ in 0..500 {
+            let mut frame = tone(0.01);
+            agc.process(&mut frame).unwrap();
+        }
+        assert!(agc.gain_db() > 15.0, "gain {}", agc.gain_db());
+
+        for _ in 0..50 {
+            let mut frame = tone(0.9);
+            agc.process(&mut frame).unwrap();
+            assert!(frame.iter().all(|s| s.abs() <= 1.0));
+        }
+        assert!(agc.gain_db() < 0.0, "gain {}", agc.gain_db());
+
+        let mut odd = vec![0i16; 3];
+        assert!(
+            Agc::new(sr, Channels::Stereo, AgcConfig::default())
+                .unwrap()
+                .process(&mut odd)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn gate_holds_then_silences_for_dtx() {
+        use crate::encoder::Encoder;
+        use crate::types::Application;
+
+        let sr = SampleRate::Hz48000;
+        let mut gate = NoiseGate::new(sr, Channels::Mono, NoiseGateConfig::default()).unwrap();
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+        encoder.set_dtx(true).unwrap();
+        let level = |amplitude: i16| -> Vec<i16> {
+            (0..960)
+                .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+                .collect()
+        };
+
+        let mut frame = level(3_000);
+        assert!(gate.process(&mut frame).unwrap());
+        // Between the thresholds: stays open while open.
+        let mut frame = level(100);
+        assert!(gate.process(&mut frame).unwrap());
+
+        // Keyboard-level noise: held open for 250 ms, then silenced.
+        let mut packet = [0u8; 1275];
+        let mut open_frames = 0;
+        let mut last_len = usize::MAX;
+        for _ in 0..40 {
+            let mut frame = level(30);
+            if gate.process(&mut frame).unwrap() {
+                open_frames += 1;
+            } else {
+                assert!(frame.iter().all(|&s| s == 0));
+            }
+            last_len = encoder.encode(&frame, &mut packet).unwrap();
+        }
+        assert!(
+            (12..=14).contains(&open_frames),
+            "open for {open_frames} frames"
+        );
+        assert!(!gate.is_open());
+        assert!(last_len <= 2, "DTX packet expected, got {last_len} bytes");
+
+        assert!(
+            NoiseGate::new(
+                sr,
+                Channels::Mono,
+                NoiseGateConfig {
+                    close_threshold_dbfs: -10.0,
+                    ..NoiseGateConfig::default()
+                }
+            )
+            .is_err()
+        );
+    }
+}
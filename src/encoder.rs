@@ -1,40 +1,189 @@
 //! Opus encoder implementation with safe wrappers
 
+#[cfg(feature = "ctl-audit")]
+use crate::audit::CtlAuditLog;
 use crate::bindings::{
-    OPUS_AUTO, OPUS_BANDWIDTH_FULLBAND, OPUS_BITRATE_MAX, OPUS_GET_BANDWIDTH_REQUEST,
-    OPUS_GET_BITRATE_REQUEST, OPUS_GET_COMPLEXITY_REQUEST, OPUS_GET_DTX_REQUEST,
-    OPUS_GET_EXPERT_FRAME_DURATION_REQUEST, OPUS_GET_FINAL_RANGE_REQUEST,
+    OPUS_AUTO, OPUS_BANDWIDTH_FULLBAND, OPUS_BITRATE_MAX, OPUS_GET_APPLICATION_REQUEST,
+    OPUS_GET_BANDWIDTH_REQUEST, OPUS_GET_BITRATE_REQUEST, OPUS_GET_COMPLEXITY_REQUEST,
+    OPUS_GET_DTX_REQUEST, OPUS_GET_EXPERT_FRAME_DURATION_REQUEST, OPUS_GET_FINAL_RANGE_REQUEST,
     OPUS_GET_FORCE_CHANNELS_REQUEST, OPUS_GET_IN_DTX_REQUEST, OPUS_GET_INBAND_FEC_REQUEST,
     OPUS_GET_LOOKAHEAD_REQUEST, OPUS_GET_LSB_DEPTH_REQUEST, OPUS_GET_MAX_BANDWIDTH_REQUEST,
     OPUS_GET_PACKET_LOSS_PERC_REQUEST, OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST,
     OPUS_GET_PREDICTION_DISABLED_REQUEST, OPUS_GET_SIGNAL_REQUEST, OPUS_GET_VBR_CONSTRAINT_REQUEST,
-    OPUS_GET_VBR_REQUEST, OPUS_SET_BANDWIDTH_REQUEST, OPUS_SET_BITRATE_REQUEST,
-    OPUS_SET_COMPLEXITY_REQUEST, OPUS_SET_DTX_REQUEST, OPUS_SET_EXPERT_FRAME_DURATION_REQUEST,
-    OPUS_SET_FORCE_CHANNELS_REQUEST, OPUS_SET_INBAND_FEC_REQUEST, OPUS_SET_LSB_DEPTH_REQUEST,
-    OPUS_SET_MAX_BANDWIDTH_REQUEST, OPUS_SET_PACKET_LOSS_PERC_REQUEST,
-    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, OPUS_SET_PREDICTION_DISABLED_REQUEST,
-    OPUS_SET_SIGNAL_REQUEST, OPUS_SET_VBR_CONSTRAINT_REQUEST, OPUS_SET_VBR_REQUEST, OpusEncoder,
-    opus_encode, opus_encode_float, opus_encoder_create, opus_encoder_ctl, opus_encoder_destroy,
+    OPUS_GET_VBR_REQUEST, OPUS_SET_APPLICATION_REQUEST, OPUS_SET_BANDWIDTH_REQUEST,
+    OPUS_SET_BITRATE_REQUEST, OPUS_SET_COMPLEXITY_REQUEST, OPUS_SET_DTX_REQUEST,
+    OPUS_SET_EXPERT_FRAME_DURATION_REQUEST, OPUS_SET_FORCE_CHANNELS_REQUEST,
+    OPUS_SET_INBAND_FEC_REQUEST, OPUS_SET_LSB_DEPTH_REQUEST, OPUS_SET_MAX_BANDWIDTH_REQUEST,
+    OPUS_SET_PACKET_LOSS_PERC_REQUEST, OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST,
+    OPUS_SET_PREDICTION_DISABLED_REQUEST, OPUS_SET_SIGNAL_REQUEST, OPUS_SET_VBR_CONSTRAINT_REQUEST,
+    OPUS_SET_VBR_REQUEST, OpusEncoder, opus_encode, opus_encode_float, opus_encoder_create,
+    opus_encoder_ctl, opus_encoder_destroy, opus_encoder_get_size, opus_encoder_init,
 };
-use crate::constants::max_frame_samples_for;
+#[cfg(feature = "dred")]
+use crate::bindings::{
+    OPUS_GET_DRED_DURATION_REQUEST, OPUS_SET_DNN_BLOB_REQUEST, OPUS_SET_DRED_DURATION_REQUEST,
+};
+use crate::constants::{OPUS_SET_FORCE_MODE_REQUEST, max_frame_samples_for, max_packet_size};
+#[cfg(feature = "dred")]
+use crate::dred::DnnBlob;
 use crate::error::{Error, Result};
+use crate::filter::{HighPassFilter, PreFilter};
+use crate::frame::PcmFrame;
+use crate::stats::{BitrateStats, BitrateTracker, EncoderStats, RangeChain};
+#[cfg(feature = "timing")]
+use crate::stats::{TimingReservoir, TimingStats};
 use crate::types::{
-    Application, Bandwidth, Bitrate, Channels, Complexity, ExpertFrameDuration, SampleRate, Signal,
+    Application, Bandwidth, Bitrate, Channels, CodecMode, Complexity, ExpertFrameDuration,
+    FrameSize, InbandFec, PacketLossPerc, Profile, SampleRate, Signal,
 };
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::time::Duration;
+#[cfg(feature = "timing")]
+use std::time::Instant;
 
 /// Safe wrapper around a libopus `OpusEncoder`.
 pub struct Encoder {
     raw: *mut OpusEncoder,
+    /// Whether `raw` was allocated by libopus; state initialized in caller memory by
+    /// [`Self::init_in`] is not freed on drop.
+    owned: bool,
+    /// Bytes available for the libopus state, bounding what [`Self::reinit`] can reuse.
+    capacity: usize,
     sample_rate: SampleRate,
     channels: Channels,
+    /// Input conditioning, present while a high-pass filter or input gain is enabled.
+    pre: Option<PreFilter>,
+    range_chain: Option<RangeChain>,
+    stats: Option<EncoderStats>,
+    /// Samples per channel encoded since position tracking was enabled.
+    position: Option<u64>,
+    /// Fixed frame duration set through [`Self::set_expert_frame_duration`].
+    frame_duration: ExpertFrameDuration,
+    /// Bandwidth forced through [`Self::set_bandwidth`]; libopus only reports the coded one.
+    forced_bandwidth: Option<Bandwidth>,
+    /// Mode pinned through [`Self::force_mode`]; libopus has no query for it.
+    forced_mode: Option<CodecMode>,
+    /// Bandwidth [`Self::force_mode`] forced for its mode, released when the mode changes.
+    mode_bandwidth: Option<Bandwidth>,
+    /// Values last applied through the setters [`Self::encode_with`] covers, letting it
+    /// skip CTLs that would not change anything.
+    applied: EncodeOverrides,
+    /// Whether the LSB depth was configured, explicitly or by the first [`Self::encode_i32`]
+    /// or [`Self::encode_s24`].
+    lsb_depth_set: bool,
+    /// Conversion buffer for [`Self::encode_i32`] and [`Self::encode_s24`], and gather
+    /// buffer for [`Self::encode_float_strided`] and [`Self::encode_float_planar`].
+    f32_scratch: Vec<f32>,
+    /// Gather buffer for [`Self::encode_strided`] and [`Self::encode_planar`].
+    i16_scratch: Vec<i16>,
+    /// Packet buffer for [`Self::encode_batch`] and [`Self::encode_float_batch`].
+    packet_scratch: Vec<u8>,
+    #[cfg(feature = "timing")]
+    timing: TimingReservoir,
+    #[cfg(feature = "ctl-audit")]
+    ctl_audit: CtlAuditLog,
+    /// Blobs libopus may point into: the last one loaded by [`Self::set_dnn_blob`], plus
+    /// any earlier ones while a failed load leaves the model partly replaced.
+    #[cfg(feature = "dred")]
+    dnn_blobs: Vec<DnnBlob>,
 }
 
 unsafe impl Send for Encoder {}
 unsafe impl Sync for Encoder {}
 
 impl Encoder {
+    /// Start an [`EncoderBuilder`] for an encoder with this layout and application.
+    pub const fn builder(
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: Application,
+    ) -> EncoderBuilder {
+        EncoderBuilder {
+            sample_rate,
+            channels,
+            application,
+            bitrate: None,
+            complexity: None,
+            vbr: None,
+            vbr_constraint: None,
+            inband_fec: None,
+            packet_loss_perc: None,
+            dtx: None,
+            signal: None,
+            bandwidth: None,
+            max_bandwidth: None,
+            force_channels: None,
+            lsb_depth: None,
+            expert_frame_duration: None,
+            prediction_disabled: None,
+            phase_inversion_disabled: None,
+        }
+    }
+
+    /// Start an [`EncoderBuilder`] preloaded with the settings of `profile`, to adjust
+    /// further before building.
+    pub const fn profile_builder(
+        sample_rate: SampleRate,
+        channels: Channels,
+        profile: Profile,
+    ) -> EncoderBuilder {
+        let builder = Self::builder(sample_rate, channels, profile.application())
+            .bitrate(Bitrate::Custom(
+                profile.bitrate_per_channel() * channels.as_i32(),
+            ))
+            .expert_frame_duration(match profile.frame_size() {
+                FrameSize::Ms10 => ExpertFrameDuration::Ms10,
+                _ => ExpertFrameDuration::Ms20,
+            });
+        let speech_band = if Bandwidth::for_rate(sample_rate).is_wider_than(Bandwidth::Wideband) {
+            Bandwidth::Wideband
+        } else {
+            Bandwidth::for_rate(sample_rate)
+        };
+        match profile {
+            Profile::VoipLowLatency => builder
+                .signal(Signal::Voice)
+                .max_bandwidth(speech_band)
+                .complexity(Complexity::new(5)),
+            Profile::VoipLossy => builder
+                .signal(Signal::Voice)
+                .max_bandwidth(speech_band)
+                .complexity(Complexity::new(10))
+                .inband_fec(InbandFec::On)
+                .packet_loss_perc(PacketLossPerc::new(10))
+                .dtx(true),
+            Profile::MusicStreaming => builder
+                .signal(Signal::Music)
+                .complexity(Complexity::new(10))
+                .vbr(true)
+                .vbr_constraint(true),
+            Profile::MusicArchive => builder
+                .signal(Signal::Music)
+                .complexity(Complexity::new(10))
+                .vbr(true)
+                .vbr_constraint(false),
+        }
+    }
+
+    /// Create an encoder configured by `profile`.
+    ///
+    /// # Errors
+    /// Returns any error from creating or configuring the encoder.
+    pub fn with_profile(
+        sample_rate: SampleRate,
+        channels: Channels,
+        profile: Profile,
+    ) -> Result<Self> {
+        Ok(Self::profile_builder(sample_rate, channels, profile).build()?)
+    }
+
     /// Create a new encoder.
     ///
+    /// Below 48 kHz the maximum bandwidth starts at [`Bandwidth::for_rate`], the widest
+    /// the input can carry, so the encoder does not spend bits on an empty upper band.
+    ///
     /// # Errors
     /// Returns an error if allocation fails or arguments are invalid.
     pub fn new(
@@ -46,6 +195,7 @@ impl Encoder {
         if !sample_rate.is_valid() {
             return Err(Error::BadArg);
         }
+        let capacity = Self::size(channels)?;
 
         let mut error = 0i32;
         let encoder = unsafe {
@@ -65,55 +215,177 @@ impl Encoder {
             return Err(Error::AllocFail);
         }
 
-        Ok(Self {
-            raw: encoder,
+        Self::wrap(encoder, true, capacity, sample_rate, channels)
+    }
+
+    /// Size in bytes of the libopus encoder state for `channels`, as needed by
+    /// [`Self::init_in`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InternalError`] if libopus reports an invalid size.
+    pub fn size(channels: Channels) -> Result<usize> {
+        let raw = unsafe { opus_encoder_get_size(channels.as_i32()) };
+        usize::try_from(raw)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or(Error::InternalError)
+    }
+
+    /// Initialize an encoder whose libopus state lives in caller-provided memory, e.g. an
+    /// arena, instead of being allocated by libopus.
+    ///
+    /// `buf` must hold at least [`Self::size`] bytes and be aligned to 8 bytes. Dropping
+    /// the encoder leaves `buf` alone; only the wrapper's own buffers are freed.
+    ///
+    /// # Safety
+    /// `buf` must outlive the returned encoder and must not be read, written or moved
+    /// while the encoder is alive.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for an invalid sample rate or a buffer that is too small or
+    /// misaligned, or a mapped libopus error if initialization fails.
+    pub unsafe fn init_in(
+        buf: &mut [u8],
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: Application,
+    ) -> Result<Self> {
+        if !sample_rate.is_valid()
+            || buf.len() < Self::size(channels)?
+            || buf.as_ptr().align_offset(8) != 0
+        {
+            return Err(Error::BadArg);
+        }
+        let raw = buf.as_mut_ptr().cast::<OpusEncoder>();
+        let r = unsafe {
+            opus_encoder_init(
+                raw,
+                sample_rate.as_i32(),
+                channels.as_i32(),
+                application as i32,
+            )
+        };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Self::wrap(raw, false, buf.len(), sample_rate, channels)
+    }
+
+    /// Wrap an initialized libopus state, matching its maximum bandwidth to the input rate.
+    fn wrap(
+        raw: *mut OpusEncoder,
+        owned: bool,
+        capacity: usize,
+        sample_rate: SampleRate,
+        channels: Channels,
+    ) -> Result<Self> {
+        let mut encoder = Self {
+            raw,
+            owned,
+            capacity,
             sample_rate,
             channels,
-        })
+            pre: None,
+            range_chain: None,
+            stats: None,
+            position: None,
+            frame_duration: ExpertFrameDuration::FromArgument,
+            forced_bandwidth: None,
+            forced_mode: None,
+            mode_bandwidth: None,
+            applied: EncodeOverrides::default(),
+            lsb_depth_set: false,
+            f32_scratch: Vec::new(),
+            i16_scratch: Vec::new(),
+            packet_scratch: Vec::new(),
+            #[cfg(feature = "timing")]
+            timing: TimingReservoir::new(),
+            #[cfg(feature = "ctl-audit")]
+            ctl_audit: CtlAuditLog::new(),
+            #[cfg(feature = "dred")]
+            dnn_blobs: Vec::new(),
+        };
+        if sample_rate != SampleRate::Hz48000 {
+            encoder.set_max_bandwidth(Bandwidth::for_rate(sample_rate))?;
+        }
+        Ok(encoder)
     }
 
     /// Encode 16-bit PCM into an Opus packet.
     ///
+    /// The frame size is `input.len() / channels` and must be a legal Opus duration (2.5,
+    /// 5, 10, 20, 40, 60, 80, 100 or 120 ms). With the default
+    /// [`ExpertFrameDuration::FromArgument`] it may change from call to call, e.g. to
+    /// switch between 10 and 20 ms frames as network conditions change; once a fixed
+    /// expert duration is set, every frame must have exactly that duration.
+    ///
+    /// Input passes through the high-pass pre-filter and input gain first when enabled.
+    ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::BadArg`] for
-    /// invalid buffer sizes or frame size, or a mapped libopus error.
+    /// Returns [`Error::InvalidState`] if the encoder is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for input that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output, or a mapped
+    /// libopus error.
     pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.encode_ptr(input, output.as_mut_ptr(), output.len())
+    }
+
+    /// Encode 16-bit PCM as [`Self::encode`] does, into a buffer that need not be
+    /// initialized, returning the packet as the initialized prefix of `output`.
+    ///
+    /// Saves zeroing a packet buffer per frame, e.g. on servers encoding many streams.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`].
+    pub fn encode_into_uninit<'a>(
+        &mut self,
+        input: &[i16],
+        output: &'a mut [MaybeUninit<u8>],
+    ) -> Result<&'a [u8]> {
+        let len = self.encode_ptr(input, output.as_mut_ptr().cast(), output.len())?;
+        Ok(unsafe { std::slice::from_raw_parts(output.as_ptr().cast(), len) })
+    }
+
+    fn encode_ptr(&mut self, input: &[i16], output: *mut u8, output_len: usize) -> Result<usize> {
+        self.observe(input.len(), |enc| match enc.pre.take() {
+            None => enc.encode_raw(input, output, output_len),
+            Some(mut pre) => {
+                let result = enc.encode_raw(pre.apply_i16(input)?, output, output_len);
+                enc.pre = Some(pre);
+                result
+            }
+        })
+    }
+
+    /// libopus writes at most `output_len` bytes through `output` and only reports
+    /// bytes it wrote, so `output` may point at uninitialized memory.
+    fn encode_raw(&mut self, input: &[i16], output: *mut u8, output_len: usize) -> Result<usize> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
 
-        // Validate input buffer size
-        if input.is_empty() {
-            return Err(Error::BadArg);
-        }
-
         // Ensure input buffer is properly sized for the number of channels
-        if !input.len().is_multiple_of(self.channels.as_usize()) {
-            return Err(Error::BadArg);
-        }
+        self.check_channels(input.len())?;
 
         let frame_size = input.len() / self.channels.as_usize();
-        // Validate frame size is within Opus limits for the configured sample rate
-        if frame_size == 0 || frame_size > max_frame_samples_for(self.sample_rate) {
-            return Err(Error::BadArg);
-        }
+        self.check_frame_size(frame_size)?;
 
         // Validate output buffer size
-        if output.is_empty() {
+        if output_len == 0 {
             return Err(Error::BadArg);
         }
-        if output.len() > i32::MAX as usize {
+        if output_len > i32::MAX as usize {
             return Err(Error::BadArg);
         }
 
         let frame_size_i32 = i32::try_from(frame_size).map_err(|_| Error::BadArg)?;
-        let out_len_i32 = i32::try_from(output.len()).map_err(|_| Error::BadArg)?;
+        let out_len_i32 = i32::try_from(output_len).map_err(|_| Error::BadArg)?;
         let result = unsafe {
             opus_encode(
                 self.raw,
                 input.as_ptr(),
                 frame_size_i32,
-                output.as_mut_ptr(),
+                output,
                 out_len_i32,
             )
         };
@@ -131,33 +403,41 @@ impl Encoder {
     /// `set_packet_loss_perc(…)` to actually make the encoder produce FEC.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::BadArg`] for
-    /// invalid buffer sizes or frame size, or a mapped libopus error.
+    /// Returns [`Error::InvalidState`] if the encoder is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for input that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output or a
+    /// `max_data_bytes` outside it, or a mapped libopus error.
     pub fn encode_limited(
         &mut self,
         input: &[i16],
         output: &mut [u8],
         max_data_bytes: usize,
+    ) -> Result<usize> {
+        self.observe(input.len(), |enc| match enc.pre.take() {
+            None => enc.encode_limited_raw(input, output, max_data_bytes),
+            Some(mut pre) => {
+                let result = enc.encode_limited_raw(pre.apply_i16(input)?, output, max_data_bytes);
+                enc.pre = Some(pre);
+                result
+            }
+        })
+    }
+
+    fn encode_limited_raw(
+        &mut self,
+        input: &[i16],
+        output: &mut [u8],
+        max_data_bytes: usize,
     ) -> Result<usize> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
 
-        // Validate input buffer size
-        if input.is_empty() {
-            return Err(Error::BadArg);
-        }
-
         // Ensure input buffer is properly sized for the number of channels
-        if !input.len().is_multiple_of(self.channels.as_usize()) {
-            return Err(Error::BadArg);
-        }
+        self.check_channels(input.len())?;
 
         let frame_size = input.len() / self.channels.as_usize();
-        // Validate frame size is within Opus limits for the configured sample rate
-        if frame_size == 0 || frame_size > max_frame_samples_for(self.sample_rate) {
-            return Err(Error::BadArg);
-        }
+        self.check_frame_size(frame_size)?;
 
         // Validate output buffer size
         if output.is_empty() {
@@ -193,8 +473,10 @@ impl Encoder {
     /// Deprecated alias for `encode_limited` (does not itself enable FEC).
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::BadArg`] for
-    /// invalid buffer sizes or frame size, or a mapped libopus error.
+    /// Returns [`Error::InvalidState`] if the encoder is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for input that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output or a
+    /// `max_data_bytes` outside it, or a mapped libopus error.
     #[deprecated(
         note = "Renamed to encode_limited; enabling FEC requires set_inband_fec + set_packet_loss_perc"
     )]
@@ -209,77 +491,766 @@ impl Encoder {
 
     /// Encode f32 PCM into an Opus packet.
     ///
+    /// Input passes through the high-pass pre-filter and input gain first when enabled.
+    ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::BadArg`] for
-    /// invalid buffer sizes or frame size, or a mapped libopus error.
+    /// Returns [`Error::InvalidState`] if the encoder is invalid,
+    /// [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`] for input that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output, or a mapped
+    /// libopus error.
     pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
-        if self.raw.is_null() {
-            return Err(Error::InvalidState);
-        }
-        if input.is_empty() {
+        self.encode_float_ptr(input, output.as_mut_ptr(), output.len())
+    }
+
+    /// Float counterpart of [`Self::encode_into_uninit`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn encode_float_into_uninit<'a>(
+        &mut self,
+        input: &[f32],
+        output: &'a mut [MaybeUninit<u8>],
+    ) -> Result<&'a [u8]> {
+        let len = self.encode_float_ptr(input, output.as_mut_ptr().cast(), output.len())?;
+        Ok(unsafe { std::slice::from_raw_parts(output.as_ptr().cast(), len) })
+    }
+
+    fn encode_float_ptr(
+        &mut self,
+        input: &[f32],
+        output: *mut u8,
+        output_len: usize,
+    ) -> Result<usize> {
+        self.observe(input.len(), |enc| match enc.pre.take() {
+            None => enc.encode_float_raw(input, output, output_len),
+            Some(mut pre) => {
+                let result = enc.encode_float_raw(pre.apply_f32(input)?, output, output_len);
+                enc.pre = Some(pre);
+                result
+            }
+        })
+    }
+
+    /// Apply `overrides`, then encode 16-bit PCM as [`Self::encode`] does.
+    ///
+    /// Only overrides that differ from the value last set on this encoder, through this
+    /// method or the matching setter, reach libopus; the others cost no CTL call. Applied
+    /// overrides stay in effect for later frames.
+    ///
+    /// # Errors
+    /// Returns the error of the first override libopus rejects, leaving the frame
+    /// unencoded, or any error of [`Self::encode`].
+    pub fn encode_with(
+        &mut self,
+        input: &[i16],
+        output: &mut [u8],
+        overrides: &EncodeOverrides,
+    ) -> Result<usize> {
+        self.apply_overrides(overrides)?;
+        self.encode(input, output)
+    }
+
+    /// Apply `overrides`, then encode f32 PCM as [`Self::encode_float`] does.
+    ///
+    /// See [`Self::encode_with`].
+    ///
+    /// # Errors
+    /// Returns the error of the first override libopus rejects, leaving the frame
+    /// unencoded, or any error of [`Self::encode_float`].
+    pub fn encode_float_with(
+        &mut self,
+        input: &[f32],
+        output: &mut [u8],
+        overrides: &EncodeOverrides,
+    ) -> Result<usize> {
+        self.apply_overrides(overrides)?;
+        self.encode_float(input, output)
+    }
+
+    /// Encode 16-bit PCM as [`Self::encode`] does, reporting with the packet size whether
+    /// the frame was coded as DTX and the final range coder state.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`], plus any error of the follow-up queries.
+    pub fn encode_ex(&mut self, input: &[i16], output: &mut [u8]) -> Result<EncodeResult> {
+        let bytes = self.encode(input, output)?;
+        self.encode_result(bytes)
+    }
+
+    /// Encode f32 PCM as [`Self::encode_float`] does, reporting the details
+    /// [`Self::encode_ex`] does.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`], plus any error of the follow-up queries.
+    pub fn encode_float_ex(&mut self, input: &[f32], output: &mut [u8]) -> Result<EncodeResult> {
+        let bytes = self.encode_float(input, output)?;
+        self.encode_result(bytes)
+    }
+
+    /// Encode left-justified 32-bit PCM (e.g. ALSA `S32_LE`, or 24-bit samples in the top
+    /// three bytes) into an Opus packet.
+    ///
+    /// Samples are scaled by 2^-31 and encoded through the float path. Unless
+    /// [`Self::set_lsb_depth`] was called, the first call sets the LSB depth to 24 bits,
+    /// the most libopus makes use of.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn encode_i32(&mut self, input: &[i32], output: &mut [u8]) -> Result<usize> {
+        self.encode_converted(input.iter().map(|&s| i32_to_f32(s)), output)
+    }
+
+    /// Encode packed little-endian 24-bit PCM (ALSA `S24_3LE`, three bytes per sample)
+    /// into an Opus packet.
+    ///
+    /// Samples are converted exactly as [`Self::encode_i32`] converts them, including the
+    /// automatic 24-bit LSB depth.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `input` is not a whole number of 3-byte samples,
+    /// otherwise the same errors as [`Self::encode_float`].
+    pub fn encode_s24(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize> {
+        if !input.len().is_multiple_of(3) {
             return Err(Error::BadArg);
         }
-        if !input.len().is_multiple_of(self.channels.as_usize()) {
-            return Err(Error::BadArg);
+        let samples = input
+            .chunks_exact(3)
+            .map(|b| i32_to_f32(i32::from_le_bytes([0, b[0], b[1], b[2]])));
+        self.encode_converted(samples, output)
+    }
+
+    /// Encode samples converted into the float scratch buffer, defaulting the LSB depth to
+    /// 24 bits.
+    fn encode_converted(
+        &mut self,
+        samples: impl Iterator<Item = f32>,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        if !self.lsb_depth_set {
+            self.set_lsb_depth(24)?;
         }
-        let frame_size = input.len() / self.channels.as_usize();
+        let mut scratch = std::mem::take(&mut self.f32_scratch);
+        scratch.clear();
+        scratch.extend(samples);
+        let result = self.encode_float(&scratch, output);
+        self.f32_scratch = scratch;
+        result
+    }
+
+    /// Encode channels `offset..offset + channels` of an interleaved buffer holding
+    /// `stride` samples per frame, e.g. two channels of an 8-channel capture buffer.
+    ///
+    /// Every complete frame of `input` (`input.len() / stride`, a trailing partial frame
+    /// is ignored) is encoded, so the frame count must be a valid Opus frame size. The
+    /// selected channels are gathered into a scratch buffer owned by the encoder, so
+    /// callers need no copy of their own.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the channels do not fit within `stride`, plus any
+    /// error from [`Self::encode`].
+    pub fn encode_strided(
+        &mut self,
+        input: &[i16],
+        stride: usize,
+        offset: usize,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        let mut scratch = std::mem::take(&mut self.i16_scratch);
+        let result = gather(
+            input,
+            &mut scratch,
+            self.channels.as_usize(),
+            stride,
+            offset,
+        )
+        .and_then(|()| self.encode(&scratch, output));
+        self.i16_scratch = scratch;
+        result
+    }
+
+    /// Float counterpart of [`Self::encode_strided`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_strided`], with errors from [`Self::encode_float`].
+    pub fn encode_float_strided(
+        &mut self,
+        input: &[f32],
+        stride: usize,
+        offset: usize,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        let mut scratch = std::mem::take(&mut self.f32_scratch);
+        let result = gather(
+            input,
+            &mut scratch,
+            self.channels.as_usize(),
+            stride,
+            offset,
+        )
+        .and_then(|()| self.encode_float(&scratch, output));
+        self.f32_scratch = scratch;
+        result
+    }
+
+    /// Encode planar PCM, one slice per channel, e.g. from a DAW or DSP graph.
+    ///
+    /// The planes are interleaved into a scratch buffer owned by the encoder, which is
+    /// sized on the first call and reused afterwards.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if there is not one plane per channel or the planes differ
+    /// in length, plus any error from [`Self::encode`].
+    pub fn encode_planar(&mut self, planes: &[&[i16]], output: &mut [u8]) -> Result<usize> {
+        let mut scratch = std::mem::take(&mut self.i16_scratch);
+        let result = interleave(planes, &mut scratch, self.channels.as_usize())
+            .and_then(|()| self.encode(&scratch, output));
+        self.i16_scratch = scratch;
+        result
+    }
+
+    /// Float counterpart of [`Self::encode_planar`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_planar`], with errors from [`Self::encode_float`].
+    pub fn encode_float_planar(&mut self, planes: &[&[f32]], output: &mut [u8]) -> Result<usize> {
+        let mut scratch = std::mem::take(&mut self.f32_scratch);
+        let result = interleave(planes, &mut scratch, self.channels.as_usize())
+            .and_then(|()| self.encode_float(&scratch, output));
+        self.f32_scratch = scratch;
+        result
+    }
+
+    /// Reject interleaved input that is not a whole number of samples per channel.
+    const fn check_channels(&self, len: usize) -> Result<()> {
+        let channels = self.channels.as_usize();
+        if !len.is_multiple_of(channels) {
+            return Err(Error::UnevenChannels { len, channels });
+        }
+        Ok(())
+    }
+
+    /// Reject frame sizes outside the Opus limits or, with a fixed expert frame duration,
+    /// of any other length: libopus would otherwise encode only the first part of a
+    /// longer frame.
+    fn check_frame_size(&self, frame_size: usize) -> Result<()> {
+        let invalid = Err(Error::InvalidFrameSize {
+            samples: frame_size,
+        });
         if frame_size == 0 || frame_size > max_frame_samples_for(self.sample_rate) {
-            return Err(Error::BadArg);
+            return invalid;
+        }
+        match self.frame_duration.samples(self.sample_rate) {
+            Some(fixed) if fixed != frame_size => invalid,
+            _ => Ok(()),
+        }
+    }
+
+    /// Run one encode call and feed it into the enabled statistics.
+    fn observe(
+        &mut self,
+        input_len: usize,
+        encode: impl FnOnce(&mut Self) -> Result<usize>,
+    ) -> Result<usize> {
+        #[cfg(feature = "timing")]
+        let started = Instant::now();
+        let result = encode(self);
+        #[cfg(feature = "timing")]
+        self.timing.record(started.elapsed());
+        if let Ok(bytes) = result {
+            let samples = input_len / self.channels.as_usize();
+            let duration = self.sample_rate.duration_of(samples);
+            if let Some(position) = self.position.as_mut() {
+                *position += samples as u64;
+            }
+            if self.range_chain.is_some() {
+                let range = self.final_range()?;
+                if let Some(chain) = self.range_chain.as_mut() {
+                    chain.push(range);
+                }
+            }
+            if self.stats.is_some() {
+                let dtx = self.in_dtx()?;
+                if let Some(stats) = self.stats.as_mut() {
+                    stats.record(bytes, duration, dtx);
+                }
+            }
         }
-        if output.is_empty() || output.len() > i32::MAX as usize {
+        result
+    }
+
+    /// Float counterpart of [`Self::encode_raw`].
+    fn encode_float_raw(
+        &mut self,
+        input: &[f32],
+        output: *mut u8,
+        output_len: usize,
+    ) -> Result<usize> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        self.check_channels(input.len())?;
+        let frame_size = input.len() / self.channels.as_usize();
+        self.check_frame_size(frame_size)?;
+        if output_len == 0 || output_len > i32::MAX as usize {
             return Err(Error::BadArg);
         }
         let frame_i32 = i32::try_from(frame_size).map_err(|_| Error::BadArg)?;
-        let out_len_i32 = i32::try_from(output.len()).map_err(|_| Error::BadArg)?;
-        let n = unsafe {
-            opus_encode_float(
-                self.raw,
-                input.as_ptr(),
-                frame_i32,
-                output.as_mut_ptr(),
-                out_len_i32,
-            )
-        };
+        let out_len_i32 = i32::try_from(output_len).map_err(|_| Error::BadArg)?;
+        let n =
+            unsafe { opus_encode_float(self.raw, input.as_ptr(), frame_i32, output, out_len_i32) };
         if n < 0 {
             return Err(Error::from_code(n));
         }
         usize::try_from(n).map_err(|_| Error::InternalError)
     }
 
+    /// Encode a fixed-size interleaved `i16` frame into a fixed-size packet buffer.
+    ///
+    /// Array lengths are checked at compile time to be non-empty; the frame must still
+    /// hold a whole number of samples per channel for this encoder.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`].
+    pub fn encode_array<const N: usize, const M: usize>(
+        &mut self,
+        input: &[i16; N],
+        output: &mut [u8; M],
+    ) -> Result<usize> {
+        const { assert!(N > 0 && M > 0, "frame and packet arrays must not be empty") };
+        self.encode(input, output)
+    }
+
+    /// Encode a fixed-size interleaved `f32` frame into a fixed-size packet buffer.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn encode_float_array<const N: usize, const M: usize>(
+        &mut self,
+        input: &[f32; N],
+        output: &mut [u8; M],
+    ) -> Result<usize> {
+        const { assert!(N > 0 && M > 0, "frame and packet arrays must not be empty") };
+        self.encode_float(input, output)
+    }
+
+    /// Encode exactly `duration` of interleaved i16 PCM.
+    ///
+    /// Unlike [`Self::encode`], which accepts any legal Opus frame length, this rejects
+    /// input that is not precisely the intended duration, catching off-by-a-frame buffer
+    /// bugs before they reach libopus.
+    ///
+    /// # Errors
+    /// Returns [`Error::FrameLengthMismatch`] with both lengths if `input.len()` is not
+    /// `duration.samples(sample_rate) * channels`, otherwise the same errors as
+    /// [`Self::encode`].
+    pub fn encode_frame(
+        &mut self,
+        input: &[i16],
+        duration: FrameSize,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        self.check_duration(input.len(), duration)?;
+        self.encode(input, output)
+    }
+
+    /// Float counterpart of [`Self::encode_frame`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_frame`], with errors from [`Self::encode_float`].
+    pub fn encode_float_frame(
+        &mut self,
+        input: &[f32],
+        duration: FrameSize,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        self.check_duration(input.len(), duration)?;
+        self.encode_float(input, output)
+    }
+
+    fn check_duration(&self, len: usize, duration: FrameSize) -> Result<()> {
+        let expected = duration.samples(self.sample_rate) * self.channels.as_usize();
+        if len == expected {
+            Ok(())
+        } else {
+            Err(Error::FrameLengthMismatch {
+                expected,
+                actual: len,
+            })
+        }
+    }
+
+    /// Encode a [`PcmFrame`] of `i16` samples, checking its rate and layout first.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the frame's sample rate or channels differ from the
+    /// encoder's, otherwise the same errors as [`Self::encode`].
+    pub fn encode_pcm_frame(&mut self, frame: &PcmFrame<i16>, output: &mut [u8]) -> Result<usize> {
+        frame.ensure_matches(self.sample_rate, self.channels)?;
+        self.encode(frame.samples(), output)
+    }
+
+    /// Encode a [`PcmFrame`] of `f32` samples, checking its rate and layout first.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the frame's sample rate or channels differ from the
+    /// encoder's, otherwise the same errors as [`Self::encode_float`].
+    pub fn encode_pcm_frame_float(
+        &mut self,
+        frame: &PcmFrame<f32>,
+        output: &mut [u8],
+    ) -> Result<usize> {
+        frame.ensure_matches(self.sample_rate, self.channels)?;
+        self.encode_float(frame.samples(), output)
+    }
+
+    /// Encode i16 PCM as [`Self::encode`] does, after checking that `output` holds the
+    /// worst-case packet.
+    ///
+    /// libopus never overruns `output`: given less room than a frame would take it lowers
+    /// the quality until the packet fits. This rejects such buffers up front instead, with
+    /// the size to reallocate to.
+    ///
+    /// # Errors
+    /// Returns [`Error::OutputTooSmall`] carrying [`Self::encode_required_size`] if
+    /// `output` is shorter, otherwise the same errors as [`Self::encode`].
+    pub fn encode_checked(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.check_output(input.len(), output.len())?;
+        self.encode(input, output)
+    }
+
+    /// Encode f32 PCM as [`Self::encode_float`] does, after the check
+    /// [`Self::encode_checked`] makes.
+    ///
+    /// # Errors
+    /// Returns [`Error::OutputTooSmall`] carrying [`Self::encode_required_size`] if
+    /// `output` is shorter, otherwise the same errors as [`Self::encode_float`].
+    pub fn encode_float_checked(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.check_output(input.len(), output.len())?;
+        self.encode_float(input, output)
+    }
+
+    fn check_output(&self, input_len: usize, output_len: usize) -> Result<()> {
+        let required = self.encode_required_size(input_len);
+        if output_len < required {
+            return Err(Error::OutputTooSmall { required });
+        }
+        Ok(())
+    }
+
+    /// Encode i16 PCM into a newly allocated packet of exactly the encoded length.
+    ///
+    /// The buffer is sized for the worst case of the frame's duration, so this never
+    /// fails with [`Error::BufferTooSmall`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`].
+    pub fn encode_vec(&mut self, input: &[i16]) -> Result<Vec<u8>> {
+        let mut packet = vec![0u8; self.encode_required_size(input.len())];
+        let len = self.encode(input, &mut packet)?;
+        packet.truncate(len);
+        Ok(packet)
+    }
+
+    /// Encode f32 PCM into a newly allocated packet of exactly the encoded length.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn encode_float_vec(&mut self, input: &[f32]) -> Result<Vec<u8>> {
+        let mut packet = vec![0u8; self.encode_required_size(input.len())];
+        let len = self.encode_float(input, &mut packet)?;
+        packet.truncate(len);
+        Ok(packet)
+    }
+
+    /// Encode each of `frames` as [`Self::encode`] does, appending one packet per frame to
+    /// `out`.
+    ///
+    /// Packets are encoded into a scratch buffer owned by the encoder and copied out at
+    /// their exact length, so a long batch allocates nothing beyond the packets
+    /// themselves.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`]; the batch stops at the first failing frame, leaving the
+    /// packets of the frames before it in `out`.
+    pub fn encode_batch(&mut self, frames: &[&[i16]], out: &mut Vec<Vec<u8>>) -> Result<()> {
+        self.batch(frames, out, Self::encode)
+    }
+
+    /// Float counterpart of [`Self::encode_batch`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_batch`], with [`Self::encode_float`]'s errors per frame.
+    pub fn encode_float_batch(&mut self, frames: &[&[f32]], out: &mut Vec<Vec<u8>>) -> Result<()> {
+        self.batch(frames, out, Self::encode_float)
+    }
+
+    fn batch<T>(
+        &mut self,
+        frames: &[&[T]],
+        out: &mut Vec<Vec<u8>>,
+        encode: fn(&mut Self, &[T], &mut [u8]) -> Result<usize>,
+    ) -> Result<()> {
+        let mut scratch = std::mem::take(&mut self.packet_scratch);
+        out.reserve(frames.len());
+        let mut result = Ok(());
+        for frame in frames {
+            let capacity = self.encode_required_size(frame.len());
+            if scratch.len() < capacity {
+                scratch.resize(capacity, 0);
+            }
+            match encode(self, frame, &mut scratch) {
+                Ok(len) => out.push(scratch[..len].to_vec()),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        self.packet_scratch = scratch;
+        result
+    }
+
+    /// Output size that holds the packet for `input_len` interleaved samples whatever the
+    /// bitrate, as for CBR at [`Bitrate::Max`]; see [`crate::constants::max_packet_size`].
+    #[must_use]
+    pub fn encode_required_size(&self, input_len: usize) -> usize {
+        let per_channel = (input_len / self.channels.as_usize()) as u64;
+        let rate = u64::from(self.sample_rate as u32);
+        max_packet_size(Duration::from_micros(per_channel * 1_000_000 / rate))
+    }
+
+    /// Enable a high-pass pre-filter at `cutoff_hz` (e.g. [`crate::filter::DEFAULT_HIGH_PASS_HZ`]),
+    /// or disable it with `None`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the cutoff is not below the Nyquist frequency.
+    pub fn set_high_pass(&mut self, cutoff_hz: Option<f32>) -> Result<()> {
+        let filter = match cutoff_hz {
+            Some(hz) => Some(HighPassFilter::new(self.sample_rate, self.channels, hz)?),
+            None => None,
+        };
+        self.update_pre(|pre| pre.set_high_pass(filter));
+        Ok(())
+    }
+
+    /// Cutoff of the high-pass pre-filter, if enabled.
+    #[must_use]
+    pub fn high_pass(&self) -> Option<f32> {
+        self.pre
+            .as_ref()
+            .and_then(PreFilter::high_pass)
+            .map(HighPassFilter::cutoff_hz)
+    }
+
+    /// Amplify (or attenuate) every input sample by `db` before encoding, after the
+    /// high-pass pre-filter, or disable the gain stage with `None`.
+    ///
+    /// Samples pushed past full scale are limited with `opus_pcm_soft_clip` rather than
+    /// clipped hard; its state is kept across frames and cleared by [`Self::reset`]. This
+    /// applies to every encode method, and so to a [`crate::stream::StreamEncoder`]
+    /// configured through its `encoder_mut`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `db` is not finite.
+    pub fn set_input_gain(&mut self, db: Option<f32>) -> Result<()> {
+        if db.is_some_and(|db| !db.is_finite()) {
+            return Err(Error::BadArg);
+        }
+        self.update_pre(|pre| pre.set_gain_db(db));
+        Ok(())
+    }
+
+    /// Input gain in dB, if enabled.
+    #[must_use]
+    pub fn input_gain(&self) -> Option<f32> {
+        self.pre.as_ref().and_then(PreFilter::gain_db)
+    }
+
+    /// Change the pre-processing chain, dropping it once no stage is left enabled.
+    fn update_pre(&mut self, change: impl FnOnce(&mut PreFilter)) {
+        let channels = self.channels;
+        let pre = self.pre.get_or_insert_with(|| PreFilter::new(channels));
+        change(pre);
+        if pre.is_idle() {
+            self.pre = None;
+        }
+    }
+
+    /// Track produced bitrate over a rolling `window` of encoded audio as part of
+    /// [`Self::stats`], enabling them if needed, or stop tracking it with `None`.
+    ///
+    /// Enabling (or changing the window) starts from an empty window.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `window` is zero.
+    pub fn set_bitrate_stats(&mut self, window: Option<Duration>) -> Result<()> {
+        let window = window.map(BitrateTracker::new).transpose()?;
+        if window.is_some() || self.stats.is_some() {
+            self.stats
+                .get_or_insert_with(EncoderStats::new)
+                .set_window(window);
+        }
+        Ok(())
+    }
+
+    /// Rolling average/peak bitrate and deviation from the configured target.
+    ///
+    /// Returns `None` when stats are disabled or nothing was encoded yet.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn bitrate_stats(&mut self) -> Result<Option<BitrateStats>> {
+        if self.stats.as_ref().and_then(EncoderStats::window).is_none() {
+            return Ok(None);
+        }
+        let target = self.get_int_ctl(OPUS_GET_BITRATE_REQUEST as i32)?;
+        Ok(self
+            .stats
+            .as_ref()
+            .and_then(EncoderStats::window)
+            .and_then(|tracker| tracker.stats(target)))
+    }
+
+    /// Start (from an empty chain) or stop chaining the `final_range` of every produced
+    /// packet, for comparison with the receiver's [`Decoder::range_chain`](crate::Decoder::range_chain).
+    pub fn set_range_chain(&mut self, enabled: bool) {
+        self.range_chain = enabled.then(RangeChain::new);
+    }
+
+    /// Chain over the packets produced since it was enabled. Not cleared by [`Self::reset`].
+    #[must_use]
+    pub const fn range_chain(&self) -> Option<RangeChain> {
+        self.range_chain
+    }
+
+    /// Start collecting [`EncoderStats`] over every produced packet, or stop collecting
+    /// them, including the window of [`Self::set_bitrate_stats`]. Enabling stats that are
+    /// already collected keeps them; [`Self::reset_stats`] restarts them from zero.
+    ///
+    /// Each encode then also queries the DTX state, one extra CTL call per frame.
+    pub fn set_stats(&mut self, enabled: bool) {
+        if !enabled {
+            self.stats = None;
+        } else if self.stats.is_none() {
+            self.stats = Some(EncoderStats::new());
+        }
+    }
+
+    /// Totals and packet size histogram since stats were enabled or last reset with
+    /// [`Self::reset_stats`]. Not cleared by [`Self::reset`], which only empties the
+    /// rolling bitrate window.
+    #[must_use]
+    pub const fn stats(&self) -> Option<&EncoderStats> {
+        self.stats.as_ref()
+    }
+
+    /// Restart the stats from zero, keeping them enabled.
+    pub fn reset_stats(&mut self) {
+        if let Some(stats) = self.stats.as_mut() {
+            stats.clear();
+        }
+    }
+
+    /// Start (from zero) or stop counting the samples each successful encode consumes.
+    pub fn set_position_tracking(&mut self, enabled: bool) {
+        self.position = enabled.then_some(0);
+    }
+
+    /// Samples per channel encoded since position tracking was enabled, at the input
+    /// rate. Not cleared by [`Self::reset`].
+    #[must_use]
+    pub const fn samples_consumed(&self) -> Option<u64> {
+        self.position
+    }
+
+    /// [`Self::samples_consumed`] at 48 kHz: the Ogg Opus granule position (or RTP
+    /// timestamp offset) at the end of the last packet, pre-skip included.
+    #[must_use]
+    pub const fn granule_position(&self) -> Option<u64> {
+        match self.position {
+            Some(samples) => Some(samples * 48_000 / self.sample_rate as u64),
+            None => None,
+        }
+    }
+
+    /// CTL mutations applied to this encoder, with their outcome.
+    #[cfg(feature = "ctl-audit")]
+    #[must_use]
+    pub const fn ctl_audit(&self) -> &CtlAuditLog {
+        &self.ctl_audit
+    }
+
+    /// Wall time spent in encode calls (including the pre-filter), or `None` before the first call.
+    #[cfg(feature = "timing")]
+    #[must_use]
+    pub fn timing_stats(&self) -> Option<TimingStats> {
+        self.timing.stats()
+    }
+
     // ===== Common encoder CTLs =====
 
-    /// Enable/disable in-band FEC generation (decoder can recover from losses).
+    /// Enable/disable in-band FEC generation (decoder can recover from losses).
+    ///
+    /// Shorthand for [`Self::set_inband_fec_mode`] with [`InbandFec::On`] or [`InbandFec::Off`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn set_inband_fec(&mut self, enabled: bool) -> Result<()> {
+        self.set_inband_fec_mode(if enabled {
+            InbandFec::On
+        } else {
+            InbandFec::Off
+        })
+    }
+    /// Query whether in-band FEC is enabled, in either [`InbandFec`] mode.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn inband_fec(&mut self) -> Result<bool> {
+        self.get_bool_ctl(OPUS_GET_INBAND_FEC_REQUEST as i32)
+    }
+
+    /// Select the in-band FEC mode.
     ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
-    pub fn set_inband_fec(&mut self, enabled: bool) -> Result<()> {
-        self.simple_ctl(OPUS_SET_INBAND_FEC_REQUEST as i32, i32::from(enabled))
+    pub fn set_inband_fec_mode(&mut self, mode: InbandFec) -> Result<()> {
+        self.simple_ctl(OPUS_SET_INBAND_FEC_REQUEST as i32, mode as i32)?;
+        self.applied.inband_fec = Some(mode);
+        Ok(())
     }
-    /// Query in-band FEC setting.
+    /// Query the in-band FEC mode.
     ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
-    pub fn inband_fec(&mut self) -> Result<bool> {
-        self.get_bool_ctl(OPUS_GET_INBAND_FEC_REQUEST as i32)
+    pub fn inband_fec_mode(&mut self) -> Result<InbandFec> {
+        InbandFec::from_ctl(self.get_int_ctl(OPUS_GET_INBAND_FEC_REQUEST as i32)?)
     }
 
-    /// Hint expected packet loss percentage [0..=100].
+    /// Hint expected packet loss percentage.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::BadArg`] for out-of-range values,
-    /// or a mapped libopus error.
-    pub fn set_packet_loss_perc(&mut self, perc: i32) -> Result<()> {
-        if !(0..=100).contains(&perc) {
-            return Err(Error::BadArg);
-        }
-        self.simple_ctl(OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, perc)
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn set_packet_loss_perc(&mut self, perc: PacketLossPerc) -> Result<()> {
+        self.simple_ctl(
+            OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+            i32::from(perc.value()),
+        )?;
+        self.applied.packet_loss_perc = Some(perc);
+        Ok(())
     }
     /// Query packet loss percentage hint.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
-    pub fn packet_loss_perc(&mut self) -> Result<i32> {
-        self.get_int_ctl(OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32)
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::InternalError`] if
+    /// libopus reports a value outside 0..=100, or a mapped libopus error.
+    pub fn packet_loss_perc(&mut self) -> Result<PacketLossPerc> {
+        let perc = self.get_int_ctl(OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32)?;
+        u8::try_from(perc)
+            .ok()
+            .filter(|&perc| perc <= 100)
+            .map(PacketLossPerc::new)
+            .ok_or(Error::InternalError)
     }
 
     /// Enable/disable DTX (discontinuous transmission).
@@ -303,6 +1274,17 @@ impl Encoder {
     pub fn in_dtx(&mut self) -> Result<bool> {
         self.get_bool_ctl(OPUS_GET_IN_DTX_REQUEST as i32)
     }
+    /// Whether the last frame was coded as silence, i.e. as a DTX or comfort-noise packet
+    /// the sender may choose not to transmit.
+    ///
+    /// Relies on DTX: with it disabled every frame is coded in full and this is `false`;
+    /// check the input with [`crate::activity::is_silence`] instead.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn last_frame_was_silence(&mut self) -> Result<bool> {
+        self.in_dtx()
+    }
 
     /// Constrain VBR to reduce instant bitrate swings.
     ///
@@ -340,9 +1322,17 @@ impl Encoder {
     /// Force a specific bandwidth (overrides automatic).
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    /// Returns [`Error::BadArg`] if `bw` is wider than the input sample rate can represent
+    /// (see [`Bandwidth::for_rate`]), [`Error::InvalidState`] if the encoder is invalid, or
+    /// a mapped libopus error.
     pub fn set_bandwidth(&mut self, bw: Bandwidth) -> Result<()> {
-        self.simple_ctl(OPUS_SET_BANDWIDTH_REQUEST as i32, bw as i32)
+        if bw.is_wider_than(Bandwidth::for_rate(self.sample_rate)) {
+            return Err(Error::BadArg);
+        }
+        self.simple_ctl(OPUS_SET_BANDWIDTH_REQUEST as i32, bw as i32)?;
+        self.forced_bandwidth = Some(bw);
+        self.mode_bandwidth = None;
+        Ok(())
     }
     /// Query current forced bandwidth.
     ///
@@ -397,6 +1387,33 @@ impl Encoder {
         }
     }
 
+    /// Switch the application mode, e.g. from [`Application::Voip`] to
+    /// [`Application::Audio`] when a call turns to music.
+    ///
+    /// libopus only accepts a different mode before the first packet is encoded, so on a
+    /// running encoder call [`Self::reset`] first; other settings survive the reset.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the mode differs from the current one and the encoder
+    /// has encoded since its creation or last reset.
+    pub fn set_application(&mut self, application: Application) -> Result<()> {
+        self.simple_ctl(OPUS_SET_APPLICATION_REQUEST as i32, application as i32)
+    }
+
+    /// Query the current application mode.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn application(&mut self) -> Result<Application> {
+        let v = self.get_int_ctl(OPUS_GET_APPLICATION_REQUEST as i32)?;
+        match v {
+            x if x == Application::Voip as i32 => Ok(Application::Voip),
+            x if x == Application::Audio as i32 => Ok(Application::Audio),
+            x if x == Application::RestrictedLowDelay as i32 => Ok(Application::RestrictedLowDelay),
+            _ => Err(Error::InternalError),
+        }
+    }
+
     /// Encoder algorithmic lookahead (in samples at 48 kHz domain).
     ///
     /// # Errors
@@ -404,6 +1421,32 @@ impl Encoder {
     pub fn lookahead(&mut self) -> Result<i32> {
         self.get_int_ctl(OPUS_GET_LOOKAHEAD_REQUEST as i32)
     }
+
+    /// Total algorithmic delay this encoder adds to the signal path.
+    ///
+    /// This is the codec lookahead at the input sample rate; it already covers the
+    /// matching decoder-side delay.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn latency(&mut self) -> Result<Duration> {
+        let lookahead = usize::try_from(self.lookahead()?).map_err(|_| Error::InternalError)?;
+        Ok(self.sample_rate.duration_of(lookahead))
+    }
+
+    /// Lookahead converted to 48 kHz samples: the pre-skip a container such as Ogg Opus
+    /// must signal so decoders drop the encoder's start-up delay.
+    ///
+    /// See [`crate::StreamEncoder`] for padding the final frame and the matching end trim.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, [`Error::InternalError`]
+    /// for a lookahead that does not fit, or a mapped libopus error.
+    pub fn pre_skip(&mut self) -> Result<u16> {
+        let lookahead = u64::try_from(self.lookahead()?).map_err(|_| Error::InternalError)?;
+        u16::try_from(lookahead * 48_000 / self.sample_rate as u64)
+            .map_err(|_| Error::InternalError)
+    }
     /// Final RNG state from the last encode (debugging/bitstream id).
     ///
     /// # Errors
@@ -427,7 +1470,9 @@ impl Encoder {
         if !(8..=24).contains(&bits) {
             return Err(Error::BadArg);
         }
-        self.simple_ctl(OPUS_SET_LSB_DEPTH_REQUEST as i32, bits)
+        self.simple_ctl(OPUS_SET_LSB_DEPTH_REQUEST as i32, bits)?;
+        self.lsb_depth_set = true;
+        Ok(())
     }
     /// Query input LSB depth.
     ///
@@ -439,10 +1484,15 @@ impl Encoder {
 
     /// Set expert frame duration choice.
     ///
+    /// Any duration other than [`ExpertFrameDuration::FromArgument`] makes the encode
+    /// methods reject frames of a different length.
+    ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
     pub fn set_expert_frame_duration(&mut self, dur: ExpertFrameDuration) -> Result<()> {
-        self.simple_ctl(OPUS_SET_EXPERT_FRAME_DURATION_REQUEST as i32, dur as i32)
+        self.simple_ctl(OPUS_SET_EXPERT_FRAME_DURATION_REQUEST as i32, dur as i32)?;
+        self.frame_duration = dur;
+        Ok(())
     }
     /// Query expert frame duration.
     ///
@@ -460,7 +1510,9 @@ impl Encoder {
             x if x == crate::bindings::OPUS_FRAMESIZE_60_MS => ExpertFrameDuration::Ms60,
             x if x == crate::bindings::OPUS_FRAMESIZE_80_MS => ExpertFrameDuration::Ms80,
             x if x == crate::bindings::OPUS_FRAMESIZE_100_MS => ExpertFrameDuration::Ms100,
-            _ => ExpertFrameDuration::Ms120,
+            x if x == crate::bindings::OPUS_FRAMESIZE_120_MS => ExpertFrameDuration::Ms120,
+            x if x == crate::bindings::OPUS_FRAMESIZE_ARG => ExpertFrameDuration::FromArgument,
+            _ => return Err(Error::InternalError),
         })
     }
 
@@ -500,8 +1552,231 @@ impl Encoder {
         self.get_bool_ctl(OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST as i32)
     }
 
+    #[cfg(feature = "dred")]
+    /// Set how much deep redundancy (DRED) to embed in each packet, in 10 ms frames
+    /// (`0..=104`); `0` disables it. Receivers recover losses from it with
+    /// [`crate::DredDecoder`], so pair it with [`Self::set_packet_loss_perc`] and enough
+    /// bitrate for the extra payload.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error
+    /// (e.g. [`Error::BadArg`] for a duration out of range).
+    pub fn set_dred_duration(&mut self, frames: i32) -> Result<()> {
+        self.simple_ctl(OPUS_SET_DRED_DURATION_REQUEST as i32, frames)
+    }
+    #[cfg(feature = "dred")]
+    /// Query DRED duration in 10 ms frames.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn dred_duration(&mut self) -> Result<i32> {
+        self.get_int_ctl(OPUS_GET_DRED_DURATION_REQUEST as i32)
+    }
+    #[cfg(feature = "dred")]
+    /// Load the DRED encoder model from a DNN blob, replacing the built-in weights.
+    ///
+    /// libopus points into the blob rather than copying the weights, so the encoder keeps
+    /// a reference to it until another blob loads successfully, or the encoder is dropped
+    /// or [reinitialized](Self::reinit).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error
+    /// for a malformed blob.
+    pub fn set_dnn_blob(&mut self, blob: &DnnBlob) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        self.dnn_blobs.push(blob.clone());
+        let r = unsafe {
+            opus_encoder_ctl(
+                self.raw,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                blob.as_bytes().as_ptr(),
+                blob.ctl_len(),
+            )
+        };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        // Every model now points into `blob`, so the earlier ones can go.
+        let last = self.dnn_blobs.len() - 1;
+        self.dnn_blobs.drain(..last);
+        Ok(())
+    }
+
+    /// Pin every frame to one coding mode, or return to automatic selection with `None`.
+    ///
+    /// An expert setting for interop and conformance testing; it uses libopus' private
+    /// `OPUS_SET_FORCE_MODE` CTL. SILK-only needs a bandwidth of at most wideband and
+    /// hybrid at least super-wideband, so when no bandwidth is forced this also forces the
+    /// widest one the mode and sample rate allow; that bandwidth is returned to automatic
+    /// when the mode changes or is cleared, while one from [`Self::set_bandwidth`] stays.
+    /// libopus still codes 2.5 and 5 ms frames with CELT, and may fall back to SILK-only
+    /// from hybrid when the bitrate is too low for it.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for SILK-only or hybrid with
+    /// [`Application::RestrictedLowDelay`] or a fixed frame duration below 10 ms, for a
+    /// forced bandwidth outside the mode's range, or for hybrid below 24 kHz; otherwise
+    /// [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn force_mode(&mut self, mode: Option<CodecMode>) -> Result<()> {
+        let Some(mode) = mode else {
+            self.simple_ctl(OPUS_SET_FORCE_MODE_REQUEST as i32, OPUS_AUTO)?;
+            self.forced_mode = None;
+            return self.set_mode_bandwidth(None);
+        };
+        if mode != CodecMode::CeltOnly {
+            let short_frames = matches!(
+                self.frame_duration,
+                ExpertFrameDuration::Ms2_5 | ExpertFrameDuration::Ms5
+            );
+            if short_frames || self.application()? == Application::RestrictedLowDelay {
+                return Err(Error::BadArg);
+            }
+        }
+        let widest = Bandwidth::for_rate(self.sample_rate);
+        let bandwidth = match mode {
+            CodecMode::SilkOnly if widest.is_wider_than(Bandwidth::Wideband) => {
+                Some(Bandwidth::Wideband)
+            }
+            CodecMode::SilkOnly => Some(widest),
+            CodecMode::Hybrid if widest.is_wider_than(Bandwidth::Wideband) => Some(widest),
+            CodecMode::Hybrid => return Err(Error::BadArg),
+            CodecMode::CeltOnly => None,
+        };
+        if let (Some(widest), Some(forced)) = (bandwidth, self.forced_bandwidth) {
+            let fits = match mode {
+                CodecMode::Hybrid => forced.is_wider_than(Bandwidth::Wideband),
+                _ => !forced.is_wider_than(widest),
+            };
+            if !fits {
+                return Err(Error::BadArg);
+            }
+        }
+        self.simple_ctl(OPUS_SET_FORCE_MODE_REQUEST as i32, mode as i32)?;
+        self.forced_mode = Some(mode);
+        if self.forced_bandwidth.is_some() {
+            return Ok(());
+        }
+        self.set_mode_bandwidth(bandwidth)
+    }
+
+    /// Force the bandwidth a forced mode needs, or return it to automatic with `None`.
+    fn set_mode_bandwidth(&mut self, bandwidth: Option<Bandwidth>) -> Result<()> {
+        if bandwidth == self.mode_bandwidth {
+            return Ok(());
+        }
+        let value = bandwidth.map_or(OPUS_AUTO, |bw| bw as i32);
+        self.simple_ctl(OPUS_SET_BANDWIDTH_REQUEST as i32, value)?;
+        self.mode_bandwidth = bandwidth;
+        Ok(())
+    }
+
+    /// Mode pinned by [`Self::force_mode`], or `None` for automatic selection.
+    #[must_use]
+    pub const fn forced_mode(&self) -> Option<CodecMode> {
+        self.forced_mode
+    }
+
+    /// Capture the current settings, e.g. to carry them over to a new encoder when a
+    /// call is renegotiated.
+    ///
+    /// An automatic bitrate is captured as the value it currently resolves to.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    pub fn settings(&mut self) -> Result<EncoderSettings> {
+        let signal = match self.get_int_ctl(OPUS_GET_SIGNAL_REQUEST as i32)? {
+            OPUS_AUTO => None,
+            _ => Some(self.signal()?),
+        };
+        Ok(EncoderSettings {
+            bitrate: self.bitrate()?,
+            complexity: self.complexity()?,
+            vbr: self.vbr()?,
+            vbr_constraint: self.vbr_constraint()?,
+            inband_fec: self.inband_fec_mode()?,
+            packet_loss_perc: self.packet_loss_perc()?,
+            dtx: self.dtx()?,
+            signal,
+            bandwidth: self.forced_bandwidth,
+            max_bandwidth: self.max_bandwidth()?,
+            force_channels: self.force_channels()?,
+            lsb_depth: self.lsb_depth()?,
+            expert_frame_duration: self.expert_frame_duration()?,
+            prediction_disabled: self.prediction_disabled()?,
+            phase_inversion_disabled: self.phase_inversion_disabled()?,
+        })
+    }
+
+    /// Apply every setting of a snapshot taken by [`Self::settings`], in the order
+    /// [`EncoderBuilder::build`] uses.
+    ///
+    /// # Errors
+    /// Returns the first error from the setters, e.g. [`Error::BadArg`] for a forced
+    /// bandwidth wider than this encoder's sample rate can represent; the settings before
+    /// it stay applied.
+    pub fn apply_settings(&mut self, settings: &EncoderSettings) -> Result<()> {
+        match settings.signal {
+            Some(signal) => self.set_signal(signal)?,
+            None => self.simple_ctl(OPUS_SET_SIGNAL_REQUEST as i32, OPUS_AUTO)?,
+        }
+        self.set_max_bandwidth(settings.max_bandwidth)?;
+        if let Some(bw) = settings.bandwidth {
+            self.set_bandwidth(bw)?;
+        } else {
+            // Keep the bandwidth a forced mode needs rather than returning to automatic.
+            let value = self.mode_bandwidth.map_or(OPUS_AUTO, |bw| bw as i32);
+            self.simple_ctl(OPUS_SET_BANDWIDTH_REQUEST as i32, value)?;
+            self.forced_bandwidth = None;
+        }
+        self.set_force_channels(settings.force_channels)?;
+        self.set_complexity(settings.complexity)?;
+        self.set_vbr(settings.vbr)?;
+        self.set_vbr_constraint(settings.vbr_constraint)?;
+        self.set_bitrate(settings.bitrate)?;
+        self.set_inband_fec_mode(settings.inband_fec)?;
+        self.set_packet_loss_perc(settings.packet_loss_perc)?;
+        self.set_dtx(settings.dtx)?;
+        self.set_lsb_depth(settings.lsb_depth)?;
+        self.set_expert_frame_duration(settings.expert_frame_duration)?;
+        self.set_prediction_disabled(settings.prediction_disabled)?;
+        self.set_phase_inversion_disabled(settings.phase_inversion_disabled)
+    }
+
     // --- internal helpers ---
+    fn encode_result(&mut self, bytes: usize) -> Result<EncodeResult> {
+        Ok(EncodeResult {
+            bytes,
+            dtx: self.in_dtx()?,
+            final_range: self.final_range()?,
+        })
+    }
+    fn apply_overrides(&mut self, overrides: &EncodeOverrides) -> Result<()> {
+        if let Some(bitrate) = overrides.bitrate
+            && self.applied.bitrate != Some(bitrate)
+        {
+            self.set_bitrate(bitrate)?;
+        }
+        if let Some(perc) = overrides.packet_loss_perc
+            && self.applied.packet_loss_perc != Some(perc)
+        {
+            self.set_packet_loss_perc(perc)?;
+        }
+        if let Some(mode) = overrides.inband_fec
+            && self.applied.inband_fec != Some(mode)
+        {
+            self.set_inband_fec_mode(mode)?;
+        }
+        Ok(())
+    }
     fn simple_ctl(&mut self, req: i32, val: i32) -> Result<()> {
+        let result = self.simple_ctl_raw(req, val);
+        #[cfg(feature = "ctl-audit")]
+        self.ctl_audit.record(req, val, &result);
+        result
+    }
+    fn simple_ctl_raw(&mut self, req: i32, val: i32) -> Result<()> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
@@ -540,25 +1815,37 @@ impl Encoder {
 
     /// Set target bitrate.
     ///
+    /// libopus still caps an explicit rate at 300 kbps per channel; [`Self::bitrate`]
+    /// reports the value in effect.
+    ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
+    /// Returns [`Error::BadArg`] for an explicit rate outside
+    /// [`Bitrate::MIN_BPS`]`..=`[`Bitrate::MAX_BPS`], which libopus would otherwise clamp
+    /// silently; [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus
+    /// error.
     pub fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<()> {
-        if self.raw.is_null() {
-            return Err(Error::InvalidState);
-        }
-
-        let result =
-            unsafe { opus_encoder_ctl(self.raw, OPUS_SET_BITRATE_REQUEST as i32, bitrate.value()) };
-
-        if result != 0 {
-            return Err(Error::from_code(result));
+        if !bitrate.is_valid() {
+            return Err(Error::BadArg);
         }
-
+        self.simple_ctl(OPUS_SET_BITRATE_REQUEST as i32, bitrate.value())?;
+        self.applied.bitrate = Some(bitrate);
         Ok(())
     }
 
+    /// Set target bitrate in kilobits per second, as [`Self::set_bitrate`] with
+    /// [`Bitrate::kbps`].
+    ///
+    /// # Errors
+    /// Same as [`Self::set_bitrate`].
+    pub fn set_bitrate_kbps(&mut self, kbps: u32) -> Result<()> {
+        self.set_bitrate(Bitrate::kbps(kbps))
+    }
+
     /// Query current bitrate.
     ///
+    /// libopus reports the effective rate: an explicit rate after its per-channel cap,
+    /// and [`Bitrate::Auto`] or [`Bitrate::Max`] resolved for the last frame size.
+    ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
     pub fn bitrate(&mut self) -> Result<Bitrate> {
@@ -586,23 +1873,10 @@ impl Encoder {
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
     pub fn set_complexity(&mut self, complexity: Complexity) -> Result<()> {
-        if self.raw.is_null() {
-            return Err(Error::InvalidState);
-        }
-
-        let result = unsafe {
-            opus_encoder_ctl(
-                self.raw,
-                OPUS_SET_COMPLEXITY_REQUEST as i32,
-                complexity.value() as i32,
-            )
-        };
-
-        if result != 0 {
-            return Err(Error::from_code(result));
-        }
-
-        Ok(())
+        self.simple_ctl(
+            OPUS_SET_COMPLEXITY_REQUEST as i32,
+            complexity.value() as i32,
+        )
     }
 
     /// Query encoder complexity.
@@ -637,18 +1911,7 @@ impl Encoder {
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder is invalid, or a mapped libopus error.
     pub fn set_vbr(&mut self, enabled: bool) -> Result<()> {
-        if self.raw.is_null() {
-            return Err(Error::InvalidState);
-        }
-
-        let vbr = i32::from(enabled);
-        let result = unsafe { opus_encoder_ctl(self.raw, OPUS_SET_VBR_REQUEST as i32, vbr) };
-
-        if result != 0 {
-            return Err(Error::from_code(result));
-        }
-
-        Ok(())
+        self.simple_ctl(OPUS_SET_VBR_REQUEST as i32, i32::from(enabled))
     }
 
     /// Query VBR status.
@@ -694,14 +1957,427 @@ impl Encoder {
         if r != 0 {
             return Err(Error::from_code(r));
         }
+        if let Some(pre) = self.pre.as_mut() {
+            pre.reset();
+        }
+        if let Some(stats) = self.stats.as_mut() {
+            stats.clear_window();
+        }
+        #[cfg(feature = "timing")]
+        self.timing.clear();
+        Ok(())
+    }
+
+    /// Reinitialize for a new stream as if freshly created, reusing the libopus state
+    /// allocation and the wrapper's scratch buffers, e.g. when a server moves on to its
+    /// next call.
+    ///
+    /// Every CTL and wrapper option returns to its default. A mono encoder switched to
+    /// stereo needs a larger state: one allocated by libopus is then replaced, while one
+    /// in caller memory from [`Self::init_in`] must have been given room for it.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for an invalid sample rate or a caller-provided state too
+    /// small for `channels`, or any error from creating the encoder; after an error from
+    /// libopus the encoder is unusable and reports [`Error::InvalidState`].
+    pub fn reinit(
+        &mut self,
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: Application,
+    ) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        if !sample_rate.is_valid() {
+            return Err(Error::BadArg);
+        }
+        if Self::size(channels)? > self.capacity {
+            if !self.owned {
+                return Err(Error::BadArg);
+            }
+            *self = Self::new(sample_rate, channels, application)?;
+            return Ok(());
+        }
+        let raw = std::mem::replace(&mut self.raw, ptr::null_mut());
+        let r = unsafe {
+            opus_encoder_init(
+                raw,
+                sample_rate.as_i32(),
+                channels.as_i32(),
+                application as i32,
+            )
+        };
+        if r != 0 {
+            if self.owned {
+                unsafe { opus_encoder_destroy(raw) };
+            }
+            return Err(Error::from_code(r));
+        }
+        let scratch = (
+            std::mem::take(&mut self.f32_scratch),
+            std::mem::take(&mut self.i16_scratch),
+            std::mem::take(&mut self.packet_scratch),
+        );
+        // `wrap` starts without DNN blobs: init points the models back at the built-in
+        // weights or marks them unloaded, so libopus no longer reads the old ones.
+        let owned = std::mem::replace(&mut self.owned, false);
+        *self = Self::wrap(raw, owned, self.capacity, sample_rate, channels)?;
+        (self.f32_scratch, self.i16_scratch, self.packet_scratch) = scratch;
         Ok(())
     }
 }
 
 impl Drop for Encoder {
     fn drop(&mut self) {
-        unsafe {
-            opus_encoder_destroy(self.raw);
+        if self.owned {
+            unsafe {
+                opus_encoder_destroy(self.raw);
+            }
+        }
+    }
+}
+
+/// Collects encoder settings and applies them all in [`Self::build`].
+///
+/// Settings left unset keep the libopus defaults. Created by [`Encoder::builder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct EncoderBuilder {
+    sample_rate: SampleRate,
+    channels: Channels,
+    application: Application,
+    bitrate: Option<Bitrate>,
+    complexity: Option<Complexity>,
+    vbr: Option<bool>,
+    vbr_constraint: Option<bool>,
+    inband_fec: Option<InbandFec>,
+    packet_loss_perc: Option<PacketLossPerc>,
+    dtx: Option<bool>,
+    signal: Option<Signal>,
+    bandwidth: Option<Bandwidth>,
+    max_bandwidth: Option<Bandwidth>,
+    force_channels: Option<Channels>,
+    lsb_depth: Option<i32>,
+    expert_frame_duration: Option<ExpertFrameDuration>,
+    prediction_disabled: Option<bool>,
+    phase_inversion_disabled: Option<bool>,
+}
+
+impl EncoderBuilder {
+    /// See [`Encoder::set_bitrate`].
+    pub const fn bitrate(mut self, bitrate: Bitrate) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// See [`Encoder::set_complexity`].
+    pub const fn complexity(mut self, complexity: Complexity) -> Self {
+        self.complexity = Some(complexity);
+        self
+    }
+
+    /// See [`Encoder::set_vbr`].
+    pub const fn vbr(mut self, enabled: bool) -> Self {
+        self.vbr = Some(enabled);
+        self
+    }
+
+    /// See [`Encoder::set_vbr_constraint`].
+    pub const fn vbr_constraint(mut self, constrained: bool) -> Self {
+        self.vbr_constraint = Some(constrained);
+        self
+    }
+
+    /// See [`Encoder::set_inband_fec_mode`].
+    pub const fn inband_fec(mut self, mode: InbandFec) -> Self {
+        self.inband_fec = Some(mode);
+        self
+    }
+
+    /// See [`Encoder::set_packet_loss_perc`].
+    pub const fn packet_loss_perc(mut self, perc: PacketLossPerc) -> Self {
+        self.packet_loss_perc = Some(perc);
+        self
+    }
+
+    /// See [`Encoder::set_dtx`].
+    pub const fn dtx(mut self, enabled: bool) -> Self {
+        self.dtx = Some(enabled);
+        self
+    }
+
+    /// See [`Encoder::set_signal`].
+    pub const fn signal(mut self, signal: Signal) -> Self {
+        self.signal = Some(signal);
+        self
+    }
+
+    /// See [`Encoder::set_bandwidth`].
+    pub const fn bandwidth(mut self, bw: Bandwidth) -> Self {
+        self.bandwidth = Some(bw);
+        self
+    }
+
+    /// See [`Encoder::set_max_bandwidth`].
+    pub const fn max_bandwidth(mut self, bw: Bandwidth) -> Self {
+        self.max_bandwidth = Some(bw);
+        self
+    }
+
+    /// See [`Encoder::set_force_channels`].
+    pub const fn force_channels(mut self, channels: Channels) -> Self {
+        self.force_channels = Some(channels);
+        self
+    }
+
+    /// See [`Encoder::set_lsb_depth`].
+    pub const fn lsb_depth(mut self, bits: i32) -> Self {
+        self.lsb_depth = Some(bits);
+        self
+    }
+
+    /// See [`Encoder::set_expert_frame_duration`].
+    pub const fn expert_frame_duration(mut self, dur: ExpertFrameDuration) -> Self {
+        self.expert_frame_duration = Some(dur);
+        self
+    }
+
+    /// See [`Encoder::set_prediction_disabled`].
+    pub const fn prediction_disabled(mut self, disabled: bool) -> Self {
+        self.prediction_disabled = Some(disabled);
+        self
+    }
+
+    /// See [`Encoder::set_phase_inversion_disabled`].
+    pub const fn phase_inversion_disabled(mut self, disabled: bool) -> Self {
+        self.phase_inversion_disabled = Some(disabled);
+        self
+    }
+
+    /// Create the encoder and apply every setting, or fail without returning a
+    /// half-configured encoder.
+    ///
+    /// # Errors
+    /// Returns an [`EncoderBuildError`] naming the first setting that failed (`"new"` if
+    /// creation itself did) together with its error.
+    pub fn build(self) -> std::result::Result<Encoder, EncoderBuildError> {
+        fn apply<T>(
+            encoder: &mut Encoder,
+            setting: &'static str,
+            value: Option<T>,
+            set: impl FnOnce(&mut Encoder, T) -> Result<()>,
+        ) -> std::result::Result<(), EncoderBuildError> {
+            match value {
+                Some(value) => {
+                    set(encoder, value).map_err(|error| EncoderBuildError { setting, error })
+                }
+                None => Ok(()),
+            }
         }
+
+        let mut encoder =
+            Encoder::new(self.sample_rate, self.channels, self.application).map_err(|error| {
+                EncoderBuildError {
+                    setting: "new",
+                    error,
+                }
+            })?;
+        let e = &mut encoder;
+        apply(e, "signal", self.signal, Encoder::set_signal)?;
+        apply(
+            e,
+            "max_bandwidth",
+            self.max_bandwidth,
+            Encoder::set_max_bandwidth,
+        )?;
+        apply(e, "bandwidth", self.bandwidth, Encoder::set_bandwidth)?;
+        apply(e, "force_channels", self.force_channels, |e, ch| {
+            e.set_force_channels(Some(ch))
+        })?;
+        apply(e, "complexity", self.complexity, Encoder::set_complexity)?;
+        apply(e, "vbr", self.vbr, Encoder::set_vbr)?;
+        apply(
+            e,
+            "vbr_constraint",
+            self.vbr_constraint,
+            Encoder::set_vbr_constraint,
+        )?;
+        apply(e, "bitrate", self.bitrate, Encoder::set_bitrate)?;
+        apply(
+            e,
+            "inband_fec",
+            self.inband_fec,
+            Encoder::set_inband_fec_mode,
+        )?;
+        apply(
+            e,
+            "packet_loss_perc",
+            self.packet_loss_perc,
+            Encoder::set_packet_loss_perc,
+        )?;
+        apply(e, "dtx", self.dtx, Encoder::set_dtx)?;
+        apply(e, "lsb_depth", self.lsb_depth, Encoder::set_lsb_depth)?;
+        apply(
+            e,
+            "expert_frame_duration",
+            self.expert_frame_duration,
+            Encoder::set_expert_frame_duration,
+        )?;
+        apply(
+            e,
+            "prediction_disabled",
+            self.prediction_disabled,
+            Encoder::set_prediction_disabled,
+        )?;
+        apply(
+            e,
+            "phase_inversion_disabled",
+            self.phase_inversion_disabled,
+            Encoder::set_phase_inversion_disabled,
+        )?;
+        Ok(encoder)
+    }
+}
+
+/// Encoder settings captured by [`Encoder::settings`] and restored by
+/// [`Encoder::apply_settings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct EncoderSettings {
+    /// Target bitrate.
+    pub bitrate: Bitrate,
+    /// Computational complexity.
+    pub complexity: Complexity,
+    /// Variable bitrate.
+    pub vbr: bool,
+    /// Constrained VBR.
+    pub vbr_constraint: bool,
+    /// In-band FEC mode.
+    pub inband_fec: InbandFec,
+    /// Expected packet loss.
+    pub packet_loss_perc: PacketLossPerc,
+    /// Discontinuous transmission.
+    pub dtx: bool,
+    /// Signal hint, or `None` to let the encoder classify the input.
+    pub signal: Option<Signal>,
+    /// Bandwidth forced by [`Encoder::set_bandwidth`], or `None` for automatic.
+    pub bandwidth: Option<Bandwidth>,
+    /// Maximum bandwidth.
+    pub max_bandwidth: Bandwidth,
+    /// Forced channel count, or `None` for automatic.
+    pub force_channels: Option<Channels>,
+    /// Input LSB depth in bits.
+    pub lsb_depth: i32,
+    /// Expert frame duration.
+    pub expert_frame_duration: ExpertFrameDuration,
+    /// Inter-frame prediction disabled.
+    pub prediction_disabled: bool,
+    /// Stereo phase inversion disabled.
+    pub phase_inversion_disabled: bool,
+}
+
+/// Per-frame adjustments for [`Encoder::encode_with`]; `None` leaves a setting as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOverrides {
+    /// Target bitrate.
+    pub bitrate: Option<Bitrate>,
+    /// Expected packet loss.
+    pub packet_loss_perc: Option<PacketLossPerc>,
+    /// In-band FEC mode.
+    pub inband_fec: Option<InbandFec>,
+}
+
+/// Outcome of [`Encoder::encode_ex`] and [`Encoder::encode_float_ex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeResult {
+    /// Bytes written to the output buffer.
+    pub bytes: usize,
+    /// Whether the encoder was in DTX for this frame, coding it as a comfort-noise update
+    /// of a few bytes or a bare TOC byte that senders may skip.
+    pub dtx: bool,
+    /// Final range coder state, to compare against [`crate::Decoder::final_range`].
+    pub final_range: u32,
+}
+
+/// Failure of [`EncoderBuilder::build`]: the setting being applied and its error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderBuildError {
+    setting: &'static str,
+    error: Error,
+}
+
+impl EncoderBuildError {
+    /// Name of the builder method whose setting failed, or `"new"` if creating the
+    /// encoder did.
+    #[must_use]
+    pub const fn setting(&self) -> &'static str {
+        self.setting
+    }
+
+    /// The underlying error.
+    #[must_use]
+    pub const fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for EncoderBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to apply encoder setting `{}`: {}",
+            self.setting, self.error
+        )
+    }
+}
+
+impl std::error::Error for EncoderBuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<EncoderBuildError> for Error {
+    fn from(err: EncoderBuildError) -> Self {
+        err.error
+    }
+}
+
+/// Scale a left-justified 32-bit sample to `[-1.0, 1.0)`.
+#[allow(clippy::cast_precision_loss)]
+fn i32_to_f32(sample: i32) -> f32 {
+    sample as f32 / 2_147_483_648.0
+}
+
+/// Collect channels `offset..offset + channels` of every complete `stride`-sample frame of
+/// `src` into `dst` as contiguous interleaved PCM.
+fn gather<S: Copy>(
+    src: &[S],
+    dst: &mut Vec<S>,
+    channels: usize,
+    stride: usize,
+    offset: usize,
+) -> Result<()> {
+    if offset.checked_add(channels).is_none_or(|end| end > stride) {
+        return Err(Error::BadArg);
+    }
+    dst.clear();
+    for frame in src.chunks_exact(stride) {
+        dst.extend_from_slice(&frame[offset..offset + channels]);
+    }
+    Ok(())
+}
+
+/// Interleave one equally long plane per channel into `dst`.
+fn interleave<S: Copy>(planes: &[&[S]], dst: &mut Vec<S>, channels: usize) -> Result<()> {
+    let [first, rest @ ..] = planes else {
+        return Err(Error::BadArg);
+    };
+    if planes.len() != channels || rest.iter().any(|plane| plane.len() != first.len()) {
+        return Err(Error::BadArg);
     }
+    dst.clear();
+    dst.extend((0..first.len()).flat_map(|i| planes.iter().map(move |plane| plane[i])));
+    Ok(())
 }
@@ -10,12 +10,48 @@ use std::fmt;
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Opus error variants.
+///
+/// Codes returned by libopus map to the unit variant of the same name. The wrapper's own
+/// checks of PCM buffer layouts and frame sizes raise the variants carrying details
+/// instead, such as [`Error::UnevenChannels`] or [`Error::InvalidFrameSize`], so a bad slice
+/// length is not mistaken for libopus rejecting the call; [`Error::libopus_code`] tells
+/// the two apart. Other argument checks in this crate still use [`Error::BadArg`].
+///
+/// New variants may be added in minor releases, so matches need a wildcard arm.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Error {
     /// Bad argument passed to a function.
     BadArg,
     /// Provided buffer was too small.
     BufferTooSmall,
+    /// Output buffer smaller than the worst case for the input, with the size that
+    /// always suffices; see [`crate::Encoder::encode_checked`].
+    OutputTooSmall {
+        /// Bytes the output buffer needs.
+        required: usize,
+    },
+    /// Interleaved buffer length that does not match the frame duration asked for, e.g. in
+    /// [`crate::Encoder::encode_frame`] or a multistream `frame_size_per_ch`.
+    FrameLengthMismatch {
+        /// Interleaved samples the duration needs.
+        expected: usize,
+        /// Interleaved samples passed in.
+        actual: usize,
+    },
+    /// Interleaved PCM buffer whose length is not a multiple of the channel count.
+    UnevenChannels {
+        /// Samples in the buffer.
+        len: usize,
+        /// Channels the codec is configured for.
+        channels: usize,
+    },
+    /// Samples per channel that are zero, above 120 ms, or, with a fixed expert frame
+    /// duration, of any other length.
+    InvalidFrameSize {
+        /// Samples per channel passed in.
+        samples: usize,
+    },
     /// Internal libopus error.
     InternalError,
     /// Packet is invalid or unsupported.
@@ -28,12 +64,14 @@ pub enum Error {
     AllocFail,
     /// Unknown error code.
     Unknown(i32),
+    /// I/O failure while reading or writing a container.
+    Io(std::io::ErrorKind),
 }
 
 impl Error {
-    /// Map a libopus error code to [`Error`].
+    /// Map an error code returned by libopus to the matching variant.
     #[must_use]
-    pub fn from_code(code: i32) -> Self {
+    pub const fn from_code(code: i32) -> Self {
         match code {
             OPUS_BAD_ARG => Self::BadArg,
             OPUS_BUFFER_TOO_SMALL => Self::BufferTooSmall,
@@ -42,7 +80,51 @@ impl Error {
             OPUS_UNIMPLEMENTED => Self::Unimplemented,
             OPUS_INVALID_STATE => Self::InvalidState,
             OPUS_ALLOC_FAIL => Self::AllocFail,
-            _ => Self::Unknown(code),
+            code => Self::Unknown(code),
+        }
+    }
+
+    /// The libopus error code this variant stands for, or `None` for the variants only
+    /// this crate's checks raise: [`Error::OutputTooSmall`], [`Error::FrameLengthMismatch`],
+    /// [`Error::UnevenChannels`], [`Error::InvalidFrameSize`] and [`Error::Io`].
+    #[must_use]
+    pub const fn libopus_code(&self) -> Option<i32> {
+        match *self {
+            Self::OutputTooSmall { .. }
+            | Self::FrameLengthMismatch { .. }
+            | Self::UnevenChannels { .. }
+            | Self::InvalidFrameSize { .. }
+            | Self::Io(_) => None,
+            Self::BadArg => Some(OPUS_BAD_ARG),
+            Self::BufferTooSmall => Some(OPUS_BUFFER_TOO_SMALL),
+            Self::InternalError => Some(OPUS_INTERNAL_ERROR),
+            Self::InvalidPacket => Some(OPUS_INVALID_PACKET),
+            Self::Unimplemented => Some(OPUS_UNIMPLEMENTED),
+            Self::InvalidState => Some(OPUS_INVALID_STATE),
+            Self::AllocFail => Some(OPUS_ALLOC_FAIL),
+            Self::Unknown(code) => Some(code),
+        }
+    }
+
+    /// The error with its details stripped: [`Error::OutputTooSmall`] becomes
+    /// [`Error::BufferTooSmall`], while [`Error::FrameLengthMismatch`],
+    /// [`Error::UnevenChannels`] and [`Error::InvalidFrameSize`] become [`Error::BadArg`];
+    /// every other variant is returned unchanged.
+    #[must_use]
+    pub const fn kind(&self) -> Self {
+        match *self {
+            Self::BadArg
+            | Self::FrameLengthMismatch { .. }
+            | Self::UnevenChannels { .. }
+            | Self::InvalidFrameSize { .. } => Self::BadArg,
+            Self::BufferTooSmall | Self::OutputTooSmall { .. } => Self::BufferTooSmall,
+            Self::InternalError => Self::InternalError,
+            Self::InvalidPacket => Self::InvalidPacket,
+            Self::Unimplemented => Self::Unimplemented,
+            Self::InvalidState => Self::InvalidState,
+            Self::AllocFail => Self::AllocFail,
+            Self::Unknown(code) => Self::Unknown(code),
+            Self::Io(kind) => Self::Io(kind),
         }
     }
 
@@ -50,9 +132,12 @@ impl Error {
     #[must_use]
     pub const fn to_code(self) -> i32 {
         match self {
-            Self::BadArg => OPUS_BAD_ARG,
-            Self::BufferTooSmall => OPUS_BUFFER_TOO_SMALL,
-            Self::InternalError => OPUS_INTERNAL_ERROR,
+            Self::BadArg
+            | Self::FrameLengthMismatch { .. }
+            | Self::UnevenChannels { .. }
+            | Self::InvalidFrameSize { .. } => OPUS_BAD_ARG,
+            Self::BufferTooSmall | Self::OutputTooSmall { .. } => OPUS_BUFFER_TOO_SMALL,
+            Self::InternalError | Self::Io(_) => OPUS_INTERNAL_ERROR,
             Self::InvalidPacket => OPUS_INVALID_PACKET,
             Self::Unimplemented => OPUS_UNIMPLEMENTED,
             Self::InvalidState => OPUS_INVALID_STATE,
@@ -67,14 +152,57 @@ impl fmt::Display for Error {
         match self {
             Self::BadArg => write!(f, "Bad arguments passed to Opus function"),
             Self::BufferTooSmall => write!(f, "Buffer too small"),
+            Self::OutputTooSmall { required } => {
+                write!(f, "Output buffer too small, {required} bytes required")
+            }
+            Self::FrameLengthMismatch { expected, actual } => write!(
+                f,
+                "Frame holds {actual} interleaved samples, {expected} expected for its duration"
+            ),
+            Self::UnevenChannels { len, channels } => write!(
+                f,
+                "Buffer of {len} interleaved samples does not divide into {channels} channels"
+            ),
+            Self::InvalidFrameSize { samples } => {
+                write!(f, "Invalid frame size of {samples} samples per channel")
+            }
             Self::InternalError => write!(f, "Internal Opus error"),
             Self::InvalidPacket => write!(f, "Invalid packet"),
             Self::Unimplemented => write!(f, "Unimplemented feature"),
             Self::InvalidState => write!(f, "Invalid state"),
             Self::AllocFail => write!(f, "Memory allocation failed"),
             Self::Unknown(code) => write!(f, "Unknown Opus error code: {code}"),
+            Self::Io(kind) => write!(f, "I/O error: {kind}"),
         }
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err.kind())
+    }
+}
+
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libopus_codes_map_to_unit_variants() {
+        let err = Error::from_code(OPUS_BAD_ARG);
+        assert_eq!(err, Error::BadArg);
+        assert_eq!(err.libopus_code(), Some(OPUS_BAD_ARG));
+        assert_eq!(err.to_code(), OPUS_BAD_ARG);
+        assert_eq!(Error::from_code(-42), Error::Unknown(-42));
+        assert_eq!(Error::OutputTooSmall { required: 1 }.libopus_code(), None);
+        let wrapper = Error::UnevenChannels {
+            len: 3,
+            channels: 2,
+        };
+        assert_eq!(wrapper.libopus_code(), None);
+        assert_eq!(wrapper.kind(), Error::BadArg);
+        assert_eq!(wrapper.to_code(), OPUS_BAD_ARG);
+    }
+}
@@ -0,0 +1,343 @@
+//! UniFFI bindings for Kotlin and Swift
+//!
+//! A curated subset of the crate exported through [UniFFI](https://mozilla.github.io/uniffi-rs/):
+//! the [`Encoder`], [`Decoder`] and [`StreamEncoder`] wrapped as thread-safe objects, the
+//! setting enums from [`crate::types`] (with
+//! [`OpusApplication`](crate::ffi::OpusApplication) standing in for [`Application`]), and
+//! [`OpusError`](crate::ffi::OpusError). Every call goes through the same validation as the
+//! Rust API; only the buffer handling differs, since foreign callers pass owned vectors and
+//! receive the encoded or decoded data back.
+
+// UniFFI lifts sequences into owned vectors, so exported methods must take them by value.
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::constants::{max_frame_samples_for, max_packet_size};
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::Error;
+use crate::stream::{StreamEncoder, StreamEnd};
+use crate::types::{Application, Bitrate, Channels, FrameSize, SampleRate};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Scratch buffer size; large enough for any packet of up to 120 ms.
+const MAX_FFI_PACKET_BYTES: usize = max_packet_size(Duration::from_millis(120));
+
+/// Error raised across the FFI boundary, one variant per [`Error::kind`].
+///
+/// The foreign message is the [`Display`](fmt::Display) text of the original error, so
+/// libopus error codes are still visible there.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum OpusError {
+    /// Bad argument passed to a function.
+    BadArg(Error),
+    /// Provided buffer was too small.
+    BufferTooSmall(Error),
+    /// Internal libopus error.
+    InternalError(Error),
+    /// Packet is invalid or unsupported.
+    InvalidPacket(Error),
+    /// Feature not implemented.
+    Unimplemented(Error),
+    /// Invalid state, including a lock poisoned by a panicking caller.
+    InvalidState(Error),
+    /// Memory allocation failure.
+    AllocFail(Error),
+    /// Unknown error code or I/O failure.
+    Other(Error),
+}
+
+impl From<Error> for OpusError {
+    fn from(err: Error) -> Self {
+        match err.kind() {
+            Error::BadArg => Self::BadArg(err),
+            Error::BufferTooSmall => Self::BufferTooSmall(err),
+            Error::InternalError => Self::InternalError(err),
+            Error::InvalidPacket => Self::InvalidPacket(err),
+            Error::Unimplemented => Self::Unimplemented(err),
+            Error::InvalidState => Self::InvalidState(err),
+            Error::AllocFail => Self::AllocFail(err),
+            _ => Self::Other(err),
+        }
+    }
+}
+
+impl fmt::Display for OpusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadArg(err)
+            | Self::BufferTooSmall(err)
+            | Self::InternalError(err)
+            | Self::InvalidPacket(err)
+            | Self::Unimplemented(err)
+            | Self::InvalidState(err)
+            | Self::AllocFail(err)
+            | Self::Other(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OpusError {}
+
+/// Mirror of [`Application`] for export, since its discriminants are libopus constants
+/// rather than literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, uniffi::Enum)]
+pub enum OpusApplication {
+    /// Best for most VoIP/videoconference applications.
+    Voip,
+    /// Best for broadcast/high-fidelity application.
+    Audio,
+    /// Only use when lowest-achievable latency is what matters most.
+    RestrictedLowDelay,
+}
+
+impl From<OpusApplication> for Application {
+    fn from(application: OpusApplication) -> Self {
+        match application {
+            OpusApplication::Voip => Self::Voip,
+            OpusApplication::Audio => Self::Audio,
+            OpusApplication::RestrictedLowDelay => Self::RestrictedLowDelay,
+        }
+    }
+}
+
+/// Result type of the exported functions.
+pub type FfiResult<T> = std::result::Result<T, OpusError>;
+
+fn lock<T>(mutex: &Mutex<T>) -> FfiResult<MutexGuard<'_, T>> {
+    mutex.lock().map_err(|_| Error::InvalidState.into())
+}
+
+/// Thread-safe [`Encoder`] handle.
+#[derive(uniffi::Object)]
+pub struct OpusEncoder {
+    inner: Mutex<Encoder>,
+}
+
+#[uniffi::export]
+impl OpusEncoder {
+    /// Create an encoder.
+    ///
+    /// # Errors
+    /// Returns any error from [`Encoder::new`].
+    #[uniffi::constructor]
+    pub fn new(
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: OpusApplication,
+    ) -> FfiResult<Arc<Self>> {
+        let encoder = Encoder::new(sample_rate, channels, application.into())?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(encoder),
+        }))
+    }
+
+    /// Set an explicit target bitrate in bits per second, or 0 to let the encoder choose.
+    ///
+    /// # Errors
+    /// Returns [`OpusError::BadArg`] if libopus rejects the bitrate.
+    pub fn set_bitrate(&self, bps: i32) -> FfiResult<()> {
+        let bitrate = if bps == 0 {
+            Bitrate::Auto
+        } else {
+            Bitrate::Custom(bps)
+        };
+        Ok(lock(&self.inner)?.set_bitrate(bitrate)?)
+    }
+
+    /// Encode one frame of interleaved i16 PCM into a packet.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::encode`].
+    pub fn encode(&self, pcm: Vec<i16>) -> FfiResult<Vec<u8>> {
+        let mut out = vec![0u8; MAX_FFI_PACKET_BYTES];
+        let len = lock(&self.inner)?.encode(&pcm, &mut out)?;
+        out.truncate(len);
+        Ok(out)
+    }
+
+    /// Encode one frame of interleaved f32 PCM into a packet.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::encode_float`].
+    pub fn encode_float(&self, pcm: Vec<f32>) -> FfiResult<Vec<u8>> {
+        let mut out = vec![0u8; MAX_FFI_PACKET_BYTES];
+        let len = lock(&self.inner)?.encode_float(&pcm, &mut out)?;
+        out.truncate(len);
+        Ok(out)
+    }
+
+    /// Reset the encoder to its freshly created state.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::reset`].
+    pub fn reset(&self) -> FfiResult<()> {
+        Ok(lock(&self.inner)?.reset()?)
+    }
+}
+
+/// Thread-safe [`Decoder`] handle.
+#[derive(uniffi::Object)]
+pub struct OpusDecoder {
+    inner: Mutex<Decoder>,
+    /// Interleaved capacity of one maximum-length frame.
+    max_frame: usize,
+}
+
+#[uniffi::export]
+impl OpusDecoder {
+    /// Create a decoder.
+    ///
+    /// # Errors
+    /// Returns any error from [`Decoder::new`].
+    #[uniffi::constructor]
+    pub fn new(sample_rate: SampleRate, channels: Channels) -> FfiResult<Arc<Self>> {
+        let decoder = Decoder::new(sample_rate, channels)?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(decoder),
+            max_frame: max_frame_samples_for(sample_rate) * channels.as_usize(),
+        }))
+    }
+
+    /// Decode `packet` to interleaved i16 PCM; an empty packet triggers concealment.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode`].
+    pub fn decode(&self, packet: Vec<u8>, fec: bool) -> FfiResult<Vec<i16>> {
+        let decoder = &mut *lock(&self.inner)?;
+        let mut pcm = vec![0i16; self.max_frame];
+        let samples = decoder.decode(&packet, &mut pcm, fec)?;
+        pcm.truncate(samples * decoder.channels().as_usize());
+        Ok(pcm)
+    }
+
+    /// Decode `packet` to interleaved f32 PCM; an empty packet triggers concealment.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode_float`].
+    pub fn decode_float(&self, packet: Vec<u8>, fec: bool) -> FfiResult<Vec<f32>> {
+        let decoder = &mut *lock(&self.inner)?;
+        let mut pcm = vec![0f32; self.max_frame];
+        let samples = decoder.decode_float(&packet, &mut pcm, fec)?;
+        pcm.truncate(samples * decoder.channels().as_usize());
+        Ok(pcm)
+    }
+
+    /// Reset the decoder to its freshly created state.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::reset`].
+    pub fn reset(&self) -> FfiResult<()> {
+        Ok(lock(&self.inner)?.reset()?)
+    }
+}
+
+/// Final packets and trim metadata, mirroring [`StreamEnd`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct OpusStreamEnd {
+    /// Packets flushing the final partial frame and the encoder's lookahead, in order.
+    pub packets: Vec<Vec<u8>>,
+    /// Silent samples per channel appended after the input.
+    pub padding: u64,
+    /// Decoder pre-skip in 48 kHz samples.
+    pub pre_skip: u16,
+    /// Samples per channel the decoded stream should play, including flushed silence.
+    pub samples: u64,
+}
+
+impl From<StreamEnd> for OpusStreamEnd {
+    fn from(end: StreamEnd) -> Self {
+        Self {
+            packets: end.packets,
+            padding: end.padding as u64,
+            pre_skip: end.pre_skip,
+            samples: end.samples,
+        }
+    }
+}
+
+/// Thread-safe [`StreamEncoder`] handle for PCM arriving in arbitrary-length chunks.
+#[derive(uniffi::Object)]
+pub struct OpusStreamEncoder {
+    inner: Mutex<StreamEncoder>,
+}
+
+#[uniffi::export]
+impl OpusStreamEncoder {
+    /// Create a stream encoder emitting one packet per `frame_size` of input.
+    ///
+    /// # Errors
+    /// Returns any error from [`StreamEncoder::new`].
+    #[uniffi::constructor]
+    pub fn new(
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: OpusApplication,
+        frame_size: FrameSize,
+    ) -> FfiResult<Arc<Self>> {
+        let encoder = StreamEncoder::new(sample_rate, channels, application.into(), frame_size)?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(encoder),
+        }))
+    }
+
+    /// Queue interleaved f32 `pcm` and return a packet for every completed frame.
+    ///
+    /// # Errors
+    /// Same as [`StreamEncoder::push`].
+    pub fn push(&self, pcm: Vec<f32>) -> FfiResult<Vec<Vec<u8>>> {
+        Ok(lock(&self.inner)?.push(&pcm)?)
+    }
+
+    /// Zero-pad and encode the queued partial frame without ending the stream; returns an
+    /// empty vector if nothing is queued.
+    ///
+    /// # Errors
+    /// Same as [`StreamEncoder::flush`].
+    pub fn flush(&self) -> FfiResult<Vec<u8>> {
+        Ok(lock(&self.inner)?.flush()?.unwrap_or_default())
+    }
+
+    /// Flush the final partial frame and report the trim metadata.
+    ///
+    /// # Errors
+    /// Same as [`StreamEncoder::finish`].
+    pub fn finish(&self) -> FfiResult<OpusStreamEnd> {
+        Ok(lock(&self.inner)?.finish()?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_round_trip_and_map_errors() {
+        let encoder =
+            OpusEncoder::new(SampleRate::Hz48000, Channels::Mono, OpusApplication::Audio).unwrap();
+        let decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+        let packet = encoder.encode(vec![0; 960]).unwrap();
+        assert_eq!(decoder.decode(packet, false).unwrap().len(), 960);
+
+        let err = encoder.encode(vec![0; 7]).unwrap_err();
+        assert!(matches!(err, OpusError::BadArg(_)));
+        assert!(matches!(
+            decoder.decode_float(vec![0xFF; 3], false),
+            Err(OpusError::InvalidPacket(_))
+        ));
+
+        let stream = OpusStreamEncoder::new(
+            SampleRate::Hz48000,
+            Channels::Stereo,
+            OpusApplication::Voip,
+            FrameSize::Ms20,
+        )
+        .unwrap();
+        assert_eq!(stream.push(vec![0.0; 2 * 1200]).unwrap().len(), 1);
+        let end = stream.finish().unwrap();
+        assert_eq!(end.samples, 1200);
+        assert!(!end.packets.is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+//! Simple IIR filters used to condition PCM before encoding
+
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use crate::packet;
+use crate::types::{Channels, SampleRate};
+
+/// Default high-pass cutoff for voice, removing rumble and DC offset.
+pub const DEFAULT_HIGH_PASS_HZ: f32 = 80.0;
+
+/// Transposed direct-form II biquad section with normalized coefficients.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    /// Section with feed-forward `b` and feedback `a` (`a0` already divided out).
+    pub(crate) const fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    pub(crate) fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.z = [0.0; 2];
+    }
+}
+
+/// Second-order Butterworth high-pass filter applied per channel to interleaved PCM.
+#[derive(Debug, Clone)]
+pub struct HighPassFilter {
+    cutoff_hz: f32,
+    sections: Vec<Biquad>,
+}
+
+impl HighPassFilter {
+    /// Create a filter for `channels` interleaved channels at `sample_rate`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] unless `0 < cutoff_hz < sample_rate / 2`.
+    pub fn new(sample_rate: SampleRate, channels: Channels, cutoff_hz: f32) -> Result<Self> {
+        let rate = f64::from(sample_rate.as_i32());
+        let cutoff = f64::from(cutoff_hz);
+        if !(cutoff > 0.0 && cutoff < rate / 2.0) {
+            return Err(Error::BadArg);
+        }
+        let w0 = 2.0 * std::f64::consts::PI * cutoff / rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin * std::f64::consts::FRAC_1_SQRT_2;
+        let a0 = 1.0 + alpha;
+        let edge = f64::midpoint(1.0, cos) / a0;
+        let section = Biquad::new(
+            [edge, -2.0 * edge, edge],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        );
+        Ok(Self {
+            cutoff_hz,
+            sections: vec![section; channels.as_usize()],
+        })
+    }
+
+    /// Create a filter with [`DEFAULT_HIGH_PASS_HZ`] cutoff.
+    ///
+    /// # Errors
+    /// Never fails for supported sample rates; kept fallible for symmetry with [`Self::new`].
+    pub fn voice(sample_rate: SampleRate, channels: Channels) -> Result<Self> {
+        Self::new(sample_rate, channels, DEFAULT_HIGH_PASS_HZ)
+    }
+
+    /// Cutoff frequency in Hz.
+    #[must_use]
+    pub const fn cutoff_hz(&self) -> f32 {
+        self.cutoff_hz
+    }
+
+    /// Filter interleaved samples in place. A trailing partial frame is left untouched.
+    pub fn process<S: Sample>(&mut self, pcm: &mut [S]) {
+        let channels = self.sections.len();
+        for frame in pcm.chunks_exact_mut(channels) {
+            for (sample, section) in frame.iter_mut().zip(self.sections.iter_mut()) {
+                let y = section.process(f64::from(sample.to_f32()));
+                *sample = S::from_f32(y as f32);
+            }
+        }
+    }
+
+    /// Clear filter history.
+    pub fn reset(&mut self) {
+        for section in &mut self.sections {
+            section.reset();
+        }
+    }
+}
+
+/// Encoder input conditioning: an optional high-pass filter followed by an optional
+/// soft-clipped gain, plus scratch buffers so encoders can process borrowed input.
+#[derive(Debug, Clone)]
+pub(crate) struct PreFilter {
+    high_pass: Option<HighPassFilter>,
+    gain: Option<InputGain>,
+    channels: usize,
+    i16_buf: Vec<i16>,
+    f32_buf: Vec<f32>,
+}
+
+/// Linear gain with per-channel `opus_pcm_soft_clip` state.
+#[derive(Debug, Clone)]
+struct InputGain {
+    db: f32,
+    factor: f32,
+    clip_mem: Vec<f32>,
+}
+
+impl PreFilter {
+    pub(crate) const fn new(channels: Channels) -> Self {
+        Self {
+            high_pass: None,
+            gain: None,
+            channels: channels.as_usize(),
+            i16_buf: Vec::new(),
+            f32_buf: Vec::new(),
+        }
+    }
+
+    /// Whether no stage is enabled, so the input can be encoded as is.
+    pub(crate) const fn is_idle(&self) -> bool {
+        self.high_pass.is_none() && self.gain.is_none()
+    }
+
+    pub(crate) const fn high_pass(&self) -> Option<&HighPassFilter> {
+        self.high_pass.as_ref()
+    }
+
+    pub(crate) fn set_high_pass(&mut self, filter: Option<HighPassFilter>) {
+        self.high_pass = filter;
+    }
+
+    pub(crate) fn gain_db(&self) -> Option<f32> {
+        self.gain.as_ref().map(|gain| gain.db)
+    }
+
+    pub(crate) fn set_gain_db(&mut self, db: Option<f32>) {
+        self.gain = db.map(|db| InputGain {
+            db,
+            factor: 10f32.powf(db / 20.0),
+            clip_mem: vec![0.0; self.channels],
+        });
+    }
+
+    pub(crate) fn reset(&mut self) {
+        if let Some(filter) = self.high_pass.as_mut() {
+            filter.reset();
+        }
+        if let Some(gain) = self.gain.as_mut() {
+            gain.clip_mem.fill(0.0);
+        }
+    }
+
+    pub(crate) fn apply_i16(&mut self, input: &[i16]) -> Result<&[i16]> {
+        self.i16_buf.clear();
+        self.i16_buf.extend_from_slice(input);
+        if let Some(filter) = self.high_pass.as_mut() {
+            filter.process(&mut self.i16_buf);
+        }
+        if self.gain.is_some() {
+            self.f32_buf.clear();
+            self.f32_buf
+                .extend(self.i16_buf.iter().map(|&s| Sample::to_f32(s)));
+            self.apply_gain()?;
+            for (out, &s) in self.i16_buf.iter_mut().zip(&self.f32_buf) {
+                *out = i16::from_f32(s);
+            }
+        }
+        Ok(&self.i16_buf)
+    }
+
+    pub(crate) fn apply_f32(&mut self, input: &[f32]) -> Result<&[f32]> {
+        self.f32_buf.clear();
+        self.f32_buf.extend_from_slice(input);
+        if let Some(filter) = self.high_pass.as_mut() {
+            filter.process(&mut self.f32_buf);
+        }
+        self.apply_gain()?;
+        Ok(&self.f32_buf)
+    }
+
+    /// Scale `f32_buf` by the gain, soft-clipping anything pushed past full scale.
+    fn apply_gain(&mut self) -> Result<()> {
+        let Some(gain) = self.gain.as_mut() else {
+            return Ok(());
+        };
+        for sample in &mut self.f32_buf {
+            *sample *= gain.factor;
+        }
+        let frames = self.f32_buf.len() / self.channels;
+        if frames == 0 {
+            return Ok(());
+        }
+        packet::soft_clip(
+            &mut self.f32_buf,
+            frames,
+            self.channels as i32,
+            &mut gain.clip_mem,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_dc_and_keeps_speech_band() {
+        let sr = SampleRate::Hz16000;
+        let mut filter = HighPassFilter::voice(sr, Channels::Mono).unwrap();
+        let mut dc = vec![0.5f32; 16_000];
+        filter.process(&mut dc);
+        assert!(dc[15_999].abs() < 1e-3);
+
+        filter.reset();
+        let mut tone: Vec<f32> = (0..16_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 1_000.0 * f64::from(i) as f32 / 16_000.0).sin())
+            .collect();
+        filter.process(&mut tone);
+        let peak = tone[8_000..].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 1.0).abs() < 0.02, "peak {peak}");
+
+        assert!(HighPassFilter::new(sr, Channels::Mono, 8_000.0).is_err());
+        assert!(HighPassFilter::new(sr, Channels::Mono, 0.0).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Runtime-checked PCM frame wrappers carrying sample rate and channel layout
+
+use crate::constants::max_frame_samples_for;
+use crate::error::{Error, Result};
+use crate::types::{Channels, FrameSize, SampleRate};
+use std::time::Duration;
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for i16 {}
+    impl Sealed for f32 {}
+}
+
+/// PCM sample formats understood by libopus (`i16` and `f32`).
+pub trait Sample: Copy + Default + PartialEq + Send + Sync + sealed::Sealed + 'static {
+    /// Convert to normalized float in `[-1.0, 1.0)`.
+    fn to_f32(self) -> f32;
+    /// Convert from normalized float, saturating where the format requires it.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for i16 {
+    fn to_f32(self) -> f32 {
+        f32::from(self) / 32768.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+    }
+}
+
+impl Sample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+/// Interleaved PCM frame tagged with its sample rate and channel layout.
+///
+/// Encoders and decoders reject frames whose rate or layout differ from their own,
+/// turning silent mismatches into errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmFrame<S: Sample> {
+    samples: Vec<S>,
+    sample_rate: SampleRate,
+    channels: Channels,
+}
+
+impl<S: Sample> PcmFrame<S> {
+    /// Wrap interleaved `samples` recorded at `sample_rate` with `channels`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `samples` is empty, not a multiple of the channel
+    /// count, or longer than the maximum Opus frame duration.
+    pub fn new(samples: Vec<S>, sample_rate: SampleRate, channels: Channels) -> Result<Self> {
+        if samples.is_empty() || !samples.len().is_multiple_of(channels.as_usize()) {
+            return Err(Error::BadArg);
+        }
+        if samples.len() / channels.as_usize() > max_frame_samples_for(sample_rate) {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            samples,
+            sample_rate,
+            channels,
+        })
+    }
+
+    /// A silent frame of the given duration.
+    #[must_use]
+    pub fn silence(sample_rate: SampleRate, channels: Channels, size: FrameSize) -> Self {
+        Self {
+            samples: vec![S::default(); size.samples(sample_rate) * channels.as_usize()],
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Interleaved samples.
+    #[must_use]
+    pub fn samples(&self) -> &[S] {
+        &self.samples
+    }
+
+    /// Mutable interleaved samples (length is fixed).
+    #[must_use]
+    pub fn samples_mut(&mut self) -> &mut [S] {
+        &mut self.samples
+    }
+
+    /// Consume the frame, returning the interleaved samples.
+    #[must_use]
+    pub fn into_samples(self) -> Vec<S> {
+        self.samples
+    }
+
+    /// Sample rate of the frame.
+    #[must_use]
+    pub const fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Channel layout of the frame.
+    #[must_use]
+    pub const fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// Samples per channel.
+    #[must_use]
+    pub fn samples_per_channel(&self) -> usize {
+        self.samples.len() / self.channels.as_usize()
+    }
+
+    /// Playback duration of the frame.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        let per_channel = self.samples_per_channel() as u64;
+        Duration::from_nanos(per_channel * 1_000_000_000 / self.sample_rate as u64)
+    }
+
+    /// Check that the frame matches a codec configured with `sample_rate` and `channels`.
+    pub(crate) fn ensure_matches(&self, sample_rate: SampleRate, channels: Channels) -> Result<()> {
+        if self.sample_rate != sample_rate || self.channels != channels {
+            return Err(Error::BadArg);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcm_frame_validates_layout() {
+        assert!(PcmFrame::new(vec![0i16; 3], SampleRate::Hz48000, Channels::Stereo).is_err());
+        assert!(PcmFrame::<f32>::new(vec![], SampleRate::Hz48000, Channels::Mono).is_err());
+        assert!(PcmFrame::new(vec![0i16; 5761], SampleRate::Hz48000, Channels::Mono).is_err());
+
+        let frame =
+            PcmFrame::<i16>::silence(SampleRate::Hz16000, Channels::Stereo, FrameSize::Ms20);
+        assert_eq!(frame.samples().len(), 640);
+        assert_eq!(frame.samples_per_channel(), 320);
+        assert_eq!(frame.duration(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn sample_conversions_saturate() {
+        assert_eq!(i16::from_f32(2.0), i16::MAX);
+        assert_eq!(i16::from_f32(-2.0), i16::MIN);
+        assert!((i16::MIN.to_f32() + 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,328 @@
+//! In-band FEC (SILK LBRR) inspection
+//!
+//! [`crate::packet_has_lbrr`] only answers whether a packet carries any redundancy.
+//! [`packet_lbrr`] decodes the SILK frame headers to report which 10/20 ms SILK frames
+//! carry LBRR data, so a receiver can tell how much of a lost packet FEC can restore.
+//! [`multistream_packet_lbrr`] does the same for every elementary stream of a
+//! multistream packet.
+
+use crate::constants::MAX_FRAME_SAMPLES_48KHZ;
+use crate::error::{Error, Result};
+use crate::packet::Toc;
+use std::time::Duration;
+
+/// `silk_LBRR_flags_2_iCDF` from libopus (`silk/tables_other.c`).
+const LBRR_FLAGS_2_ICDF: [u8; 3] = [203, 150, 0];
+/// `silk_LBRR_flags_3_iCDF` from libopus (`silk/tables_other.c`).
+const LBRR_FLAGS_3_ICDF: [u8; 7] = [215, 195, 166, 125, 110, 82, 0];
+
+/// LBRR layout of one packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LbrrInfo {
+    silk_frame_samples: usize,
+    frames: Vec<u8>,
+}
+
+impl LbrrInfo {
+    /// Samples per channel (at 48 kHz) covered by one SILK frame: 480 for 10 ms Opus
+    /// frames, 960 otherwise, or 0 for CELT-only packets.
+    #[must_use]
+    pub const fn silk_frame_samples(&self) -> usize {
+        self.silk_frame_samples
+    }
+
+    /// One bitmask per Opus frame in the packet; bit `i` is set when the `i`-th SILK frame
+    /// carries LBRR data (for any channel). Empty (DTX) frames get a mask of 0, and the
+    /// list is empty for CELT-only packets.
+    #[must_use]
+    pub fn frames(&self) -> &[u8] {
+        &self.frames
+    }
+
+    /// Total number of SILK frames with LBRR data across the packet.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.frames.iter().map(|m| m.count_ones() as usize).sum()
+    }
+
+    /// Samples per channel (at 48 kHz) of the previous packet that an FEC decode of this
+    /// packet can restore.
+    ///
+    /// libopus only uses the redundancy in the first Opus frame when decoding with FEC.
+    #[must_use]
+    pub fn recoverable_samples(&self) -> usize {
+        self.frames
+            .first()
+            .map_or(0, |m| m.count_ones() as usize * self.silk_frame_samples)
+    }
+
+    /// [`Self::recoverable_samples`] as a duration.
+    #[must_use]
+    pub fn recoverable_duration(&self) -> Duration {
+        Duration::from_micros(self.recoverable_samples() as u64 * 1_000 / 48)
+    }
+}
+
+/// Report which SILK frames of `packet` carry in-band FEC (LBRR) data.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] for an empty packet or [`Error::InvalidPacket`] if it cannot be
+/// parsed.
+pub fn packet_lbrr(packet: &[u8]) -> Result<LbrrInfo> {
+    let (toc, frames, _) = split_frames(packet, false)?;
+    lbrr_of(toc, &frames)
+}
+
+/// Report LBRR coverage for each of the `streams` elementary streams in a multistream packet.
+///
+/// The first `streams - 1` sub-packets are self-delimited (RFC 6716 appendix B); the
+/// result has one entry per stream in stream order.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `streams` is zero or the packet is empty, and
+/// [`Error::InvalidPacket`] if a sub-packet is malformed or the lengths do not add up.
+pub fn multistream_packet_lbrr(packet: &[u8], streams: u8) -> Result<Vec<LbrrInfo>> {
+    if streams == 0 || packet.is_empty() {
+        return Err(Error::BadArg);
+    }
+    let mut rest = packet;
+    (0..streams)
+        .map(|stream| {
+            let self_delimited = stream + 1 < streams;
+            let (toc, frames, consumed) = split_frames(rest, self_delimited)?;
+            let info = lbrr_of(toc, &frames)?;
+            rest = &rest[consumed..];
+            Ok(info)
+        })
+        .collect()
+}
+
+/// Replace the contents of `tocs` with the TOC of each of the `streams` elementary
+/// streams in a multistream packet.
+pub(crate) fn multistream_tocs(packet: &[u8], streams: u8, tocs: &mut Vec<Toc>) -> Result<()> {
+    tocs.clear();
+    let mut rest = packet;
+    for stream in 0..streams {
+        let (toc, _, consumed) = split_frames(rest, stream + 1 < streams)?;
+        tocs.push(toc);
+        rest = &rest[consumed..];
+    }
+    Ok(())
+}
+
+fn lbrr_of(toc: Toc, frames: &[&[u8]]) -> Result<LbrrInfo> {
+    if !toc.has_silk_layer() {
+        return Ok(LbrrInfo {
+            silk_frame_samples: 0,
+            frames: Vec::new(),
+        });
+    }
+    let opus_frame = toc.samples_per_frame(crate::SampleRate::Hz48000);
+    let silk_frame_samples = opus_frame.min(960);
+    let silk_frames = opus_frame / silk_frame_samples;
+    let channels = toc.channels().as_usize();
+    let frames = frames
+        .iter()
+        .map(|frame| {
+            if frame.is_empty() {
+                Ok(0)
+            } else {
+                frame_lbrr_mask(frame, silk_frames, channels)
+            }
+        })
+        .collect::<Result<_>>()?;
+    Ok(LbrrInfo {
+        silk_frame_samples,
+        frames,
+    })
+}
+
+/// Read a frame length (RFC 6716 section 3.2.1).
+fn read_length(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let first = *data.get(*pos).ok_or(Error::InvalidPacket)?;
+    *pos += 1;
+    if first < 252 {
+        return Ok(usize::from(first));
+    }
+    let second = *data.get(*pos).ok_or(Error::InvalidPacket)?;
+    *pos += 1;
+    Ok(usize::from(first) + 4 * usize::from(second))
+}
+
+/// Split the Opus packet at the start of `data` into its frames, returning the TOC, the
+/// frames (empty ones included) and the number of bytes the packet occupies (including
+/// padding).
+fn split_frames(data: &[u8], self_delimited: bool) -> Result<(Toc, Vec<&[u8]>, usize)> {
+    let toc = Toc::of(data)?;
+    let mut pos = 1;
+    let mut padding = 0;
+    let mut sizes = Vec::new();
+    let (count, cbr) = match toc.frame_code() {
+        0 => (1, true),
+        1 => (2, true),
+        2 => {
+            sizes.push(read_length(data, &mut pos)?);
+            (2, false)
+        }
+        _ => {
+            let header = *data.get(pos).ok_or(Error::InvalidPacket)?;
+            pos += 1;
+            let count = usize::from(header & 0x3F);
+            let duration = count * toc.samples_per_frame(crate::SampleRate::Hz48000);
+            if count == 0 || duration > MAX_FRAME_SAMPLES_48KHZ {
+                return Err(Error::InvalidPacket);
+            }
+            if header & 0x40 != 0 {
+                loop {
+                    let byte = *data.get(pos).ok_or(Error::InvalidPacket)?;
+                    pos += 1;
+                    padding += if byte == 255 { 254 } else { usize::from(byte) };
+                    if byte != 255 {
+                        break;
+                    }
+                }
+            }
+            let vbr = header & 0x80 != 0;
+            if vbr {
+                for _ in 1..count {
+                    sizes.push(read_length(data, &mut pos)?);
+                }
+            }
+            (count, !vbr)
+        }
+    };
+    let last = if self_delimited {
+        read_length(data, &mut pos)?
+    } else {
+        let available = data
+            .len()
+            .checked_sub(pos + padding)
+            .ok_or(Error::InvalidPacket)?;
+        if cbr {
+            if !available.is_multiple_of(count) {
+                return Err(Error::InvalidPacket);
+            }
+            available / count
+        } else {
+            available
+                .checked_sub(sizes.iter().sum())
+                .ok_or(Error::InvalidPacket)?
+        }
+    };
+    if cbr {
+        sizes = vec![last; count];
+    } else {
+        sizes.push(last);
+    }
+    let mut frames = Vec::with_capacity(count);
+    for size in sizes {
+        let frame = data.get(pos..pos + size).ok_or(Error::InvalidPacket)?;
+        frames.push(frame);
+        pos += size;
+    }
+    let consumed = pos + padding;
+    if consumed > data.len() || (!self_delimited && consumed != data.len()) {
+        return Err(Error::InvalidPacket);
+    }
+    Ok((toc, frames, consumed))
+}
+
+/// Decode the SILK header (VAD and LBRR flags, RFC 6716 section 4.2.3-4.2.4) of one frame.
+fn frame_lbrr_mask(frame: &[u8], silk_frames: usize, channels: usize) -> Result<u8> {
+    let mut dec = RangeDecoder::new(frame);
+    let mut any = [false; 2];
+    for flag in any.iter_mut().take(channels) {
+        for _ in 0..silk_frames {
+            dec.bit_logp(1);
+        }
+        *flag = dec.bit_logp(1);
+    }
+    let mut mask = 0u8;
+    for &flag in any.iter().take(channels) {
+        if !flag {
+            continue;
+        }
+        mask |= match silk_frames {
+            1 => 1,
+            2 => dec.icdf(&LBRR_FLAGS_2_ICDF, 8) + 1,
+            3 => dec.icdf(&LBRR_FLAGS_3_ICDF, 8) + 1,
+            _ => return Err(Error::InvalidPacket),
+        };
+    }
+    Ok(mask)
+}
+
+/// Minimal range decoder (RFC 6716 section 4.1), enough for the SILK header symbols.
+struct RangeDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    rng: u32,
+    val: u32,
+    rem: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    const SYM_BITS: u32 = 8;
+    const CODE_EXTRA: u32 = 7;
+    const CODE_TOP: u32 = 1 << 31;
+    const CODE_BOT: u32 = 1 << 23;
+
+    fn new(data: &'a [u8]) -> Self {
+        let mut dec = Self {
+            data,
+            offset: 0,
+            rng: 1 << Self::CODE_EXTRA,
+            val: 0,
+            rem: 0,
+        };
+        dec.rem = dec.read_byte();
+        dec.val = dec.rng - 1 - (dec.rem >> (Self::SYM_BITS - Self::CODE_EXTRA));
+        dec.normalize();
+        dec
+    }
+
+    fn read_byte(&mut self) -> u32 {
+        let byte = self.data.get(self.offset).copied().unwrap_or(0);
+        self.offset += 1;
+        u32::from(byte)
+    }
+
+    fn normalize(&mut self) {
+        while self.rng <= Self::CODE_BOT {
+            self.rng <<= Self::SYM_BITS;
+            let sym = self.rem;
+            self.rem = self.read_byte();
+            let sym = ((sym << Self::SYM_BITS) | self.rem) >> (Self::SYM_BITS - Self::CODE_EXTRA);
+            self.val = ((self.val << Self::SYM_BITS) + (0xFF & !sym)) & (Self::CODE_TOP - 1);
+        }
+    }
+
+    fn bit_logp(&mut self, logp: u32) -> bool {
+        let s = self.rng >> logp;
+        let bit = self.val < s;
+        if bit {
+            self.rng = s;
+        } else {
+            self.val -= s;
+            self.rng -= s;
+        }
+        self.normalize();
+        bit
+    }
+
+    fn icdf(&mut self, icdf: &[u8], ftb: u32) -> u8 {
+        let r = self.rng >> ftb;
+        let mut symbol = 0;
+        let mut t = self.rng;
+        let mut s = r * u32::from(icdf[0]);
+        while self.val < s {
+            symbol += 1;
+            t = s;
+            s = r * u32::from(icdf[symbol]);
+        }
+        self.val -= s;
+        self.rng = t - s;
+        self.normalize();
+        symbol as u8
+    }
+}
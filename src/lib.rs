@@ -13,36 +13,116 @@ mod bindings {
     include!("bindings.rs");
 }
 
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+pub mod activity;
+pub mod adaptive;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+#[cfg(feature = "ctl-audit")]
+/// Bounded log of CTL mutations for debugging who changed a codec setting.
+pub mod audit;
+pub mod codec;
 pub mod constants;
 pub mod decoder;
 #[cfg(feature = "dred")]
 /// Deep Redundancy (DRED) decoder support.
 pub mod dred;
+#[cfg(feature = "dsp")]
+/// Optional pre-processing stages (automatic gain control, noise gate).
+pub mod dsp;
 pub mod encoder;
 pub mod error;
+#[cfg(feature = "uniffi")]
+/// UniFFI bindings for Kotlin and Swift.
+pub mod ffi;
+pub mod filter;
+pub mod frame;
+pub mod lbrr;
+pub mod loudness;
+pub mod mix;
 pub mod multistream;
+pub mod ogg;
 pub mod packet;
+pub mod pool;
 pub mod projection;
+pub mod render;
 pub mod repacketizer;
+#[cfg(feature = "resample")]
+/// Decoder output resampling to arbitrary rates.
+pub mod resample;
+pub mod session;
+pub mod shared;
+pub mod simul;
+pub mod stats;
+#[cfg(feature = "proptest")]
+/// `proptest` strategies for settings, PCM frames and packets.
+pub mod strategies;
+pub mod stream;
+pub mod telemetry;
+pub mod testsignal;
+#[cfg(feature = "parallel")]
+/// Parallel offline transcoding of Ogg Opus files.
+pub mod transcode;
 pub mod types;
+pub mod watch;
 
-pub use constants::{MAX_FRAME_SAMPLES_48KHZ, MAX_PACKET_DURATION_MS, max_frame_samples_for};
-pub use decoder::Decoder;
+pub use activity::{VoiceActivity, VoiceActivityConfig, is_silence};
+pub use adaptive::{AutoTuneConfig, ComplexityController, ComplexityControllerConfig};
+#[cfg(feature = "ctl-audit")]
+pub use audit::{CtlAuditEntry, CtlAuditLog};
+pub use codec::{AudioDecoder, AudioEncoder};
+pub use constants::{
+    MAX_FRAME_BYTES, MAX_FRAME_SAMPLES_48KHZ, MAX_PACKET_DURATION_MS, MAX_PACKET_SIZE,
+    max_frame_samples_for, max_multistream_packet_size, max_packet_size,
+};
+pub use decoder::{
+    DEFAULT_MAX_CONCEALED_PACKETS, DecodeProcessing, Decoder, FecRecovery, LastPacketInfo,
+    StreamDecoder,
+};
 #[cfg(feature = "dred")]
-pub use dred::{DredDecoder, DredState};
-pub use encoder::Encoder;
+pub use dred::{DnnBlob, DredDecoder, DredState};
+#[cfg(feature = "dsp")]
+pub use dsp::{Agc, AgcConfig, NoiseGate, NoiseGateConfig};
+pub use encoder::{
+    EncodeOverrides, EncodeResult, Encoder, EncoderBuildError, EncoderBuilder, EncoderSettings,
+};
 pub use error::{Error, Result};
+pub use filter::HighPassFilter;
+pub use frame::{PcmFrame, Sample};
+pub use lbrr::{LbrrInfo, multistream_packet_lbrr, packet_lbrr};
+pub use loudness::{LoudnessMeter, Normalization};
+pub use mix::{SurroundLayout, downmix};
 pub use multistream::{MSDecoder, MSEncoder, Mapping};
 pub use packet::{
-    packet_bandwidth, packet_channels, packet_has_lbrr, packet_nb_frames, packet_nb_samples,
-    packet_parse, packet_samples_per_frame, soft_clip,
+    Toc, packet_bandwidth, packet_channels, packet_has_lbrr, packet_nb_frames, packet_nb_samples,
+    packet_pad_vec, packet_padding, packet_padding_payload, packet_parse, packet_samples_per_frame,
+    packet_set_padding_payload, packet_unpad_vec, soft_clip,
 };
-pub use projection::{ProjectionDecoder, ProjectionEncoder};
+pub use pool::{DecoderPool, EncoderPool, PoolStats, Pooled};
+pub use projection::{AmbisonicsLayout, ProjectionDecoder, ProjectionEncoder, SessionParams};
+pub use render::{AmbisonicRenderer, StereoRenderer};
 pub use repacketizer::Repacketizer;
+#[cfg(feature = "resample")]
+pub use resample::{Resampler, ResamplingDecoder};
+pub use session::{Session, SessionConfig};
+pub use shared::{SharedDecoder, SharedEncoder};
+pub use simul::SimulEncoder;
+#[cfg(feature = "timing")]
+pub use stats::TimingStats;
+pub use stats::{
+    BitrateStats, ConcealmentStats, EncoderStats, FrameLevel, RangeChain, SizeHistogram,
+};
+pub use stream::StreamEncoder;
+pub use telemetry::{AbrTelemetry, DecoderTelemetry, EncoderTelemetry, PacketSizes, Telemetry};
+#[cfg(feature = "parallel")]
+pub use transcode::{TranscodeConfig, TranscodeReport, parallel_encode, transcode, transcode_file};
 pub use types::{
-    Application, Bandwidth, Bitrate, Channels, Complexity, ExpertFrameDuration, FrameSize,
-    SampleRate, Signal,
+    Application, Bandwidth, Bitrate, Channels, CodecMode, Complexity, ExpertFrameDuration,
+    FrameSize, InbandFec, PacketLossPerc, Profile, Q8Gain, SampleRate, Signal,
 };
+pub use watch::{StreamChange, StreamConfig, StreamWatcher};
 
 #[doc(hidden)]
 pub use bindings::*;
@@ -0,0 +1,321 @@
+//! ITU-R BS.1770 loudness measurement and normalization gain helpers
+
+use crate::error::{Error, Result};
+use crate::filter::Biquad;
+use crate::frame::Sample;
+use crate::mix::SurroundLayout;
+use crate::types::SampleRate;
+
+/// Reference level used by `R128_TRACK_GAIN`/`R128_ALBUM_GAIN` tags (RFC 7845).
+pub const R128_REFERENCE_LUFS: f64 = -23.0;
+
+/// Byte offset of the little-endian Q7.8 output gain inside an `OpusHead` packet.
+const OPUS_HEAD_GAIN_OFFSET: usize = 16;
+/// Minimum size of an `OpusHead` packet (RFC 7845 section 5.1).
+const OPUS_HEAD_MIN_LEN: usize = 19;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// Gating blocks are 400 ms long and overlap by 75 %, so they are built from 100 ms steps.
+const STEPS_PER_BLOCK: usize = 4;
+const SURROUND_WEIGHT: f64 = 1.41;
+
+/// K-weighting filter pair (high-shelf followed by high-pass) for `rate`.
+fn k_weighting(rate: f64) -> [Biquad; 2] {
+    use std::f64::consts::PI;
+
+    let f0 = 1_681.974_450_955_533;
+    let gain_db = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.135_470_876_024_44;
+    let q = 0.500_327_037_323_877_3;
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+    [shelf, high_pass]
+}
+
+/// Channel weights per BS.1770: surrounds are boosted and LFE is ignored.
+fn channel_weights(channels: usize) -> Vec<f64> {
+    match SurroundLayout::from_channels(channels) {
+        Some(SurroundLayout::Surround51) => {
+            vec![1.0, 1.0, 1.0, SURROUND_WEIGHT, SURROUND_WEIGHT, 0.0]
+        }
+        Some(SurroundLayout::Surround71) => vec![
+            1.0,
+            1.0,
+            1.0,
+            SURROUND_WEIGHT,
+            SURROUND_WEIGHT,
+            SURROUND_WEIGHT,
+            SURROUND_WEIGHT,
+            0.0,
+        ],
+        None => vec![1.0; channels],
+    }
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Streaming integrated-loudness meter (BS.1770-4 gating).
+///
+/// Multichannel input is assumed to be in Vorbis order, matching mapping family 1.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    step_len: usize,
+    step_energy: f64,
+    step_filled: usize,
+    steps: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    /// Create a meter for interleaved audio with `channels` channels at `sample_rate`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `channels` is zero.
+    pub fn new(sample_rate: SampleRate, channels: usize) -> Result<Self> {
+        if channels == 0 {
+            return Err(Error::BadArg);
+        }
+        let rate = sample_rate.as_i32();
+        let filters = vec![k_weighting(f64::from(rate)); channels];
+        Ok(Self {
+            channels,
+            weights: channel_weights(channels),
+            filters,
+            step_len: usize::try_from(rate / 10).map_err(|_| Error::BadArg)?,
+            step_energy: 0.0,
+            step_filled: 0,
+            steps: Vec::new(),
+        })
+    }
+
+    /// Feed interleaved samples.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `pcm` is not a whole number of frames.
+    pub fn add<S: Sample>(&mut self, pcm: &[S]) -> Result<()> {
+        if !pcm.len().is_multiple_of(self.channels) {
+            return Err(Error::BadArg);
+        }
+        for frame in pcm.chunks_exact(self.channels) {
+            let mut energy = 0.0;
+            for ((sample, filter), weight) in
+                frame.iter().zip(self.filters.iter_mut()).zip(&self.weights)
+            {
+                let x = f64::from(sample.to_f32());
+                let [pre, rlb] = filter;
+                let y = rlb.process(pre.process(x));
+                energy += weight * y * y;
+            }
+            self.step_energy += energy;
+            self.step_filled += 1;
+            if self.step_filled == self.step_len {
+                #[allow(clippy::cast_precision_loss)]
+                self.steps.push(self.step_energy / self.step_len as f64);
+                self.step_energy = 0.0;
+                self.step_filled = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gated integrated loudness in LUFS, or `None` until a block passes the gates.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|w| w.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+            .filter(|&e| e > 0.0 && energy_to_lufs(e) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+        let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+        let relative_gate = energy_to_lufs(mean) + RELATIVE_GATE_LU;
+        let (sum, count) = blocks
+            .iter()
+            .filter(|&&e| energy_to_lufs(e) > relative_gate)
+            .fold((0.0, 0usize), |(s, n), &e| (s + e, n + 1));
+        (count > 0).then(|| energy_to_lufs(sum / count as f64))
+    }
+
+    /// Discard all measured audio and filter state.
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            for stage in filter.iter_mut() {
+                stage.reset();
+            }
+        }
+        self.step_energy = 0.0;
+        self.step_filled = 0;
+        self.steps.clear();
+    }
+}
+
+/// Measure the integrated loudness of a complete interleaved buffer.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] for a zero channel count or a partial trailing frame.
+pub fn measure_integrated<S: Sample>(
+    sample_rate: SampleRate,
+    channels: usize,
+    pcm: &[S],
+) -> Result<Option<f64>> {
+    let mut meter = LoudnessMeter::new(sample_rate, channels)?;
+    meter.add(pcm)?;
+    Ok(meter.integrated_lufs())
+}
+
+/// Gain needed to move a programme from `measured_lufs` to `target_lufs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    /// Measured integrated loudness (LUFS).
+    pub measured_lufs: f64,
+    /// Desired integrated loudness (LUFS), e.g. `-16.0`.
+    pub target_lufs: f64,
+}
+
+impl Normalization {
+    /// Normalization from `measured_lufs` to `target_lufs`.
+    #[must_use]
+    pub const fn new(measured_lufs: f64, target_lufs: f64) -> Self {
+        Self {
+            measured_lufs,
+            target_lufs,
+        }
+    }
+
+    /// Required gain in dB.
+    #[must_use]
+    pub fn gain_db(&self) -> f64 {
+        self.target_lufs - self.measured_lufs
+    }
+
+    /// Required gain as a Q7.8 value suitable for the `OpusHead` output gain field.
+    #[must_use]
+    pub fn output_gain_q78(&self) -> i16 {
+        db_to_q78(self.gain_db())
+    }
+
+    /// `R128_TRACK_GAIN` value (Q7.8) to reach the -23 LUFS reference once the decoder
+    /// has applied `output_gain_q78` from the header.
+    #[must_use]
+    pub fn r128_track_gain_q78(&self, output_gain_q78: i16) -> i16 {
+        let header_db = f64::from(output_gain_q78) / 256.0;
+        db_to_q78(R128_REFERENCE_LUFS - self.measured_lufs - header_db)
+    }
+
+    /// `R128_TRACK_GAIN=<q7.8>` comment for `OpusTags`, relative to `output_gain_q78`.
+    #[must_use]
+    pub fn r128_track_gain_tag(&self, output_gain_q78: i16) -> String {
+        format!(
+            "R128_TRACK_GAIN={}",
+            self.r128_track_gain_q78(output_gain_q78)
+        )
+    }
+
+    /// Apply the gain directly to PCM before encoding (saturating for `i16`).
+    pub fn apply<S: Sample>(&self, pcm: &mut [S]) {
+        apply_gain_db(pcm, self.gain_db());
+    }
+}
+
+/// Convert a gain in dB to saturated Q7.8 fixed point.
+#[must_use]
+pub fn db_to_q78(db: f64) -> i16 {
+    (db * 256.0)
+        .round()
+        .clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16
+}
+
+/// Overwrite the output gain field of a serialized `OpusHead` packet.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `head` is not an `OpusHead` packet.
+pub fn write_opus_head_output_gain(head: &mut [u8], gain_q78: i16) -> Result<()> {
+    if head.len() < OPUS_HEAD_MIN_LEN || !head.starts_with(b"OpusHead") {
+        return Err(Error::BadArg);
+    }
+    head[OPUS_HEAD_GAIN_OFFSET..OPUS_HEAD_GAIN_OFFSET + 2].copy_from_slice(&gain_q78.to_le_bytes());
+    Ok(())
+}
+
+/// Scale interleaved PCM by `gain_db` (saturating for `i16`).
+pub fn apply_gain_db<S: Sample>(pcm: &mut [S], gain_db: f64) {
+    let factor = 10f64.powf(gain_db / 20.0) as f32;
+    for sample in pcm {
+        *sample = S::from_f32(sample.to_f32() * factor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, seconds: u32) -> Vec<f32> {
+        (0..48_000 * seconds)
+            .map(|i| {
+                let t = f64::from(i) as f32 / 48_000.0;
+                amplitude * (2.0 * std::f32::consts::PI * 997.0 * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_tone_measures_expected_loudness() {
+        // BS.1770: a 997 Hz sine at 0 dBFS in one channel reads -3.01 LUFS.
+        let lufs = measure_integrated(SampleRate::Hz48000, 1, &sine(0.1, 3))
+            .unwrap()
+            .unwrap();
+        assert!((lufs + 23.01).abs() < 0.1, "measured {lufs}");
+        assert_eq!(
+            measure_integrated(SampleRate::Hz48000, 1, &vec![0.0f32; 48_000]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn normalization_values() {
+        let norm = Normalization::new(-23.0, -16.0);
+        assert_eq!(norm.output_gain_q78(), 7 * 256);
+        assert_eq!(norm.r128_track_gain_q78(norm.output_gain_q78()), -7 * 256);
+        assert_eq!(norm.r128_track_gain_q78(0), 0);
+        assert_eq!(norm.r128_track_gain_tag(0), "R128_TRACK_GAIN=0");
+
+        let mut head = *b"OpusHead\x01\x02\x38\x01\x80\xbb\x00\x00\x00\x00\x00";
+        write_opus_head_output_gain(&mut head, norm.output_gain_q78()).unwrap();
+        assert_eq!(i16::from_le_bytes([head[16], head[17]]), 7 * 256);
+        assert!(write_opus_head_output_gain(&mut head[..10], 0).is_err());
+
+        let mut pcm = sine(0.1, 3);
+        norm.apply(&mut pcm);
+        let lufs = measure_integrated(SampleRate::Hz48000, 1, &pcm)
+            .unwrap()
+            .unwrap();
+        assert!((lufs + 16.0).abs() < 0.1, "measured {lufs}");
+    }
+}
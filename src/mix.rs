@@ -0,0 +1,143 @@
+//! Channel downmixing for playback on stereo-only devices
+
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+
+/// -3 dB, the ITU-R BS.775 weight for centre and surround channels.
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Multichannel layouts in Vorbis channel order (as produced by mapping family 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum SurroundLayout {
+    /// 5.1: front left, centre, front right, rear left, rear right, LFE.
+    Surround51,
+    /// 7.1: front left, centre, front right, side left, side right, rear left, rear right, LFE.
+    Surround71,
+}
+
+impl SurroundLayout {
+    /// Layout for a family-1 channel count, if it is one of the supported layouts.
+    #[must_use]
+    pub const fn from_channels(channels: usize) -> Option<Self> {
+        match channels {
+            6 => Some(Self::Surround51),
+            8 => Some(Self::Surround71),
+            _ => None,
+        }
+    }
+
+    /// Number of interleaved input channels.
+    #[must_use]
+    pub const fn channels(self) -> usize {
+        match self {
+            Self::Surround51 => 6,
+            Self::Surround71 => 8,
+        }
+    }
+
+    /// Per-channel `(left, right)` weights, normalized so a full-scale signal on every
+    /// input channel cannot clip either output. The LFE channel is dropped.
+    #[must_use]
+    pub fn coefficients(self) -> &'static [(f32, f32)] {
+        const K51: f32 = 1.0 / (1.0 + 2.0 * MINUS_3DB);
+        const K71: f32 = 1.0 / (1.0 + 3.0 * MINUS_3DB);
+        const S51: f32 = MINUS_3DB * K51;
+        const S71: f32 = MINUS_3DB * K71;
+        match self {
+            Self::Surround51 => &[
+                (K51, 0.0),
+                (S51, S51),
+                (0.0, K51),
+                (S51, 0.0),
+                (0.0, S51),
+                (0.0, 0.0),
+            ],
+            Self::Surround71 => &[
+                (K71, 0.0),
+                (S71, S71),
+                (0.0, K71),
+                (S71, 0.0),
+                (0.0, S71),
+                (S71, 0.0),
+                (0.0, S71),
+                (0.0, 0.0),
+            ],
+        }
+    }
+}
+
+/// Downmix interleaved `input` in `layout` to a newly allocated interleaved stereo buffer.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `input` is not a whole number of frames.
+pub fn downmix<S: Sample>(layout: SurroundLayout, input: &[S]) -> Result<Vec<S>> {
+    let mut out = vec![S::default(); input.len() / layout.channels() * 2];
+    downmix_into(layout, input, &mut out)?;
+    Ok(out)
+}
+
+/// Downmix interleaved `input` in `layout` into interleaved stereo `output`.
+///
+/// Returns the number of frames written.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `input` is not a whole number of frames, or
+/// [`Error::BufferTooSmall`] if `output` cannot hold the stereo result.
+pub fn downmix_into<S: Sample>(
+    layout: SurroundLayout,
+    input: &[S],
+    output: &mut [S],
+) -> Result<usize> {
+    let channels = layout.channels();
+    if !input.len().is_multiple_of(channels) {
+        return Err(Error::BadArg);
+    }
+    let frames = input.len() / channels;
+    if output.len() < frames * 2 {
+        return Err(Error::BufferTooSmall);
+    }
+    let coefficients = layout.coefficients();
+    for (frame, out) in input.chunks_exact(channels).zip(output.chunks_exact_mut(2)) {
+        let (left, right) = frame.iter().zip(coefficients).fold(
+            (0.0f32, 0.0f32),
+            |(l, r), (&sample, &(cl, cr))| {
+                let s = sample.to_f32();
+                (l + s * cl, r + s * cr)
+            },
+        );
+        out[0] = S::from_f32(left.clamp(-1.0, 1.0));
+        out[1] = S::from_f32(right.clamp(-1.0, 1.0));
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_scale_input_does_not_clip() {
+        for layout in [SurroundLayout::Surround51, SurroundLayout::Surround71] {
+            let input = vec![i16::MAX; layout.channels() * 4];
+            let out = downmix(layout, &input).unwrap();
+            assert_eq!(out.len(), 8);
+            assert!(out.iter().all(|&s| s > 32_000));
+
+            let input = vec![-1.0f32; layout.channels()];
+            let out = downmix(layout, &input).unwrap();
+            assert!(out.iter().all(|&s| (-1.0..-0.99).contains(&s)));
+        }
+    }
+
+    #[test]
+    fn centre_is_split_and_lfe_dropped() {
+        let mut input = [0.0f32; 6];
+        input[1] = 0.5;
+        input[5] = 1.0;
+        let out = downmix(SurroundLayout::Surround51, &input).unwrap();
+        assert!((out[0] - out[1]).abs() < f32::EPSILON);
+        assert!(out[0] > 0.1);
+        assert!(downmix(SurroundLayout::Surround51, &input[..5]).is_err());
+    }
+}
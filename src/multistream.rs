@@ -23,11 +23,20 @@ use crate::bindings::{
     opus_multistream_encoder_create, opus_multistream_encoder_ctl,
     opus_multistream_encoder_destroy, opus_multistream_surround_encoder_create,
 };
+use crate::decoder::{LastDecode, LastPacketInfo};
 use crate::error::{Error, Result};
-use crate::types::{Application, Bandwidth, Bitrate, Channels, Complexity, SampleRate, Signal};
+use crate::lbrr::multistream_tocs;
+use crate::packet;
+use crate::types::{
+    Application, Bandwidth, Bitrate, Channels, Complexity, InbandFec, PacketLossPerc, Q8Gain,
+    SampleRate, Signal,
+};
+use std::mem::MaybeUninit;
+use std::time::Duration;
 
 /// Describes the multistream mapping configuration.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Mapping<'a> {
     /// Total input/output channels.
     pub channels: u8,
@@ -36,12 +45,91 @@ pub struct Mapping<'a> {
     /// Number of coupled stereo streams (each counts as 2 channels).
     pub coupled_streams: u8,
     /// Channel-to-stream mapping table (length == channels).
+    ///
+    /// With the `arbitrary` feature this table is raw fuzzer input, so generated
+    /// mappings exercise validation and may be rejected.
     pub mapping: &'a [u8],
 }
 
+static MONO_TABLE: [u8; 1] = [0];
+static STEREO_TABLE: [u8; 2] = [0, 1];
+static LINEAR_SURROUND_TABLE: [u8; 3] = [0, 2, 1];
+static QUAD_TABLE: [u8; 4] = [0, 1, 2, 3];
+static SURROUND_50_TABLE: [u8; 5] = [0, 4, 1, 2, 3];
+static SURROUND_51_TABLE: [u8; 6] = [0, 4, 1, 2, 3, 5];
+static SURROUND_61_TABLE: [u8; 7] = [0, 4, 1, 2, 3, 5, 6];
+static SURROUND_71_TABLE: [u8; 8] = [0, 6, 1, 2, 3, 4, 5, 7];
+
+/// Channel mapping family 1 layouts (RFC 7845 section 5.1.1.2), in Vorbis channel order.
+///
+/// Passing one of these constants to [`MSEncoder::new`] or [`MSDecoder::new`] skips the
+/// mapping validation, since they are known to be consistent.
+impl Mapping<'static> {
+    /// Mono: one uncoupled stream.
+    pub const MONO: Self = Self::family1_const(1, 1, 0, &MONO_TABLE);
+    /// Stereo: left, right.
+    pub const STEREO: Self = Self::family1_const(2, 1, 1, &STEREO_TABLE);
+    /// Linear surround: left, centre, right.
+    pub const LINEAR_SURROUND: Self = Self::family1_const(3, 2, 1, &LINEAR_SURROUND_TABLE);
+    /// Quadraphonic: front left, front right, rear left, rear right.
+    pub const QUAD: Self = Self::family1_const(4, 2, 2, &QUAD_TABLE);
+    /// 5.0: front left, centre, front right, rear left, rear right.
+    pub const SURROUND_50: Self = Self::family1_const(5, 3, 2, &SURROUND_50_TABLE);
+    /// 5.1: front left, centre, front right, rear left, rear right, LFE.
+    pub const SURROUND_51: Self = Self::family1_const(6, 4, 2, &SURROUND_51_TABLE);
+    /// 6.1: front left, centre, front right, side left, side right, rear centre, LFE.
+    pub const SURROUND_61: Self = Self::family1_const(7, 4, 3, &SURROUND_61_TABLE);
+    /// 7.1: front left, centre, front right, side left, side right, rear left, rear right,
+    /// LFE.
+    pub const SURROUND_71: Self = Self::family1_const(8, 5, 3, &SURROUND_71_TABLE);
+
+    const fn family1_const(
+        channels: u8,
+        streams: u8,
+        coupled_streams: u8,
+        mapping: &'static [u8],
+    ) -> Self {
+        Self {
+            channels,
+            streams,
+            coupled_streams,
+            mapping,
+        }
+    }
+
+    /// Family 1 layout for `channels` (1 to 8), if any.
+    #[must_use]
+    pub const fn family1(channels: u8) -> Option<Self> {
+        match channels {
+            1 => Some(Self::MONO),
+            2 => Some(Self::STEREO),
+            3 => Some(Self::LINEAR_SURROUND),
+            4 => Some(Self::QUAD),
+            5 => Some(Self::SURROUND_50),
+            6 => Some(Self::SURROUND_51),
+            7 => Some(Self::SURROUND_61),
+            8 => Some(Self::SURROUND_71),
+            _ => None,
+        }
+    }
+}
+
 impl Mapping<'_> {
+    /// Whether this is one of the family 1 constants, which need no validation. Tables
+    /// are compared by address, so an equal copy is still validated.
+    fn is_family1_constant(&self) -> bool {
+        Mapping::family1(self.channels).is_some_and(|known| {
+            known.streams == self.streams
+                && known.coupled_streams == self.coupled_streams
+                && std::ptr::eq(known.mapping, self.mapping)
+        })
+    }
+
     /// Validate that mapping length matches channels.
     fn validate(&self) -> Result<()> {
+        if self.is_family1_constant() {
+            return Ok(());
+        }
         let channel_count = usize::from(self.channels);
         if channel_count == 0 {
             return Err(Error::BadArg);
@@ -137,22 +225,55 @@ impl MSEncoder {
     /// Encode interleaved i16 PCM into a multistream Opus packet.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder handle is invalid, [`Error::BadArg`]
-    /// for buffer mismatches, or the mapped libopus error code.
+    /// Returns [`Error::InvalidState`] if the encoder handle is invalid,
+    /// [`Error::FrameLengthMismatch`] if the PCM length is not `frame_size_per_ch` times
+    /// the channel count, [`Error::BadArg`] for an empty or oversized output, or the
+    /// mapped libopus error code.
     #[allow(clippy::missing_panics_doc)]
     pub fn encode(
         &mut self,
         pcm: &[i16],
         frame_size_per_ch: usize,
         out: &mut [u8],
+    ) -> Result<usize> {
+        self.encode_raw(pcm, frame_size_per_ch, out.as_mut_ptr(), out.len())
+    }
+
+    /// Encode i16 PCM as [`Self::encode`] does, into a buffer that need not be
+    /// initialized, returning the packet as the initialized prefix of `out`.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`].
+    pub fn encode_into_uninit<'a>(
+        &mut self,
+        pcm: &[i16],
+        frame_size_per_ch: usize,
+        out: &'a mut [MaybeUninit<u8>],
+    ) -> Result<&'a [u8]> {
+        let len = self.encode_raw(pcm, frame_size_per_ch, out.as_mut_ptr().cast(), out.len())?;
+        Ok(unsafe { std::slice::from_raw_parts(out.as_ptr().cast(), len) })
+    }
+
+    /// libopus writes at most `out_len` bytes through `out` and only reports bytes it
+    /// wrote, so `out` may point at uninitialized memory.
+    fn encode_raw(
+        &mut self,
+        pcm: &[i16],
+        frame_size_per_ch: usize,
+        out: *mut u8,
+        out_len: usize,
     ) -> Result<usize> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
-        if pcm.len() != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if pcm.len() != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: pcm.len(),
+            });
         }
-        if out.is_empty() || out.len() > i32::MAX as usize {
+        if out_len == 0 || out_len > i32::MAX as usize {
             return Err(Error::BadArg);
         }
         let n = unsafe {
@@ -160,8 +281,8 @@ impl MSEncoder {
                 self.raw,
                 pcm.as_ptr(),
                 i32::try_from(frame_size_per_ch).map_err(|_| Error::BadArg)?,
-                out.as_mut_ptr(),
-                i32::try_from(out.len()).map_err(|_| Error::BadArg)?,
+                out,
+                i32::try_from(out_len).map_err(|_| Error::BadArg)?,
             )
         };
         if n < 0 {
@@ -173,21 +294,53 @@ impl MSEncoder {
     /// Encode interleaved f32 PCM into a multistream Opus packet.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder handle is invalid, [`Error::BadArg`]
-    /// for buffer mismatches, or the mapped libopus error code.
+    /// Returns [`Error::InvalidState`] if the encoder handle is invalid,
+    /// [`Error::FrameLengthMismatch`] if the PCM length is not `frame_size_per_ch` times
+    /// the channel count, [`Error::BadArg`] for an empty or oversized output, or the
+    /// mapped libopus error code.
     pub fn encode_float(
         &mut self,
         pcm: &[f32],
         frame_size_per_ch: usize,
         out: &mut [u8],
+    ) -> Result<usize> {
+        self.encode_float_raw(pcm, frame_size_per_ch, out.as_mut_ptr(), out.len())
+    }
+
+    /// Float counterpart of [`Self::encode_into_uninit`].
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn encode_float_into_uninit<'a>(
+        &mut self,
+        pcm: &[f32],
+        frame_size_per_ch: usize,
+        out: &'a mut [MaybeUninit<u8>],
+    ) -> Result<&'a [u8]> {
+        let len =
+            self.encode_float_raw(pcm, frame_size_per_ch, out.as_mut_ptr().cast(), out.len())?;
+        Ok(unsafe { std::slice::from_raw_parts(out.as_ptr().cast(), len) })
+    }
+
+    /// Float counterpart of [`Self::encode_raw`].
+    fn encode_float_raw(
+        &mut self,
+        pcm: &[f32],
+        frame_size_per_ch: usize,
+        out: *mut u8,
+        out_len: usize,
     ) -> Result<usize> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
-        if pcm.len() != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if pcm.len() != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: pcm.len(),
+            });
         }
-        if out.is_empty() || out.len() > i32::MAX as usize {
+        if out_len == 0 || out_len > i32::MAX as usize {
             return Err(Error::BadArg);
         }
         let n = unsafe {
@@ -195,8 +348,8 @@ impl MSEncoder {
                 self.raw,
                 pcm.as_ptr(),
                 i32::try_from(frame_size_per_ch).map_err(|_| Error::BadArg)?,
-                out.as_mut_ptr(),
-                i32::try_from(out.len()).map_err(|_| Error::BadArg)?,
+                out,
+                i32::try_from(out_len).map_err(|_| Error::BadArg)?,
             )
         };
         if n < 0 {
@@ -301,14 +454,20 @@ impl MSEncoder {
 
     /// Enable/disable in-band FEC generation.
     ///
+    /// Shorthand for [`Self::set_inband_fec_mode`] with [`InbandFec::On`] or [`InbandFec::Off`].
+    ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
     /// reported by libopus.
     pub fn set_inband_fec(&mut self, enabled: bool) -> Result<()> {
-        self.simple_ctl(OPUS_SET_INBAND_FEC_REQUEST as i32, i32::from(enabled))
+        self.set_inband_fec_mode(if enabled {
+            InbandFec::On
+        } else {
+            InbandFec::Off
+        })
     }
 
-    /// Query whether in-band FEC is enabled.
+    /// Query whether in-band FEC is enabled, in either [`InbandFec`] mode.
     ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
@@ -317,25 +476,49 @@ impl MSEncoder {
         self.get_bool_ctl(OPUS_GET_INBAND_FEC_REQUEST as i32)
     }
 
-    /// Set expected packet loss percentage (0..=100).
+    /// Select the in-band FEC mode for every stream.
     ///
     /// # Errors
-    /// Returns [`Error::BadArg`] when `perc` is outside `0..=100`, [`Error::InvalidState`] if
-    /// the encoder handle is null, or propagates any error reported by libopus.
-    pub fn set_packet_loss_perc(&mut self, perc: i32) -> Result<()> {
-        if !(0..=100).contains(&perc) {
-            return Err(Error::BadArg);
-        }
-        self.simple_ctl(OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, perc)
+    /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
+    /// reported by libopus.
+    pub fn set_inband_fec_mode(&mut self, mode: InbandFec) -> Result<()> {
+        self.simple_ctl(OPUS_SET_INBAND_FEC_REQUEST as i32, mode as i32)
     }
 
-    /// Query expected packet loss percentage.
+    /// Query the in-band FEC mode.
     ///
     /// # Errors
     /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
     /// reported by libopus.
-    pub fn packet_loss_perc(&mut self) -> Result<i32> {
-        self.get_int_ctl(OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32)
+    pub fn inband_fec_mode(&mut self) -> Result<InbandFec> {
+        InbandFec::from_ctl(self.get_int_ctl(OPUS_GET_INBAND_FEC_REQUEST as i32)?)
+    }
+
+    /// Set expected packet loss percentage.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
+    /// reported by libopus.
+    pub fn set_packet_loss_perc(&mut self, perc: PacketLossPerc) -> Result<()> {
+        self.simple_ctl(
+            OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+            i32::from(perc.value()),
+        )
+    }
+
+    /// Query expected packet loss percentage.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder handle is null, [`Error::InternalError`]
+    /// if libopus reports a value outside 0..=100, or propagates any error reported by
+    /// libopus.
+    pub fn packet_loss_perc(&mut self) -> Result<PacketLossPerc> {
+        let perc = self.get_int_ctl(OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32)?;
+        u8::try_from(perc)
+            .ok()
+            .filter(|&perc| perc <= 100)
+            .map(PacketLossPerc::new)
+            .ok_or(Error::InternalError)
     }
 
     /// Enable/disable variable bitrate.
@@ -474,6 +657,16 @@ impl MSEncoder {
         self.get_int_ctl(OPUS_GET_LOOKAHEAD_REQUEST as i32)
     }
 
+    /// Total algorithmic delay this encoder adds to the signal path (the codec lookahead).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder handle is null or propagates any error
+    /// reported by libopus.
+    pub fn latency(&mut self) -> Result<Duration> {
+        let lookahead = usize::try_from(self.lookahead()?).map_err(|_| Error::InternalError)?;
+        Ok(self.sample_rate.duration_of(lookahead))
+    }
+
     /// Reset the encoder state (retaining configuration).
     ///
     /// # Errors
@@ -643,6 +836,10 @@ pub struct MSDecoder {
     raw: *mut OpusMSDecoder,
     sample_rate: SampleRate,
     channels: u8,
+    streams: u8,
+    /// Per-channel `opus_pcm_soft_clip` state, present while soft clipping is enabled.
+    soft_clip: Option<Vec<f32>>,
+    last_decode: Option<LastDecode>,
 }
 
 unsafe impl Send for MSDecoder {}
@@ -677,14 +874,19 @@ impl MSDecoder {
             raw: dec,
             sample_rate: sr,
             channels: mapping.channels,
+            streams: mapping.streams,
+            soft_clip: None,
+            last_decode: None,
         })
     }
 
     /// Decode into interleaved i16 PCM (`frame_size` is per-channel).
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle is invalid, [`Error::BadArg`]
-    /// for buffer mismatches, or the mapped libopus error code.
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid,
+    /// [`Error::FrameLengthMismatch`] if the output length is not `frame_size_per_ch` times
+    /// the channel count, [`Error::BadArg`] for an oversized packet, or the mapped libopus
+    /// error code.
     pub fn decode(
         &mut self,
         packet: &[u8],
@@ -695,8 +897,12 @@ impl MSDecoder {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
-        if out.len() != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if out.len() != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: out.len(),
+            });
         }
         let n = unsafe {
             opus_multistream_decode(
@@ -719,14 +925,20 @@ impl MSDecoder {
         if n < 0 {
             return Err(Error::from_code(n));
         }
-        usize::try_from(n).map_err(|_| Error::InternalError)
+        let decoded = usize::try_from(n).map_err(|_| Error::InternalError)?;
+        self.record_decode(packet, fec, decoded)?;
+        Ok(decoded)
     }
 
     /// Decode into interleaved f32 PCM (`frame_size` is per-channel).
     ///
+    /// When [`Self::set_soft_clip`] is enabled, the output is soft-clipped to `[-1, 1]`.
+    ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle is invalid, [`Error::BadArg`]
-    /// for buffer mismatches, or the mapped libopus error code.
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid,
+    /// [`Error::FrameLengthMismatch`] if the output length is not `frame_size_per_ch` times
+    /// the channel count, [`Error::BadArg`] for an oversized packet, or the mapped libopus
+    /// error code.
     pub fn decode_float(
         &mut self,
         packet: &[u8],
@@ -737,8 +949,12 @@ impl MSDecoder {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
         }
-        if out.len() != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if out.len() != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: out.len(),
+            });
         }
         let n = unsafe {
             opus_multistream_decode_float(
@@ -761,7 +977,30 @@ impl MSDecoder {
         if n < 0 {
             return Err(Error::from_code(n));
         }
-        usize::try_from(n).map_err(|_| Error::InternalError)
+        let decoded = usize::try_from(n).map_err(|_| Error::InternalError)?;
+        self.record_decode(packet, fec, decoded)?;
+        if let Some(mem) = self.soft_clip.as_mut()
+            && decoded > 0
+        {
+            packet::soft_clip(out, decoded, i32::from(self.channels), mem)?;
+        }
+        Ok(decoded)
+    }
+
+    /// Duration, bandwidth, per-stream channels and concealment of the last successful
+    /// decode, or `None` before the first one and after [`Self::reset`].
+    #[must_use]
+    pub fn last_packet_info(&self) -> Option<LastPacketInfo> {
+        self.last_decode
+            .as_ref()
+            .map(|last| last.info(self.sample_rate))
+    }
+
+    fn record_decode(&mut self, packet: &[u8], fec: bool, samples: usize) -> Result<()> {
+        let streams = self.streams;
+        LastDecode::record(&mut self.last_decode, packet, fec, samples, |tocs| {
+            multistream_tocs(packet, streams, tocs)
+        })
     }
 
     /// Final RNG state from the last decode.
@@ -796,9 +1035,27 @@ impl MSDecoder {
         if r != 0 {
             return Err(Error::from_code(r));
         }
+        if let Some(mem) = self.soft_clip.as_mut() {
+            mem.fill(0.0);
+        }
+        self.last_decode = None;
         Ok(())
     }
 
+    /// Run `opus_pcm_soft_clip` over every [`Self::decode_float`] output, keeping one
+    /// state value per output channel across calls, so samples slightly over ±1.0 are
+    /// smoothly limited instead of clipping hard downstream. Enabling starts from fresh
+    /// state.
+    pub fn set_soft_clip(&mut self, enabled: bool) {
+        self.soft_clip = enabled.then(|| vec![0.0; usize::from(self.channels)]);
+    }
+
+    /// Whether float output is soft-clipped.
+    #[must_use]
+    pub const fn soft_clip(&self) -> bool {
+        self.soft_clip.is_some()
+    }
+
     /// Set post-decode gain in Q8 dB units.
     ///
     /// # Errors
@@ -817,6 +1074,24 @@ impl MSDecoder {
         self.get_int_ctl(OPUS_GET_GAIN_REQUEST as i32)
     }
 
+    /// Set post-decode gain in decibels, rounded to the Q8 steps libopus uses.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for a gain outside [`Q8Gain`]'s range, otherwise the
+    /// errors of [`Self::set_gain`].
+    pub fn set_gain_db(&mut self, db: f32) -> Result<()> {
+        self.set_gain(i32::from(Q8Gain::from_db(db)?.value()))
+    }
+
+    /// Query post-decode gain in decibels.
+    ///
+    /// # Errors
+    /// Same as [`Self::gain`].
+    pub fn gain_db(&mut self) -> Result<f32> {
+        let q8 = i16::try_from(self.gain()?).map_err(|_| Error::InternalError)?;
+        Ok(Q8Gain::new(q8).db())
+    }
+
     /// Disable or enable phase inversion (CELT stereo decorrelation).
     ///
     /// # Errors
@@ -876,6 +1151,16 @@ impl MSDecoder {
         self.sample_rate
     }
 
+    /// Algorithmic delay added by decoding.
+    ///
+    /// libopus decoders add none of their own: the decoder-side delay is already
+    /// included in the encoder's [`MSEncoder::latency`].
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub const fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+
     /// Create a multistream decoder using libopus surround mapping helpers.
     ///
     /// # Errors
@@ -934,6 +1219,9 @@ impl MSDecoder {
                 raw: dec,
                 sample_rate: sr,
                 channels,
+                streams: u8::try_from(streams).map_err(|_| Error::BadArg)?,
+                soft_clip: None,
+                last_decode: None,
             },
             mapping,
             u8::try_from(streams).map_err(|_| Error::BadArg)?,
@@ -1021,6 +1309,30 @@ mod tests {
         assert!(mapping.validate().is_ok());
     }
 
+    #[test]
+    fn family1_constants_match_libopus_surround_layouts() {
+        for channels in 1..=8 {
+            let known = Mapping::family1(channels).unwrap();
+            assert!(known.is_family1_constant());
+            let copy = known.mapping.to_vec();
+            let copied = Mapping {
+                mapping: &copy,
+                ..known
+            };
+            assert!(!copied.is_family1_constant());
+            assert!(copied.validate().is_ok());
+
+            let (encoder, table) =
+                MSEncoder::new_surround(SampleRate::Hz48000, channels, 1, Application::Audio)
+                    .unwrap();
+            assert_eq!(encoder.streams(), known.streams);
+            assert_eq!(encoder.coupled_streams(), known.coupled_streams);
+            assert_eq!(table, known.mapping);
+            assert!(MSDecoder::new(SampleRate::Hz48000, known).is_ok());
+        }
+        assert!(Mapping::family1(9).is_none());
+    }
+
     #[test]
     fn mapping_rejects_duplicate_mono_assignments() {
         let mapping = Mapping {
@@ -0,0 +1,976 @@
+//! Ogg Opus container support (RFC 3533 pages, RFC 7845 headers)
+//!
+//! [`Page`] reads and writes single Ogg pages, [`PacketReader`] and [`PacketWriter`]
+//! reassemble and paginate packets, [`OpusHead`] and [`OpusTags`] model the two Opus
+//! header packets, and [`OpusWriter`] ties them together for writing `.opus` files.
+//! [`retag`] rewrites the metadata of an existing file without touching its audio, [`cut`]
+//! trims one to a time range without re-encoding, and [`SeekIndex`] maps granule
+//! positions to page offsets for fast seeking. [`check_stream`] lists every place a
+//! stream breaks the RFC 7845 encapsulation rules, and [`estimate_duration`] reads a
+//! file's length from its granule positions. [`OpusFile`] decodes a whole file to PCM
+//! with seeking, for playback without handling packets. With the `tokio` feature,
+//! [`AsyncPacketReader`] and [`AsyncOpusReader`] do the same over `tokio::io::AsyncRead`.
+
+#[cfg(feature = "tokio")]
+mod async_io;
+mod check;
+mod cut;
+mod duration;
+mod file;
+mod seek;
+
+#[cfg(feature = "tokio")]
+pub use async_io::{AsyncOpusReader, AsyncPacketReader};
+pub use check::{Violation, ViolationKind, check_stream};
+pub use cut::{cut, cut_stream};
+pub use duration::estimate_duration;
+pub use file::OpusFile;
+pub use seek::{SeekEntry, SeekIndex};
+
+use crate::error::{Error, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Magic signature of the identification header.
+pub const OPUS_HEAD_MAGIC: &[u8; 8] = b"OpusHead";
+/// Magic signature of the comment header.
+pub const OPUS_TAGS_MAGIC: &[u8; 8] = b"OpusTags";
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+const PAGE_HEADER_LEN: usize = 27;
+/// Buffered pages are flushed once their body reaches this size (as libogg does).
+const PAGE_TARGET_BYTES: usize = 4096;
+/// Granule position of a page on which no packet ends.
+const NO_GRANULE: u64 = u64::MAX;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize];
+    }
+    crc
+}
+
+/// One Ogg page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// Header flags ([`Page::CONTINUED`], [`Page::BOS`], [`Page::EOS`]).
+    pub header_type: u8,
+    /// Granule position; `u64::MAX` (-1) when no packet ends on this page.
+    pub granule_position: u64,
+    /// Logical bitstream serial number.
+    pub serial: u32,
+    /// Page sequence number within the logical bitstream.
+    pub sequence: u32,
+    /// Lacing values (segment table).
+    pub lacing: Vec<u8>,
+    /// Page body.
+    pub body: Vec<u8>,
+}
+
+impl Page {
+    /// The first packet on the page continues one from the previous page.
+    pub const CONTINUED: u8 = 0x01;
+    /// First page of a logical bitstream.
+    pub const BOS: u8 = 0x02;
+    /// Last page of a logical bitstream.
+    pub const EOS: u8 = 0x04;
+
+    /// Whether the page starts with the continuation of a packet.
+    #[must_use]
+    pub const fn is_continued(&self) -> bool {
+        self.header_type & Self::CONTINUED != 0
+    }
+
+    /// Whether this is the first page of its logical bitstream.
+    #[must_use]
+    pub const fn is_bos(&self) -> bool {
+        self.header_type & Self::BOS != 0
+    }
+
+    /// Whether this is the last page of its logical bitstream.
+    #[must_use]
+    pub const fn is_eos(&self) -> bool {
+        self.header_type & Self::EOS != 0
+    }
+
+    /// Granule position, or `None` when no packet ends on this page.
+    #[must_use]
+    pub const fn granule(&self) -> Option<u64> {
+        if self.granule_position == NO_GRANULE {
+            None
+        } else {
+            Some(self.granule_position)
+        }
+    }
+
+    /// Number of packets that end on this page.
+    #[must_use]
+    pub fn packets_ending(&self) -> usize {
+        self.lacing.iter().filter(|&&l| l < 255).count()
+    }
+
+    /// Total encoded size of the page in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        PAGE_HEADER_LEN + self.lacing.len() + self.body.len()
+    }
+
+    /// Whether the page has no segments.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lacing.is_empty()
+    }
+
+    /// Read the next page from `reader`, or `None` at a clean end of input.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] for a bad capture pattern, version, CRC or a
+    /// truncated page, and [`Error::Io`] if reading fails.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut header = [0u8; PAGE_HEADER_LEN];
+        let mut filled = 0;
+        while filled < header.len() {
+            match reader.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(Error::InvalidPacket),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if &header[..4] != CAPTURE_PATTERN || header[4] != 0 {
+            return Err(Error::InvalidPacket);
+        }
+        let mut lacing = vec![0u8; usize::from(header[26])];
+        read_exact(reader, &mut lacing)?;
+        let body_len = lacing.iter().map(|&l| usize::from(l)).sum();
+        let mut body = vec![0u8; body_len];
+        read_exact(reader, &mut body)?;
+
+        let page = Self {
+            header_type: header[5],
+            granule_position: u64::from_le_bytes(header[6..14].try_into().unwrap_or_default()),
+            serial: u32::from_le_bytes(header[14..18].try_into().unwrap_or_default()),
+            sequence: u32::from_le_bytes(header[18..22].try_into().unwrap_or_default()),
+            lacing,
+            body,
+        };
+        let stored = u32::from_le_bytes(header[22..26].try_into().unwrap_or_default());
+        if page.crc() != stored {
+            return Err(Error::InvalidPacket);
+        }
+        Ok(Some(page))
+    }
+
+    /// Parse the page at the start of `data`, returning it with its encoded length, or
+    /// `None` if `data` does not hold a whole page yet.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] for a bad capture pattern, version or CRC.
+    pub fn parse(data: &[u8]) -> Result<Option<(Self, usize)>> {
+        if data.len() < PAGE_HEADER_LEN {
+            return Ok(None);
+        }
+        if &data[..4] != CAPTURE_PATTERN || data[4] != 0 {
+            return Err(Error::InvalidPacket);
+        }
+        let segments = usize::from(data[26]);
+        let Some(lacing) = data.get(PAGE_HEADER_LEN..PAGE_HEADER_LEN + segments) else {
+            return Ok(None);
+        };
+        let len =
+            PAGE_HEADER_LEN + segments + lacing.iter().map(|&l| usize::from(l)).sum::<usize>();
+        if data.len() < len {
+            return Ok(None);
+        }
+        Ok(Self::read(&mut &data[..len])?.map(|page| (page, len)))
+    }
+
+    /// Serialize the page, computing its CRC.
+    ///
+    /// # Panics
+    /// Panics if the page has more than 255 lacing values.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len());
+        self.write_header(&mut out, self.crc());
+        out.extend_from_slice(&self.lacing);
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    /// Write the serialized page to `writer`.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing fails.
+    ///
+    /// # Panics
+    /// Panics if the page has more than 255 lacing values.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    fn write_header(&self, out: &mut Vec<u8>, crc: u32) {
+        let segments = u8::try_from(self.lacing.len()).expect("at most 255 lacing values");
+        out.extend_from_slice(CAPTURE_PATTERN);
+        out.push(0);
+        out.push(self.header_type);
+        out.extend_from_slice(&self.granule_position.to_le_bytes());
+        out.extend_from_slice(&self.serial.to_le_bytes());
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.push(segments);
+    }
+
+    fn crc(&self) -> u32 {
+        let mut header = Vec::with_capacity(PAGE_HEADER_LEN);
+        self.write_header(&mut header, 0);
+        let crc = crc_update(0, &header);
+        let crc = crc_update(crc, &self.lacing);
+        crc_update(crc, &self.body)
+    }
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|err| {
+        if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::InvalidPacket
+        } else {
+            err.into()
+        }
+    })
+}
+
+/// A packet reassembled from one or more pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OggPacket {
+    /// Packet payload.
+    pub data: Vec<u8>,
+    /// Serial of the logical bitstream the packet belongs to.
+    pub serial: u32,
+    /// Granule position of the page the packet ends on, set only for the last packet
+    /// ending on that page.
+    pub granule_position: Option<u64>,
+    /// First packet of its logical bitstream.
+    pub bos: bool,
+    /// Last packet of its logical bitstream.
+    pub eos: bool,
+    /// Byte offset of the page the packet ends on.
+    pub page_offset: u64,
+}
+
+/// Reassembles packets from a sequence of pages.
+pub struct PacketReader<R: Read> {
+    inner: R,
+    offset: u64,
+    assembler: PacketAssembler,
+}
+
+impl<R: Read> PacketReader<R> {
+    /// Read pages from `inner`, which should be positioned at a page boundary.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            assembler: PacketAssembler::default(),
+        }
+    }
+
+    /// Byte offset of the next page to be read.
+    #[must_use]
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Seek the underlying reader to the page at byte `offset` (e.g. from a
+    /// [`SeekIndex`]) and discard partially assembled packets.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if seeking fails.
+    pub fn seek_to(&mut self, offset: u64) -> Result<()>
+    where
+        R: Seek,
+    {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        self.assembler = PacketAssembler::default();
+        Ok(())
+    }
+
+    /// Read the next raw page and its byte offset, bypassing packet assembly.
+    ///
+    /// # Errors
+    /// Same as [`Page::read`].
+    pub fn next_page(&mut self) -> Result<Option<(u64, Page)>> {
+        let offset = self.offset;
+        let page = Page::read(&mut self.inner)?;
+        if let Some(page) = &page {
+            self.offset += page.len() as u64;
+        }
+        Ok(page.map(|page| (offset, page)))
+    }
+
+    /// Read the next complete packet, or `None` at the end of input.
+    ///
+    /// Continuation data without a start (e.g. after seeking) is dropped, as is an
+    /// unfinished packet followed by a page that does not continue it.
+    ///
+    /// # Errors
+    /// Same as [`Page::read`].
+    pub fn next_packet(&mut self) -> Result<Option<OggPacket>> {
+        loop {
+            if let Some(packet) = self.assembler.pop() {
+                return Ok(Some(packet));
+            }
+            let Some((offset, page)) = self.next_page()? else {
+                return Ok(None);
+            };
+            self.assembler.push_page(offset, &page);
+        }
+    }
+}
+
+/// Packet reassembly state shared by the blocking and async readers.
+#[derive(Default)]
+struct PacketAssembler {
+    partial: HashMap<u32, Vec<u8>>,
+    queue: VecDeque<OggPacket>,
+}
+
+impl PacketAssembler {
+    fn pop(&mut self) -> Option<OggPacket> {
+        self.queue.pop_front()
+    }
+
+    fn push_page(&mut self, offset: u64, page: &Page) {
+        let last_ending = page.lacing.iter().rposition(|&l| l < 255);
+        let mut partial = self.partial.remove(&page.serial);
+        let mut skip = false;
+        if page.is_continued() {
+            skip = partial.is_none();
+        } else {
+            partial = None;
+        }
+        let mut data = partial.unwrap_or_default();
+        let mut pos = 0;
+        let mut first = true;
+        for (i, &lace) in page.lacing.iter().enumerate() {
+            let len = usize::from(lace);
+            if !skip {
+                data.extend_from_slice(&page.body[pos..pos + len]);
+            }
+            pos += len;
+            if lace < 255 {
+                if !skip {
+                    let last = Some(i) == last_ending;
+                    self.queue.push_back(OggPacket {
+                        data: std::mem::take(&mut data),
+                        serial: page.serial,
+                        granule_position: if last { page.granule() } else { None },
+                        bos: page.is_bos() && first,
+                        eos: page.is_eos() && last,
+                        page_offset: offset,
+                    });
+                }
+                skip = false;
+                first = false;
+            }
+        }
+        if !skip && page.lacing.last() == Some(&255) {
+            self.partial.insert(page.serial, data);
+        }
+    }
+}
+
+impl<R: Read> Iterator for PacketReader<R> {
+    type Item = Result<OggPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// How [`PacketWriter::write_packet`] treats the page after a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketEnd {
+    /// Keep filling the current page; it is written once it reaches about 4 KiB.
+    Buffered,
+    /// Write the page now, so the next packet starts a new page.
+    EndPage,
+    /// Write the page now with the end-of-stream flag.
+    EndStream,
+}
+
+/// Paginates packets of one logical bitstream.
+pub struct PacketWriter<W: Write> {
+    inner: W,
+    serial: u32,
+    sequence: u32,
+    bos: bool,
+    continued: bool,
+    granule: u64,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+}
+
+impl<W: Write> PacketWriter<W> {
+    /// Start a new logical bitstream with `serial`; the first page gets the BOS flag.
+    pub fn new(inner: W, serial: u32) -> Self {
+        Self::resume(inner, serial, 0)
+    }
+
+    /// Continue a logical bitstream at page `sequence`, without a BOS flag.
+    fn resume(inner: W, serial: u32, sequence: u32) -> Self {
+        Self {
+            inner,
+            serial,
+            sequence,
+            bos: sequence == 0,
+            continued: false,
+            granule: NO_GRANULE,
+            lacing: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Sequence number of the next page to be written.
+    #[must_use]
+    pub const fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Consume the writer, returning the underlying writer. Buffered packets that were
+    /// not flushed are lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Append `packet`, ending at granule position `granule`.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing a page fails.
+    pub fn write_packet(&mut self, packet: &[u8], granule: u64, end: PacketEnd) -> Result<()> {
+        let mut rest = packet;
+        loop {
+            if self.lacing.len() == 255 {
+                // Only a trailing 255 leaves a packet open across the page boundary.
+                let continued = self.lacing.last() == Some(&255);
+                self.write_page(false)?;
+                self.continued = continued;
+            }
+            let len = rest.len().min(255);
+            self.lacing.push(len as u8);
+            self.body.extend_from_slice(&rest[..len]);
+            rest = &rest[len..];
+            if len < 255 {
+                break;
+            }
+        }
+        self.granule = granule;
+        match end {
+            PacketEnd::Buffered if self.body.len() < PAGE_TARGET_BYTES => Ok(()),
+            PacketEnd::Buffered | PacketEnd::EndPage => self.write_page(false),
+            PacketEnd::EndStream => self.write_page(true),
+        }
+    }
+
+    /// Write any buffered packets as a page.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing fails.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.lacing.is_empty() {
+            self.write_page(false)?;
+        }
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    fn write_page(&mut self, eos: bool) -> Result<()> {
+        let mut header_type = 0;
+        if self.continued {
+            header_type |= Page::CONTINUED;
+        }
+        if self.bos {
+            header_type |= Page::BOS;
+        }
+        if eos {
+            header_type |= Page::EOS;
+        }
+        let page = Page {
+            header_type,
+            granule_position: self.granule,
+            serial: self.serial,
+            sequence: self.sequence,
+            lacing: std::mem::take(&mut self.lacing),
+            body: std::mem::take(&mut self.body),
+        };
+        page.write(&mut self.inner)?;
+        self.sequence += 1;
+        self.bos = false;
+        self.continued = false;
+        self.granule = NO_GRANULE;
+        Ok(())
+    }
+}
+
+/// Identification header (`OpusHead`, RFC 7845 section 5.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusHead {
+    /// Encapsulation version; 1 for this specification.
+    pub version: u8,
+    /// Output channel count.
+    pub channels: u8,
+    /// Samples (at 48 kHz) to discard from the decoder output at the start.
+    pub pre_skip: u16,
+    /// Sample rate of the original input (informational only).
+    pub input_sample_rate: u32,
+    /// Output gain in Q7.8 dB to apply when decoding.
+    pub output_gain: i16,
+    /// Channel mapping family.
+    pub mapping_family: u8,
+    /// Number of streams (family != 0 only).
+    pub stream_count: u8,
+    /// Number of coupled streams (family != 0 only).
+    pub coupled_count: u8,
+    /// Channel mapping table (family != 0 only).
+    pub mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    /// Mono/stereo (mapping family 0) header.
+    #[must_use]
+    pub fn new(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Self {
+        Self {
+            version: 1,
+            channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain: 0,
+            mapping_family: 0,
+            stream_count: 1,
+            coupled_count: channels.saturating_sub(1),
+            mapping: Vec::new(),
+        }
+    }
+
+    /// Parse an `OpusHead` packet.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] if the magic, version or lengths are wrong.
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < 19 || &packet[..8] != OPUS_HEAD_MAGIC {
+            return Err(Error::InvalidPacket);
+        }
+        let version = packet[8];
+        let channels = packet[9];
+        if version >> 4 != 0 || channels == 0 {
+            return Err(Error::InvalidPacket);
+        }
+        let mut head = Self {
+            version,
+            channels,
+            pre_skip: u16::from_le_bytes([packet[10], packet[11]]),
+            input_sample_rate: u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]),
+            output_gain: i16::from_le_bytes([packet[16], packet[17]]),
+            mapping_family: packet[18],
+            stream_count: 1,
+            coupled_count: channels.saturating_sub(1),
+            mapping: Vec::new(),
+        };
+        if head.mapping_family == 0 {
+            if channels > 2 {
+                return Err(Error::InvalidPacket);
+            }
+        } else {
+            let table = packet
+                .get(19..21 + usize::from(channels))
+                .ok_or(Error::InvalidPacket)?;
+            head.stream_count = table[0];
+            head.coupled_count = table[1];
+            head.mapping = table[2..].to_vec();
+            if head.stream_count == 0 || head.coupled_count > head.stream_count {
+                return Err(Error::InvalidPacket);
+            }
+        }
+        Ok(head)
+    }
+
+    /// Serialize to an `OpusHead` packet.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(21 + self.mapping.len());
+        out.extend_from_slice(OPUS_HEAD_MAGIC);
+        out.push(self.version);
+        out.push(self.channels);
+        out.extend_from_slice(&self.pre_skip.to_le_bytes());
+        out.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        out.push(self.mapping_family);
+        if self.mapping_family != 0 {
+            out.push(self.stream_count);
+            out.push(self.coupled_count);
+            out.extend_from_slice(&self.mapping);
+        }
+        out
+    }
+}
+
+/// Comment header (`OpusTags`, RFC 7845 section 5.2).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpusTags {
+    /// Vendor string.
+    pub vendor: String,
+    /// `KEY=value` comments.
+    pub comments: Vec<String>,
+    /// Binary data following the comments, preserved verbatim.
+    pub extra: Vec<u8>,
+}
+
+impl OpusTags {
+    /// Empty tags with `vendor`.
+    #[must_use]
+    pub fn new(vendor: impl Into<String>) -> Self {
+        Self {
+            vendor: vendor.into(),
+            comments: Vec::new(),
+            extra: Vec::new(),
+        }
+    }
+
+    /// Append a `key=value` comment.
+    pub fn add(&mut self, key: &str, value: &str) {
+        self.comments.push(format!("{key}={value}"));
+    }
+
+    /// Values of all comments whose key matches `key` case-insensitively.
+    pub fn get<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.comments.iter().filter_map(move |comment| {
+            let (k, v) = comment.split_once('=')?;
+            k.eq_ignore_ascii_case(key).then_some(v)
+        })
+    }
+
+    /// Remove all comments whose key matches `key` case-insensitively.
+    pub fn remove(&mut self, key: &str) {
+        self.comments.retain(|comment| {
+            comment
+                .split_once('=')
+                .is_none_or(|(k, _)| !k.eq_ignore_ascii_case(key))
+        });
+    }
+
+    /// Parse an `OpusTags` packet.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] if the magic is wrong, a length runs past the
+    /// packet or a string is not UTF-8.
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < 8 || &packet[..8] != OPUS_TAGS_MAGIC {
+            return Err(Error::InvalidPacket);
+        }
+        let mut pos = 8;
+        let vendor = read_string(packet, &mut pos)?;
+        let count = read_u32(packet, &mut pos)?;
+        let mut comments = Vec::new();
+        for _ in 0..count {
+            comments.push(read_string(packet, &mut pos)?);
+        }
+        Ok(Self {
+            vendor,
+            comments,
+            extra: packet[pos..].to_vec(),
+        })
+    }
+
+    /// Serialize to an `OpusTags` packet.
+    ///
+    /// # Panics
+    /// Panics if a string or the comment count exceeds `u32::MAX`.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(OPUS_TAGS_MAGIC);
+        write_string(&mut out, &self.vendor);
+        let count = u32::try_from(self.comments.len()).expect("comment count fits u32");
+        out.extend_from_slice(&count.to_le_bytes());
+        for comment in &self.comments {
+            write_string(&mut out, comment);
+        }
+        out.extend_from_slice(&self.extra);
+        out
+    }
+}
+
+fn read_u32(data: &[u8], pos: &mut usize) -> Result<u32> {
+    let bytes = data.get(*pos..*pos + 4).ok_or(Error::InvalidPacket)?;
+    *pos += 4;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_string(data: &[u8], pos: &mut usize) -> Result<String> {
+    let len = read_u32(data, pos)? as usize;
+    let end = pos.checked_add(len).ok_or(Error::InvalidPacket)?;
+    let bytes = data.get(*pos..end).ok_or(Error::InvalidPacket)?;
+    *pos = end;
+    String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidPacket)
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    let len = u32::try_from(value.len()).expect("string length fits u32");
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Writes an Ogg Opus stream: the two header pages, then audio packets.
+pub struct OpusWriter<W: Write> {
+    packets: PacketWriter<W>,
+}
+
+impl<W: Write> OpusWriter<W> {
+    /// Write `head` and `tags` as the first two pages of logical bitstream `serial`.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing fails.
+    pub fn new(inner: W, serial: u32, head: &OpusHead, tags: &OpusTags) -> Result<Self> {
+        let mut packets = PacketWriter::new(inner, serial);
+        packets.write_packet(&head.to_bytes(), 0, PacketEnd::EndPage)?;
+        packets.write_packet(&tags.to_bytes(), 0, PacketEnd::EndPage)?;
+        Ok(Self { packets })
+    }
+
+    /// Append an audio packet whose last sample is at 48 kHz granule position `granule`
+    /// (pre-skip included).
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing fails.
+    pub fn write_packet(&mut self, packet: &[u8], granule: u64) -> Result<()> {
+        self.packets
+            .write_packet(packet, granule, PacketEnd::Buffered)
+    }
+
+    /// Append the final audio packet with the end-of-stream flag and return the writer.
+    ///
+    /// `granule` may be smaller than the packet's natural end to trim padding from the
+    /// decoded output.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if writing fails.
+    pub fn finish(mut self, packet: &[u8], granule: u64) -> Result<W> {
+        self.packets
+            .write_packet(packet, granule, PacketEnd::EndStream)?;
+        self.packets.inner.flush()?;
+        Ok(self.packets.into_inner())
+    }
+}
+
+/// Replace the `OpusTags` of the `.opus` file at `path` with `tags`.
+///
+/// Writes to a temporary file next to `path` and renames it over the original, so the
+/// file is never left half-written.
+///
+/// # Errors
+/// Same as [`retag_stream`].
+pub fn retag(path: impl AsRef<Path>, tags: &OpusTags) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp_name = path.file_name().ok_or(Error::BadArg)?.to_os_string();
+    tmp_name.push(".retag.tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let result: Result<()> = (|| {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        retag_stream(&mut reader, &mut writer, tags)?;
+        writer
+            .into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Copy the Ogg Opus stream from `reader` to `writer`, replacing the `OpusTags` packet of
+/// the first logical bitstream with `tags`.
+///
+/// Audio pages are copied unchanged apart from their sequence numbers (and CRC), which
+/// shift when the new tags need a different number of pages.
+///
+/// # Errors
+/// Returns [`Error::InvalidPacket`] if the stream does not start with valid Opus headers
+/// or the tags do not end their last page, and [`Error::Io`] if reading or writing fails.
+pub fn retag_stream<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    tags: &OpusTags,
+) -> Result<()> {
+    let head = Page::read(reader)?.ok_or(Error::InvalidPacket)?;
+    if !head.is_bos() || !head.body.starts_with(OPUS_HEAD_MAGIC) {
+        return Err(Error::InvalidPacket);
+    }
+    head.write(writer)?;
+    let serial = head.serial;
+
+    let mut old_pages = 0u32;
+    loop {
+        let page = Page::read(reader)?.ok_or(Error::InvalidPacket)?;
+        if page.serial != serial {
+            page.write(writer)?;
+            continue;
+        }
+        if old_pages == 0 && !page.body.starts_with(OPUS_TAGS_MAGIC) {
+            return Err(Error::InvalidPacket);
+        }
+        old_pages += 1;
+        match page.lacing.iter().position(|&l| l < 255) {
+            Some(end) if end + 1 == page.lacing.len() => break,
+            Some(_) => return Err(Error::InvalidPacket),
+            None => {}
+        }
+    }
+
+    let mut packets = PacketWriter::resume(&mut *writer, serial, head.sequence + 1);
+    packets.write_packet(&tags.to_bytes(), 0, PacketEnd::EndPage)?;
+    let new_pages = packets.sequence() - head.sequence - 1;
+
+    while let Some(mut page) = Page::read(reader)? {
+        if page.serial == serial {
+            page.sequence = page
+                .sequence
+                .wrapping_add(new_pages)
+                .wrapping_sub(old_pages);
+        }
+        page.write(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_matches_reference() {
+        // Check value for polynomial 0x04C11DB7 with zero init and no reflection.
+        assert_eq!(crc_update(0, b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn packets_survive_pagination() {
+        let mut out = Vec::new();
+        let mut writer = PacketWriter::new(&mut out, 7);
+        let packets: Vec<Vec<u8>> = [0usize, 1, 254, 255, 256, 510, 70_000, 3]
+            .iter()
+            .map(|&len| (0..len).map(|i| i as u8).collect())
+            .collect();
+        for (i, packet) in packets.iter().enumerate() {
+            let end = if i + 1 == packets.len() {
+                PacketEnd::EndStream
+            } else {
+                PacketEnd::Buffered
+            };
+            writer.write_packet(packet, i as u64, end).unwrap();
+        }
+
+        let read: Vec<OggPacket> = PacketReader::new(out.as_slice())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), packets.len());
+        for (got, want) in read.iter().zip(&packets) {
+            assert_eq!(&got.data, want);
+            assert_eq!(got.serial, 7);
+        }
+        assert!(read[0].bos && !read[1].bos);
+        assert!(read.last().unwrap().eos);
+        assert_eq!(read.last().unwrap().granule_position, Some(7));
+
+        let mut corrupt = out.clone();
+        corrupt[30] ^= 1;
+        assert_eq!(
+            Page::read(&mut corrupt.as_slice()),
+            Err(Error::InvalidPacket)
+        );
+    }
+
+    #[test]
+    fn small_packets_fill_whole_pages() {
+        let mut out = Vec::new();
+        let mut writer = PacketWriter::new(&mut out, 7);
+        // Tiny packets hit the 255 lacing value limit long before the page size target.
+        let packets: Vec<Vec<u8>> = (0..300u16).map(|i| vec![i as u8; 3]).collect();
+        for (i, packet) in packets.iter().enumerate() {
+            writer
+                .write_packet(packet, i as u64, PacketEnd::Buffered)
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read: Vec<OggPacket> = PacketReader::new(out.as_slice())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), packets.len());
+        for (got, want) in read.iter().zip(&packets) {
+            assert_eq!(&got.data, want);
+        }
+    }
+
+    #[test]
+    fn tags_roundtrip() {
+        let mut tags = OpusTags::new("opus-codec");
+        tags.add("TITLE", "Song");
+        tags.add("artist", "Someone");
+        tags.add("ARTIST", "Someone Else");
+        let parsed = OpusTags::parse(&tags.to_bytes()).unwrap();
+        assert_eq!(parsed, tags);
+        assert_eq!(parsed.get("Artist").count(), 2);
+        let mut removed = parsed;
+        removed.remove("ARTIST");
+        assert_eq!(removed.comments, ["TITLE=Song"]);
+
+        let head = OpusHead::new(2, 312, 44_100);
+        assert_eq!(OpusHead::parse(&head.to_bytes()).unwrap(), head);
+        assert!(OpusHead::parse(b"OpusHead").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Async Ogg Opus reading over `tokio::io::AsyncRead`
+
+use super::file::Playback;
+use super::{OggPacket, OpusHead, OpusTags, PacketAssembler, Page};
+use crate::error::{Error, Result};
+use crate::types::Channels;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Bytes requested from the reader whenever a page is incomplete.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Async counterpart of [`super::PacketReader`].
+///
+/// Input is buffered internally and pages are only consumed once complete, so dropping
+/// an unfinished [`Self::next_packet`] future (e.g. a losing `tokio::select!` branch)
+/// loses no data; the next call picks up where it stopped.
+pub struct AsyncPacketReader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    offset: u64,
+    assembler: PacketAssembler,
+}
+
+impl<R: AsyncRead + Unpin> AsyncPacketReader<R> {
+    /// Read pages from `inner`, which should be positioned at a page boundary.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+            offset: 0,
+            assembler: PacketAssembler::default(),
+        }
+    }
+
+    /// Byte offset of the next page to be parsed.
+    #[must_use]
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the reader, returning the underlying reader. Buffered bytes are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next raw page and its byte offset, bypassing packet assembly.
+    ///
+    /// Cancel safe.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] for a corrupt page or input ending mid-page, and
+    /// [`Error::Io`] if reading fails.
+    pub async fn next_page(&mut self) -> Result<Option<(u64, Page)>> {
+        loop {
+            if let Some((page, len)) = Page::parse(&self.buffer)? {
+                self.buffer.drain(..len);
+                let offset = self.offset;
+                self.offset += len as u64;
+                return Ok(Some((offset, page)));
+            }
+            self.buffer.reserve(READ_CHUNK);
+            if self.inner.read_buf(&mut self.buffer).await? == 0 {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(Error::InvalidPacket)
+                };
+            }
+        }
+    }
+
+    /// Read the next complete packet, or `None` at the end of input.
+    ///
+    /// Cancel safe.
+    ///
+    /// # Errors
+    /// Same as [`Self::next_page`].
+    pub async fn next_packet(&mut self) -> Result<Option<OggPacket>> {
+        loop {
+            if let Some(packet) = self.assembler.pop() {
+                return Ok(Some(packet));
+            }
+            let Some((offset, page)) = self.next_page().await? else {
+                return Ok(None);
+            };
+            self.assembler.push_page(offset, &page);
+        }
+    }
+}
+
+/// Decodes an Ogg Opus stream arriving through an [`AsyncRead`] to 48 kHz PCM.
+///
+/// Behaves like [`super::OpusFile`] without seeking: pre-skip, end trimming and the
+/// `OpusHead` output gain are applied, and only the first link of a chained stream is
+/// played. Decoding itself runs synchronously once a packet is complete, so
+/// [`Self::read_f32`] never blocks on I/O and is cancel safe.
+pub struct AsyncOpusReader<R> {
+    packets: AsyncPacketReader<R>,
+    tags: OpusTags,
+    playback: Playback,
+}
+
+impl<R: AsyncRead + Unpin> AsyncOpusReader<R> {
+    /// Read the `OpusHead` and `OpusTags` headers from `inner`.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] if the stream does not start with valid headers,
+    /// [`Error::Unimplemented`] for mapping families other than 0, and [`Error::Io`] if
+    /// reading fails.
+    pub async fn new(inner: R) -> Result<Self> {
+        let mut packets = AsyncPacketReader::new(inner);
+        let first = packets.next_packet().await?.ok_or(Error::InvalidPacket)?;
+        let playback = Playback::new(&first)?;
+        let comments = packets.next_packet().await?.ok_or(Error::InvalidPacket)?;
+        let tags = OpusTags::parse(&comments.data)?;
+        Ok(Self {
+            packets,
+            tags,
+            playback,
+        })
+    }
+
+    /// Identification header.
+    #[must_use]
+    pub const fn head(&self) -> &OpusHead {
+        &self.playback.head
+    }
+
+    /// Comment header.
+    #[must_use]
+    pub const fn tags(&self) -> &OpusTags {
+        &self.tags
+    }
+
+    /// Output channel count.
+    #[must_use]
+    pub const fn channels(&self) -> Channels {
+        self.playback.channels
+    }
+
+    /// Samples per channel returned so far.
+    #[must_use]
+    pub fn tell(&self) -> u64 {
+        self.playback.tell()
+    }
+
+    /// Decode interleaved audio into `buf`, returning the samples per channel written or
+    /// 0 at the end of the stream.
+    ///
+    /// # Errors
+    /// Returns [`Error::BufferTooSmall`] if `buf` cannot hold one sample per channel, and
+    /// any error from reading or decoding.
+    pub async fn read_f32(&mut self, buf: &mut [f32]) -> Result<usize> {
+        self.playback.check_buffer(buf)?;
+        while !self.playback.has_pending() {
+            if self.playback.finished {
+                return Ok(0);
+            }
+            match self.packets.next_packet().await? {
+                Some(packet) => self.playback.decode(&packet)?,
+                None => self.playback.finished = true,
+            }
+        }
+        Ok(self.playback.read(buf))
+    }
+}
@@ -0,0 +1,407 @@
+//! RFC 7845 encapsulation conformance checks
+
+use super::{OpusHead, OpusTags, Page};
+use crate::error::{Error, Result};
+use crate::packet::packet_nb_samples;
+use crate::types::SampleRate;
+use std::fmt;
+use std::io::Read;
+
+/// Largest audio packet per elementary stream a reader has to accept (RFC 7845 section 6).
+const MAX_STREAM_PACKET_BYTES: usize = 61_440;
+/// Longest Opus packet, in 48 kHz samples.
+const MAX_PACKET_SAMPLES: u64 = 5_760;
+
+/// Rule broken by a [`Violation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The page failed its capture pattern, version or CRC check, or was truncated.
+    /// Checking stops at this page.
+    CorruptPage,
+    /// The stream does not start with a beginning-of-stream page.
+    MissingBos,
+    /// The first packet is not a valid `OpusHead`.
+    BadIdHeader,
+    /// The `OpusHead` packet does not finish its page alone with granule position 0.
+    IdHeaderPage,
+    /// The second packet is missing or not a valid `OpusTags`.
+    BadCommentHeader,
+    /// The page finishing `OpusTags` has a nonzero granule position or carries audio.
+    CommentHeaderPage,
+    /// The page sequence number does not follow the previous page of the stream.
+    SequenceGap {
+        /// Sequence number the page should have had.
+        expected: u32,
+        /// Sequence number found.
+        found: u32,
+    },
+    /// The continuation flag does not match whether the previous page left a packet
+    /// unfinished.
+    ContinuationMismatch,
+    /// A page on which no packet ends has a granule position other than -1.
+    UnexpectedGranule,
+    /// A page on which a packet ends has granule position -1.
+    MissingGranule,
+    /// The granule position is lower than the previous audio page's.
+    GranuleDecreased {
+        /// Granule position of the previous audio page.
+        previous: u64,
+        /// Granule position found.
+        found: u64,
+    },
+    /// The granule position does not advance by the duration of the packets ending on
+    /// the page (end trimming is only allowed on the last page).
+    GranuleMismatch {
+        /// Previous granule position plus the packets' duration.
+        expected: u64,
+        /// Granule position found.
+        found: u64,
+    },
+    /// The first audio page's granule position is smaller than the duration of its
+    /// packets, which would start playback before sample zero.
+    NegativeStart,
+    /// The final granule position is smaller than the pre-skip, so nothing is left to play.
+    PreSkipBeyondEnd {
+        /// Pre-skip from `OpusHead`.
+        pre_skip: u16,
+        /// Final granule position.
+        end: u64,
+    },
+    /// An audio packet is empty, malformed or longer than 120 ms.
+    InvalidAudioPacket,
+    /// An audio packet exceeds 61,440 bytes per elementary stream.
+    OversizedPacket {
+        /// Packet length in bytes.
+        len: usize,
+    },
+    /// The stream ends in the middle of a packet.
+    UnterminatedPacket,
+    /// The last page of the stream lacks the end-of-stream flag.
+    MissingEos,
+    /// A page follows the end-of-stream page of the same logical stream.
+    PageAfterEos,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CorruptPage => write!(f, "corrupt page"),
+            Self::MissingBos => write!(f, "stream does not start with a BOS page"),
+            Self::BadIdHeader => write!(f, "first packet is not a valid OpusHead"),
+            Self::IdHeaderPage => write!(f, "OpusHead must end its page alone, at granule 0"),
+            Self::BadCommentHeader => write!(f, "second packet is not a valid OpusTags"),
+            Self::CommentHeaderPage => {
+                write!(f, "OpusTags must end its page alone, at granule 0")
+            }
+            Self::SequenceGap { expected, found } => {
+                write!(f, "page sequence {found}, expected {expected}")
+            }
+            Self::ContinuationMismatch => write!(f, "continuation flag mismatch"),
+            Self::UnexpectedGranule => write!(f, "granule set on a page where no packet ends"),
+            Self::MissingGranule => write!(f, "granule -1 on a page where a packet ends"),
+            Self::GranuleDecreased { previous, found } => {
+                write!(f, "granule {found} is below previous granule {previous}")
+            }
+            Self::GranuleMismatch { expected, found } => {
+                write!(f, "granule {found}, expected {expected}")
+            }
+            Self::NegativeStart => write!(f, "first audio page starts before sample zero"),
+            Self::PreSkipBeyondEnd { pre_skip, end } => {
+                write!(f, "final granule {end} is below pre-skip {pre_skip}")
+            }
+            Self::InvalidAudioPacket => write!(f, "invalid audio packet"),
+            Self::OversizedPacket { len } => write!(f, "audio packet of {len} bytes"),
+            Self::UnterminatedPacket => write!(f, "stream ends inside a packet"),
+            Self::MissingEos => write!(f, "last page lacks the EOS flag"),
+            Self::PageAfterEos => write!(f, "page after end of stream"),
+        }
+    }
+}
+
+/// One conformance problem found by [`check_stream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Byte offset of the page the problem was found on.
+    pub page_offset: u64,
+    /// Index of the packet involved within its logical stream (0 is `OpusHead`), if the
+    /// problem concerns a single packet.
+    pub packet: Option<u64>,
+    /// Rule that was broken.
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page at byte {}", self.page_offset)?;
+        if let Some(packet) = self.packet {
+            write!(f, ", packet {packet}")?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+/// Check an Ogg Opus stream against the RFC 7845 encapsulation rules: header packet
+/// order and placement, page sequencing and continuation, granule positions, pre-skip
+/// and packet limits.
+///
+/// The logical stream started by the first page is checked; pages of other serials are
+/// skipped, and a new beginning-of-stream page after the end of a stream starts the next
+/// link of a chained file. An empty result means no problems were found. Checking stops
+/// at the first corrupt page, which is reported as [`ViolationKind::CorruptPage`].
+///
+/// # Errors
+/// Returns [`Error::Io`] if reading fails.
+pub fn check_stream<R: Read>(mut reader: R) -> Result<Vec<Violation>> {
+    let mut checker = Checker::default();
+    let mut offset = 0;
+    loop {
+        let page = match Page::read(&mut reader) {
+            Ok(Some(page)) => page,
+            Ok(None) => break,
+            Err(Error::InvalidPacket) => {
+                checker.report(offset, None, ViolationKind::CorruptPage);
+                return Ok(checker.violations);
+            }
+            Err(err) => return Err(err),
+        };
+        checker.page(offset, &page);
+        offset += page.len() as u64;
+    }
+    checker.finish();
+    Ok(checker.violations)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    IdHeader,
+    CommentHeader,
+    Audio,
+}
+
+/// State of the logical stream being checked.
+struct Link {
+    serial: u32,
+    next_sequence: u32,
+    stage: Stage,
+    head: Option<OpusHead>,
+    /// Bytes of a packet continued on the next page.
+    partial: Option<Vec<u8>>,
+    packets: u64,
+    /// Duration of audio packets completed since the last granule position.
+    samples: u64,
+    granule: Option<u64>,
+    last_page: u64,
+    eos: bool,
+}
+
+#[derive(Default)]
+struct Checker {
+    link: Option<Link>,
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn report(&mut self, page_offset: u64, packet: Option<u64>, kind: ViolationKind) {
+        self.violations.push(Violation {
+            page_offset,
+            packet,
+            kind,
+        });
+    }
+
+    fn page(&mut self, offset: u64, page: &Page) {
+        match &self.link {
+            None => {
+                if !page.is_bos() {
+                    self.report(offset, None, ViolationKind::MissingBos);
+                }
+                self.start_link(offset, page);
+            }
+            Some(link) if link.eos && page.is_bos() => self.start_link(offset, page),
+            Some(link) if link.serial != page.serial => {}
+            Some(link) if link.eos => self.report(offset, None, ViolationKind::PageAfterEos),
+            Some(_) => self.link_page(offset, page),
+        }
+    }
+
+    fn start_link(&mut self, offset: u64, page: &Page) {
+        self.link = Some(Link {
+            serial: page.serial,
+            next_sequence: page.sequence,
+            stage: Stage::IdHeader,
+            head: None,
+            partial: None,
+            packets: 0,
+            samples: 0,
+            granule: None,
+            last_page: offset,
+            eos: false,
+        });
+        self.link_page(offset, page);
+        if page.packets_ending() != 1
+            || page.lacing.last() == Some(&255)
+            || page.granule_position != 0
+        {
+            self.report(offset, Some(0), ViolationKind::IdHeaderPage);
+        }
+    }
+
+    fn link_page(&mut self, offset: u64, page: &Page) {
+        let Some(mut link) = self.link.take() else {
+            return;
+        };
+        link.last_page = offset;
+        if page.sequence != link.next_sequence {
+            self.report(
+                offset,
+                None,
+                ViolationKind::SequenceGap {
+                    expected: link.next_sequence,
+                    found: page.sequence,
+                },
+            );
+        }
+        link.next_sequence = page.sequence.wrapping_add(1);
+
+        let mut data = match (link.partial.take(), page.is_continued()) {
+            (Some(partial), true) => Some(partial),
+            (None, false) => Some(Vec::new()),
+            // A dangling start or continuation cannot be reassembled; drop it.
+            (Some(_), false) | (None, true) => {
+                self.report(offset, None, ViolationKind::ContinuationMismatch);
+                (!page.is_continued()).then(Vec::new)
+            }
+        };
+        let stage_before = link.stage;
+        let mut ending = 0;
+        let mut pos = 0;
+        for &lacing in &page.lacing {
+            let segment = &page.body[pos..pos + usize::from(lacing)];
+            pos += segment.len();
+            if let Some(data) = data.as_mut() {
+                data.extend_from_slice(segment);
+            }
+            if lacing < 255
+                && let Some(packet) = data.replace(Vec::new())
+            {
+                ending += 1;
+                self.packet(offset, &mut link, &packet);
+            }
+        }
+        link.partial = data.filter(|_| page.lacing.last() == Some(&255));
+
+        if ending == 0 {
+            if page.granule().is_some() {
+                self.report(offset, None, ViolationKind::UnexpectedGranule);
+            }
+        } else if stage_before == Stage::Audio {
+            self.audio_granule(offset, &mut link, page);
+        } else if link.stage == Stage::Audio
+            && (page.granule_position != 0 || ending > 1 || link.partial.is_some())
+        {
+            // The comment header finished on this page; audio must start on a fresh one.
+            self.report(offset, Some(1), ViolationKind::CommentHeaderPage);
+        }
+
+        if page.is_eos() {
+            self.end_link(offset, &mut link);
+        }
+        self.link = Some(link);
+    }
+
+    fn packet(&mut self, offset: u64, link: &mut Link, packet: &[u8]) {
+        let index = link.packets;
+        link.packets += 1;
+        match link.stage {
+            Stage::IdHeader => {
+                link.head = OpusHead::parse(packet).ok();
+                if link.head.is_none() {
+                    self.report(offset, Some(index), ViolationKind::BadIdHeader);
+                }
+                link.stage = Stage::CommentHeader;
+            }
+            Stage::CommentHeader => {
+                if OpusTags::parse(packet).is_err() {
+                    self.report(offset, Some(index), ViolationKind::BadCommentHeader);
+                }
+                link.stage = Stage::Audio;
+            }
+            Stage::Audio => {
+                let streams = link
+                    .head
+                    .as_ref()
+                    .map_or(1, |h| usize::from(h.stream_count));
+                if packet.len() > MAX_STREAM_PACKET_BYTES * streams.max(1) {
+                    let kind = ViolationKind::OversizedPacket { len: packet.len() };
+                    self.report(offset, Some(index), kind);
+                }
+                match packet_nb_samples(packet, SampleRate::Hz48000) {
+                    Ok(samples) if !packet.is_empty() && samples as u64 <= MAX_PACKET_SAMPLES => {
+                        link.samples += samples as u64;
+                    }
+                    _ => self.report(offset, Some(index), ViolationKind::InvalidAudioPacket),
+                }
+            }
+        }
+    }
+
+    fn audio_granule(&mut self, offset: u64, link: &mut Link, page: &Page) {
+        let samples = std::mem::take(&mut link.samples);
+        let Some(found) = page.granule() else {
+            self.report(offset, None, ViolationKind::MissingGranule);
+            return;
+        };
+        match link.granule {
+            None if found < samples && !page.is_eos() => {
+                self.report(offset, None, ViolationKind::NegativeStart);
+            }
+            None => {}
+            Some(previous) if found < previous => {
+                let kind = ViolationKind::GranuleDecreased { previous, found };
+                self.report(offset, None, kind);
+            }
+            Some(previous) => {
+                let expected = previous + samples;
+                if found > expected || (found < expected && !page.is_eos()) {
+                    self.report(
+                        offset,
+                        None,
+                        ViolationKind::GranuleMismatch { expected, found },
+                    );
+                }
+            }
+        }
+        link.granule = Some(found);
+    }
+
+    fn end_link(&mut self, offset: u64, link: &mut Link) {
+        link.eos = true;
+        if link.partial.take().is_some() {
+            self.report(offset, None, ViolationKind::UnterminatedPacket);
+        }
+        match link.stage {
+            Stage::IdHeader => self.report(offset, Some(0), ViolationKind::BadIdHeader),
+            Stage::CommentHeader => self.report(offset, Some(1), ViolationKind::BadCommentHeader),
+            Stage::Audio => {}
+        }
+        if let (Some(head), Some(end)) = (&link.head, link.granule)
+            && end < u64::from(head.pre_skip)
+        {
+            let kind = ViolationKind::PreSkipBeyondEnd {
+                pre_skip: head.pre_skip,
+                end,
+            };
+            self.report(offset, None, kind);
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(mut link) = self.link.take() else {
+            return;
+        };
+        if !link.eos {
+            self.report(link.last_page, None, ViolationKind::MissingEos);
+            self.end_link(link.last_page, &mut link);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Lossless cutting of Ogg Opus streams
+
+use super::{OpusHead, OpusTags, OpusWriter, PacketReader};
+use crate::error::{Error, Result};
+use crate::packet::packet_nb_samples;
+use crate::types::SampleRate;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Audio kept before the cut start so the decoder converges before the first audible
+/// sample (RFC 7845 section 4.6 recommends 80 ms).
+const CUT_PREROLL: u64 = 3_840;
+
+/// Cut the `.opus` file at `input` to `start..end` and write the result to `output`.
+///
+/// # Errors
+/// Same as [`cut_stream`], plus [`Error::Io`] if either file cannot be opened.
+pub fn cut(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    start: Duration,
+    end: Duration,
+) -> Result<u64> {
+    let reader = BufReader::new(File::open(input)?);
+    let writer = BufWriter::new(File::create(output)?);
+    cut_stream(reader, writer, start, end)
+}
+
+/// Copy the `start..end` span of the first logical Opus stream of `reader` to `writer`
+/// without re-encoding, returning the playable length of the result in 48 kHz samples.
+///
+/// Packets are kept whole from 80 ms before `start` (so the decoder has converged) up to
+/// the one containing `end`. The output's pre-skip is set to discard everything before
+/// `start` and its final granule position trims everything after `end`, so a conforming
+/// decoder plays exactly the requested samples; only the boundary packets are decoded
+/// and thrown away, never re-encoded. `end` past the stream's end is clamped to it. The
+/// serial number, comment header and output gain are kept.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `start` is not before `end` or lies at or past the end of
+/// the stream, [`Error::InvalidPacket`] if the stream does not start with valid Opus
+/// headers or contains a malformed audio packet, and [`Error::Io`] if reading or writing
+/// fails.
+pub fn cut_stream<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    start: Duration,
+    end: Duration,
+) -> Result<u64> {
+    if start >= end {
+        return Err(Error::BadArg);
+    }
+    let mut packets = PacketReader::new(reader);
+    let first = packets.next_packet()?.ok_or(Error::InvalidPacket)?;
+    let mut head = OpusHead::parse(&first.data)?;
+    let comments = packets.next_packet()?.ok_or(Error::InvalidPacket)?;
+    if comments.serial != first.serial {
+        return Err(Error::InvalidPacket);
+    }
+    let tags = OpusTags::parse(&comments.data)?;
+
+    // Positions below are granule positions of the input: samples decoded from the start
+    // of the stream, pre-skip included.
+    let pre_skip = u64::from(head.pre_skip);
+    let from = pre_skip.saturating_add(duration_samples(start));
+    let to = pre_skip.saturating_add(duration_samples(end));
+    let keep_from = from.saturating_sub(CUT_PREROLL);
+
+    let mut writer = Some(writer);
+    // Output writer and the input position its granule positions count from.
+    let mut out: Option<(OpusWriter<W>, u64)> = None;
+    // Last kept packet and its end position, held back until we know whether it is final.
+    let mut pending: Option<(Vec<u8>, u64)> = None;
+    let mut pos = 0u64;
+    let mut stream_end = u64::MAX;
+    while let Some(packet) = packets.next_packet()? {
+        if packet.serial != first.serial {
+            continue;
+        }
+        let packet_start = pos;
+        pos += packet_nb_samples(&packet.data, SampleRate::Hz48000)
+            .map_err(|_| Error::InvalidPacket)? as u64;
+        if packet.eos
+            && let Some(granule) = packet.granule_position
+        {
+            stream_end = granule;
+        }
+        if packet_start >= to || packet_start >= stream_end {
+            break;
+        }
+        if pos > keep_from {
+            if out.is_none() {
+                head.pre_skip =
+                    u16::try_from(from - packet_start).map_err(|_| Error::InternalError)?;
+                let inner = writer.take().ok_or(Error::InternalError)?;
+                let opus = OpusWriter::new(inner, first.serial, &head, &tags)?;
+                out = Some((opus, packet_start));
+            }
+            if let Some((data, packet_end)) = pending.replace((packet.data, pos))
+                && let Some((opus, origin)) = &mut out
+            {
+                opus.write_packet(&data, packet_end - *origin)?;
+            }
+        }
+        if packet.eos {
+            break;
+        }
+    }
+
+    let (Some((opus, origin)), Some((data, packet_end))) = (out, pending) else {
+        return Err(Error::BadArg);
+    };
+    let last = to.min(packet_end).min(stream_end);
+    if last <= from {
+        return Err(Error::BadArg);
+    }
+    opus.finish(&data, last - origin)?;
+    Ok(last - from)
+}
+
+fn duration_samples(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros() * 48 / 1_000).unwrap_or(u64::MAX)
+}
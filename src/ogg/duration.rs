@@ -0,0 +1,135 @@
+//! Duration estimation from granule positions
+
+use super::{CAPTURE_PATTERN, NO_GRANULE, OpusHead, PAGE_HEADER_LEN, Page, read_exact};
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Bytes read from the end of the stream per step while looking for the last page.
+const TAIL_CHUNK: u64 = 64 * 1024;
+
+/// Estimate the playback duration of an Ogg Opus stream in milliseconds without decoding
+/// it, from the final granule position minus the pre-skip of each chained link.
+///
+/// `reader` must be positioned at the start of the stream. For a single-link file only
+/// the first page and the tail are read; if the last page belongs to a different logical
+/// stream (a chained or multiplexed file), every page header is walked instead, skipping
+/// page bodies. Links are told apart by their serial numbers, so chained links must not
+/// reuse the first link's serial.
+///
+/// # Errors
+/// Returns [`Error::InvalidPacket`] if the stream does not start with an `OpusHead` page
+/// or a page header is malformed, and [`Error::Io`] if reading or seeking fails.
+pub fn estimate_duration<R: Read + Seek>(mut reader: R) -> Result<u64> {
+    let start = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start))?;
+    let first = Page::read(&mut reader)?.ok_or(Error::InvalidPacket)?;
+    let head = opus_head(first.header_type, &first.body).ok_or(Error::InvalidPacket)?;
+
+    let samples = match last_granule_page(&mut reader, start, end)? {
+        None => 0,
+        Some(last) if last.serial == first.serial => last
+            .granule_position
+            .saturating_sub(u64::from(head.pre_skip)),
+        Some(_) => {
+            reader.seek(SeekFrom::Start(start))?;
+            walk_links(&mut reader)?
+        }
+    };
+    Ok(samples / 48)
+}
+
+/// `OpusHead` carried by a beginning-of-stream page, if any.
+fn opus_head(header_type: u8, body: &[u8]) -> Option<OpusHead> {
+    if header_type & Page::BOS == 0 {
+        return None;
+    }
+    OpusHead::parse(body).ok()
+}
+
+/// Last valid page with a granule position between `start` and `end`, reading
+/// progressively larger tails of the stream until one is found.
+pub(super) fn last_granule_page<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+) -> Result<Option<Page>> {
+    let mut chunk = TAIL_CHUNK;
+    loop {
+        let from = end.saturating_sub(chunk).max(start);
+        reader.seek(SeekFrom::Start(from))?;
+        let mut tail = Vec::new();
+        reader.by_ref().take(end - from).read_to_end(&mut tail)?;
+
+        let mut last = None;
+        let mut pos = 0;
+        while let Some(found) = tail[pos..]
+            .windows(CAPTURE_PATTERN.len())
+            .position(|w| w == CAPTURE_PATTERN)
+        {
+            let at = pos + found;
+            // Capture patterns inside packet data fail the CRC check.
+            match Page::read(&mut &tail[at..]) {
+                Ok(Some(page)) => {
+                    pos = at + page.len();
+                    if page.granule().is_some() {
+                        last = Some(page);
+                    }
+                }
+                _ => pos = at + 1,
+            }
+        }
+        if last.is_some() || from == start {
+            return Ok(last);
+        }
+        chunk *= 2;
+    }
+}
+
+/// Sum the lengths of all Opus links by walking page headers from the current position.
+fn walk_links<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    // Pre-skip and latest granule position per serial of the Opus streams seen so far.
+    let mut links: HashMap<u32, (u16, u64)> = HashMap::new();
+    let mut total = 0u64;
+    let mut header = [0u8; PAGE_HEADER_LEN];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        if &header[..4] != CAPTURE_PATTERN {
+            return Err(Error::InvalidPacket);
+        }
+        let header_type = header[5];
+        let granule = u64::from_le_bytes(header[6..14].try_into().unwrap_or_default());
+        let serial = u32::from_le_bytes(header[14..18].try_into().unwrap_or_default());
+        let mut lacing = vec![0u8; usize::from(header[26])];
+        read_exact(reader, &mut lacing)?;
+        let body_len: u64 = lacing.iter().map(|&l| u64::from(l)).sum();
+
+        if header_type & Page::BOS == 0 {
+            reader.seek(SeekFrom::Current(body_len as i64))?;
+            if let Some((_, last)) = links.get_mut(&serial)
+                && granule != NO_GRANULE
+            {
+                *last = granule;
+            }
+            continue;
+        }
+        let mut body = vec![0u8; usize::try_from(body_len).map_err(|_| Error::InvalidPacket)?];
+        read_exact(reader, &mut body)?;
+        if let Some(head) = opus_head(header_type, &body)
+            && let Some((pre_skip, last)) = links.insert(serial, (head.pre_skip, 0))
+        {
+            // A serial reused by a later link: the earlier one is complete.
+            total += last.saturating_sub(u64::from(pre_skip));
+        }
+    }
+    Ok(total
+        + links
+            .values()
+            .map(|&(pre_skip, last)| last.saturating_sub(u64::from(pre_skip)))
+            .sum::<u64>())
+}
@@ -0,0 +1,289 @@
+//! Sample-level playback of Ogg Opus files
+
+use super::duration::last_granule_page;
+use super::{OggPacket, OpusHead, OpusTags, PacketReader, SeekIndex};
+use crate::constants::MAX_FRAME_SAMPLES_48KHZ;
+use crate::decoder::Decoder;
+use crate::error::{Error, Result};
+use crate::types::{Channels, SampleRate};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Spacing of the seek index built on the first [`OpusFile::pcm_seek`].
+const SEEK_GRANULARITY: Duration = Duration::from_secs(1);
+/// Audio decoded and discarded before a seek target so the decoder converges
+/// (RFC 7845 section 4.6 recommends 80 ms).
+const SEEK_PREROLL: u64 = 3_840;
+
+/// Decoded PCM access to an Ogg Opus stream, in the spirit of libopusfile.
+///
+/// Output is always 48 kHz. Pre-skip is removed, the final page's end trimming and the
+/// `OpusHead` output gain are applied, and positions count samples per channel from the
+/// first audible sample. Only the first link of a chained file is played, and only
+/// mono/stereo (mapping family 0) streams are supported.
+pub struct OpusFile<R: Read + Seek = BufReader<File>> {
+    reader: PacketReader<R>,
+    start: u64,
+    tags: OpusTags,
+    playback: Playback,
+    index: Option<SeekIndex>,
+}
+
+impl OpusFile {
+    /// Open the Ogg Opus file at `path`.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file cannot be opened, otherwise the same errors as
+    /// [`Self::new`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> OpusFile<R> {
+    /// Read the headers of the Ogg Opus stream starting at the current position of
+    /// `reader` and locate its end.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPacket`] if the stream does not start with valid `OpusHead`
+    /// and `OpusTags` packets, [`Error::Unimplemented`] for mapping families other than 0,
+    /// and [`Error::Io`] if reading or seeking fails.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let stream_end = reader.seek(SeekFrom::End(0))?;
+        let last = last_granule_page(&mut reader, start, stream_end)?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut reader = PacketReader::new(reader);
+        let first = reader.next_packet()?.ok_or(Error::InvalidPacket)?;
+        let mut playback = Playback::new(&first)?;
+        let comments = reader.next_packet()?.ok_or(Error::InvalidPacket)?;
+        let tags = OpusTags::parse(&comments.data)?;
+        playback.end = last
+            .filter(|page| page.serial == first.serial)
+            .map(|page| page.granule_position);
+        Ok(Self {
+            reader,
+            start,
+            tags,
+            playback,
+            index: None,
+        })
+    }
+
+    /// Identification header.
+    #[must_use]
+    pub const fn head(&self) -> &OpusHead {
+        &self.playback.head
+    }
+
+    /// Comment header.
+    #[must_use]
+    pub const fn tags(&self) -> &OpusTags {
+        &self.tags
+    }
+
+    /// Output channel count.
+    #[must_use]
+    pub const fn channels(&self) -> Channels {
+        self.playback.channels
+    }
+
+    /// Output sample rate; Opus always decodes to 48 kHz here.
+    #[must_use]
+    pub const fn sample_rate(&self) -> SampleRate {
+        SampleRate::Hz48000
+    }
+
+    /// Sample rate of the original input, from `OpusHead` (informational only).
+    #[must_use]
+    pub const fn input_sample_rate(&self) -> u32 {
+        self.playback.head.input_sample_rate
+    }
+
+    /// Playable length in samples per channel, or `None` if the stream's last page
+    /// belongs to another logical stream.
+    #[must_use]
+    pub fn pcm_total(&self) -> Option<u64> {
+        self.playback
+            .end
+            .map(|end| end.saturating_sub(u64::from(self.playback.head.pre_skip)))
+    }
+
+    /// Position of the next sample [`Self::read_f32`] returns, in samples per channel.
+    #[must_use]
+    pub fn tell(&self) -> u64 {
+        self.playback.tell()
+    }
+
+    /// Decode interleaved audio into `buf`, returning the samples per channel written or
+    /// 0 at the end of the stream.
+    ///
+    /// # Errors
+    /// Returns [`Error::BufferTooSmall`] if `buf` cannot hold one sample per channel, and
+    /// any error from reading or decoding.
+    pub fn read_f32(&mut self, buf: &mut [f32]) -> Result<usize> {
+        self.playback.check_buffer(buf)?;
+        while !self.playback.has_pending() {
+            if self.playback.finished {
+                return Ok(0);
+            }
+            match self.reader.next_packet()? {
+                Some(packet) => self.playback.decode(&packet)?,
+                None => self.playback.finished = true,
+            }
+        }
+        Ok(self.playback.read(buf))
+    }
+
+    /// Seek so the next [`Self::read_f32`] starts at `sample` (per channel).
+    ///
+    /// The first seek scans the stream once to build a [`SeekIndex`]; later seeks jump
+    /// straight to the right page and decode a short pre-roll before the target.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `sample` is past [`Self::pcm_total`], and any error
+    /// from reading or resetting the decoder.
+    pub fn pcm_seek(&mut self, sample: u64) -> Result<()> {
+        if self.pcm_total().is_some_and(|total| sample > total) {
+            return Err(Error::BadArg);
+        }
+        if self.index.is_none() {
+            let inner = self.reader.get_mut();
+            inner.seek(SeekFrom::Start(self.start))?;
+            self.index = Some(SeekIndex::build(&mut *inner, SEEK_GRANULARITY)?);
+        }
+        let target = sample + u64::from(self.playback.head.pre_skip);
+        let entry = self
+            .index
+            .as_ref()
+            .and_then(|index| index.lookup(target.saturating_sub(SEEK_PREROLL)));
+
+        let playback = &mut self.playback;
+        playback.pending.clear();
+        playback.pending_pos = 0;
+        playback.pending_granule = target;
+        playback.skip_until = target;
+        let Some(entry) = entry else {
+            playback.finished = true;
+            return Ok(());
+        };
+        self.reader.seek_to(self.start + entry.offset)?;
+        playback.decoder.reset()?;
+        playback.decoded_end = entry.granule;
+        playback.finished = false;
+        Ok(())
+    }
+}
+
+/// Decoder plus the granule bookkeeping that turns packets into trimmed PCM.
+pub(super) struct Playback {
+    pub(super) head: OpusHead,
+    serial: u32,
+    pub(super) channels: Channels,
+    decoder: Decoder,
+    /// Final granule position, when known up front.
+    end: Option<u64>,
+    scratch: Vec<f32>,
+    /// Decoded samples not yet returned, starting at `pending_granule`.
+    pending: Vec<f32>,
+    pending_pos: usize,
+    pending_granule: u64,
+    /// Granule position just past the last decoded sample.
+    decoded_end: u64,
+    /// Decoded output before this granule position is discarded.
+    skip_until: u64,
+    pub(super) finished: bool,
+}
+
+impl Playback {
+    /// Set up decoding for the stream whose `OpusHead` is `head_packet`.
+    pub(super) fn new(head_packet: &OggPacket) -> Result<Self> {
+        let head = OpusHead::parse(&head_packet.data)?;
+        if head.mapping_family != 0 {
+            return Err(Error::Unimplemented);
+        }
+        let channels = if head.channels == 1 {
+            Channels::Mono
+        } else {
+            Channels::Stereo
+        };
+        let mut decoder = Decoder::new(SampleRate::Hz48000, channels)?;
+        decoder.set_gain(i32::from(head.output_gain))?;
+        let pre_skip = u64::from(head.pre_skip);
+        Ok(Self {
+            head,
+            serial: head_packet.serial,
+            channels,
+            decoder,
+            end: None,
+            scratch: vec![0.0; MAX_FRAME_SAMPLES_48KHZ * channels.as_usize()],
+            pending: Vec::new(),
+            pending_pos: 0,
+            pending_granule: pre_skip,
+            decoded_end: 0,
+            skip_until: pre_skip,
+            finished: false,
+        })
+    }
+
+    pub(super) fn tell(&self) -> u64 {
+        let consumed = (self.pending_pos / self.channels.as_usize()) as u64;
+        (self.pending_granule + consumed).saturating_sub(u64::from(self.head.pre_skip))
+    }
+
+    pub(super) fn check_buffer(&self, buf: &[f32]) -> Result<()> {
+        if buf.len() < self.channels.as_usize() {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    pub(super) fn has_pending(&self) -> bool {
+        self.pending_pos < self.pending.len()
+    }
+
+    /// Decode `packet` if it belongs to the stream, keeping its audible part as pending.
+    pub(super) fn decode(&mut self, packet: &OggPacket) -> Result<()> {
+        if packet.serial != self.serial || self.finished {
+            return Ok(());
+        }
+        let frames = self
+            .decoder
+            .decode_float(&packet.data, &mut self.scratch, false)?;
+        let start = self.decoded_end;
+        self.decoded_end += frames as u64;
+        let mut end = self.decoded_end;
+        if let Some(limit) = self.end {
+            end = end.min(limit);
+        }
+        if packet.eos {
+            self.finished = true;
+            if let Some(granule) = packet.granule_position {
+                end = end.min(granule);
+            }
+        }
+        let from = start.max(self.skip_until);
+        if from < end {
+            let ch = self.channels.as_usize();
+            let range = (from - start) as usize * ch..(end - start) as usize * ch;
+            self.pending.clear();
+            self.pending.extend_from_slice(&self.scratch[range]);
+            self.pending_pos = 0;
+            self.pending_granule = from;
+        }
+        Ok(())
+    }
+
+    /// Copy pending samples into `buf`, returning samples per channel copied.
+    pub(super) fn read(&mut self, buf: &mut [f32]) -> usize {
+        let ch = self.channels.as_usize();
+        let available = &self.pending[self.pending_pos..];
+        let len = available.len().min(buf.len() / ch * ch);
+        buf[..len].copy_from_slice(&available[..len]);
+        self.pending_pos += len;
+        len / ch
+    }
+}
@@ -0,0 +1,234 @@
+//! Byte-offset to granule index for constant-time seeking
+
+use super::{OPUS_HEAD_MAGIC, Page};
+use crate::error::{Error, Result};
+use std::io::Read;
+use std::time::Duration;
+
+const INDEX_MAGIC: &[u8; 4] = b"OSKX";
+const INDEX_VERSION: u8 = 1;
+const INDEX_HEADER_LEN: usize = 4 + 1 + 4 + 8 + 8 + 4;
+
+/// A page decoding can start from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeekEntry {
+    /// Granule position (48 kHz samples, pre-skip included) of the first sample decoded
+    /// from packets starting on this page.
+    pub granule: u64,
+    /// Byte offset of the page in the stream.
+    pub offset: u64,
+}
+
+/// Index mapping granule positions of one logical Opus stream to page offsets.
+///
+/// Entry `k` is the last page starting a packet at or before granule `k * interval`, so a
+/// lookup is a single division. Serialize it with [`Self::to_bytes`] (or `serde`) to skip
+/// the scan in later sessions, checking [`Self::stream_len`] against the file to detect
+/// stale indexes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawSeekIndex"))]
+pub struct SeekIndex {
+    serial: u32,
+    interval: u64,
+    stream_len: u64,
+    entries: Vec<SeekEntry>,
+}
+
+/// Unvalidated [`SeekIndex`] fields, checked through [`SeekIndex::from_parts`] on
+/// deserialization.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSeekIndex {
+    serial: u32,
+    interval: u64,
+    stream_len: u64,
+    entries: Vec<SeekEntry>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSeekIndex> for SeekIndex {
+    type Error = Error;
+
+    fn try_from(raw: RawSeekIndex) -> Result<Self> {
+        Self::from_parts(raw.serial, raw.interval, raw.stream_len, raw.entries)
+    }
+}
+
+impl SeekIndex {
+    /// Scan the whole stream in `reader` and index its first logical Opus stream with one
+    /// entry per `granularity` of audio.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `granularity` is under one 48 kHz sample,
+    /// [`Error::InvalidPacket`] if the stream does not start with an `OpusHead` page or a
+    /// page is corrupt, and [`Error::Io`] if reading fails.
+    pub fn build<R: Read>(mut reader: R, granularity: Duration) -> Result<Self> {
+        let interval =
+            u64::try_from(granularity.as_nanos() * 48 / 1_000_000).map_err(|_| Error::BadArg)?;
+        if interval == 0 {
+            return Err(Error::BadArg);
+        }
+        let head = Page::read(&mut reader)?.ok_or(Error::InvalidPacket)?;
+        if !head.is_bos() || !head.body.starts_with(OPUS_HEAD_MAGIC) {
+            return Err(Error::InvalidPacket);
+        }
+        let mut index = Self {
+            serial: head.serial,
+            interval,
+            stream_len: head.len() as u64,
+            entries: Vec::new(),
+        };
+
+        // Audio starts after the page that ends the comment header.
+        let mut in_headers = true;
+        let mut previous_granule = 0;
+        let mut last: Option<SeekEntry> = None;
+        while let Some(page) = Page::read(&mut reader)? {
+            let offset = index.stream_len;
+            index.stream_len += page.len() as u64;
+            if page.serial != index.serial {
+                continue;
+            }
+            if in_headers {
+                in_headers = page.lacing.last() == Some(&255);
+                continue;
+            }
+            if !page.is_continued() && page.packets_ending() > 0 {
+                index.fill_until(last, previous_granule);
+                last = Some(SeekEntry {
+                    granule: previous_granule,
+                    offset,
+                });
+            }
+            if let Some(granule) = page.granule() {
+                previous_granule = granule;
+            }
+        }
+        index.fill_until(last, previous_granule.saturating_add(1));
+        Ok(index)
+    }
+
+    /// Point every bucket below `granule` that is not yet filled at `entry`.
+    fn fill_until(&mut self, entry: Option<SeekEntry>, granule: u64) {
+        let Some(entry) = entry else { return };
+        while (self.entries.len() as u64).saturating_mul(self.interval) < granule {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Serial number of the indexed logical stream.
+    #[must_use]
+    pub const fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Granule spacing between entries.
+    #[must_use]
+    pub const fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Length in bytes of the stream the index was built from.
+    #[must_use]
+    pub const fn stream_len(&self) -> u64 {
+        self.stream_len
+    }
+
+    /// All entries, one per interval.
+    #[must_use]
+    pub fn entries(&self) -> &[SeekEntry] {
+        &self.entries
+    }
+
+    /// Page to start reading from to decode the sample at `granule`: the last page that
+    /// starts a packet at or before it. `None` for a stream without audio.
+    ///
+    /// Opus needs some pre-roll to converge after a seek (RFC 7845 recommends 80 ms), so
+    /// look up `granule - 3840` when exact output matters.
+    #[must_use]
+    pub fn lookup(&self, granule: u64) -> Option<SeekEntry> {
+        let bucket = usize::try_from(granule / self.interval).unwrap_or(usize::MAX);
+        self.entries
+            .get(bucket)
+            .or_else(|| self.entries.last())
+            .copied()
+    }
+
+    /// Serialize as a compact little-endian blob.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the index has more entries than the blob's 32-bit
+    /// count can describe.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let count = u32::try_from(self.entries.len()).map_err(|_| Error::BadArg)?;
+        let mut out = Vec::with_capacity(INDEX_HEADER_LEN + self.entries.len() * 16);
+        out.extend_from_slice(INDEX_MAGIC);
+        out.push(INDEX_VERSION);
+        out.extend_from_slice(&self.serial.to_le_bytes());
+        out.extend_from_slice(&self.interval.to_le_bytes());
+        out.extend_from_slice(&self.stream_len.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        for entry in &self.entries {
+            out.extend_from_slice(&entry.granule.to_le_bytes());
+            out.extend_from_slice(&entry.offset.to_le_bytes());
+        }
+        Ok(out)
+    }
+
+    /// Parse a blob produced by [`Self::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the blob is truncated, has the wrong magic or version,
+    /// or describes a zero interval.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < INDEX_HEADER_LEN || &bytes[..4] != INDEX_MAGIC || bytes[4] != INDEX_VERSION
+        {
+            return Err(Error::BadArg);
+        }
+        let u64_at = |pos: usize| {
+            bytes
+                .get(pos..pos + 8)
+                .and_then(|b| b.try_into().ok())
+                .map(u64::from_le_bytes)
+                .ok_or(Error::BadArg)
+        };
+        let serial = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        let interval = u64_at(9)?;
+        let stream_len = u64_at(17)?;
+        let count = u32::from_le_bytes([bytes[25], bytes[26], bytes[27], bytes[28]]) as usize;
+        if bytes.len() != INDEX_HEADER_LEN + count * 16 {
+            return Err(Error::BadArg);
+        }
+        let entries = (0..count)
+            .map(|i| {
+                let pos = INDEX_HEADER_LEN + i * 16;
+                Ok(SeekEntry {
+                    granule: u64_at(pos)?,
+                    offset: u64_at(pos + 8)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Self::from_parts(serial, interval, stream_len, entries)
+    }
+
+    /// Assemble an index read back from storage, rejecting a zero interval that would
+    /// make [`Self::lookup`] divide by zero.
+    fn from_parts(
+        serial: u32,
+        interval: u64,
+        stream_len: u64,
+        entries: Vec<SeekEntry>,
+    ) -> Result<Self> {
+        if interval == 0 {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            serial,
+            interval,
+            stream_len,
+            entries,
+        })
+    }
+}
@@ -12,7 +12,113 @@ use crate::bindings::{
     opus_pcm_soft_clip,
 };
 use crate::error::{Error, Result};
-use crate::types::{Bandwidth, Channels, SampleRate};
+use crate::types::{Bandwidth, Channels, CodecMode, SampleRate};
+
+/// Table-of-contents byte that starts every Opus packet (RFC 6716 section 3.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Toc(u8);
+
+impl Toc {
+    /// Wrap a raw TOC byte; every byte value is a valid TOC.
+    #[must_use]
+    pub const fn from_byte(byte: u8) -> Self {
+        Self(byte)
+    }
+
+    /// Build a TOC from its fields.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `config` is above 31 or `frame_code` above 3.
+    pub const fn new(config: u8, stereo: bool, frame_code: u8) -> Result<Self> {
+        if config > 31 || frame_code > 3 {
+            return Err(Error::BadArg);
+        }
+        Ok(Self((config << 3) | ((stereo as u8) << 2) | frame_code))
+    }
+
+    /// Read the TOC of `packet`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `packet` is empty.
+    pub fn of(packet: &[u8]) -> Result<Self> {
+        packet.first().copied().map(Self).ok_or(Error::BadArg)
+    }
+
+    /// Raw byte.
+    #[must_use]
+    pub const fn byte(self) -> u8 {
+        self.0
+    }
+
+    /// Configuration number (0..=31) selecting mode, bandwidth and frame duration.
+    #[must_use]
+    pub const fn config(self) -> u8 {
+        self.0 >> 3
+    }
+
+    /// Whether the frames are coded as stereo.
+    #[must_use]
+    pub const fn is_stereo(self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// Coded channel count.
+    #[must_use]
+    pub const fn channels(self) -> Channels {
+        if self.is_stereo() {
+            Channels::Stereo
+        } else {
+            Channels::Mono
+        }
+    }
+
+    /// Frame count code (0: one frame, 1: two equal, 2: two different, 3: arbitrary).
+    #[must_use]
+    pub const fn frame_code(self) -> u8 {
+        self.0 & 0x03
+    }
+
+    /// Coded audio bandwidth.
+    #[must_use]
+    pub const fn bandwidth(self) -> Bandwidth {
+        match self.config() {
+            0..=3 | 16..=19 => Bandwidth::Narrowband,
+            4..=7 => Bandwidth::Mediumband,
+            8..=11 | 20..=23 => Bandwidth::Wideband,
+            12..=13 | 24..=27 => Bandwidth::SuperWideband,
+            _ => Bandwidth::Fullband,
+        }
+    }
+
+    /// Coding mode of the frames.
+    #[must_use]
+    pub const fn mode(self) -> CodecMode {
+        match self.config() {
+            0..=11 => CodecMode::SilkOnly,
+            12..=15 => CodecMode::Hybrid,
+            _ => CodecMode::CeltOnly,
+        }
+    }
+
+    /// Whether frames start with a SILK layer (SILK-only or hybrid mode), which is where
+    /// in-band FEC lives.
+    #[must_use]
+    pub const fn has_silk_layer(self) -> bool {
+        self.config() < 16
+    }
+
+    /// Samples per channel in each frame at `sample_rate`.
+    #[must_use]
+    pub const fn samples_per_frame(self, sample_rate: SampleRate) -> usize {
+        let tenths_ms = match self.config() {
+            config @ 0..=11 => [100, 200, 400, 600][(config % 4) as usize],
+            config @ 12..=15 => [100, 200][(config % 2) as usize],
+            config => [25, 50, 100, 200][(config % 4) as usize],
+        };
+        tenths_ms * sample_rate as usize / 10_000
+    }
+}
 
 /// Get bandwidth from a packet.
 ///
@@ -213,6 +319,36 @@ pub fn packet_parse(packet: &[u8]) -> Result<(u8, usize, Vec<&[u8]>)> {
     ))
 }
 
+/// Compressed size of every frame in `packet`, including empty ones.
+pub(crate) fn frame_sizes(packet: &[u8]) -> Result<Vec<usize>> {
+    if packet.is_empty() {
+        return Err(Error::BadArg);
+    }
+    let mut toc: u8 = 0;
+    let mut offset: i32 = 0;
+    let mut frames: [*const u8; 48] = [std::ptr::null(); 48];
+    let mut sizes: [i16; 48] = [0; 48];
+    let len_i32 = i32::try_from(packet.len()).map_err(|_| Error::BadArg)?;
+    let n = unsafe {
+        opus_packet_parse(
+            packet.as_ptr(),
+            len_i32,
+            &raw mut toc,
+            frames.as_mut_ptr(),
+            sizes.as_mut_ptr(),
+            &raw mut offset,
+        )
+    };
+    if n < 0 {
+        return Err(Error::from_code(n));
+    }
+    let count = usize::try_from(n).map_err(|_| Error::InternalError)?;
+    sizes[..count]
+        .iter()
+        .map(|&size| usize::try_from(size).map_err(|_| Error::InternalError))
+        .collect()
+}
+
 /// Increase a packet's size by adding padding to reach `new_len`.
 ///
 /// # Errors
@@ -246,6 +382,154 @@ pub fn packet_unpad(packet: &mut [u8], len: usize) -> Result<usize> {
     usize::try_from(n).map_err(|_| Error::InternalError)
 }
 
+/// Pad the packet filling `packet` to `new_len` bytes, growing the vector as needed.
+///
+/// On error the vector is left unchanged.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `new_len` is shorter than the packet, or a mapped libopus
+/// error if padding fails.
+pub fn packet_pad_vec(packet: &mut Vec<u8>, new_len: usize) -> Result<()> {
+    let len = packet.len();
+    if new_len < len {
+        return Err(Error::BadArg);
+    }
+    packet.resize(new_len, 0);
+    let result = packet_pad(packet, len, new_len);
+    if result.is_err() {
+        packet.truncate(len);
+    }
+    result
+}
+
+/// Remove padding from the packet filling `packet`, truncating the vector to the result.
+///
+/// # Errors
+/// Returns a mapped libopus error if the packet is invalid; the vector is then unchanged.
+pub fn packet_unpad_vec(packet: &mut Vec<u8>) -> Result<()> {
+    let len = packet.len();
+    let unpadded = packet_unpad(packet, len)?;
+    packet.truncate(unpadded);
+    Ok(())
+}
+
+/// Padding region at the end of `packet`: empty unless it is a code 3 packet with the
+/// padding flag set.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] for an empty packet and [`Error::InvalidPacket`] if the
+/// padding length runs past the end of the packet.
+pub fn packet_padding(packet: &[u8]) -> Result<&[u8]> {
+    if Toc::of(packet)?.frame_code() != 3 {
+        return Ok(&[]);
+    }
+    let header = *packet.get(1).ok_or(Error::InvalidPacket)?;
+    if header & 0x40 == 0 {
+        return Ok(&[]);
+    }
+    let mut pos = 2;
+    let mut padding = 0;
+    loop {
+        let byte = *packet.get(pos).ok_or(Error::InvalidPacket)?;
+        pos += 1;
+        padding += if byte == 255 { 254 } else { usize::from(byte) };
+        if byte != 255 {
+            break;
+        }
+    }
+    if padding > packet.len() - pos {
+        return Err(Error::InvalidPacket);
+    }
+    Ok(&packet[packet.len() - padding..])
+}
+
+/// Replace the padding of the packet filling `packet` with opaque `payload` bytes.
+///
+/// libopus 1.5 reads padding as a sequence of packet extensions, so the payload is
+/// stored as one extension with the given `id`, which must be a long extension ID
+/// (32..=127; libopus uses 126 for DRED). The repacketizer and [`packet_pad`] keep it, and
+/// decoders ignore it, so it survives Opus-aware middleboxes that leave padding alone.
+/// Any existing padding and extensions are dropped. Read it back with
+/// [`packet_padding_payload`].
+///
+/// On error the vector is left unchanged.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `id` is outside 32..=127, or a mapped libopus error if
+/// the packet is invalid or the payload does not fit.
+pub fn packet_set_padding_payload(packet: &mut Vec<u8>, id: u8, payload: &[u8]) -> Result<()> {
+    if !(32..=127).contains(&id) {
+        return Err(Error::BadArg);
+    }
+    let mut unpadded = packet.clone();
+    packet_unpad_vec(&mut unpadded)?;
+    let needed = payload.len() + 1;
+    // The padding length field grows with the padding, so converge on the total size
+    // that leaves exactly `needed` bytes of padding.
+    let mut new_len = unpadded.len() + needed;
+    let mut padded;
+    loop {
+        padded = unpadded.clone();
+        packet_pad_vec(&mut padded, new_len)?;
+        let padding = packet_padding(&padded)?.len();
+        if padding == needed {
+            break;
+        }
+        new_len = new_len + needed - padding;
+    }
+    let start = padded.len() - needed;
+    padded[start] = id << 1;
+    padded[start + 1..].copy_from_slice(payload);
+    *packet = padded;
+    Ok(())
+}
+
+/// Payload of the first padding extension of `packet` with the given `id`, as written by
+/// [`packet_set_padding_payload`], or `None` if there is none.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `id` is outside 32..=127 or the packet is empty, and
+/// [`Error::InvalidPacket`] if the padding is not a valid extension sequence.
+pub fn packet_padding_payload(packet: &[u8], id: u8) -> Result<Option<&[u8]>> {
+    if !(32..=127).contains(&id) {
+        return Err(Error::BadArg);
+    }
+    let mut data = packet_padding(packet)?;
+    while let Some((&header, rest)) = data.split_first() {
+        let long = header & 1 == 1;
+        let (body, next) = match header >> 1 {
+            // A single padding byte.
+            0 if long => (&[][..], rest),
+            // Short extensions carry zero or one byte.
+            1..=31 => {
+                let len = usize::from(long);
+                (rest.get(..len).ok_or(Error::InvalidPacket)?, &rest[len..])
+            }
+            // Without a length, the extension (or padding, for ID 0) runs to the end.
+            _ if !long => (rest, &[][..]),
+            _ => {
+                let mut len = 0;
+                let mut pos = 0;
+                loop {
+                    let byte = *rest.get(pos).ok_or(Error::InvalidPacket)?;
+                    pos += 1;
+                    len += usize::from(byte);
+                    if byte != 255 {
+                        break;
+                    }
+                }
+                let body = rest.get(pos..pos + len).ok_or(Error::InvalidPacket)?;
+                (body, &rest[pos + len..])
+            }
+        };
+        if header >> 1 == id {
+            return Ok(Some(body));
+        }
+        data = next;
+    }
+    Ok(None)
+}
+
 /// Pad a multistream packet to `new_len` given `nb_streams`.
 ///
 /// # Errors
@@ -270,6 +554,31 @@ pub fn multistream_packet_pad(
     Ok(())
 }
 
+/// Pad the multistream packet of `nb_streams` streams filling `packet` to `new_len`
+/// bytes, growing the vector as needed (e.g. for constant-size surround transport).
+///
+/// On error the vector is left unchanged.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if `new_len` is shorter than the packet, or a mapped libopus
+/// error if padding fails.
+pub fn multistream_packet_pad_vec(
+    packet: &mut Vec<u8>,
+    new_len: usize,
+    nb_streams: i32,
+) -> Result<()> {
+    let len = packet.len();
+    if new_len < len {
+        return Err(Error::BadArg);
+    }
+    packet.resize(new_len, 0);
+    let result = multistream_packet_pad(packet, len, new_len, nb_streams);
+    if result.is_err() {
+        packet.truncate(len);
+    }
+    result
+}
+
 /// Remove padding from a multistream packet; returns new length.
 ///
 /// # Errors
@@ -285,3 +594,15 @@ pub fn multistream_packet_unpad(packet: &mut [u8], len: usize, nb_streams: i32)
     }
     usize::try_from(n).map_err(|_| Error::InternalError)
 }
+
+/// Remove padding from the multistream packet filling `packet`, truncating the vector to
+/// the result.
+///
+/// # Errors
+/// Returns a mapped libopus error if the packet is invalid; the vector is then unchanged.
+pub fn multistream_packet_unpad_vec(packet: &mut Vec<u8>, nb_streams: i32) -> Result<()> {
+    let len = packet.len();
+    let unpadded = multistream_packet_unpad(packet, len, nb_streams)?;
+    packet.truncate(unpadded);
+    Ok(())
+}
@@ -0,0 +1,295 @@
+//! Pools of reusable codecs for servers handling many short-lived streams
+//!
+//! Creating a codec allocates and initializes tens of kilobytes of libopus state. An
+//! [`EncoderPool`] or [`DecoderPool`] keeps finished codecs instead: [`EncoderPool::get`]
+//! hands out an idle one when available, and the returned [`Pooled`] guard gives it back
+//! on drop, reinitialized in place (see [`Encoder::reinit`]) and, for encoders,
+//! reconfigured with the pool's settings. Pools are cheap to clone and share between
+//! threads; [`PoolStats`] counts how often checkouts were served from the pool.
+
+use crate::decoder::Decoder;
+use crate::encoder::{Encoder, EncoderBuilder, EncoderSettings};
+use crate::error::{Error, Result};
+use crate::types::{Channels, SampleRate};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Checkout counters of a pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Checkouts served by an idle codec.
+    pub hits: u64,
+    /// Checkouts that had to create a codec.
+    pub misses: u64,
+    /// Returned codecs dropped because the pool was full or reinitialization failed.
+    pub discarded: u64,
+    /// Codecs currently idle in the pool.
+    pub idle: usize,
+}
+
+type Factory<T> = Box<dyn Fn() -> Result<T> + Send + Sync>;
+type Recycler<T> = Box<dyn Fn(&mut T) -> Result<()> + Send + Sync>;
+
+struct Inner<T> {
+    idle: Mutex<Vec<T>>,
+    max_idle: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    discarded: AtomicU64,
+    create: Factory<T>,
+    recycle: Recycler<T>,
+}
+
+impl<T> Inner<T> {
+    fn new(max_idle: usize, create: Factory<T>, recycle: Recycler<T>) -> Self {
+        Self {
+            idle: Mutex::new(Vec::with_capacity(max_idle)),
+            max_idle,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+            create,
+            recycle,
+        }
+    }
+
+    fn get(self: &Arc<Self>) -> Result<Pooled<T>> {
+        let idle = self.idle.lock().map_err(|_| Error::InvalidState)?.pop();
+        let item = if let Some(item) = idle {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            item
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            (self.create)()?
+        };
+        Ok(Pooled {
+            item: Some(item),
+            pool: Arc::clone(self),
+        })
+    }
+
+    fn put(&self, mut item: T) {
+        // Skip recycling a codec a full pool would drop anyway; check again afterwards,
+        // as other returns may have filled the pool meanwhile.
+        let kept = self
+            .idle
+            .lock()
+            .is_ok_and(|idle| idle.len() < self.max_idle)
+            && (self.recycle)(&mut item).is_ok()
+            && self.idle.lock().is_ok_and(|mut idle| {
+                if idle.len() < self.max_idle {
+                    idle.push(item);
+                    true
+                } else {
+                    false
+                }
+            });
+        if !kept {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            idle: self.idle.lock().map_or(0, |idle| idle.len()),
+        }
+    }
+}
+
+/// Codec checked out of a pool, returned to it when dropped.
+pub struct Pooled<T> {
+    item: Option<T>,
+    pool: Arc<Inner<T>>,
+}
+
+impl<T> Pooled<T> {
+    /// Take the codec out of the pool for good.
+    #[must_use]
+    pub fn detach(mut self) -> T {
+        let Some(item) = self.item.take() else {
+            unreachable!("pooled codec present until drop")
+        };
+        item
+    }
+}
+
+impl<T> Deref for Pooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().expect("pooled codec present until drop")
+    }
+}
+
+impl<T> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().expect("pooled codec present until drop")
+    }
+}
+
+impl<T> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.put(item);
+        }
+    }
+}
+
+/// Pool of identically configured [`Encoder`]s.
+#[derive(Clone)]
+pub struct EncoderPool {
+    inner: Arc<Inner<Encoder>>,
+}
+
+impl EncoderPool {
+    /// Pool encoders configured by `builder`, keeping at most `max_idle` of them.
+    ///
+    /// One encoder is built up front to validate the configuration and capture its
+    /// settings; it becomes the first idle encoder. Returned encoders are reinitialized
+    /// and get the captured settings applied again, undoing any changes made while
+    /// checked out.
+    ///
+    /// # Errors
+    /// Returns the error from building the first encoder or capturing its settings.
+    pub fn new(builder: EncoderBuilder, max_idle: usize) -> Result<Self> {
+        let mut first = builder.build()?;
+        let settings: EncoderSettings = first.settings()?;
+        let application = first.application()?;
+        let (sample_rate, channels) = (first.sample_rate(), first.channels());
+        let inner = Arc::new(Inner::new(
+            max_idle,
+            Box::new(move || Ok(builder.build()?)),
+            Box::new(move |encoder: &mut Encoder| {
+                encoder.reinit(sample_rate, channels, application)?;
+                encoder.apply_settings(&settings)
+            }),
+        ));
+        inner.put(first);
+        Ok(Self { inner })
+    }
+
+    /// Check out an idle encoder, or create one if none is idle.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the pool's lock is poisoned, or any error from
+    /// creating an encoder.
+    pub fn get(&self) -> Result<Pooled<Encoder>> {
+        self.inner.get()
+    }
+
+    /// Checkout counters so far.
+    #[must_use]
+    pub fn stats(&self) -> PoolStats {
+        self.inner.stats()
+    }
+}
+
+/// Pool of [`Decoder`]s sharing one sample rate and channel layout.
+#[derive(Clone)]
+pub struct DecoderPool {
+    inner: Arc<Inner<Decoder>>,
+}
+
+impl DecoderPool {
+    /// Pool decoders for `sample_rate` and `channels`, keeping at most `max_idle` of them.
+    /// Returned decoders are reinitialized to their defaults.
+    #[must_use]
+    pub fn new(sample_rate: SampleRate, channels: Channels, max_idle: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner::new(
+                max_idle,
+                Box::new(move || Decoder::new(sample_rate, channels)),
+                Box::new(move |decoder: &mut Decoder| decoder.reinit(sample_rate, channels)),
+            )),
+        }
+    }
+
+    /// Check out an idle decoder, or create one if none is idle.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the pool's lock is poisoned, or any error from
+    /// creating a decoder.
+    pub fn get(&self) -> Result<Pooled<Decoder>> {
+        self.inner.get()
+    }
+
+    /// Checkout counters so far.
+    #[must_use]
+    pub fn stats(&self) -> PoolStats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_PACKET_SIZE;
+    use crate::types::{Application, Bitrate};
+
+    #[test]
+    fn pools_recycle_and_restore_codecs() {
+        let sr = SampleRate::Hz48000;
+        let builder = Encoder::builder(sr, Channels::Mono, Application::Voip)
+            .bitrate(Bitrate::Custom(24_000))
+            .dtx(true);
+        let encoders = EncoderPool::new(builder, 2).unwrap();
+        let decoders = DecoderPool::new(sr, Channels::Mono, 1);
+        assert_eq!(encoders.stats().idle, 1);
+
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        {
+            let mut encoder = encoders.get().unwrap();
+            let mut decoder = decoders.get().unwrap();
+            encoder.set_bitrate(Bitrate::Custom(64_000)).unwrap();
+            let len = encoder.encode(&[0; 960], &mut packet).unwrap();
+            decoder.set_gain(-256).unwrap();
+            assert_eq!(
+                decoder.decode(&packet[..len], &mut [0; 960], false),
+                Ok(960)
+            );
+        }
+        let mut encoder = encoders.get().unwrap();
+        assert_eq!(encoder.bitrate().unwrap(), Bitrate::Custom(24_000));
+        assert!(encoder.dtx().unwrap());
+        assert_eq!(decoders.get().unwrap().gain().unwrap(), 0);
+
+        let second = encoders.get().unwrap();
+        let third = encoders.get().unwrap();
+        let detached = third.detach();
+        drop((encoder, second, detached));
+        assert_eq!(
+            encoders.stats(),
+            PoolStats {
+                hits: 2,
+                misses: 2,
+                discarded: 0,
+                idle: 2,
+            }
+        );
+        assert_eq!(decoders.stats().hits, 1);
+    }
+
+    #[test]
+    fn full_pool_skips_recycling() {
+        let recycled = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&recycled);
+        let pool = Arc::new(Inner::new(
+            1,
+            Box::new(|| Ok(())),
+            Box::new(move |()| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }),
+        ));
+        let first = pool.get().unwrap();
+        let second = pool.get().unwrap();
+        drop((first, second));
+        assert_eq!(recycled.load(Ordering::Relaxed), 1);
+        assert_eq!(pool.stats().discarded, 1);
+        assert_eq!(pool.stats().idle, 1);
+    }
+}
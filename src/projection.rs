@@ -1,23 +1,189 @@
 //! Safe wrappers for the libopus projection (ambisonics) API
 
 use crate::bindings::{
-    OPUS_BITRATE_MAX, OPUS_GET_BITRATE_REQUEST, OPUS_PROJECTION_GET_DEMIXING_MATRIX_GAIN_REQUEST,
-    OPUS_PROJECTION_GET_DEMIXING_MATRIX_REQUEST, OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE_REQUEST,
-    OPUS_SET_BITRATE_REQUEST, OpusProjectionDecoder, OpusProjectionEncoder,
+    OPUS_BITRATE_MAX, OPUS_GET_BITRATE_REQUEST, OPUS_GET_GAIN_REQUEST, OPUS_GET_LOOKAHEAD_REQUEST,
+    OPUS_PROJECTION_GET_DEMIXING_MATRIX_GAIN_REQUEST, OPUS_PROJECTION_GET_DEMIXING_MATRIX_REQUEST,
+    OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE_REQUEST, OPUS_RESET_STATE, OPUS_SET_BITRATE_REQUEST,
+    OPUS_SET_GAIN_REQUEST, OpusProjectionDecoder, OpusProjectionEncoder,
     opus_projection_ambisonics_encoder_create, opus_projection_decode,
-    opus_projection_decode_float, opus_projection_decoder_create, opus_projection_decoder_destroy,
-    opus_projection_encode, opus_projection_encode_float, opus_projection_encoder_ctl,
-    opus_projection_encoder_destroy,
+    opus_projection_decode_float, opus_projection_decoder_create, opus_projection_decoder_ctl,
+    opus_projection_decoder_destroy, opus_projection_encode, opus_projection_encode_float,
+    opus_projection_encoder_ctl, opus_projection_encoder_destroy,
 };
 use crate::constants::max_frame_samples_for;
 use crate::error::{Error, Result};
-use crate::types::{Application, Bitrate, SampleRate};
+use crate::render::AmbisonicRenderer;
+use crate::types::{Application, Bitrate, Q8Gain, SampleRate};
+use std::time::Duration;
+
+/// Channel mapping family used by libopus for projection (ambisonics) coding.
+pub const AMBISONICS_MAPPING_FAMILY: i32 = 3;
+
+/// Highest ambisonics order libopus ships projection matrices for.
+pub const MAX_AMBISONICS_ORDER: u8 = 5;
+
+/// Ambisonics channel layout: `(order + 1)^2` ACN channels, optionally followed by a
+/// head-locked (non-diegetic) stereo pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AmbisonicsLayout {
+    order: u8,
+    non_diegetic_stereo: bool,
+}
+
+impl AmbisonicsLayout {
+    /// Describe a layout of the given `order` (1..=5), with or without the head-locked stereo pair.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `order` is outside the range libopus supports.
+    pub const fn new(order: u8, non_diegetic_stereo: bool) -> Result<Self> {
+        if order == 0 || order > MAX_AMBISONICS_ORDER {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            order,
+            non_diegetic_stereo,
+        })
+    }
+
+    /// Derive the layout from a total channel count (`(n+1)^2` or `(n+1)^2 + 2`).
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `channels` is not a supported ambisonics channel count.
+    pub const fn from_channels(channels: u8) -> Result<Self> {
+        let order_plus_one = channels.isqrt();
+        let acn = order_plus_one * order_plus_one;
+        match channels - acn {
+            0 => Self::new(order_plus_one.saturating_sub(1), false),
+            2 => Self::new(order_plus_one.saturating_sub(1), true),
+            _ => Err(Error::BadArg),
+        }
+    }
+
+    /// Ambisonics order.
+    #[must_use]
+    pub const fn order(&self) -> u8 {
+        self.order
+    }
+
+    /// Whether a head-locked stereo pair follows the ambisonics channels.
+    #[must_use]
+    pub const fn has_non_diegetic_stereo(&self) -> bool {
+        self.non_diegetic_stereo
+    }
+
+    /// Number of ambisonics (ACN) channels.
+    #[must_use]
+    pub const fn ambisonic_channels(&self) -> u8 {
+        (self.order + 1) * (self.order + 1)
+    }
+
+    /// Total channel count including the optional stereo pair.
+    #[must_use]
+    pub const fn channels(&self) -> u8 {
+        self.ambisonic_channels() + if self.non_diegetic_stereo { 2 } else { 0 }
+    }
+
+    /// Interleaved channel indices of the head-locked stereo pair, if present.
+    #[must_use]
+    pub const fn non_diegetic_channels(&self) -> Option<(usize, usize)> {
+        if self.non_diegetic_stereo {
+            let left = self.ambisonic_channels() as usize;
+            Some((left, left + 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// Everything a receiver needs to build a [`ProjectionDecoder`] for a given sender.
+///
+/// Serializes to a compact byte blob via [`Self::to_bytes`], or through `serde`
+/// when the `serde` feature is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionParams {
+    /// Output channel count.
+    pub channels: u8,
+    /// Number of coded streams.
+    pub streams: u8,
+    /// Number of coupled (stereo) coded streams.
+    pub coupled: u8,
+    /// Demixing matrix as produced by [`ProjectionEncoder::demixing_matrix_bytes`].
+    pub matrix: Vec<u8>,
+}
+
+impl SessionParams {
+    /// Length of the fixed header preceding the matrix in [`Self::to_bytes`].
+    const HEADER_LEN: usize = 3;
+
+    /// Expected demixing matrix size in bytes for these stream/channel counts.
+    #[must_use]
+    pub fn expected_matrix_len(&self) -> usize {
+        // Matrix is `channels` rows by `streams + coupled` columns of 16-bit coefficients.
+        usize::from(self.channels)
+            * (usize::from(self.streams) + usize::from(self.coupled))
+            * std::mem::size_of::<i16>()
+    }
+
+    /// Ambisonics layout implied by the channel count, including whether the
+    /// head-locked stereo pair is present.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `channels` is not a supported ambisonics channel count.
+    pub const fn layout(&self) -> Result<AmbisonicsLayout> {
+        AmbisonicsLayout::from_channels(self.channels)
+    }
+
+    /// Check the parameters are self-consistent.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] when counts are zero, the channel count is not a valid
+    /// ambisonics layout, or the matrix length does not match.
+    pub fn validate(&self) -> Result<()> {
+        self.layout()?;
+        if usize::from(self.streams) + usize::from(self.coupled) == 0 {
+            return Err(Error::BadArg);
+        }
+        if self.matrix.is_empty() || self.matrix.len() != self.expected_matrix_len() {
+            return Err(Error::BadArg);
+        }
+        Ok(())
+    }
+
+    /// Serialize as `[channels, streams, coupled, matrix...]`.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::HEADER_LEN + self.matrix.len());
+        out.extend_from_slice(&[self.channels, self.streams, self.coupled]);
+        out.extend_from_slice(&self.matrix);
+        out
+    }
+
+    /// Parse a blob produced by [`Self::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the blob is truncated or inconsistent.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= Self::HEADER_LEN {
+            return Err(Error::BadArg);
+        }
+        let params = Self {
+            channels: bytes[0],
+            streams: bytes[1],
+            coupled: bytes[2],
+            matrix: bytes[Self::HEADER_LEN..].to_vec(),
+        };
+        params.validate()?;
+        Ok(params)
+    }
+}
 
 /// Safe wrapper around `OpusProjectionEncoder`.
 pub struct ProjectionEncoder {
     raw: *mut OpusProjectionEncoder,
     sample_rate: SampleRate,
     channels: u8,
+    layout: AmbisonicsLayout,
     streams: u8,
     coupled_streams: u8,
 }
@@ -29,7 +195,8 @@ impl ProjectionEncoder {
     /// Create a new projection encoder using the ambisonics helper.
     ///
     /// Returns [`Error::BadArg`] for unsupported channel/mapping combinations
-    /// or propagates libopus allocation failures.
+    /// or propagates libopus allocation failures. `channels` may include a trailing
+    /// head-locked stereo pair (`(n+1)^2 + 2`); see [`AmbisonicsLayout`].
     ///
     /// # Errors
     /// Returns [`Error::BadArg`] for invalid arguments or the libopus error produced by
@@ -40,6 +207,10 @@ impl ProjectionEncoder {
         mapping_family: i32,
         application: Application,
     ) -> Result<Self> {
+        if mapping_family != AMBISONICS_MAPPING_FAMILY {
+            return Err(Error::BadArg);
+        }
+        let layout = AmbisonicsLayout::from_channels(channels)?;
         let mut err = 0i32;
         let mut streams = 0i32;
         let mut coupled = 0i32;
@@ -64,21 +235,45 @@ impl ProjectionEncoder {
             raw: enc,
             sample_rate,
             channels,
+            layout,
             streams: u8::try_from(streams).map_err(|_| Error::BadArg)?,
             coupled_streams: u8::try_from(coupled).map_err(|_| Error::BadArg)?,
         })
     }
 
+    /// Create an ambisonics encoder for an explicit `layout`.
+    ///
+    /// # Errors
+    /// Same as [`Self::new`].
+    pub fn new_ambisonics(
+        sample_rate: SampleRate,
+        layout: AmbisonicsLayout,
+        application: Application,
+    ) -> Result<Self> {
+        Self::new(
+            sample_rate,
+            layout.channels(),
+            AMBISONICS_MAPPING_FAMILY,
+            application,
+        )
+    }
+
     fn validate_frame_size(&self, frame_size_per_ch: usize) -> Result<i32> {
         if frame_size_per_ch == 0 || frame_size_per_ch > max_frame_samples_for(self.sample_rate) {
-            return Err(Error::BadArg);
+            return Err(Error::InvalidFrameSize {
+                samples: frame_size_per_ch,
+            });
         }
         i32::try_from(frame_size_per_ch).map_err(|_| Error::BadArg)
     }
 
     fn ensure_pcm_layout(&self, len: usize, frame_size_per_ch: usize) -> Result<()> {
-        if len != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if len != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: len,
+            });
         }
         Ok(())
     }
@@ -86,9 +281,11 @@ impl ProjectionEncoder {
     /// Encode interleaved `i16` PCM.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder handle was freed, [`Error::BadArg`] for
-    /// buffer/layout issues, the libopus error mapped via [`Error::from_code`], or
-    /// [`Error::InternalError`] if libopus reports an impossible packet length.
+    /// Returns [`Error::InvalidState`] if the encoder handle was freed,
+    /// [`Error::FrameLengthMismatch`] or [`Error::InvalidFrameSize`] for PCM that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output, the libopus error
+    /// mapped via [`Error::from_code`], or [`Error::InternalError`] if libopus reports an
+    /// impossible packet length.
     pub fn encode(
         &mut self,
         pcm: &[i16],
@@ -122,9 +319,11 @@ impl ProjectionEncoder {
     /// Encode interleaved `f32` PCM.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the encoder handle was freed, [`Error::BadArg`] for
-    /// buffer/layout issues, the libopus error mapped via [`Error::from_code`], or
-    /// [`Error::InternalError`] if libopus reports an impossible packet length.
+    /// Returns [`Error::InvalidState`] if the encoder handle was freed,
+    /// [`Error::FrameLengthMismatch`] or [`Error::InvalidFrameSize`] for PCM that is not one
+    /// valid frame, [`Error::BadArg`] for an empty or oversized output, the libopus error
+    /// mapped via [`Error::from_code`], or [`Error::InternalError`] if libopus reports an
+    /// impossible packet length.
     pub fn encode_float(
         &mut self,
         pcm: &[f32],
@@ -234,6 +433,19 @@ impl ProjectionEncoder {
         Ok(buf)
     }
 
+    /// Collect the parameters a receiver needs to build a matching decoder.
+    ///
+    /// # Errors
+    /// Propagates errors from [`Self::demixing_matrix_bytes`].
+    pub fn session_params(&mut self) -> Result<SessionParams> {
+        Ok(SessionParams {
+            channels: self.channels,
+            streams: self.streams,
+            coupled: self.coupled_streams,
+            matrix: self.demixing_matrix_bytes()?,
+        })
+    }
+
     /// Number of coded streams.
     #[must_use]
     pub const fn streams(&self) -> u8 {
@@ -252,12 +464,56 @@ impl ProjectionEncoder {
         self.channels
     }
 
+    /// Ambisonics layout of the input channels.
+    #[must_use]
+    pub const fn layout(&self) -> AmbisonicsLayout {
+        self.layout
+    }
+
+    /// Whether the last two input channels are a head-locked stereo pair.
+    #[must_use]
+    pub const fn has_non_diegetic_stereo(&self) -> bool {
+        self.layout.has_non_diegetic_stereo()
+    }
+
     /// Encoder sample rate.
     #[must_use]
     pub const fn sample_rate(&self) -> SampleRate {
         self.sample_rate
     }
 
+    /// Query the algorithmic lookahead in samples.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder handle is invalid or a mapped libopus error.
+    pub fn lookahead(&mut self) -> Result<i32> {
+        self.get_int_ctl(OPUS_GET_LOOKAHEAD_REQUEST as i32)
+    }
+
+    /// Total algorithmic delay this encoder adds to the signal path (the codec lookahead).
+    ///
+    /// # Errors
+    /// Propagates errors from [`Self::lookahead`].
+    pub fn latency(&mut self) -> Result<Duration> {
+        let lookahead = usize::try_from(self.lookahead()?).map_err(|_| Error::InternalError)?;
+        Ok(self.sample_rate.duration_of(lookahead))
+    }
+
+    /// Reset the encoder state (retaining configuration).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the encoder handle is invalid or a mapped libopus error.
+    pub fn reset(&mut self) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        let r = unsafe { opus_projection_encoder_ctl(self.raw, OPUS_RESET_STATE as i32) };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Ok(())
+    }
+
     fn simple_ctl(&mut self, req: i32, val: i32) -> Result<()> {
         if self.raw.is_null() {
             return Err(Error::InvalidState);
@@ -346,16 +602,38 @@ impl ProjectionDecoder {
         })
     }
 
+    /// Create a projection decoder from serialized session parameters.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `params` are inconsistent, otherwise the same errors
+    /// as [`Self::new`].
+    pub fn from_params(sample_rate: SampleRate, params: &SessionParams) -> Result<Self> {
+        params.validate()?;
+        Self::new(
+            sample_rate,
+            params.channels,
+            params.streams,
+            params.coupled,
+            &params.matrix,
+        )
+    }
+
     fn validate_frame_size(&self, frame_size_per_ch: usize) -> Result<i32> {
         if frame_size_per_ch == 0 || frame_size_per_ch > max_frame_samples_for(self.sample_rate) {
-            return Err(Error::BadArg);
+            return Err(Error::InvalidFrameSize {
+                samples: frame_size_per_ch,
+            });
         }
         i32::try_from(frame_size_per_ch).map_err(|_| Error::BadArg)
     }
 
     fn ensure_output_layout(&self, len: usize, frame_size_per_ch: usize) -> Result<()> {
-        if len != frame_size_per_ch * self.channels as usize {
-            return Err(Error::BadArg);
+        let expected = frame_size_per_ch * self.channels as usize;
+        if len != expected {
+            return Err(Error::FrameLengthMismatch {
+                expected,
+                actual: len,
+            });
         }
         Ok(())
     }
@@ -363,9 +641,11 @@ impl ProjectionDecoder {
     /// Decode into interleaved `i16` PCM.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle was freed, [`Error::BadArg`] for
-    /// buffer/layout issues, a mapped libopus error, or [`Error::InternalError`] if libopus
-    /// reports an impossible decoded sample count.
+    /// Returns [`Error::InvalidState`] if the decoder handle was freed,
+    /// [`Error::FrameLengthMismatch`] or [`Error::InvalidFrameSize`] for an output that is
+    /// not one valid frame, [`Error::BadArg`] for an oversized packet, a mapped libopus
+    /// error, or [`Error::InternalError`] if libopus reports an impossible decoded sample
+    /// count.
     pub fn decode(
         &mut self,
         packet: &[u8],
@@ -406,9 +686,11 @@ impl ProjectionDecoder {
     /// Decode into interleaved `f32` PCM.
     ///
     /// # Errors
-    /// Returns [`Error::InvalidState`] if the decoder handle was freed, [`Error::BadArg`] for
-    /// buffer/layout issues, a mapped libopus error, or [`Error::InternalError`] if libopus
-    /// reports an impossible decoded sample count.
+    /// Returns [`Error::InvalidState`] if the decoder handle was freed,
+    /// [`Error::FrameLengthMismatch`] or [`Error::InvalidFrameSize`] for an output that is
+    /// not one valid frame, [`Error::BadArg`] for an oversized packet, a mapped libopus
+    /// error, or [`Error::InternalError`] if libopus reports an impossible decoded sample
+    /// count.
     pub fn decode_float(
         &mut self,
         packet: &[u8],
@@ -452,6 +734,45 @@ impl ProjectionDecoder {
         self.channels
     }
 
+    /// Decode a packet and pass the ambisonics output through `renderer`.
+    ///
+    /// `out` receives `frame_size_per_ch * renderer.output_channels()` interleaved samples;
+    /// returns the number of samples per channel rendered.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the decoder channel count is not an ambisonics layout,
+    /// otherwise the errors of [`Self::decode_float`] and of the renderer.
+    pub fn decode_rendered<R: AmbisonicRenderer + ?Sized>(
+        &mut self,
+        packet: &[u8],
+        renderer: &mut R,
+        out: &mut [f32],
+        frame_size_per_ch: usize,
+        fec: bool,
+    ) -> Result<usize> {
+        let layout = self.layout().ok_or(Error::BadArg)?;
+        if out.len() != frame_size_per_ch * renderer.output_channels() {
+            return Err(Error::BadArg);
+        }
+        let mut scene = vec![0f32; frame_size_per_ch * usize::from(self.channels)];
+        let decoded = self.decode_float(packet, &mut scene, frame_size_per_ch, fec)?;
+        let scene_len = decoded * usize::from(self.channels);
+        renderer.render(layout, &scene[..scene_len], out)
+    }
+
+    /// Ambisonics layout of the output channels, if the channel count describes one.
+    #[must_use]
+    pub fn layout(&self) -> Option<AmbisonicsLayout> {
+        AmbisonicsLayout::from_channels(self.channels).ok()
+    }
+
+    /// Whether the last two output channels are a head-locked stereo pair.
+    #[must_use]
+    pub fn has_non_diegetic_stereo(&self) -> bool {
+        self.layout()
+            .is_some_and(|layout| layout.has_non_diegetic_stereo())
+    }
+
     /// Number of coded streams expected in the input bitstream.
     #[must_use]
     pub const fn streams(&self) -> u8 {
@@ -469,6 +790,72 @@ impl ProjectionDecoder {
     pub const fn sample_rate(&self) -> SampleRate {
         self.sample_rate
     }
+
+    /// Reset the decoder state, dropping any concealment history.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid or a mapped libopus error.
+    pub fn reset(&mut self) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        let r = unsafe { opus_projection_decoder_ctl(self.raw, OPUS_RESET_STATE as i32) };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Ok(())
+    }
+
+    /// Set post-decode gain in Q8 dB units.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid or a mapped libopus error.
+    pub fn set_gain(&mut self, q8_db: i32) -> Result<()> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        let r =
+            unsafe { opus_projection_decoder_ctl(self.raw, OPUS_SET_GAIN_REQUEST as i32, q8_db) };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Ok(())
+    }
+
+    /// Query post-decode gain in Q8 dB units.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the decoder handle is invalid or a mapped libopus error.
+    pub fn gain(&mut self) -> Result<i32> {
+        if self.raw.is_null() {
+            return Err(Error::InvalidState);
+        }
+        let mut v = 0i32;
+        let r =
+            unsafe { opus_projection_decoder_ctl(self.raw, OPUS_GET_GAIN_REQUEST as i32, &mut v) };
+        if r != 0 {
+            return Err(Error::from_code(r));
+        }
+        Ok(v)
+    }
+
+    /// Set post-decode gain in decibels, rounded to the Q8 steps libopus uses.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for a gain outside [`Q8Gain`]'s range, otherwise the
+    /// errors of [`Self::set_gain`].
+    pub fn set_gain_db(&mut self, db: f32) -> Result<()> {
+        self.set_gain(i32::from(Q8Gain::from_db(db)?.value()))
+    }
+
+    /// Query post-decode gain in decibels.
+    ///
+    /// # Errors
+    /// Same as [`Self::gain`].
+    pub fn gain_db(&mut self) -> Result<f32> {
+        let q8 = i16::try_from(self.gain()?).map_err(|_| Error::InternalError)?;
+        Ok(Q8Gain::new(q8).db())
+    }
 }
 
 impl Drop for ProjectionDecoder {
@@ -0,0 +1,125 @@
+//! Rendering of decoded ambisonics frames to loudspeaker or headphone feeds
+
+use crate::error::{Error, Result};
+use crate::projection::AmbisonicsLayout;
+
+/// Turns interleaved ambisonics frames into interleaved output frames.
+///
+/// Implementations receive decoder output in ACN channel order with SN3D
+/// normalization (`AmbiX`), optionally followed by the head-locked stereo pair
+/// described by `layout`.
+pub trait AmbisonicRenderer {
+    /// Number of interleaved output channels produced per frame.
+    fn output_channels(&self) -> usize;
+
+    /// Render `input` (`layout.channels()` interleaved channels) into `output`.
+    ///
+    /// Returns the number of frames (samples per channel) written.
+    ///
+    /// # Errors
+    /// Implementations should return [`Error::BadArg`] when buffer lengths do not
+    /// match the layout or the layout is unsupported.
+    fn render(
+        &mut self,
+        layout: AmbisonicsLayout,
+        input: &[f32],
+        output: &mut [f32],
+    ) -> Result<usize>;
+}
+
+// ACN indices of the first-order components.
+const ACN_W: usize = 0;
+const ACN_Y: usize = 1;
+const ACN_X: usize = 3;
+
+/// Basic stereo decoder using a pair of virtual cardioid microphones.
+///
+/// Only the first-order components are used, so higher orders are rendered as
+/// their first-order projection. The head-locked stereo pair, when present, is
+/// mixed straight into left/right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoRenderer {
+    left_gains: [f32; 3],
+    right_gains: [f32; 3],
+}
+
+impl StereoRenderer {
+    /// Virtual cardioids pointing at `+angle_degrees` (left) and `-angle_degrees` (right).
+    #[must_use]
+    pub fn new(angle_degrees: f32) -> Self {
+        let (sin, cos) = angle_degrees.to_radians().sin_cos();
+        Self {
+            left_gains: [0.5, 0.5 * sin, 0.5 * cos],
+            right_gains: [0.5, -0.5 * sin, 0.5 * cos],
+        }
+    }
+}
+
+impl Default for StereoRenderer {
+    /// Cardioids facing hard left and right (`L = (W + Y) / 2`, `R = (W - Y) / 2`).
+    fn default() -> Self {
+        Self::new(90.0)
+    }
+}
+
+impl AmbisonicRenderer for StereoRenderer {
+    fn output_channels(&self) -> usize {
+        2
+    }
+
+    fn render(
+        &mut self,
+        layout: AmbisonicsLayout,
+        input: &[f32],
+        output: &mut [f32],
+    ) -> Result<usize> {
+        let channels = usize::from(layout.channels());
+        if !input.len().is_multiple_of(channels) {
+            return Err(Error::BadArg);
+        }
+        let frames = input.len() / channels;
+        if output.len() < frames * 2 {
+            return Err(Error::BufferTooSmall);
+        }
+        let stereo = layout.non_diegetic_channels();
+        for (frame, out) in input.chunks_exact(channels).zip(output.chunks_exact_mut(2)) {
+            let components = [frame[ACN_W], frame[ACN_Y], frame[ACN_X]];
+            let project = |gains: &[f32; 3]| -> f32 {
+                gains.iter().zip(components).map(|(g, c)| g * c).sum()
+            };
+            let (mut left, mut right) = (project(&self.left_gains), project(&self.right_gains));
+            if let Some((l, r)) = stereo {
+                left += frame[l];
+                right += frame[r];
+            }
+            out[0] = left;
+            out[1] = right;
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stereo_renderer_pans_lateral_sources() {
+        let layout = AmbisonicsLayout::new(1, true).unwrap();
+        // Source hard left: W = 1, Y = 1 (SN3D), plus head-locked right-only signal.
+        let input = [1.0, 1.0, 0.0, 0.0, 0.0, 0.25];
+        let mut out = [0.0f32; 2];
+        let frames = StereoRenderer::default()
+            .render(layout, &input, &mut out)
+            .unwrap();
+        assert_eq!(frames, 1);
+        assert!((out[0] - 1.0).abs() < 1e-6);
+        assert!((out[1] - 0.25).abs() < 1e-6);
+
+        let mut short = [0.0f32; 1];
+        assert_eq!(
+            StereoRenderer::default().render(layout, &input, &mut short),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
@@ -6,10 +6,17 @@ use crate::bindings::{
     opus_repacketizer_out_range,
 };
 use crate::error::{Error, Result};
+use crate::packet::{frame_sizes, packet_pad};
+
+/// Output bytes a merged packet needs beyond its frames' own bounds: TOC, frame count and
+/// padding length.
+const HEADER_BOUND: usize = 3;
 
 /// Repackages Opus frames into packets.
 pub struct Repacketizer {
     rp: *mut OpusRepacketizer,
+    /// Upper bound on the output bytes each queued frame contributes.
+    frame_bounds: Vec<usize>,
 }
 
 unsafe impl Send for Repacketizer {}
@@ -25,12 +32,16 @@ impl Repacketizer {
         if rp.is_null() {
             return Err(Error::AllocFail);
         }
-        Ok(Self { rp })
+        Ok(Self {
+            rp,
+            frame_bounds: Vec::new(),
+        })
     }
 
     /// Reset internal state.
     pub fn reset(&mut self) {
         unsafe { opus_repacketizer_init(self.rp) };
+        self.frame_bounds.clear();
     }
 
     /// Add a packet to the current state.
@@ -46,6 +57,15 @@ impl Repacketizer {
         if r != 0 {
             return Err(Error::from_code(r));
         }
+        // Each frame may need a two-byte length and a two-byte extension separator; the
+        // packet's header and padding (which carries its extensions) go to its first frame.
+        let sizes = frame_sizes(packet)?;
+        let overhead = packet.len() - sizes.iter().sum::<usize>();
+        let first = self.frame_bounds.len();
+        self.frame_bounds.extend(sizes.iter().map(|size| size + 4));
+        if let Some(bound) = self.frame_bounds.get_mut(first) {
+            *bound += overhead;
+        }
         Ok(())
     }
 
@@ -91,6 +111,53 @@ impl Repacketizer {
         }
         usize::try_from(n).map_err(|_| Error::InternalError)
     }
+
+    /// Emit frames `[begin, end)` as a new, exactly sized packet.
+    ///
+    /// The buffer is sized from the queued frames' lengths, so unlike [`Self::out_range`]
+    /// it can never be too small.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if the range is empty or extends past the queued frames.
+    pub fn out_range_to_vec(&mut self, begin: i32, end: i32) -> Result<Vec<u8>> {
+        let (Ok(first), Ok(last)) = (usize::try_from(begin), usize::try_from(end)) else {
+            return Err(Error::BadArg);
+        };
+        if last <= first || last > self.frame_bounds.len() {
+            return Err(Error::BadArg);
+        }
+        let bound = HEADER_BOUND + self.frame_bounds[first..last].iter().sum::<usize>();
+        let mut out = vec![0u8; bound];
+        let len = self.out_range(begin, end, &mut out)?;
+        out.truncate(len);
+        Ok(out)
+    }
+
+    /// Emit all queued frames as a new, exactly sized packet.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if no frames are queued.
+    pub fn out_to_vec(&mut self) -> Result<Vec<u8>> {
+        let end = i32::try_from(self.frame_bounds.len()).map_err(|_| Error::InternalError)?;
+        self.out_range_to_vec(0, end)
+    }
+
+    /// Emit a packet with all queued frames, padded to exactly `target_len` bytes.
+    ///
+    /// Returns `target_len`.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `target_len` is zero or exceeds `out`, or
+    /// [`Error::BufferTooSmall`] if the merged frames do not fit in `target_len` bytes.
+    pub fn out_padded(&mut self, out: &mut [u8], target_len: usize) -> Result<usize> {
+        if target_len == 0 || target_len > out.len() {
+            return Err(Error::BadArg);
+        }
+        let out = &mut out[..target_len];
+        let len = self.out(out)?;
+        packet_pad(out, len, target_len)?;
+        Ok(target_len)
+    }
 }
 
 impl Drop for Repacketizer {
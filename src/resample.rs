@@ -0,0 +1,291 @@
+//! Sample-rate conversion for playback at rates outside the Opus set (e.g. 44.1 kHz)
+
+use crate::decoder::Decoder;
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use crate::types::{Channels, SampleRate};
+use std::f64::consts::PI;
+use std::time::Duration;
+
+/// Kernel half-width in input samples; also the resampler's buffering delay.
+const HALF_TAPS: usize = 16;
+
+/// Highest output rate accepted by [`Resampler::new`].
+pub const MAX_OUTPUT_RATE: u32 = 384_000;
+
+/// Streaming windowed-sinc resampler for interleaved `f32` audio.
+///
+/// Output is time-aligned with the input; the last 16 input samples of each call are held
+/// back until the next call supplies the lookahead they need.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    /// Input samples advanced per output sample, as the reduced fraction `step / output_rate`.
+    step: u64,
+    denom: u64,
+    cutoff: f64,
+    channels: usize,
+    buffer: Vec<f32>,
+    /// Position of the next output sample in `buffer`, in units of `1 / denom` input samples.
+    position: u64,
+}
+
+impl Resampler {
+    /// Create a resampler from `input_rate` to `output_rate` Hz.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if either rate is zero or above [`MAX_OUTPUT_RATE`].
+    pub fn new(input_rate: u32, output_rate: u32, channels: Channels) -> Result<Self> {
+        if input_rate == 0
+            || output_rate == 0
+            || input_rate > MAX_OUTPUT_RATE
+            || output_rate > MAX_OUTPUT_RATE
+        {
+            return Err(Error::BadArg);
+        }
+        let gcd = gcd(u64::from(input_rate), u64::from(output_rate));
+        let mut resampler = Self {
+            input_rate,
+            output_rate,
+            step: u64::from(input_rate) / gcd,
+            denom: u64::from(output_rate) / gcd,
+            // Slightly below the lower Nyquist frequency to leave room for the window's transition band.
+            cutoff: 0.95 * f64::min(1.0, f64::from(output_rate) / f64::from(input_rate)),
+            channels: channels.as_usize(),
+            buffer: Vec::new(),
+            position: 0,
+        };
+        resampler.reset();
+        Ok(resampler)
+    }
+
+    /// Input rate in Hz.
+    #[must_use]
+    pub const fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    /// Output rate in Hz.
+    #[must_use]
+    pub const fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Delay between receiving an input sample and being able to emit output for it.
+    #[must_use]
+    pub fn latency(&self) -> Duration {
+        Duration::from_secs(HALF_TAPS as u64) / self.input_rate
+    }
+
+    /// Upper bound on output samples per channel produced from `input_frames` input samples per channel.
+    #[must_use]
+    pub fn max_output_frames(&self, input_frames: usize) -> usize {
+        (input_frames as u64 * self.denom).div_ceil(self.step) as usize + 1
+    }
+
+    /// Resample interleaved `input`, appending interleaved output to `output`.
+    ///
+    /// Returns the number of samples per channel appended.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `input` is not a whole number of frames.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) -> Result<usize> {
+        if !input.len().is_multiple_of(self.channels) {
+            return Err(Error::BadArg);
+        }
+        self.buffer.extend_from_slice(input);
+        let frames = self.buffer.len() / self.channels;
+        let mut produced = 0;
+        loop {
+            let centre = (self.position / self.denom) as usize;
+            if centre + HALF_TAPS >= frames {
+                break;
+            }
+            let frac = (self.position % self.denom) as f64 / self.denom as f64;
+            let first = centre + 1 - HALF_TAPS;
+            let weights: [f64; 2 * HALF_TAPS] = std::array::from_fn(|k| {
+                let x = (first + k) as f64 - centre as f64 - frac;
+                self.kernel(x)
+            });
+            for ch in 0..self.channels {
+                let sum: f64 = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| w * f64::from(self.buffer[(first + k) * self.channels + ch]))
+                    .sum();
+                output.push(sum as f32);
+            }
+            self.position += self.step;
+            produced += 1;
+        }
+        let drop = ((self.position / self.denom) as usize).saturating_sub(HALF_TAPS);
+        self.buffer.drain(..drop * self.channels);
+        self.position -= drop as u64 * self.denom;
+        Ok(produced)
+    }
+
+    /// Clear history; the next call starts a new, time-aligned stream.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(HALF_TAPS * self.channels, 0.0);
+        self.position = HALF_TAPS as u64 * self.denom;
+    }
+
+    /// Blackman-windowed sinc low-pass at `cutoff` (relative to the input Nyquist).
+    #[allow(clippy::cast_precision_loss)]
+    fn kernel(&self, x: f64) -> f64 {
+        let t = x / HALF_TAPS as f64;
+        if t.abs() >= 1.0 {
+            return 0.0;
+        }
+        let window = 0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos();
+        let arg = PI * self.cutoff * x;
+        let sinc = if arg.abs() < 1e-9 {
+            1.0
+        } else {
+            arg.sin() / arg
+        };
+        self.cutoff * sinc * window
+    }
+}
+
+const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Decoder that decodes at 48 kHz and resamples to an arbitrary output rate.
+pub struct ResamplingDecoder {
+    decoder: Decoder,
+    resampler: Resampler,
+    decoded: Vec<f32>,
+    resampled: Vec<f32>,
+}
+
+impl ResamplingDecoder {
+    /// Create a decoder producing `output_rate` Hz audio.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] for an unsupported output rate, or any error from
+    /// creating the decoder.
+    pub fn new(output_rate: u32, channels: Channels) -> Result<Self> {
+        let decoder = Decoder::new(SampleRate::Hz48000, channels)?;
+        Ok(Self {
+            resampler: Resampler::new(48_000, output_rate, channels)?,
+            decoder,
+            decoded: Vec::new(),
+            resampled: Vec::new(),
+        })
+    }
+
+    /// Output rate in Hz.
+    #[must_use]
+    pub const fn output_rate(&self) -> u32 {
+        self.resampler.output_rate()
+    }
+
+    /// Underlying 48 kHz decoder, e.g. for CTLs.
+    #[must_use]
+    pub const fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Algorithmic delay added by decoding: the resampler's buffering delay.
+    #[must_use]
+    pub fn latency(&self) -> Duration {
+        self.decoder.latency() + self.resampler.latency()
+    }
+
+    /// Largest interleaved output length one call can produce for `packet` (or for
+    /// concealment when `packet` is empty).
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode_pcm_frame`].
+    pub fn max_output_len(&mut self, packet: &[u8]) -> Result<usize> {
+        let channels = self.decoder.channels().as_usize();
+        let frames = self.decoder.frame_len_for(packet)? / channels;
+        Ok(self.resampler.max_output_frames(frames) * channels)
+    }
+
+    /// Decode `packet` (empty for PLC) and write resampled interleaved audio to `output`.
+    ///
+    /// Returns samples per channel written, which varies by a sample between calls for
+    /// non-integer rate ratios.
+    ///
+    /// # Errors
+    /// Returns [`Error::BufferTooSmall`] if `output` is shorter than
+    /// [`Self::max_output_len`], otherwise the same errors as [`Decoder::decode_float`].
+    pub fn decode<S: Sample>(
+        &mut self,
+        packet: &[u8],
+        output: &mut [S],
+        fec: bool,
+    ) -> Result<usize> {
+        let channels = self.decoder.channels().as_usize();
+        let len = self.decoder.frame_len_for(packet)?;
+        if output.len() < self.resampler.max_output_frames(len / channels) * channels {
+            return Err(Error::BufferTooSmall);
+        }
+        self.decoded.resize(len, 0.0);
+        let frames = self.decoder.decode_float(packet, &mut self.decoded, fec)?;
+        self.resampled.clear();
+        let produced = self
+            .resampler
+            .process(&self.decoded[..frames * channels], &mut self.resampled)?;
+        for (out, &sample) in output.iter_mut().zip(&self.resampled) {
+            *out = S::from_f32(sample);
+        }
+        Ok(produced)
+    }
+
+    /// Reset decoder and resampler state.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::reset`].
+    pub fn reset(&mut self) -> Result<()> {
+        self.resampler.reset();
+        self.decoder.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn resampled_sine_matches_ideal() {
+        let input: Vec<f32> =
+            crate::testsignal::sine(SampleRate::Hz48000, Channels::Mono, 1_000.0, 0.5, 48_000);
+        let mut resampler = Resampler::new(48_000, 44_100, Channels::Mono).unwrap();
+        let mut output = Vec::new();
+        for chunk in input.chunks(960) {
+            let produced = resampler.process(chunk, &mut output).unwrap();
+            assert!(produced <= resampler.max_output_frames(chunk.len()));
+        }
+        assert!(
+            (44_080..=44_100).contains(&output.len()),
+            "{}",
+            output.len()
+        );
+
+        let max_error = output
+            .iter()
+            .enumerate()
+            .skip(HALF_TAPS)
+            .map(|(n, &y)| {
+                let ideal = 0.5 * (2.0 * PI * 1_000.0 * n as f64 / 44_100.0).sin();
+                (f64::from(y) - ideal).abs()
+            })
+            .fold(0.0, f64::max);
+        assert!(max_error < 1e-3, "max error {max_error}");
+
+        assert!(Resampler::new(0, 44_100, Channels::Mono).is_err());
+        let mut stereo = Resampler::new(48_000, 32_000, Channels::Stereo).unwrap();
+        assert!(stereo.process(&[0.0; 3], &mut output).is_err());
+    }
+}
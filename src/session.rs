@@ -0,0 +1,437 @@
+//! Sans-IO model of a call's send and receive timelines
+//!
+//! [`Session`] is a pure state machine: it owns no codec, thread, socket or clock. The
+//! application feeds it [`Event`]s (PCM ready, packet arrived, loss feedback, clock
+//! ticks) and drains the resulting [`Action`]s with [`Session::poll_action`], performing
+//! the encoding, sending, decoding and concealment itself. The same logic therefore
+//! serves async runtimes, blocking loops and embedded schedulers alike, and can be
+//! tested by replaying event sequences.
+
+use crate::error::{Error, Result};
+use crate::types::{Bitrate, FrameSize, PacketLossPerc, SampleRate};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Timing and rate-adaptation settings of a [`Session`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionConfig {
+    /// Duration of every frame, on both sides of the call.
+    pub frame_size: FrameSize,
+    /// Delay between the first packet arriving and its playout, absorbing jitter.
+    pub playout_delay: Duration,
+    /// Playout stops after concealing this long without a packet, and restarts with
+    /// [`Self::playout_delay`] once packets arrive again.
+    pub max_concealment: Duration,
+    /// Received packets held at most; the oldest is dropped beyond that.
+    pub max_buffered: usize,
+    /// Initial encoder bitrate in bits per second.
+    pub start_bitrate: i32,
+    /// Lowest bitrate rate adaptation goes to.
+    pub min_bitrate: i32,
+    /// Highest bitrate rate adaptation goes to.
+    pub max_bitrate: i32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            frame_size: FrameSize::Ms20,
+            playout_delay: Duration::from_millis(60),
+            max_concealment: Duration::from_millis(500),
+            max_buffered: 50,
+            start_bitrate: 32_000,
+            min_bitrate: 6_000,
+            max_bitrate: 128_000,
+        }
+    }
+}
+
+/// Input to [`Session::handle`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A frame of PCM is ready to be encoded.
+    PcmReady,
+    /// The application encoded the frame requested by the last [`Action::Encode`].
+    Encoded(Vec<u8>),
+    /// A packet arrived from the peer.
+    PacketArrived {
+        /// Transport sequence number.
+        seq: u16,
+        /// Transport timestamp in 48 kHz samples.
+        timestamp: u32,
+        /// Opus packet.
+        payload: Vec<u8>,
+    },
+    /// The peer reported how many of our packets it lost.
+    LossReport {
+        /// Share of packets lost.
+        loss: PacketLossPerc,
+    },
+    /// Monotonic time since an arbitrary origin; drives playout.
+    Tick {
+        /// Current time.
+        now: Duration,
+    },
+}
+
+/// Output of a [`Session`], in the order the application should perform them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Encode the pending frame now and report it with [`Event::Encoded`].
+    Encode {
+        /// Sequence number the packet will carry.
+        seq: u16,
+        /// Timestamp the packet will carry.
+        timestamp: u32,
+    },
+    /// Send an encoded packet to the peer.
+    Send {
+        /// Transport sequence number.
+        seq: u16,
+        /// Transport timestamp in 48 kHz samples.
+        timestamp: u32,
+        /// Opus packet.
+        payload: Vec<u8>,
+    },
+    /// Decode a packet for playout.
+    Decode {
+        /// Sequence number of the packet.
+        seq: u16,
+        /// Opus packet.
+        payload: Vec<u8>,
+    },
+    /// Packet `seq` is missing but the next one is here: recover it from that packet's
+    /// in-band FEC by decoding `payload` with `fec` set.
+    DecodeFec {
+        /// Sequence number of the missing packet.
+        seq: u16,
+        /// The following packet, carrying the redundancy.
+        payload: Vec<u8>,
+    },
+    /// No packet is available for this slot: run packet-loss concealment.
+    Conceal {
+        /// Audio to conceal.
+        duration: Duration,
+    },
+    /// Set the encoder bitrate.
+    AdjustBitrate(Bitrate),
+}
+
+/// Loss above which rate adaptation backs off.
+const LOSS_HIGH: PacketLossPerc = PacketLossPerc::new(10);
+/// Loss below which rate adaptation probes upwards.
+const LOSS_LOW: PacketLossPerc = PacketLossPerc::new(2);
+
+/// Sans-IO call state machine; see the [module documentation](self).
+///
+/// Sequence numbers wrap; packets arriving after their playout slot are dropped.
+#[derive(Debug, Clone)]
+pub struct Session {
+    config: SessionConfig,
+    frame: Duration,
+    frame_samples: u32,
+    now: Duration,
+    actions: VecDeque<Action>,
+    send_seq: u16,
+    send_timestamp: u32,
+    pending_encode: Option<(u16, u32)>,
+    bitrate: i32,
+    /// Received packets keyed by sequence number extended past wrap-around.
+    buffer: BTreeMap<u64, Vec<u8>>,
+    /// Extended sequence number played next, once the first packet arrived.
+    next_play: Option<u64>,
+    /// When the next playout slot is due, while playing.
+    next_due: Option<Duration>,
+    concealed: Duration,
+}
+
+impl Session {
+    /// Create a session.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `max_buffered` is 0 or the bitrates are not
+    /// positive and ordered `min <= start <= max`.
+    pub fn new(config: SessionConfig) -> Result<Self> {
+        if config.max_buffered == 0
+            || config.min_bitrate <= 0
+            || config.min_bitrate > config.start_bitrate
+            || config.start_bitrate > config.max_bitrate
+        {
+            return Err(Error::BadArg);
+        }
+        let frame_samples = config.frame_size.samples(SampleRate::Hz48000);
+        Ok(Self {
+            config,
+            frame: SampleRate::Hz48000.duration_of(frame_samples),
+            frame_samples: frame_samples as u32,
+            now: Duration::ZERO,
+            actions: VecDeque::new(),
+            send_seq: 0,
+            send_timestamp: 0,
+            pending_encode: None,
+            bitrate: config.start_bitrate,
+            buffer: BTreeMap::new(),
+            next_play: None,
+            next_due: None,
+            concealed: Duration::ZERO,
+        })
+    }
+
+    /// Settings the session was created with.
+    #[must_use]
+    pub const fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Bitrate last requested through [`Action::AdjustBitrate`], or the start bitrate.
+    #[must_use]
+    pub const fn bitrate(&self) -> i32 {
+        self.bitrate
+    }
+
+    /// Packets received and waiting for playout.
+    #[must_use]
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Feed one event; the actions it causes are queued for [`Self::poll_action`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] for [`Event::Encoded`] without a pending
+    /// [`Action::Encode`], and [`Error::BadArg`] for an empty packet.
+    pub fn handle(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::PcmReady => {
+                let (seq, timestamp) = (self.send_seq, self.send_timestamp);
+                self.send_seq = seq.wrapping_add(1);
+                self.send_timestamp = timestamp.wrapping_add(self.frame_samples);
+                self.pending_encode = Some((seq, timestamp));
+                self.actions.push_back(Action::Encode { seq, timestamp });
+            }
+            Event::Encoded(payload) => {
+                let (seq, timestamp) = self.pending_encode.take().ok_or(Error::InvalidState)?;
+                self.actions.push_back(Action::Send {
+                    seq,
+                    timestamp,
+                    payload,
+                });
+            }
+            Event::PacketArrived { seq, payload, .. } => {
+                if payload.is_empty() {
+                    return Err(Error::BadArg);
+                }
+                self.receive(seq, payload);
+            }
+            Event::LossReport { loss } => self.adapt_bitrate(loss),
+            Event::Tick { now } => {
+                self.now = now;
+                self.play_due();
+            }
+        }
+        Ok(())
+    }
+
+    /// Next action to perform, or `None` once the queue is drained.
+    pub fn poll_action(&mut self) -> Option<Action> {
+        self.actions.pop_front()
+    }
+
+    fn receive(&mut self, seq: u16, payload: Vec<u8>) {
+        let next = *self.next_play.get_or_insert(u64::from(seq));
+        let offset = i64::from(seq.wrapping_sub(next as u16) as i16);
+        let Some(extended) = next.checked_add_signed(offset).filter(|&ext| ext >= next) else {
+            // Too late for its playout slot.
+            return;
+        };
+        self.buffer.insert(extended, payload);
+        while self.buffer.len() > self.config.max_buffered {
+            // Overflow drops the oldest packet and moves playout past it.
+            if let Some((oldest, _)) = self.buffer.pop_first() {
+                self.next_play = Some(oldest + 1);
+            }
+        }
+        if self.next_due.is_none() {
+            self.next_due = Some(self.now + self.config.playout_delay);
+            self.concealed = Duration::ZERO;
+        }
+    }
+
+    fn play_due(&mut self) {
+        while let (Some(due), Some(next)) = (self.next_due, self.next_play)
+            && due <= self.now
+        {
+            let seq = next as u16;
+            if let Some(payload) = self.buffer.remove(&next) {
+                self.actions.push_back(Action::Decode { seq, payload });
+                self.concealed = Duration::ZERO;
+            } else if let Some(payload) = self.buffer.get(&(next + 1)) {
+                self.actions.push_back(Action::DecodeFec {
+                    seq,
+                    payload: payload.clone(),
+                });
+                self.concealed = Duration::ZERO;
+            } else {
+                self.actions.push_back(Action::Conceal {
+                    duration: self.frame,
+                });
+                self.concealed += self.frame;
+            }
+            self.next_play = Some(next + 1);
+            self.next_due = Some(due + self.frame);
+            if self.buffer.is_empty() && self.concealed >= self.config.max_concealment {
+                // The peer went quiet: wait for it and resynchronize on its next packet.
+                self.next_play = None;
+                self.next_due = None;
+            }
+        }
+    }
+
+    /// Additive increase, multiplicative decrease on the peer's loss reports.
+    #[allow(clippy::cast_precision_loss)]
+    fn adapt_bitrate(&mut self, loss: PacketLossPerc) {
+        let target = if loss > LOSS_HIGH {
+            (self.bitrate as f32 * (1.0 - loss.fraction() / 2.0)) as i32
+        } else if loss < LOSS_LOW {
+            self.bitrate + self.bitrate / 20
+        } else {
+            self.bitrate
+        };
+        let target = target.clamp(self.config.min_bitrate, self.config.max_bitrate);
+        if target != self.bitrate {
+            self.bitrate = target;
+            self.actions
+                .push_back(Action::AdjustBitrate(Bitrate::Custom(target)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(session: &mut Session) -> Vec<Action> {
+        std::iter::from_fn(|| session.poll_action()).collect()
+    }
+
+    fn arrive(session: &mut Session, seq: u16) {
+        session
+            .handle(Event::PacketArrived {
+                seq,
+                timestamp: u32::from(seq) * 960,
+                payload: vec![seq as u8],
+            })
+            .unwrap();
+    }
+
+    fn tick(session: &mut Session, millis: u64) -> Vec<Action> {
+        session
+            .handle(Event::Tick {
+                now: Duration::from_millis(millis),
+            })
+            .unwrap();
+        drain(session)
+    }
+
+    #[test]
+    fn send_path_numbers_packets() {
+        let mut session = Session::new(SessionConfig::default()).unwrap();
+        assert_eq!(
+            session.handle(Event::Encoded(vec![1])),
+            Err(Error::InvalidState)
+        );
+        for seq in 0..2u16 {
+            session.handle(Event::PcmReady).unwrap();
+            let timestamp = u32::from(seq) * 960;
+            assert_eq!(drain(&mut session), [Action::Encode { seq, timestamp }]);
+            session.handle(Event::Encoded(vec![7])).unwrap();
+            assert_eq!(
+                drain(&mut session),
+                [Action::Send {
+                    seq,
+                    timestamp,
+                    payload: vec![7],
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn receive_path_reorders_recovers_and_conceals() {
+        let config = SessionConfig {
+            max_concealment: Duration::from_millis(40),
+            ..SessionConfig::default()
+        };
+        let mut session = Session::new(config).unwrap();
+        arrive(&mut session, 65_534);
+        arrive(&mut session, 1);
+        arrive(&mut session, 65_535);
+        // Nothing plays before the playout delay.
+        assert!(tick(&mut session, 59).is_empty());
+        let played = tick(&mut session, 100);
+        assert_eq!(
+            played,
+            [
+                Action::Decode {
+                    seq: 65_534,
+                    payload: vec![254],
+                },
+                Action::Decode {
+                    seq: 65_535,
+                    payload: vec![255],
+                },
+                Action::DecodeFec {
+                    seq: 0,
+                    payload: vec![1],
+                },
+            ]
+        );
+        // Seq 65535 is now late, 1 still plays.
+        arrive(&mut session, 65_535);
+        let conceal = Action::Conceal {
+            duration: Duration::from_millis(20),
+        };
+        assert_eq!(
+            tick(&mut session, 160),
+            [
+                Action::Decode {
+                    seq: 1,
+                    payload: vec![1],
+                },
+                conceal.clone(),
+                conceal,
+            ]
+        );
+        // Playout stopped after 40 ms of concealment and restarts on the next packet.
+        assert!(tick(&mut session, 500).is_empty());
+        arrive(&mut session, 9);
+        assert!(tick(&mut session, 559).is_empty());
+        assert_eq!(tick(&mut session, 560).len(), 1);
+    }
+
+    #[test]
+    fn loss_reports_adapt_bitrate() {
+        let mut session = Session::new(SessionConfig::default()).unwrap();
+        session
+            .handle(Event::LossReport {
+                loss: PacketLossPerc::from_fraction(0.2),
+            })
+            .unwrap();
+        assert_eq!(
+            drain(&mut session),
+            [Action::AdjustBitrate(Bitrate::Custom(28_800))]
+        );
+        session
+            .handle(Event::LossReport {
+                loss: PacketLossPerc::new(5),
+            })
+            .unwrap();
+        assert!(drain(&mut session).is_empty());
+        session
+            .handle(Event::LossReport {
+                loss: PacketLossPerc::ZERO,
+            })
+            .unwrap();
+        assert_eq!(session.bitrate(), 30_240);
+    }
+}
@@ -0,0 +1,226 @@
+//! Cloneable, thread-safe codec handles
+//!
+//! [`Encoder`] and [`Decoder`] take `&mut self` for every call, so code touching one
+//! codec from several tasks needs a lock around it. [`SharedEncoder`] and
+//! [`SharedDecoder`] provide that lock: clones share one codec behind an
+//! `Arc<Mutex<_>>`, each call locks for exactly its own duration, and the `try_*`
+//! variants return `None` instead of waiting when another task holds the codec. A lock
+//! poisoned by a panicking caller surfaces as [`Error::InvalidState`].
+
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    mutex.lock().map_err(|_| Error::InvalidState)
+}
+
+fn try_lock<T>(mutex: &Mutex<T>) -> Result<Option<MutexGuard<'_, T>>> {
+    match mutex.try_lock() {
+        Ok(guard) => Ok(Some(guard)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Poisoned(_)) => Err(Error::InvalidState),
+    }
+}
+
+/// [`Encoder`] shared between threads or tasks; clones refer to the same encoder.
+#[derive(Clone)]
+pub struct SharedEncoder {
+    inner: Arc<Mutex<Encoder>>,
+}
+
+impl SharedEncoder {
+    /// Share `encoder`.
+    #[must_use]
+    pub fn new(encoder: Encoder) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(encoder)),
+        }
+    }
+
+    /// Lock the encoder for the duration of `f`, e.g. to apply several CTLs atomically.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the lock is poisoned, or the error of `f`.
+    pub fn with<T>(&self, f: impl FnOnce(&mut Encoder) -> Result<T>) -> Result<T> {
+        f(&mut *lock(&self.inner)?)
+    }
+
+    /// Like [`Self::with`], but returns `Ok(None)` without running `f` if the encoder is
+    /// in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::with`].
+    pub fn try_with<T>(&self, f: impl FnOnce(&mut Encoder) -> Result<T>) -> Result<Option<T>> {
+        try_lock(&self.inner)?
+            .map(|mut encoder| f(&mut encoder))
+            .transpose()
+    }
+
+    /// Encode i16 PCM, waiting for the encoder if needed.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::encode`], plus [`Error::InvalidState`] for a poisoned lock.
+    pub fn encode(&self, input: &[i16], output: &mut [u8]) -> Result<usize> {
+        self.with(|encoder| encoder.encode(input, output))
+    }
+
+    /// Encode f32 PCM, waiting for the encoder if needed.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::encode_float`], plus [`Error::InvalidState`] for a poisoned lock.
+    pub fn encode_float(&self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        self.with(|encoder| encoder.encode_float(input, output))
+    }
+
+    /// Encode i16 PCM, or return `Ok(None)` at once if the encoder is in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode`].
+    pub fn try_encode(&self, input: &[i16], output: &mut [u8]) -> Result<Option<usize>> {
+        self.try_with(|encoder| encoder.encode(input, output))
+    }
+
+    /// Encode f32 PCM, or return `Ok(None)` at once if the encoder is in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::encode_float`].
+    pub fn try_encode_float(&self, input: &[f32], output: &mut [u8]) -> Result<Option<usize>> {
+        self.try_with(|encoder| encoder.encode_float(input, output))
+    }
+}
+
+impl From<Encoder> for SharedEncoder {
+    fn from(encoder: Encoder) -> Self {
+        Self::new(encoder)
+    }
+}
+
+/// [`Decoder`] shared between threads or tasks; clones refer to the same decoder.
+#[derive(Clone)]
+pub struct SharedDecoder {
+    inner: Arc<Mutex<Decoder>>,
+}
+
+impl SharedDecoder {
+    /// Share `decoder`.
+    #[must_use]
+    pub fn new(decoder: Decoder) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(decoder)),
+        }
+    }
+
+    /// Lock the decoder for the duration of `f`.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidState`] if the lock is poisoned, or the error of `f`.
+    pub fn with<T>(&self, f: impl FnOnce(&mut Decoder) -> Result<T>) -> Result<T> {
+        f(&mut *lock(&self.inner)?)
+    }
+
+    /// Like [`Self::with`], but returns `Ok(None)` without running `f` if the decoder is
+    /// in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::with`].
+    pub fn try_with<T>(&self, f: impl FnOnce(&mut Decoder) -> Result<T>) -> Result<Option<T>> {
+        try_lock(&self.inner)?
+            .map(|mut decoder| f(&mut decoder))
+            .transpose()
+    }
+
+    /// Decode into i16 PCM, waiting for the decoder if needed.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode`], plus [`Error::InvalidState`] for a poisoned lock.
+    pub fn decode(&self, input: &[u8], output: &mut [i16], fec: bool) -> Result<usize> {
+        self.with(|decoder| decoder.decode(input, output, fec))
+    }
+
+    /// Decode into f32 PCM, waiting for the decoder if needed.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode_float`], plus [`Error::InvalidState`] for a poisoned lock.
+    pub fn decode_float(&self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
+        self.with(|decoder| decoder.decode_float(input, output, fec))
+    }
+
+    /// Decode into i16 PCM, or return `Ok(None)` at once if the decoder is in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::decode`].
+    pub fn try_decode(&self, input: &[u8], output: &mut [i16], fec: bool) -> Result<Option<usize>> {
+        self.try_with(|decoder| decoder.decode(input, output, fec))
+    }
+
+    /// Decode into f32 PCM, or return `Ok(None)` at once if the decoder is in use.
+    ///
+    /// # Errors
+    /// Same as [`Self::decode_float`].
+    pub fn try_decode_float(
+        &self,
+        input: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<Option<usize>> {
+        self.try_with(|decoder| decoder.decode_float(input, output, fec))
+    }
+}
+
+impl From<Decoder> for SharedDecoder {
+    fn from(decoder: Decoder) -> Self {
+        Self::new(decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_PACKET_SIZE;
+    use crate::types::{Application, Bitrate, Channels, SampleRate};
+    use std::thread;
+
+    #[test]
+    fn clones_share_one_codec_across_threads() {
+        let sr = SampleRate::Hz48000;
+        let encoder: SharedEncoder = Encoder::new(sr, Channels::Mono, Application::Voip)
+            .unwrap()
+            .into();
+        let decoder: SharedDecoder = Decoder::new(sr, Channels::Mono).unwrap().into();
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let (encoder, decoder) = (encoder.clone(), decoder.clone());
+                thread::spawn(move || {
+                    let mut packet = vec![0u8; MAX_PACKET_SIZE];
+                    let mut pcm = vec![0i16; 960];
+                    for _ in 0..10 {
+                        let len = encoder.encode(&[0; 960], &mut packet).unwrap();
+                        assert_eq!(decoder.decode(&packet[..len], &mut pcm, false), Ok(960));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(decoder.with(|d| Ok(d.concealment_stats().decoded)), Ok(40));
+
+        encoder
+            .with(|e| e.set_bitrate(Bitrate::Custom(24_000)))
+            .unwrap();
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let busy = encoder
+            .with(|_| Ok(encoder.try_encode(&[0; 960], &mut packet)))
+            .unwrap();
+        assert_eq!(busy, Ok(None));
+        assert!(
+            encoder
+                .try_encode(&[0; 960], &mut packet)
+                .unwrap()
+                .is_some()
+        );
+    }
+}
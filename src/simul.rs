@@ -0,0 +1,207 @@
+//! Encode one PCM input at several quality tiers at once
+
+use crate::constants::MAX_PACKET_SIZE;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use crate::types::{Application, Bandwidth, Bitrate, Channels, FrameSize, SampleRate};
+
+/// Settings for one quality tier of a [`SimulEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tier {
+    /// Target bitrate.
+    pub bitrate: Bitrate,
+    /// Optional bandwidth cap.
+    pub max_bandwidth: Option<Bandwidth>,
+}
+
+impl Tier {
+    /// Tier at `bitrate` with no bandwidth cap.
+    #[must_use]
+    pub const fn new(bitrate: Bitrate) -> Self {
+        Self {
+            bitrate,
+            max_bandwidth: None,
+        }
+    }
+}
+
+/// Packets produced by every tier for the same input frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSet {
+    /// Zero-based index of the frame in the input stream.
+    pub index: u64,
+    /// One packet per tier, in the order the tiers were configured.
+    pub packets: Vec<Vec<u8>>,
+}
+
+/// Feeds one interleaved PCM stream to several encoders that differ only in bitrate and
+/// bandwidth, emitting frame-aligned packet sets.
+///
+/// Input of any length is accepted; it is converted to `f32` once and split into frames
+/// shared by all tiers, with leftovers carried into the next call.
+pub struct SimulEncoder {
+    encoders: Vec<Encoder>,
+    channels: Channels,
+    frame_len: usize,
+    pending: Vec<f32>,
+    next_index: u64,
+    scratch: Vec<u8>,
+}
+
+impl SimulEncoder {
+    /// Create one encoder per tier.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `tiers` is empty, or any error from creating and
+    /// configuring the encoders.
+    pub fn new(
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: Application,
+        frame_size: FrameSize,
+        tiers: &[Tier],
+    ) -> Result<Self> {
+        if tiers.is_empty() {
+            return Err(Error::BadArg);
+        }
+        let encoders = tiers
+            .iter()
+            .map(|tier| {
+                let mut encoder = Encoder::new(sample_rate, channels, application)?;
+                encoder.set_bitrate(tier.bitrate)?;
+                if let Some(bandwidth) = tier.max_bandwidth {
+                    encoder.set_max_bandwidth(bandwidth)?;
+                }
+                Ok(encoder)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let frame_len = frame_size.samples(sample_rate) * channels.as_usize();
+        Ok(Self {
+            encoders,
+            channels,
+            frame_len,
+            pending: Vec::with_capacity(frame_len),
+            next_index: 0,
+            scratch: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    /// Number of tiers.
+    #[must_use]
+    pub fn tiers(&self) -> usize {
+        self.encoders.len()
+    }
+
+    /// Encoder for tier `index`, e.g. to adjust settings mid-stream.
+    #[must_use]
+    pub fn encoder_mut(&mut self, index: usize) -> Option<&mut Encoder> {
+        self.encoders.get_mut(index)
+    }
+
+    /// Interleaved samples per frame.
+    #[must_use]
+    pub const fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Queue interleaved `pcm` and encode every complete frame at all tiers.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `pcm` is not a whole number of sample frames, or any
+    /// error from the encoders.
+    pub fn push<S: Sample>(&mut self, pcm: &[S]) -> Result<Vec<FrameSet>> {
+        if !pcm.len().is_multiple_of(self.channels.as_usize()) {
+            return Err(Error::BadArg);
+        }
+        let mut sets = Vec::new();
+        let mut rest = pcm;
+        while !rest.is_empty() {
+            let take = (self.frame_len - self.pending.len()).min(rest.len());
+            let (head, tail) = rest.split_at(take);
+            self.pending.extend(head.iter().map(|s| s.to_f32()));
+            rest = tail;
+            if self.pending.len() == self.frame_len {
+                sets.push(self.encode_pending()?);
+            }
+        }
+        Ok(sets)
+    }
+
+    /// Pad any queued partial frame with silence and encode it.
+    ///
+    /// # Errors
+    /// Returns any error from the encoders.
+    pub fn flush(&mut self) -> Result<Option<FrameSet>> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        self.pending.resize(self.frame_len, 0.0);
+        self.encode_pending().map(Some)
+    }
+
+    fn encode_pending(&mut self) -> Result<FrameSet> {
+        let packets = self
+            .encoders
+            .iter_mut()
+            .map(|encoder| {
+                let len = encoder.encode_float(&self.pending, &mut self.scratch)?;
+                Ok(self.scratch[..len].to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.pending.clear();
+        let index = self.next_index;
+        self.next_index += 1;
+        Ok(FrameSet { index, packets })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsignal::pink_noise;
+
+    #[test]
+    fn tiers_emit_aligned_packets() {
+        let sr = SampleRate::Hz48000;
+        let tiers = [
+            Tier::new(Bitrate::Custom(16_000)),
+            Tier {
+                bitrate: Bitrate::Custom(96_000),
+                max_bandwidth: Some(Bandwidth::Fullband),
+            },
+        ];
+        let mut simul = SimulEncoder::new(
+            sr,
+            Channels::Stereo,
+            Application::Audio,
+            FrameSize::Ms20,
+            &tiers,
+        )
+        .unwrap();
+        assert_eq!(simul.frame_len(), 1920);
+
+        // 1.5 frames, then 1.5 frames: three complete frames once both are pushed.
+        let pcm = pink_noise::<i16>(Channels::Stereo, 0.5, 960 * 3, 9);
+        let first = simul.push(&pcm[..2880]).unwrap();
+        let second = simul.push(&pcm[2880..]).unwrap();
+        assert_eq!((first.len(), second.len()), (1, 2));
+        assert!(simul.flush().unwrap().is_none());
+
+        let sets: Vec<_> = first.into_iter().chain(second).collect();
+        assert_eq!(sets.iter().map(|s| s.index).collect::<Vec<_>>(), [0, 1, 2]);
+        let (low, high): (usize, usize) = sets
+            .iter()
+            .map(|s| (s.packets[0].len(), s.packets[1].len()))
+            .fold((0, 0), |(l, h), (a, b)| (l + a, h + b));
+        assert!(high > 3 * low, "low {low} high {high}");
+
+        simul.push(&pcm[..100]).unwrap();
+        assert_eq!(simul.flush().unwrap().unwrap().index, 3);
+        assert!(simul.push(&pcm[..3]).is_err());
+        assert!(
+            SimulEncoder::new(sr, Channels::Mono, Application::Audio, FrameSize::Ms20, &[])
+                .is_err()
+        );
+    }
+}
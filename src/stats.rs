@@ -0,0 +1,654 @@
+//! Runtime statistics gathered from encoded traffic
+
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Snapshot of the bitrate an encoder actually produced over a rolling window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitrateStats {
+    /// Audio duration covered by the packets in the window.
+    pub duration: Duration,
+    /// Number of packets in the window.
+    pub packets: usize,
+    /// Total bits divided by total duration.
+    pub average_bps: f64,
+    /// Highest single-packet bitrate in the window.
+    pub peak_bps: f64,
+    /// Bitrate the encoder is configured to target.
+    pub target_bps: i32,
+}
+
+impl BitrateStats {
+    /// Average minus target; positive when the encoder overshoots.
+    #[must_use]
+    pub fn deviation_bps(&self) -> f64 {
+        self.average_bps - f64::from(self.target_bps)
+    }
+
+    /// Deviation relative to the target (e.g. `0.1` is 10% over), or `None` without a positive target.
+    #[must_use]
+    pub fn deviation_ratio(&self) -> Option<f64> {
+        (self.target_bps > 0).then(|| self.deviation_bps() / f64::from(self.target_bps))
+    }
+}
+
+/// Rolling window of packet sizes and durations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitrateTracker {
+    window: Duration,
+    packets: VecDeque<(usize, Duration)>,
+    bytes: usize,
+    duration: Duration,
+}
+
+impl BitrateTracker {
+    /// Track packets covering at most `window` of audio.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `window` is zero.
+    pub fn new(window: Duration) -> Result<Self> {
+        if window.is_zero() {
+            return Err(Error::BadArg);
+        }
+        Ok(Self {
+            window,
+            packets: VecDeque::new(),
+            bytes: 0,
+            duration: Duration::ZERO,
+        })
+    }
+
+    /// Window length.
+    #[must_use]
+    pub const fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Record one packet of `bytes` bytes carrying `duration` of audio, evicting the oldest
+    /// packets once the window is exceeded.
+    pub fn record(&mut self, bytes: usize, duration: Duration) {
+        self.packets.push_back((bytes, duration));
+        self.bytes += bytes;
+        self.duration += duration;
+        while self.duration > self.window && self.packets.len() > 1 {
+            if let Some((old_bytes, old_duration)) = self.packets.pop_front() {
+                self.bytes -= old_bytes;
+                self.duration -= old_duration;
+            }
+        }
+    }
+
+    /// Statistics for the current window against `target_bps`, or `None` before the first packet.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn stats(&self, target_bps: i32) -> Option<BitrateStats> {
+        if self.duration.is_zero() {
+            return None;
+        }
+        let peak_bps = self
+            .packets
+            .iter()
+            .filter(|(_, d)| !d.is_zero())
+            .map(|&(b, d)| (b * 8) as f64 / d.as_secs_f64())
+            .fold(0.0, f64::max);
+        Some(BitrateStats {
+            duration: self.duration,
+            packets: self.packets.len(),
+            average_bps: (self.bytes * 8) as f64 / self.duration.as_secs_f64(),
+            peak_bps,
+            target_bps,
+        })
+    }
+
+    /// Forget all recorded packets.
+    pub fn clear(&mut self) {
+        self.packets.clear();
+        self.bytes = 0;
+        self.duration = Duration::ZERO;
+    }
+}
+
+/// Histogram of packet sizes in bytes with percentile queries.
+///
+/// Stores one counter per byte size, so memory grows with the largest packet recorded
+/// (a few KiB at most for Opus packets).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    counts: Vec<u64>,
+    total: u64,
+    sum: u64,
+}
+
+impl SizeHistogram {
+    /// Empty histogram.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            counts: Vec::new(),
+            total: 0,
+            sum: 0,
+        }
+    }
+
+    /// Record one packet of `bytes` bytes.
+    pub fn record(&mut self, bytes: usize) {
+        if bytes >= self.counts.len() {
+            self.counts.resize(bytes + 1, 0);
+        }
+        self.counts[bytes] += 1;
+        self.total += 1;
+        self.sum += bytes as u64;
+    }
+
+    /// Number of packets recorded.
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Smallest recorded size.
+    #[must_use]
+    pub fn min(&self) -> Option<usize> {
+        self.counts.iter().position(|&c| c > 0)
+    }
+
+    /// Largest recorded size.
+    #[must_use]
+    pub fn max(&self) -> Option<usize> {
+        self.counts.iter().rposition(|&c| c > 0)
+    }
+
+    /// Mean packet size in bytes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> Option<f64> {
+        (self.total > 0).then(|| self.sum as f64 / self.total as f64)
+    }
+
+    /// Smallest size such that at least `percentile`% of packets are no larger
+    /// (nearest-rank method), or `None` when empty.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] unless `0 < percentile <= 100`.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation
+    )]
+    pub fn percentile(&self, percentile: f64) -> Result<Option<usize>> {
+        if !(percentile > 0.0 && percentile <= 100.0) {
+            return Err(Error::BadArg);
+        }
+        if self.total == 0 {
+            return Ok(None);
+        }
+        let rank = (percentile / 100.0 * self.total as f64).ceil() as u64;
+        let mut seen = 0;
+        Ok(self.counts.iter().position(|&c| {
+            seen += c;
+            seen >= rank
+        }))
+    }
+
+    /// Median packet size.
+    #[must_use]
+    pub fn p50(&self) -> Option<usize> {
+        self.percentile(50.0).ok().flatten()
+    }
+
+    /// 95th-percentile packet size.
+    #[must_use]
+    pub fn p95(&self) -> Option<usize> {
+        self.percentile(95.0).ok().flatten()
+    }
+
+    /// 99th-percentile packet size.
+    #[must_use]
+    pub fn p99(&self) -> Option<usize> {
+        self.percentile(99.0).ok().flatten()
+    }
+
+    /// Add all packets recorded in `other`.
+    pub fn merge(&mut self, other: &Self) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (mine, theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *mine += theirs;
+        }
+        self.total += other.total;
+        self.sum += other.sum;
+    }
+
+    /// Forget all recorded packets.
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.total = 0;
+        self.sum = 0;
+    }
+}
+
+impl Extend<usize> for SizeHistogram {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, sizes: I) {
+        for bytes in sizes {
+            self.record(bytes);
+        }
+    }
+}
+
+/// Running totals of an encoder's output since stats were enabled or last reset, plus an
+/// optional rolling bitrate window; see [`crate::Encoder::set_stats`] and
+/// [`crate::Encoder::set_bitrate_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncoderStats {
+    frames: u64,
+    bytes: u64,
+    dtx_frames: u64,
+    duration: Duration,
+    sizes: SizeHistogram,
+    window: Option<BitrateTracker>,
+}
+
+impl EncoderStats {
+    /// No frames recorded.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            frames: 0,
+            bytes: 0,
+            dtx_frames: 0,
+            duration: Duration::ZERO,
+            sizes: SizeHistogram::new(),
+            window: None,
+        }
+    }
+
+    /// Record one packet of `bytes` bytes covering `duration` of audio, coded as DTX or not.
+    pub fn record(&mut self, bytes: usize, duration: Duration, dtx: bool) {
+        self.frames += 1;
+        self.bytes += bytes as u64;
+        self.dtx_frames += u64::from(dtx);
+        self.duration += duration;
+        self.sizes.record(bytes);
+        if let Some(window) = self.window.as_mut() {
+            window.record(bytes, duration);
+        }
+    }
+
+    /// Frames encoded.
+    #[must_use]
+    pub const fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Total bytes produced.
+    #[must_use]
+    pub const fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Frames coded while the encoder was in DTX.
+    #[must_use]
+    pub const fn dtx_frames(&self) -> u64 {
+        self.dtx_frames
+    }
+
+    /// Audio duration encoded.
+    #[must_use]
+    pub const fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Average bitrate in bits per second over everything recorded, or `None` before
+    /// the first frame.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_bitrate(&self) -> Option<f64> {
+        (!self.duration.is_zero()).then(|| self.bytes as f64 * 8.0 / self.duration.as_secs_f64())
+    }
+
+    /// Histogram of the packet sizes.
+    #[must_use]
+    pub const fn sizes(&self) -> &SizeHistogram {
+        &self.sizes
+    }
+
+    /// Rolling bitrate window, if one was added.
+    #[must_use]
+    pub const fn window(&self) -> Option<&BitrateTracker> {
+        self.window.as_ref()
+    }
+
+    /// Add, replace or (with `None`) remove the rolling bitrate window.
+    pub(crate) fn set_window(&mut self, window: Option<BitrateTracker>) {
+        self.window = window;
+    }
+
+    /// Empty the rolling bitrate window, keeping the totals.
+    pub(crate) fn clear_window(&mut self) {
+        if let Some(window) = self.window.as_mut() {
+            window.clear();
+        }
+    }
+
+    /// Forget everything recorded, keeping the window length.
+    pub fn clear(&mut self) {
+        let window = self.window.take();
+        *self = Self::new();
+        self.window = window;
+        self.clear_window();
+    }
+}
+
+/// How a decoder produced its output, counted per decode call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConcealmentStats {
+    /// Packets decoded normally.
+    pub decoded: u64,
+    /// Lost packets filled in by packet loss concealment.
+    pub concealed: u64,
+    /// Lost packets recovered from the in-band FEC of the following packet.
+    pub fec_recovered: u64,
+}
+
+impl ConcealmentStats {
+    /// Record one successful decode call for `input` with the given `fec` flag.
+    pub fn record(&mut self, input: &[u8], fec: bool) {
+        if input.is_empty() {
+            self.concealed += 1;
+        } else if fec {
+            self.fec_recovered += 1;
+        } else {
+            self.decoded += 1;
+        }
+    }
+
+    /// Share of calls that had to conceal or recover a lost packet, or `None` before the first call.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn loss_ratio(&self) -> Option<f64> {
+        let lost = self.concealed + self.fec_recovered;
+        let total = self.decoded + lost;
+        (total > 0).then(|| lost as f64 / total as f64)
+    }
+}
+
+/// Running digest of the `final_range` value of every packet in a stream.
+///
+/// The encoder and decoder end each packet with the same range coder state, so a sender
+/// and receiver that both chain it over the same packets end up with equal digests; any
+/// dropped, reordered or differently decoded packet makes them diverge from then on. The
+/// digest is 64-bit FNV-1a over each value's little-endian bytes, starting from the FNV
+/// offset basis, so it can be reproduced outside this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RangeChain {
+    digest: u64,
+    packets: u64,
+}
+
+impl RangeChain {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    /// Empty chain.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            digest: Self::OFFSET_BASIS,
+            packets: 0,
+        }
+    }
+
+    /// Fold the `final_range` of the next packet into the digest.
+    pub fn push(&mut self, final_range: u32) {
+        for byte in final_range.to_le_bytes() {
+            self.digest = (self.digest ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+        self.packets += 1;
+    }
+
+    /// Digest over every value pushed so far.
+    #[must_use]
+    pub const fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Number of packets chained.
+    #[must_use]
+    pub const fn packets(&self) -> u64 {
+        self.packets
+    }
+}
+
+impl Default for RangeChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Peak and RMS level of one decoded frame, over all channels, on a linear full scale
+/// of 1.0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameLevel {
+    /// Largest absolute sample.
+    pub peak: f32,
+    /// Root mean square of the samples.
+    pub rms: f32,
+}
+
+impl FrameLevel {
+    /// Measure interleaved `pcm`; silence (or no samples) measures 0.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn measure<S: Sample>(pcm: &[S]) -> Self {
+        if pcm.is_empty() {
+            return Self::default();
+        }
+        let (peak, sum) = pcm.iter().fold((0f32, 0f32), |(peak, sum), s| {
+            let s = s.to_f32();
+            (peak.max(s.abs()), sum + s * s)
+        });
+        Self {
+            peak,
+            rms: (sum / pcm.len() as f32).sqrt(),
+        }
+    }
+
+    /// Peak level in dBFS (`-inf` for silence).
+    #[must_use]
+    pub fn peak_dbfs(&self) -> f32 {
+        20.0 * self.peak.log10()
+    }
+
+    /// RMS level in dBFS (`-inf` for silence).
+    #[must_use]
+    pub fn rms_dbfs(&self) -> f32 {
+        20.0 * self.rms.log10()
+    }
+}
+
+/// Number of most recent calls [`TimingReservoir`] keeps for percentile queries.
+#[cfg(feature = "timing")]
+pub const TIMING_RESERVOIR_LEN: usize = 1024;
+
+/// Wall-time summary of codec calls.
+#[cfg(feature = "timing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimingStats {
+    /// Calls recorded since creation or the last reset.
+    pub calls: u64,
+    /// Fastest call.
+    pub min: Duration,
+    /// Mean over all calls.
+    pub avg: Duration,
+    /// 99th percentile over the last [`TIMING_RESERVOIR_LEN`] calls.
+    pub p99: Duration,
+    /// Slowest call.
+    pub max: Duration,
+}
+
+/// Running min/avg/max of call durations plus a ring of recent samples for percentiles.
+#[cfg(feature = "timing")]
+#[derive(Debug, Clone)]
+pub struct TimingReservoir {
+    recent: Vec<Duration>,
+    next: usize,
+    calls: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+#[cfg(feature = "timing")]
+impl TimingReservoir {
+    /// Empty reservoir.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            recent: Vec::new(),
+            next: 0,
+            calls: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Record one call.
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.recent.len() < TIMING_RESERVOIR_LEN {
+            self.recent.push(elapsed);
+        } else {
+            self.recent[self.next] = elapsed;
+        }
+        self.next = (self.next + 1) % TIMING_RESERVOIR_LEN;
+        self.calls += 1;
+        self.total += elapsed;
+        self.min = self.min.min(elapsed);
+        self.max = self.max.max(elapsed);
+    }
+
+    /// Summary of the recorded calls, or `None` before the first one.
+    #[must_use]
+    pub fn stats(&self) -> Option<TimingStats> {
+        if self.calls == 0 {
+            return None;
+        }
+        let mut sorted = self.recent.clone();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * 99).div_ceil(100);
+        Some(TimingStats {
+            calls: self.calls,
+            min: self.min,
+            avg: Duration::from_nanos((self.total.as_nanos() / u128::from(self.calls)) as u64),
+            p99: sorted[rank.max(1) - 1],
+            max: self.max,
+        })
+    }
+
+    /// Forget all recorded calls.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(feature = "timing")]
+impl Default for TimingReservoir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn timing_reservoir_keeps_recent_calls() {
+        let mut reservoir = TimingReservoir::default();
+        assert!(reservoir.stats().is_none());
+        for micros in 1..=100 {
+            reservoir.record(Duration::from_micros(micros));
+        }
+        let stats = reservoir.stats().unwrap();
+        assert_eq!(stats.calls, 100);
+        assert_eq!(stats.min, Duration::from_micros(1));
+        assert_eq!(stats.max, Duration::from_micros(100));
+        assert_eq!(stats.p99, Duration::from_micros(99));
+        assert_eq!(stats.avg, Duration::from_nanos(50_500));
+
+        for _ in 0..TIMING_RESERVOIR_LEN {
+            reservoir.record(Duration::from_micros(5));
+        }
+        let stats = reservoir.stats().unwrap();
+        assert_eq!(stats.p99, Duration::from_micros(5));
+        assert_eq!(stats.max, Duration::from_micros(100));
+    }
+
+    #[test]
+    fn frame_level_measures_peak_and_rms() {
+        assert_eq!(FrameLevel::measure::<f32>(&[]), FrameLevel::default());
+        assert!(FrameLevel::measure(&[0i16; 8]).rms_dbfs().is_infinite());
+        let level = FrameLevel::measure(&[0.5f32, -0.5, 0.5, -0.5]);
+        assert_eq!(
+            level,
+            FrameLevel {
+                peak: 0.5,
+                rms: 0.5
+            }
+        );
+        assert!((level.peak_dbfs() + 6.0206).abs() < 1e-3);
+        let level = FrameLevel::measure(&[i16::MIN, 0]);
+        assert!((level.peak - 1.0).abs() < f32::EPSILON);
+        assert!((level.rms - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn histogram_percentiles_use_nearest_rank() {
+        let mut histogram = SizeHistogram::new();
+        assert_eq!(histogram.p50(), None);
+        histogram.extend(1..=100);
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.p50(), Some(50));
+        assert_eq!(histogram.p95(), Some(95));
+        assert_eq!(histogram.p99(), Some(99));
+        assert_eq!(histogram.percentile(100.0).unwrap(), Some(100));
+        assert_eq!((histogram.min(), histogram.max()), (Some(1), Some(100)));
+        assert!((histogram.mean().unwrap() - 50.5).abs() < 1e-9);
+        assert!(histogram.percentile(0.0).is_err());
+
+        let mut other = SizeHistogram::new();
+        other.extend([1_000; 100]);
+        histogram.merge(&other);
+        assert_eq!(histogram.p50(), Some(100));
+        assert_eq!(histogram.p95(), Some(1_000));
+    }
+
+    #[test]
+    fn rolling_window_evicts_old_packets() {
+        let mut tracker = BitrateTracker::new(Duration::from_millis(100)).unwrap();
+        assert!(tracker.stats(32_000).is_none());
+        let frame = Duration::from_millis(20);
+        for _ in 0..10 {
+            tracker.record(80, frame);
+        }
+        tracker.record(160, frame);
+        let stats = tracker.stats(32_000).unwrap();
+        assert_eq!(stats.packets, 5);
+        assert_eq!(stats.duration, Duration::from_millis(100));
+        assert!((stats.average_bps - 38_400.0).abs() < 1e-6);
+        assert!((stats.peak_bps - 64_000.0).abs() < 1e-6);
+        assert!((stats.deviation_ratio().unwrap() - 0.2).abs() < 1e-9);
+
+        tracker.clear();
+        assert!(tracker.stats(32_000).is_none());
+        assert!(BitrateTracker::new(Duration::ZERO).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! `proptest` strategies for codec settings, PCM frames and packets
+
+use crate::constants::{MAX_FRAME_BYTES, MAX_FRAME_SAMPLES_48KHZ};
+use crate::encoder::Encoder;
+use crate::error::Result;
+use crate::frame::PcmFrame;
+use crate::packet::Toc;
+use crate::types::{
+    Application, Bandwidth, Bitrate, Channels, Complexity, FrameSize, SampleRate, Signal,
+};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// Any supported sample rate.
+pub fn sample_rate() -> impl Strategy<Value = SampleRate> {
+    prop_oneof![
+        Just(SampleRate::Hz8000),
+        Just(SampleRate::Hz12000),
+        Just(SampleRate::Hz16000),
+        Just(SampleRate::Hz24000),
+        Just(SampleRate::Hz48000),
+    ]
+}
+
+/// Mono or stereo.
+pub fn channels() -> impl Strategy<Value = Channels> {
+    prop_oneof![Just(Channels::Mono), Just(Channels::Stereo)]
+}
+
+/// Any encoder application mode.
+pub fn application() -> impl Strategy<Value = Application> {
+    prop_oneof![
+        Just(Application::Voip),
+        Just(Application::Audio),
+        Just(Application::RestrictedLowDelay),
+    ]
+}
+
+/// Any coded bandwidth.
+pub fn bandwidth() -> impl Strategy<Value = Bandwidth> {
+    prop_oneof![
+        Just(Bandwidth::Narrowband),
+        Just(Bandwidth::Mediumband),
+        Just(Bandwidth::Wideband),
+        Just(Bandwidth::SuperWideband),
+        Just(Bandwidth::Fullband),
+    ]
+}
+
+/// Any signal hint.
+pub fn signal() -> impl Strategy<Value = Signal> {
+    prop_oneof![Just(Signal::Voice), Just(Signal::Music)]
+}
+
+/// Any frame size accepted by `opus_encode`.
+pub fn frame_size() -> impl Strategy<Value = FrameSize> {
+    prop_oneof![
+        Just(FrameSize::Ms2_5),
+        Just(FrameSize::Ms5),
+        Just(FrameSize::Ms10),
+        Just(FrameSize::Ms20),
+        Just(FrameSize::Ms40),
+        Just(FrameSize::Ms60),
+    ]
+}
+
+/// Complexity 0..=10.
+pub fn complexity() -> impl Strategy<Value = Complexity> {
+    (0u32..=10).prop_map(Complexity::new)
+}
+
+/// Automatic, maximum or explicit bitrates, biased towards the 500 bps / 512 kbps edges.
+pub fn bitrate() -> impl Strategy<Value = Bitrate> {
+    prop_oneof![
+        Just(Bitrate::Auto),
+        Just(Bitrate::Max),
+        Just(Bitrate::Custom(500)),
+        Just(Bitrate::Custom(512_000)),
+        (500i32..=512_000).prop_map(Bitrate::Custom),
+    ]
+}
+
+/// Encoder settings that libopus accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct EncoderConfig {
+    /// Input sample rate.
+    pub sample_rate: SampleRate,
+    /// Input channels.
+    pub channels: Channels,
+    /// Application mode.
+    pub application: Application,
+    /// Target bitrate.
+    pub bitrate: Bitrate,
+    /// Complexity.
+    pub complexity: Complexity,
+    /// Frame duration fed to each encode call.
+    pub frame_size: FrameSize,
+}
+
+impl EncoderConfig {
+    /// Create and configure an encoder from these settings.
+    ///
+    /// # Errors
+    /// Propagates errors from [`Encoder::new`] and the setters.
+    pub fn build(&self) -> Result<Encoder> {
+        let mut encoder = Encoder::new(self.sample_rate, self.channels, self.application)?;
+        encoder.set_bitrate(self.bitrate)?;
+        encoder.set_complexity(self.complexity)?;
+        Ok(encoder)
+    }
+
+    /// Interleaved samples per encode call.
+    #[must_use]
+    pub const fn frame_len(&self) -> usize {
+        self.frame_size.samples(self.sample_rate) * self.channels.as_usize()
+    }
+}
+
+/// Valid encoder configurations.
+pub fn encoder_config() -> impl Strategy<Value = EncoderConfig> {
+    (
+        sample_rate(),
+        channels(),
+        application(),
+        bitrate(),
+        complexity(),
+        frame_size(),
+    )
+        .prop_map(
+            |(sample_rate, channels, application, bitrate, complexity, frame_size)| EncoderConfig {
+                sample_rate,
+                channels,
+                application,
+                bitrate,
+                complexity,
+                frame_size,
+            },
+        )
+}
+
+/// Sample values biased towards silence and full-scale extremes.
+fn sample_i16() -> impl Strategy<Value = i16> {
+    prop_oneof![
+        4 => any::<i16>(),
+        1 => Just(0i16),
+        1 => Just(i16::MIN),
+        1 => Just(i16::MAX),
+    ]
+}
+
+/// `i16` frames of a valid shape for the given layout.
+///
+/// # Panics
+/// Only if `frame_size` is longer than a valid Opus frame, which no [`FrameSize`] is.
+pub fn pcm_frame_for(
+    sample_rate: SampleRate,
+    channels: Channels,
+    frame_size: FrameSize,
+) -> impl Strategy<Value = PcmFrame<i16>> {
+    let len = frame_size.samples(sample_rate) * channels.as_usize();
+    vec(sample_i16(), len).prop_map(move |samples| {
+        PcmFrame::new(samples, sample_rate, channels).expect("strategy produces valid frame shapes")
+    })
+}
+
+/// `i16` frames with any valid rate, layout and frame size.
+pub fn pcm_frame() -> impl Strategy<Value = PcmFrame<i16>> {
+    (sample_rate(), channels(), frame_size())
+        .prop_flat_map(|(sr, ch, size)| pcm_frame_for(sr, ch, size))
+}
+
+fn frame_length_bytes(len: usize, out: &mut Vec<u8>) {
+    if len < 252 {
+        out.push(len as u8);
+    } else {
+        let first = 252 + (len & 3);
+        out.push(first as u8);
+        out.push(((len - first) >> 2) as u8);
+    }
+}
+
+/// Packets with well-formed TOC and framing (codes 0-3) and arbitrary frame payloads.
+///
+/// The frames are not guaranteed to decode to meaningful audio, but every packet
+/// passes `opus_packet_parse`.
+pub fn packet() -> impl Strategy<Value = Vec<u8>> {
+    (0u8..32, any::<bool>(), 0u8..4).prop_flat_map(|(config, stereo, code)| {
+        let toc = (config << 3) | (u8::from(stereo) << 2) | code;
+        let max_frames =
+            MAX_FRAME_SAMPLES_48KHZ / Toc::from_byte(toc).samples_per_frame(SampleRate::Hz48000);
+        match code {
+            0 => vec(any::<u8>(), 0..=MAX_FRAME_BYTES)
+                .prop_map(move |frame| [vec![toc], frame].concat())
+                .boxed(),
+            1 => (0..=MAX_FRAME_BYTES)
+                .prop_flat_map(move |len| vec(any::<u8>(), 2 * len))
+                .prop_map(move |frames| [vec![toc], frames].concat())
+                .boxed(),
+            2 => (
+                vec(any::<u8>(), 0..=MAX_FRAME_BYTES),
+                vec(any::<u8>(), 0..=MAX_FRAME_BYTES),
+            )
+                .prop_map(move |(first, second)| {
+                    let mut out = vec![toc];
+                    frame_length_bytes(first.len(), &mut out);
+                    out.extend_from_slice(&first);
+                    out.extend_from_slice(&second);
+                    out
+                })
+                .boxed(),
+            _ => (1..=max_frames, 0..=MAX_FRAME_BYTES / 4)
+                .prop_flat_map(|(count, len)| {
+                    vec(any::<u8>(), count * len).prop_map(move |d| (count, d))
+                })
+                .prop_map(move |(count, frames)| {
+                    // CBR, no padding: the count byte carries only the frame count.
+                    let mut out = vec![toc, count as u8];
+                    out.extend_from_slice(&frames);
+                    out
+                })
+                .boxed(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::packet_parse;
+
+    proptest! {
+        #[test]
+        fn packets_have_valid_framing(packet in packet()) {
+            prop_assert!(packet_parse(&packet).is_ok());
+        }
+
+        #[test]
+        fn configs_build_and_encode(config in encoder_config()) {
+            let mut encoder = config.build().unwrap();
+            let pcm = vec![0i16; config.frame_len()];
+            let mut out = [0u8; 1500];
+            prop_assert!(encoder.encode(&pcm, &mut out).unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Frame-buffering encoder for PCM arriving in arbitrary-length chunks
+
+use crate::constants::MAX_PACKET_SIZE;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::frame::Sample;
+use crate::types::{Application, Channels, FrameSize, SampleRate};
+use std::time::Duration;
+
+/// Final packet and trim metadata returned by [`StreamEncoder::finish`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEnd {
+    /// Packets encoding the zero-padded final partial frame and any silent frames needed
+    /// to flush the encoder's lookahead, in order.
+    pub packets: Vec<Vec<u8>>,
+    /// Silent samples per channel (at the encoder's rate) appended after the input.
+    pub padding: usize,
+    /// Decoder pre-skip in 48 kHz samples, as written to an `OpusHead`.
+    pub pre_skip: u16,
+    /// Samples per channel the decoded stream should play: the real input consumed since
+    /// the stream started, plus any silence inserted by [`StreamEncoder::flush`].
+    pub samples: u64,
+}
+
+impl StreamEnd {
+    /// End-of-stream granule position for an Ogg Opus page: pre-skip plus
+    /// [`Self::samples`], in 48 kHz samples.
+    #[must_use]
+    pub const fn end_granule(&self, sample_rate: SampleRate) -> u64 {
+        self.pre_skip as u64 + self.samples * 48_000 / sample_rate as u64
+    }
+
+    /// Samples per channel, at the encoder's `sample_rate`, to drop from the end of the
+    /// decoded stream once pre-skip has been removed from its start, leaving exactly
+    /// [`Self::samples`]: the padding not consumed by the lookahead.
+    #[must_use]
+    pub const fn end_trim(&self, sample_rate: SampleRate) -> usize {
+        let lookahead = self.pre_skip as usize * sample_rate as usize / 48_000;
+        self.padding.saturating_sub(lookahead)
+    }
+}
+
+/// Accepts interleaved PCM of any length, encodes complete frames as they fill up and
+/// pads the tail on [`Self::flush`] or [`Self::finish`].
+pub struct StreamEncoder {
+    encoder: Encoder,
+    frame_len: usize,
+    pending: Vec<f32>,
+    scratch: Vec<u8>,
+    samples: u64,
+}
+
+impl StreamEncoder {
+    /// Create an encoder emitting one packet per `frame_size` of input.
+    ///
+    /// # Errors
+    /// Returns any error from creating the encoder.
+    pub fn new(
+        sample_rate: SampleRate,
+        channels: Channels,
+        application: Application,
+        frame_size: FrameSize,
+    ) -> Result<Self> {
+        let encoder = Encoder::new(sample_rate, channels, application)?;
+        let frame_len = frame_size.samples(sample_rate) * channels.as_usize();
+        Ok(Self {
+            encoder,
+            frame_len,
+            pending: Vec::with_capacity(frame_len),
+            scratch: vec![0; MAX_PACKET_SIZE],
+            samples: 0,
+        })
+    }
+
+    /// Underlying encoder, e.g. to adjust bitrate mid-stream.
+    #[must_use]
+    pub const fn encoder_mut(&mut self) -> &mut Encoder {
+        &mut self.encoder
+    }
+
+    /// Interleaved samples per frame.
+    #[must_use]
+    pub const fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Samples per channel the stream covers so far: the input consumed plus any silence
+    /// inserted by [`Self::flush`], as later reported in [`StreamEnd::samples`].
+    #[must_use]
+    pub const fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Input samples per channel queued for the next frame.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.len() / self.encoder.channels().as_usize()
+    }
+
+    /// Encoder lookahead converted to 48 kHz samples, i.e. the `OpusHead` pre-skip.
+    ///
+    /// # Errors
+    /// Same as [`Encoder::pre_skip`].
+    pub fn pre_skip(&mut self) -> Result<u16> {
+        self.encoder.pre_skip()
+    }
+
+    /// Total delay from pushing a sample to its packet: one frame of buffering plus the
+    /// encoder's [`Encoder::latency`].
+    ///
+    /// # Errors
+    /// Same as [`Encoder::latency`].
+    pub fn latency(&mut self) -> Result<Duration> {
+        let sample_rate = self.encoder.sample_rate();
+        let frame = self.frame_len / self.encoder.channels().as_usize();
+        Ok(sample_rate.duration_of(frame) + self.encoder.latency()?)
+    }
+
+    /// Queue interleaved `pcm` and encode every complete frame.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `pcm` is not a whole number of sample frames, or any
+    /// error from the encoder.
+    pub fn push<S: Sample>(&mut self, pcm: &[S]) -> Result<Vec<Vec<u8>>> {
+        let channels = self.encoder.channels().as_usize();
+        if !pcm.len().is_multiple_of(channels) {
+            return Err(Error::BadArg);
+        }
+        let mut packets = Vec::new();
+        let mut rest = pcm;
+        while !rest.is_empty() {
+            let take = (self.frame_len - self.pending.len()).min(rest.len());
+            let (head, tail) = rest.split_at(take);
+            self.pending.extend(head.iter().map(|s| s.to_f32()));
+            rest = tail;
+            if self.pending.len() == self.frame_len {
+                packets.push(self.encode_pending()?);
+            }
+        }
+        self.samples += (pcm.len() / channels) as u64;
+        Ok(packets)
+    }
+
+    /// Zero-pad the queued partial frame and encode it, e.g. before a pause in capture,
+    /// or return `None` if nothing is queued.
+    ///
+    /// Unlike [`Self::finish`] this keeps the stream going: the encoder's lookahead stays
+    /// in place and the inserted silence becomes part of the stream, counted in
+    /// [`Self::samples`] and [`StreamEnd::samples`].
+    ///
+    /// # Errors
+    /// Returns any error from the encoder.
+    pub fn flush(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let silence = (self.frame_len - self.pending.len()) / self.encoder.channels().as_usize();
+        self.pending.resize(self.frame_len, 0.0);
+        let packet = self.encode_pending()?;
+        self.samples += silence as u64;
+        Ok(Some(packet))
+    }
+
+    /// Zero-pad the final partial frame, encode it and report the trim metadata a
+    /// container needs for gapless playback.
+    ///
+    /// Silent frames are appended until the encoder's lookahead has been flushed, so the
+    /// decoded stream covers the whole input once pre-skip is removed. The sample count
+    /// restarts afterwards, so the encoder can begin a new stream.
+    ///
+    /// # Errors
+    /// Returns any error from the encoder.
+    pub fn finish(&mut self) -> Result<StreamEnd> {
+        let frame = (self.frame_len / self.encoder.channels().as_usize()) as u64;
+        let lookahead =
+            u64::try_from(self.encoder.lookahead()?).map_err(|_| Error::InternalError)?;
+        let pre_skip = self.pre_skip()?;
+        let samples = self.samples;
+        let frames = if samples == 0 {
+            0
+        } else {
+            (samples + lookahead).div_ceil(frame)
+        };
+        let mut packets = Vec::new();
+        for _ in samples / frame..frames {
+            self.pending.resize(self.frame_len, 0.0);
+            packets.push(self.encode_pending()?);
+        }
+        let end = StreamEnd {
+            packets,
+            padding: (frames * frame - samples) as usize,
+            pre_skip,
+            samples,
+        };
+        self.samples = 0;
+        Ok(end)
+    }
+
+    fn encode_pending(&mut self) -> Result<Vec<u8>> {
+        let len = self
+            .encoder
+            .encode_float(&self.pending, &mut self.scratch)?;
+        self.pending.clear();
+        Ok(self.scratch[..len].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsignal::sine;
+
+    #[test]
+    fn finish_pads_tail_and_reports_trim() {
+        let sr = SampleRate::Hz48000;
+        let mut stream =
+            StreamEncoder::new(sr, Channels::Stereo, Application::Audio, FrameSize::Ms20).unwrap();
+        let pcm = sine::<i16>(sr, Channels::Stereo, 440.0, 0.5, 2_500);
+        let mut packets = Vec::new();
+        for chunk in pcm.chunks(441 * 2) {
+            packets.extend(stream.push(chunk).unwrap());
+        }
+        assert_eq!(packets.len(), 2);
+        assert_eq!(stream.samples(), 2_500);
+
+        let end = stream.finish().unwrap();
+        assert_eq!(end.packets.len(), 1);
+        assert_eq!(end.padding, 3 * 960 - 2_500);
+        assert_eq!(end.pre_skip, 312);
+        assert_eq!(end.end_granule(sr), 312 + 2_500);
+        assert_eq!(end.end_trim(sr), 3 * 960 - 2_500 - 312);
+        assert_eq!(
+            stream.latency().unwrap(),
+            Duration::from_millis(20) + Duration::from_micros(6_500)
+        );
+
+        // Decoding every packet and trimming both ends leaves exactly the input.
+        let mut decoder = crate::Decoder::new(sr, Channels::Stereo).unwrap();
+        let mut total = 0;
+        for packet in packets.iter().chain(&end.packets) {
+            total += decoder.decode(packet, &mut [0; 2 * 960], false).unwrap();
+        }
+        assert_eq!(total - 312 - end.end_trim(sr), 2_500);
+
+        let empty = stream.finish().unwrap();
+        assert_eq!(
+            (empty.packets.len(), empty.padding, empty.samples),
+            (0, 0, 0)
+        );
+
+        // Whole frames leave the lookahead in the encoder; one silent frame flushes it.
+        stream.push(&pcm[..2 * 1_920]).unwrap();
+        let end = stream.finish().unwrap();
+        assert_eq!((end.packets.len(), end.padding), (1, 960));
+        assert!(stream.push(&pcm[..3]).is_err());
+    }
+
+    #[test]
+    fn flush_pads_partial_frame_and_keeps_stream_open() {
+        let sr = SampleRate::Hz48000;
+        let mut stream =
+            StreamEncoder::new(sr, Channels::Mono, Application::Voip, FrameSize::Ms20).unwrap();
+        let pcm = sine::<f32>(sr, Channels::Mono, 440.0, 0.5, 1_500);
+        assert_eq!(stream.flush().unwrap(), None);
+
+        assert_eq!(stream.push(&pcm).unwrap().len(), 1);
+        assert_eq!(stream.pending(), 540);
+        assert!(stream.flush().unwrap().is_some());
+        // The inserted silence counts towards the stream, as it will in `StreamEnd`.
+        assert_eq!((stream.pending(), stream.samples()), (0, 960 * 2));
+
+        assert_eq!(stream.push(&pcm[..960]).unwrap().len(), 1);
+        let end = stream.finish().unwrap();
+        // 3 frames so far hold 2 460 input samples plus 420 of flushed silence.
+        assert_eq!(end.samples, 3 * 960);
+        assert_eq!((end.packets.len(), end.padding), (1, 960));
+    }
+}
@@ -0,0 +1,204 @@
+//! One serializable snapshot of a call's codec state
+//!
+//! [`Telemetry`] gathers what the crate tracks about an encoder, a decoder and the
+//! [`ComplexityController`] into plain data, plus the jitter-buffer depth the application
+//! reports itself. With the `serde` feature every type here serializes, so a service can
+//! ship one structured blob per call to its analytics pipeline.
+
+use crate::adaptive::ComplexityController;
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::Result;
+#[cfg(feature = "timing")]
+use crate::stats::TimingStats;
+use crate::stats::{BitrateStats, ConcealmentStats, SizeHistogram};
+use crate::types::Bandwidth;
+use std::time::Duration;
+
+/// Summary of a [`SizeHistogram`], in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PacketSizes {
+    /// Packets recorded.
+    pub count: u64,
+    /// Mean packet size.
+    pub mean: f64,
+    /// Median packet size.
+    pub p50: usize,
+    /// 95th-percentile packet size.
+    pub p95: usize,
+    /// Largest packet.
+    pub max: usize,
+}
+
+impl PacketSizes {
+    /// Summarize `histogram`, or `None` if it is empty.
+    #[must_use]
+    pub fn from_histogram(histogram: &SizeHistogram) -> Option<Self> {
+        Some(Self {
+            count: histogram.count(),
+            mean: histogram.mean()?,
+            p50: histogram.p50()?,
+            p95: histogram.p95()?,
+            max: histogram.max()?,
+        })
+    }
+}
+
+/// Encoder side of a [`Telemetry`] snapshot; each part is `None` while its tracking is
+/// disabled or nothing was encoded yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderTelemetry {
+    /// From [`Encoder::bitrate_stats`].
+    pub bitrate: Option<BitrateStats>,
+    /// From the packet sizes in [`Encoder::stats`].
+    pub packet_sizes: Option<PacketSizes>,
+    /// From [`Encoder::timing_stats`].
+    #[cfg(feature = "timing")]
+    pub timing: Option<TimingStats>,
+}
+
+impl EncoderTelemetry {
+    /// Snapshot the statistics `encoder` currently tracks.
+    ///
+    /// # Errors
+    /// Returns any error from [`Encoder::bitrate_stats`].
+    pub fn capture(encoder: &mut Encoder) -> Result<Self> {
+        Ok(Self {
+            bitrate: encoder.bitrate_stats()?,
+            packet_sizes: encoder
+                .stats()
+                .and_then(|stats| PacketSizes::from_histogram(stats.sizes())),
+            #[cfg(feature = "timing")]
+            timing: encoder.timing_stats(),
+        })
+    }
+}
+
+/// Decoder side of a [`Telemetry`] snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecoderTelemetry {
+    /// From [`Decoder::concealment_stats`].
+    pub concealment: ConcealmentStats,
+    /// From [`Decoder::timing_stats`].
+    #[cfg(feature = "timing")]
+    pub timing: Option<TimingStats>,
+}
+
+impl DecoderTelemetry {
+    /// Snapshot the statistics `decoder` currently tracks.
+    #[must_use]
+    pub fn capture(decoder: &Decoder) -> Self {
+        Self {
+            concealment: decoder.concealment_stats(),
+            #[cfg(feature = "timing")]
+            timing: decoder.timing_stats(),
+        }
+    }
+}
+
+/// Settings a [`ComplexityController`] last applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AbrTelemetry {
+    /// Encoder complexity, 0..=10.
+    pub complexity: u32,
+    /// Maximum coded bandwidth.
+    pub max_bandwidth: Bandwidth,
+}
+
+impl From<&ComplexityController> for AbrTelemetry {
+    fn from(controller: &ComplexityController) -> Self {
+        Self {
+            complexity: controller.complexity().value(),
+            max_bandwidth: controller.max_bandwidth(),
+        }
+    }
+}
+
+/// Structured snapshot of one call, derived on demand.
+///
+/// [`Self::capture`] fills the codec parts; set [`Self::jitter_buffer_depth`] and
+/// [`Self::abr`] from the application's own state before shipping it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Telemetry {
+    /// Sending side, if the call encodes.
+    pub encoder: Option<EncoderTelemetry>,
+    /// Receiving side, if the call decodes.
+    pub decoder: Option<DecoderTelemetry>,
+    /// Audio currently queued in the application's jitter buffer.
+    pub jitter_buffer_depth: Option<Duration>,
+    /// Adaptation state, if a [`ComplexityController`] drives the encoder.
+    pub abr: Option<AbrTelemetry>,
+}
+
+impl Telemetry {
+    /// Snapshot whichever of `encoder` and `decoder` the call uses.
+    ///
+    /// # Errors
+    /// Returns any error from [`EncoderTelemetry::capture`].
+    pub fn capture(encoder: Option<&mut Encoder>, decoder: Option<&Decoder>) -> Result<Self> {
+        Ok(Self {
+            encoder: encoder.map(EncoderTelemetry::capture).transpose()?,
+            decoder: decoder.map(DecoderTelemetry::capture),
+            jitter_buffer_depth: None,
+            abr: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptive::ComplexityControllerConfig;
+    use crate::types::{Application, Channels, Complexity, SampleRate};
+
+    #[test]
+    fn capture_collects_encoder_decoder_and_app_state() {
+        let mut encoder =
+            Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+        let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+        encoder
+            .set_bitrate_stats(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let mut packet = [0u8; 1500];
+        let mut pcm = [0i16; 960];
+        for i in 0..10 {
+            let len = encoder.encode(&[0; 960], &mut packet).unwrap();
+            let input = if i == 3 { &[][..] } else { &packet[..len] };
+            decoder.decode(input, &mut pcm, false).unwrap();
+        }
+        decoder.decode(&packet[..1], &mut pcm, true).unwrap();
+
+        let controller =
+            ComplexityController::new(ComplexityControllerConfig::default(), Complexity::new(7))
+                .unwrap();
+        let mut telemetry = Telemetry::capture(Some(&mut encoder), Some(&decoder)).unwrap();
+        telemetry.jitter_buffer_depth = Some(Duration::from_millis(60));
+        telemetry.abr = Some((&controller).into());
+
+        let sent = telemetry.encoder.unwrap();
+        assert_eq!(sent.bitrate.unwrap().packets, 10);
+        assert_eq!(sent.packet_sizes.unwrap().count, 10);
+        let received = telemetry.decoder.unwrap().concealment;
+        assert_eq!(
+            received,
+            ConcealmentStats {
+                decoded: 9,
+                concealed: 1,
+                fec_recovered: 1,
+            }
+        );
+        assert_eq!(received.loss_ratio(), Some(2.0 / 11.0));
+        assert_eq!(telemetry.abr.unwrap().complexity, 7);
+
+        assert_eq!(
+            Telemetry::capture(None, None).unwrap(),
+            Telemetry::default()
+        );
+    }
+}
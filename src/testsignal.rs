@@ -0,0 +1,182 @@
+//! Deterministic test-signal generators (tones, noise, sweeps)
+//!
+//! Every generator takes a length in samples per channel and returns exactly
+//! `samples_per_channel * channels` interleaved samples, with the same signal on
+//! every channel.
+
+use crate::frame::Sample;
+use crate::types::{Channels, SampleRate};
+use std::f64::consts::TAU;
+
+/// Small `SplitMix64` generator so noise is reproducible without extra dependencies.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[-1.0, 1.0)`.
+    #[allow(clippy::cast_precision_loss)]
+    fn next_bipolar(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+fn render<S: Sample>(
+    channels: Channels,
+    samples_per_channel: usize,
+    mut value: impl FnMut(usize) -> f64,
+) -> Vec<S> {
+    let mut out = Vec::with_capacity(samples_per_channel * channels.as_usize());
+    for n in 0..samples_per_channel {
+        let sample = S::from_f32(value(n) as f32);
+        out.extend(std::iter::repeat_n(sample, channels.as_usize()));
+    }
+    out
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn time(n: usize, sample_rate: SampleRate) -> f64 {
+    n as f64 / f64::from(sample_rate.as_i32())
+}
+
+/// Number of samples per channel covering `millis` milliseconds at `sample_rate`.
+#[must_use]
+pub fn samples_for_ms(sample_rate: SampleRate, millis: u32) -> usize {
+    sample_rate as usize * millis as usize / 1_000
+}
+
+/// Sine tone at `freq_hz` with peak `amplitude` (full scale = 1.0).
+#[must_use]
+pub fn sine<S: Sample>(
+    sample_rate: SampleRate,
+    channels: Channels,
+    freq_hz: f64,
+    amplitude: f64,
+    samples_per_channel: usize,
+) -> Vec<S> {
+    multitone(
+        sample_rate,
+        channels,
+        &[(freq_hz, amplitude)],
+        samples_per_channel,
+    )
+}
+
+/// Sum of sines given as `(frequency_hz, amplitude)` pairs.
+#[must_use]
+pub fn multitone<S: Sample>(
+    sample_rate: SampleRate,
+    channels: Channels,
+    tones: &[(f64, f64)],
+    samples_per_channel: usize,
+) -> Vec<S> {
+    render(channels, samples_per_channel, |n| {
+        let t = time(n, sample_rate);
+        tones
+            .iter()
+            .map(|&(freq, amplitude)| amplitude * (TAU * freq * t).sin())
+            .sum()
+    })
+}
+
+/// Uniform white noise with peak `amplitude`, reproducible for a given `seed`.
+#[must_use]
+pub fn white_noise<S: Sample>(
+    channels: Channels,
+    amplitude: f64,
+    samples_per_channel: usize,
+    seed: u64,
+) -> Vec<S> {
+    let mut rng = SplitMix64(seed);
+    render(channels, samples_per_channel, |_| {
+        amplitude * rng.next_bipolar()
+    })
+}
+
+/// Pink (1/f) noise scaled to roughly `amplitude` peak, reproducible for a given `seed`.
+///
+/// Uses Paul Kellet's economy filter on white noise.
+#[must_use]
+pub fn pink_noise<S: Sample>(
+    channels: Channels,
+    amplitude: f64,
+    samples_per_channel: usize,
+    seed: u64,
+) -> Vec<S> {
+    let mut rng = SplitMix64(seed);
+    let mut b = [0.0f64; 3];
+    render(channels, samples_per_channel, |_| {
+        let white = rng.next_bipolar();
+        b[0] = 0.99765 * b[0] + white * 0.099_046_0;
+        b[1] = 0.96300 * b[1] + white * 0.296_516_4;
+        b[2] = 0.57000 * b[2] + white * 1.052_691_3;
+        let pink = b[0] + b[1] + b[2] + white * 0.1848;
+        // The filter has a peak gain of roughly 4.
+        (amplitude * pink / 4.0).clamp(-amplitude, amplitude)
+    })
+}
+
+/// Exponential sine sweep from `start_hz` to `end_hz` over the whole signal.
+#[must_use]
+pub fn sweep<S: Sample>(
+    sample_rate: SampleRate,
+    channels: Channels,
+    start_hz: f64,
+    end_hz: f64,
+    amplitude: f64,
+    samples_per_channel: usize,
+) -> Vec<S> {
+    let duration = time(samples_per_channel, sample_rate);
+    let ratio = (end_hz / start_hz).ln();
+    render(channels, samples_per_channel, |n| {
+        let t = time(n, sample_rate);
+        let phase = if ratio.abs() < f64::EPSILON {
+            TAU * start_hz * t
+        } else {
+            TAU * start_hz * duration / ratio * ((t / duration * ratio).exp() - 1.0)
+        };
+        amplitude * phase.sin()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generators_produce_exact_lengths() {
+        let sr = SampleRate::Hz48000;
+        let n = samples_for_ms(sr, 20);
+        assert_eq!(n, 960);
+        assert_eq!(sine::<i16>(sr, Channels::Stereo, 440.0, 0.5, n).len(), 1920);
+        assert_eq!(
+            multitone::<f32>(sr, Channels::Mono, &[(300.0, 0.2), (900.0, 0.2)], 7).len(),
+            7
+        );
+        assert_eq!(
+            sweep::<f32>(sr, Channels::Mono, 20.0, 20_000.0, 0.5, n).len(),
+            n
+        );
+
+        let stereo = white_noise::<f32>(Channels::Stereo, 0.5, n, 7);
+        assert!(
+            stereo
+                .chunks_exact(2)
+                .all(|f| f[0].to_bits() == f[1].to_bits())
+        );
+        assert!(stereo.iter().all(|s| s.abs() <= 0.5));
+        assert_eq!(stereo, white_noise::<f32>(Channels::Stereo, 0.5, n, 7));
+        assert_ne!(stereo, white_noise::<f32>(Channels::Stereo, 0.5, n, 8));
+
+        let pink = pink_noise::<i16>(Channels::Mono, 0.5, 48_000, 1);
+        assert!(pink.iter().all(|&s| s.unsigned_abs() <= 16_384));
+        assert!(pink.iter().any(|&s| s.unsigned_abs() > 1_000));
+    }
+}
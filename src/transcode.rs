@@ -0,0 +1,260 @@
+//! Parallel offline transcoding of Ogg Opus files
+//!
+//! [`transcode()`] decodes the whole input, cuts the PCM into segments of
+//! [`TranscodeConfig::segment`] and encodes each one on its own [`Encoder`] across a rayon
+//! pool. Every segment after the first re-encodes [`TranscodeConfig::overlap`] of the
+//! preceding audio and drops those packets as pre-roll. The packets keep the timing of
+//! one continuous encoder, but their contents only approximate it: each segment starts
+//! from a fresh encoder state, and the pre-roll lets that state settle without matching
+//! it exactly.
+
+use crate::constants::{MAX_FRAME_SAMPLES_48KHZ, MAX_PACKET_SIZE};
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::ogg::{OpusHead, OpusTags, OpusWriter, PacketReader};
+use crate::types::{Application, Bitrate, Channels, FrameSize, SampleRate};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Serial number of the output stream.
+const OUTPUT_SERIAL: u32 = 0x4F50_5553;
+
+/// Settings for [`transcode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodeConfig {
+    /// Encoder application.
+    pub application: Application,
+    /// Target bitrate of the output.
+    pub bitrate: Bitrate,
+    /// Output frame duration.
+    pub frame_size: FrameSize,
+    /// Audio encoded by each independent encoder; shorter segments spread better across
+    /// threads but add more boundaries.
+    pub segment: Duration,
+    /// Audio re-encoded and discarded before each segment boundary to warm the encoder up.
+    pub overlap: Duration,
+    /// Worker threads, or 0 for rayon's global pool.
+    pub threads: usize,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        Self {
+            application: Application::Audio,
+            bitrate: Bitrate::Auto,
+            frame_size: FrameSize::Ms20,
+            segment: Duration::from_secs(10),
+            overlap: Duration::from_millis(200),
+            threads: 0,
+        }
+    }
+}
+
+/// Summary of a finished [`transcode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodeReport {
+    /// Audio samples per channel (at 48 kHz) written, excluding pre-skip.
+    pub samples: u64,
+    /// Audio packets written.
+    pub packets: usize,
+    /// Independently encoded segments.
+    pub segments: usize,
+}
+
+/// Transcode the Ogg Opus file at `input` into a new file at `output`.
+///
+/// # Errors
+/// Same as [`transcode`], plus [`Error::Io`] if either file cannot be opened.
+pub fn transcode_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    config: &TranscodeConfig,
+) -> Result<TranscodeReport> {
+    let input = BufReader::new(File::open(input)?);
+    let output = BufWriter::new(File::create(output)?);
+    transcode(input, output, config)
+}
+
+/// Decode the first logical Opus stream of `input` and re-encode it to `output` with
+/// `config`, encoding segments in parallel.
+///
+/// The output keeps the input's comment header and input sample rate; the input's output
+/// gain is applied while decoding.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] for a zero segment length or if the thread pool cannot be
+/// built, [`Error::InvalidPacket`] if the input does not start with valid Opus headers,
+/// [`Error::Unimplemented`] for multistream (mapping family other than 0) input, and any
+/// decoder, encoder or I/O error.
+pub fn transcode<R: Read, W: Write>(
+    input: R,
+    output: W,
+    config: &TranscodeConfig,
+) -> Result<TranscodeReport> {
+    let frame = config.frame_size.samples(SampleRate::Hz48000);
+    if duration_samples(config.segment) < frame {
+        return Err(Error::BadArg);
+    }
+
+    let (channels, tags, input_sample_rate, mut pcm) = decode_stream(input)?;
+    let ch = channels.as_usize();
+    let samples = pcm.len() / ch;
+    let pre_skip = new_encoder(config, channels)?.lookahead()?;
+    let pre_skip = usize::try_from(pre_skip).map_err(|_| Error::InternalError)?;
+    // Pad so the final packet still covers the end of the input once pre-skip is removed.
+    let frames = (samples + pre_skip).div_ceil(frame);
+    pcm.resize(frames * frame * ch, 0.0);
+
+    let (packets, segments) = encode_segments(config, channels, frames, |index| {
+        &pcm[index * frame * ch..(index + 1) * frame * ch]
+    })?;
+
+    let pre_skip = u16::try_from(pre_skip).map_err(|_| Error::InternalError)?;
+    let head = OpusHead::new(channels.as_usize() as u8, pre_skip, input_sample_rate);
+    let mut writer = OpusWriter::new(output, OUTPUT_SERIAL, &head, &tags)?;
+    let mut packets = packets.into_iter();
+    let mut granule = 0;
+    let mut last = packets.next().ok_or(Error::InternalError)?;
+    for packet in packets {
+        granule += frame as u64;
+        writer.write_packet(&last, granule)?;
+        last = packet;
+    }
+    writer
+        .finish(&last, u64::from(pre_skip) + samples as u64)?
+        .flush()?;
+    Ok(TranscodeReport {
+        samples: samples as u64,
+        packets: frames,
+        segments,
+    })
+}
+
+/// Encode interleaved 48 kHz `frames` of [`TranscodeConfig::frame_size`] each in parallel,
+/// returning one packet per frame in input order.
+///
+/// Frames are split into contiguous runs of [`TranscodeConfig::segment`], each encoded on
+/// its own [`Encoder`] with the same warm-up overlap as [`transcode`]. The packets keep a
+/// continuous encoder's frame timing, but each run starts from a fresh state that the
+/// pre-roll only brings close to the continuous one, so their contents differ.
+///
+/// # Errors
+/// Returns [`Error::BadArg`] if a frame does not hold exactly one frame of `channels`
+/// audio, for a segment shorter than one frame, or if the thread pool cannot be built;
+/// otherwise any encoder error.
+pub fn parallel_encode(
+    frames: &[&[f32]],
+    channels: Channels,
+    config: &TranscodeConfig,
+) -> Result<Vec<Vec<u8>>> {
+    let len = config.frame_size.samples(SampleRate::Hz48000) * channels.as_usize();
+    if frames.iter().any(|frame| frame.len() != len) {
+        return Err(Error::BadArg);
+    }
+    if frames.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (packets, _) = encode_segments(config, channels, frames.len(), |index| frames[index])?;
+    Ok(packets)
+}
+
+/// Encode frames `0..count` in segments across the configured thread pool, returning the
+/// packets in order and the number of segments.
+fn encode_segments<'a>(
+    config: &TranscodeConfig,
+    channels: Channels,
+    count: usize,
+    frame_at: impl Fn(usize) -> &'a [f32] + Sync,
+) -> Result<(Vec<Vec<u8>>, usize)> {
+    let frame = config.frame_size.samples(SampleRate::Hz48000);
+    let segment_frames = duration_samples(config.segment) / frame;
+    if segment_frames == 0 {
+        return Err(Error::BadArg);
+    }
+    let overlap_frames = duration_samples(config.overlap).div_ceil(frame);
+
+    let starts: Vec<usize> = (0..count).step_by(segment_frames).collect();
+    let encode_segment = |&start: &usize| -> Result<Vec<Vec<u8>>> {
+        let end = (start + segment_frames).min(count);
+        let mut encoder = new_encoder(config, channels)?;
+        let mut out = vec![0u8; MAX_PACKET_SIZE];
+        let mut packets = Vec::with_capacity(end - start);
+        for index in start - start.min(overlap_frames)..end {
+            let len = encoder.encode_float(frame_at(index), &mut out)?;
+            if index >= start {
+                packets.push(out[..len].to_vec());
+            }
+        }
+        Ok(packets)
+    };
+    let segments: Vec<Vec<Vec<u8>>> = if config.threads == 0 {
+        starts
+            .par_iter()
+            .map(encode_segment)
+            .collect::<Result<_>>()?
+    } else {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config.threads)
+            .build()
+            .map_err(|_| Error::BadArg)?
+            .install(|| starts.par_iter().map(encode_segment).collect::<Result<_>>())?
+    };
+    Ok((segments.into_iter().flatten().collect(), starts.len()))
+}
+
+fn new_encoder(config: &TranscodeConfig, channels: Channels) -> Result<Encoder> {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, channels, config.application)?;
+    encoder.set_bitrate(config.bitrate)?;
+    Ok(encoder)
+}
+
+fn duration_samples(duration: Duration) -> usize {
+    usize::try_from(duration.as_micros() * 48 / 1_000).unwrap_or(usize::MAX)
+}
+
+/// Decode the first logical stream of `input` to interleaved PCM with pre-skip and end
+/// trimming applied.
+fn decode_stream<R: Read>(input: R) -> Result<(Channels, OpusTags, u32, Vec<f32>)> {
+    let mut reader = PacketReader::new(input);
+    let first = reader.next_packet()?.ok_or(Error::InvalidPacket)?;
+    let head = OpusHead::parse(&first.data)?;
+    if head.mapping_family != 0 {
+        return Err(Error::Unimplemented);
+    }
+    let channels = if head.channels == 1 {
+        Channels::Mono
+    } else {
+        Channels::Stereo
+    };
+    let ch = channels.as_usize();
+    let comments = reader.next_packet()?.ok_or(Error::InvalidPacket)?;
+    let tags = OpusTags::parse(&comments.data)?;
+
+    let mut decoder = Decoder::new(SampleRate::Hz48000, channels)?;
+    decoder.set_gain(i32::from(head.output_gain))?;
+    let mut buf = vec![0.0f32; MAX_FRAME_SAMPLES_48KHZ * ch];
+    let mut pcm = Vec::new();
+    let mut end = None;
+    while let Some(packet) = reader.next_packet()? {
+        if packet.serial != first.serial {
+            continue;
+        }
+        let frames = decoder.decode_float(&packet.data, &mut buf, false)?;
+        pcm.extend_from_slice(&buf[..frames * ch]);
+        if packet.granule_position.is_some() {
+            end = packet.granule_position;
+        }
+        if packet.eos {
+            break;
+        }
+    }
+    let end = end.map_or(0, |g| usize::try_from(g).unwrap_or(usize::MAX));
+    let start = usize::from(head.pre_skip).min(pcm.len() / ch);
+    pcm.truncate(end.clamp(start, pcm.len() / ch) * ch);
+    pcm.drain(..start * ch);
+    Ok((channels, tags, head.input_sample_rate, pcm))
+}
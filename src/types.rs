@@ -6,11 +6,15 @@ use crate::bindings::{
     OPUS_BANDWIDTH_SUPERWIDEBAND, OPUS_BANDWIDTH_WIDEBAND, OPUS_BITRATE_MAX, OPUS_FRAMESIZE_2_5_MS,
     OPUS_FRAMESIZE_5_MS, OPUS_FRAMESIZE_10_MS, OPUS_FRAMESIZE_20_MS, OPUS_FRAMESIZE_40_MS,
     OPUS_FRAMESIZE_60_MS, OPUS_FRAMESIZE_80_MS, OPUS_FRAMESIZE_100_MS, OPUS_FRAMESIZE_120_MS,
-    OPUS_SIGNAL_MUSIC, OPUS_SIGNAL_VOICE,
+    OPUS_FRAMESIZE_ARG, OPUS_SIGNAL_MUSIC, OPUS_SIGNAL_VOICE,
 };
+use crate::error::{Error, Result};
+use std::fmt;
+use std::time::Duration;
 
 /// Encoder application mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Application {
     /// Optimize for conversational speech.
     #[default]
@@ -23,6 +27,8 @@ pub enum Application {
 
 /// Audio channel layout.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 pub enum Channels {
     /// Single-channel audio.
     Mono = 1,
@@ -46,6 +52,8 @@ impl Channels {
 
 /// Supported input/output sample rates.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 pub enum SampleRate {
     /// 8 kHz.
     Hz8000 = 8000,
@@ -67,6 +75,12 @@ impl SampleRate {
         self as i32
     }
 
+    /// Playback duration of `samples` samples per channel at this rate.
+    #[must_use]
+    pub const fn duration_of(self, samples: usize) -> Duration {
+        Duration::from_nanos(samples as u64 * 1_000_000_000 / self as u64)
+    }
+
     /// Return true if the sample rate is valid for Opus.
     #[must_use]
     pub const fn is_valid(self) -> bool {
@@ -79,6 +93,8 @@ impl SampleRate {
 
 /// Coded bandwidth classifications in packets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Bandwidth {
     /// 4 kHz bandpass.
     Narrowband = OPUS_BANDWIDTH_NARROWBAND as isize,
@@ -92,8 +108,31 @@ pub enum Bandwidth {
     Fullband = OPUS_BANDWIDTH_FULLBAND as isize,
 }
 
+impl Bandwidth {
+    /// Widest bandwidth a signal sampled at `sample_rate` can represent: the one whose
+    /// passband fits below its Nyquist frequency.
+    #[must_use]
+    pub const fn for_rate(sample_rate: SampleRate) -> Self {
+        match sample_rate {
+            SampleRate::Hz8000 => Self::Narrowband,
+            SampleRate::Hz12000 => Self::Mediumband,
+            SampleRate::Hz16000 => Self::Wideband,
+            SampleRate::Hz24000 => Self::SuperWideband,
+            SampleRate::Hz48000 => Self::Fullband,
+        }
+    }
+
+    /// Whether this bandwidth is wider than `other`.
+    #[must_use]
+    pub const fn is_wider_than(self, other: Self) -> bool {
+        self as i32 > other as i32
+    }
+}
+
 /// Convenience frame sizes in milliseconds.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 pub enum FrameSize {
     /// 2.5 ms.
     Ms2_5 = 25,
@@ -120,6 +159,7 @@ impl FrameSize {
 
 /// Hint the encoder about the type of content.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Signal {
     /// Voice-optimized mode.
     Voice = OPUS_SIGNAL_VOICE as isize,
@@ -127,9 +167,107 @@ pub enum Signal {
     Music = OPUS_SIGNAL_MUSIC as isize,
 }
 
-/// Expert frame duration settings for the encoder.
+/// Coding mode of an Opus frame (RFC 6716 section 2).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum CodecMode {
+    /// Linear-prediction SILK layer only, up to wideband.
+    SilkOnly = 1000,
+    /// SILK below 8 kHz plus CELT above, at super-wideband or fullband.
+    Hybrid = 1001,
+    /// MDCT-based CELT layer only.
+    CeltOnly = 1002,
+}
+
+/// In-band forward error correction mode (`OPUS_SET_INBAND_FEC`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum InbandFec {
+    /// No FEC.
+    #[default]
+    Off = 0,
+    /// FEC enabled; the encoder may switch to SILK or lower the bitrate to make room for it.
+    On = 1,
+    /// FEC enabled, but the encoder stays in CELT for input it is confident is music
+    /// instead of switching to SILK to carry it (libopus 1.5+). Speech still gets LBRR
+    /// as with [`Self::On`].
+    OnNoBitrateAdjust = 2,
+}
+
+impl InbandFec {
+    /// Map an `OPUS_GET_INBAND_FEC` value back to the enum.
+    pub(crate) const fn from_ctl(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Off),
+            1 => Ok(Self::On),
+            2 => Ok(Self::OnNoBitrateAdjust),
+            _ => Err(Error::InternalError),
+        }
+    }
+}
+
+/// Vetted encoder configurations for common uses, applied by
+/// [`Encoder::with_profile`](crate::Encoder::with_profile).
+///
+/// Every profile fixes the frame duration, so frames passed to the encoder must be
+/// exactly [`Self::frame_size`] long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Profile {
+    /// Interactive speech on a good network: 10 ms frames, 24 kb/s per channel up to
+    /// wideband, medium complexity, no FEC or DTX.
+    VoipLowLatency,
+    /// Speech over a lossy network: 20 ms frames, 32 kb/s per channel up to wideband,
+    /// in-band FEC tuned for 10 % loss and DTX during silence.
+    VoipLossy,
+    /// Music for live streaming: 20 ms frames, 64 kb/s per channel, VBR, full
+    /// complexity.
+    MusicStreaming,
+    /// Music for storage: 20 ms frames, 96 kb/s per channel, unconstrained VBR, full
+    /// complexity.
+    MusicArchive,
+}
+
+impl Profile {
+    /// Application mode the profile creates the encoder with.
+    #[must_use]
+    pub const fn application(self) -> Application {
+        match self {
+            Self::VoipLowLatency | Self::VoipLossy => Application::Voip,
+            Self::MusicStreaming | Self::MusicArchive => Application::Audio,
+        }
+    }
+
+    /// Frame duration every encoded frame must have.
+    #[must_use]
+    pub const fn frame_size(self) -> FrameSize {
+        match self {
+            Self::VoipLowLatency => FrameSize::Ms10,
+            Self::VoipLossy | Self::MusicStreaming | Self::MusicArchive => FrameSize::Ms20,
+        }
+    }
+
+    /// Target bitrate per channel in bits per second.
+    #[must_use]
+    pub const fn bitrate_per_channel(self) -> i32 {
+        match self {
+            Self::VoipLowLatency => 24_000,
+            Self::VoipLossy => 32_000,
+            Self::MusicStreaming => 64_000,
+            Self::MusicArchive => 96_000,
+        }
+    }
+}
+
+/// Expert frame duration settings for the encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ExpertFrameDuration {
+    /// Use the size of each input frame (the default), so every encode call may pick
+    /// its own duration. This is `OPUS_FRAMESIZE_ARG`; set it to return to
+    /// argument-driven framing after a fixed duration.
+    #[default]
+    FromArgument = OPUS_FRAMESIZE_ARG as isize,
     /// 2.5 ms.
     Ms2_5 = OPUS_FRAMESIZE_2_5_MS as isize,
     /// 5 ms.
@@ -150,6 +288,28 @@ pub enum ExpertFrameDuration {
     Ms120 = OPUS_FRAMESIZE_120_MS as isize,
 }
 
+impl ExpertFrameDuration {
+    /// Samples per channel every input frame must have at `sample_rate`, or `None` for
+    /// [`Self::FromArgument`].
+    #[must_use]
+    pub const fn samples(self, sample_rate: SampleRate) -> Option<usize> {
+        // Tenths of a millisecond, as in FrameSize.
+        let tenths = match self {
+            Self::FromArgument => return None,
+            Self::Ms2_5 => 25,
+            Self::Ms5 => 50,
+            Self::Ms10 => 100,
+            Self::Ms20 => 200,
+            Self::Ms40 => 400,
+            Self::Ms60 => 600,
+            Self::Ms80 => 800,
+            Self::Ms100 => 1_000,
+            Self::Ms120 => 1_200,
+        };
+        Some(tenths * sample_rate as usize / 10_000)
+    }
+}
+
 /// Encoder complexity wrapper in the range 0..=10.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Complexity(u32);
@@ -178,6 +338,152 @@ impl Default for Complexity {
     }
 }
 
+/// Expected packet loss in whole percent, 0..=100.
+///
+/// Used by [`crate::Encoder::set_packet_loss_perc`] and
+/// [`crate::MSEncoder::set_packet_loss_perc`] so a percentage cannot be mixed up with a
+/// per-mille count or a 0.0..=1.0 fraction. Displays as e.g. `25%`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawPacketLossPerc"))]
+pub struct PacketLossPerc(u8);
+
+/// Unvalidated [`PacketLossPerc`], range-checked on deserialization.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawPacketLossPerc(u8);
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawPacketLossPerc> for PacketLossPerc {
+    type Error = Error;
+
+    fn try_from(raw: RawPacketLossPerc) -> Result<Self> {
+        if raw.0 > 100 {
+            return Err(Error::BadArg);
+        }
+        Ok(Self(raw.0))
+    }
+}
+
+impl PacketLossPerc {
+    /// No expected loss.
+    pub const ZERO: Self = Self(0);
+
+    /// Create a loss percentage in range 0..=100.
+    ///
+    /// # Panics
+    /// Panics when `perc` is greater than 100.
+    #[must_use]
+    pub const fn new(perc: u8) -> Self {
+        assert!(
+            perc <= 100,
+            "Packet loss percentage must be between 0 and 100"
+        );
+        Self(perc)
+    }
+
+    /// Round a loss fraction (0.0..=1.0, e.g. from an RTCP receiver report) to whole
+    /// percent, clamping values outside the range.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn from_fraction(fraction: f32) -> Self {
+        Self((fraction.clamp(0.0, 1.0) * 100.0).round() as u8)
+    }
+
+    /// Raw percentage.
+    #[must_use]
+    pub const fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Loss as a fraction, 0.0..=1.0.
+    #[must_use]
+    pub fn fraction(self) -> f32 {
+        f32::from(self.0) / 100.0
+    }
+}
+
+/// Saturates at 100.
+impl From<u8> for PacketLossPerc {
+    fn from(perc: u8) -> Self {
+        Self(perc.min(100))
+    }
+}
+
+impl From<PacketLossPerc> for u8 {
+    fn from(perc: PacketLossPerc) -> Self {
+        perc.0
+    }
+}
+
+impl fmt::Display for PacketLossPerc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// Decoder output gain in Q8 dB (1/256 dB), the unit of `OPUS_SET_GAIN`.
+///
+/// The `i16` range is exactly what libopus accepts, about ±128 dB. Displays as e.g.
+/// `-6.00 dB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Q8Gain(i16);
+
+impl Q8Gain {
+    /// Unity gain.
+    pub const ZERO: Self = Self(0);
+
+    /// Gain of `q8` 1/256 dB steps.
+    #[must_use]
+    pub const fn new(q8: i16) -> Self {
+        Self(q8)
+    }
+
+    /// Round a gain in decibels to the nearest Q8 step.
+    ///
+    /// # Errors
+    /// Returns [`Error::BadArg`] if `db` is not finite or outside the Q8 range.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_db(db: f32) -> Result<Self> {
+        let q8 = (f64::from(db) * 256.0).round();
+        if !q8.is_finite() || q8 < f64::from(i16::MIN) || q8 > f64::from(i16::MAX) {
+            return Err(Error::BadArg);
+        }
+        Ok(Self(q8 as i16))
+    }
+
+    /// Raw Q8 value.
+    #[must_use]
+    pub const fn value(self) -> i16 {
+        self.0
+    }
+
+    /// Gain in decibels.
+    #[must_use]
+    pub fn db(self) -> f32 {
+        f32::from(self.0) / 256.0
+    }
+}
+
+impl From<i16> for Q8Gain {
+    fn from(q8: i16) -> Self {
+        Self(q8)
+    }
+}
+
+impl From<Q8Gain> for i16 {
+    fn from(gain: Q8Gain) -> Self {
+        gain.0
+    }
+}
+
+impl fmt::Display for Q8Gain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} dB", self.db())
+    }
+}
+
 /// Bitrate control options.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Bitrate {
@@ -190,6 +496,32 @@ pub enum Bitrate {
 }
 
 impl Bitrate {
+    /// Lowest explicit bitrate libopus accepts, in bits per second.
+    pub const MIN_BPS: i32 = 500;
+    /// Highest explicit bitrate libopus accepts, in bits per second.
+    pub const MAX_BPS: i32 = 512_000;
+
+    /// Explicit bitrate of `kbps` kilobits per second.
+    #[must_use]
+    pub const fn kbps(kbps: u32) -> Self {
+        let bps = kbps.saturating_mul(1_000);
+        Self::Custom(if bps > i32::MAX as u32 {
+            i32::MAX
+        } else {
+            bps as i32
+        })
+    }
+
+    /// Whether libopus takes this bitrate as is: [`Self::Auto`], [`Self::Max`] or an
+    /// explicit rate within [`Self::MIN_BPS`]`..=`[`Self::MAX_BPS`].
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        match self {
+            Self::Auto | Self::Max => true,
+            Self::Custom(bps) => bps >= Self::MIN_BPS && bps <= Self::MAX_BPS,
+        }
+    }
+
     /// Convert to libopus `i32` value.
     #[must_use]
     pub const fn value(self) -> i32 {
@@ -211,4 +543,23 @@ mod tests {
         assert_eq!(FrameSize::Ms5.samples(SampleRate::Hz16000), 80);
         assert_eq!(FrameSize::Ms2_5.samples(SampleRate::Hz8000), 20);
     }
+
+    #[test]
+    fn bitrate_kbps_and_range() {
+        assert_eq!(Bitrate::kbps(64), Bitrate::Custom(64_000));
+        assert_eq!(Bitrate::kbps(u32::MAX), Bitrate::Custom(i32::MAX));
+        assert!(Bitrate::kbps(512).is_valid() && Bitrate::Custom(500).is_valid());
+        assert!(!Bitrate::kbps(513).is_valid() && !Bitrate::Custom(499).is_valid());
+        assert!(Bitrate::Auto.is_valid() && Bitrate::Max.is_valid());
+    }
+
+    #[test]
+    fn q8_gain_converts_decibels() {
+        assert_eq!(Q8Gain::from_db(-6.0), Ok(Q8Gain::new(-1_536)));
+        assert_eq!(Q8Gain::from_db(0.001), Ok(Q8Gain::ZERO));
+        assert_eq!(Q8Gain::from_db(-128.0), Ok(Q8Gain::new(i16::MIN)));
+        assert_eq!(Q8Gain::from_db(128.0), Err(Error::BadArg));
+        assert_eq!(Q8Gain::from_db(f32::NAN), Err(Error::BadArg));
+        assert_eq!(Q8Gain::new(384).to_string(), "1.50 dB");
+    }
 }
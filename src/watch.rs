@@ -0,0 +1,296 @@
+//! Detection of mid-stream configuration changes
+//!
+//! A sender may switch between mono and stereo, or change its coded bandwidth, at any
+//! packet. libopus decodes either way, but a decoder created for mono output downmixes a
+//! stereo stream and a stereo one plays a mono stream on both channels. [`StreamWatcher`]
+//! reads the TOC of every incoming packet, reports such changes and can rebuild its
+//! wrapped [`Decoder`] for the new channel layout, crossfading from the old one.
+
+use crate::decoder::Decoder;
+use crate::error::Result;
+use crate::packet::Toc;
+use crate::types::{Bandwidth, Channels};
+use std::mem;
+use std::time::Duration;
+
+/// Channel layout and bandwidth a packet was coded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamConfig {
+    /// Coded channels.
+    pub channels: Channels,
+    /// Coded bandwidth.
+    pub bandwidth: Bandwidth,
+}
+
+impl StreamConfig {
+    /// Read the configuration from the TOC of `packet`.
+    ///
+    /// # Errors
+    /// Returns [`crate::Error::BadArg`] if `packet` is empty.
+    pub fn of(packet: &[u8]) -> Result<Self> {
+        let toc = Toc::of(packet)?;
+        Ok(Self {
+            channels: toc.channels(),
+            bandwidth: toc.bandwidth(),
+        })
+    }
+}
+
+/// Configuration change between two consecutive packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamChange {
+    /// Configuration of the stream so far.
+    pub previous: StreamConfig,
+    /// Configuration of the packet that changed it.
+    pub current: StreamConfig,
+}
+
+impl StreamChange {
+    /// Whether the sender switched between mono and stereo, which calls for a decoder
+    /// with the new output layout.
+    #[must_use]
+    pub fn requires_reconfigure(&self) -> bool {
+        self.previous.channels != self.current.channels
+    }
+
+    /// Whether the coded bandwidth changed.
+    #[must_use]
+    pub fn bandwidth_changed(&self) -> bool {
+        self.previous.bandwidth != self.current.bandwidth
+    }
+}
+
+/// Replaced decoder still being faded out.
+struct Fade {
+    decoder: Decoder,
+    /// Samples per channel already faded.
+    position: usize,
+    /// Samples per channel in the whole fade.
+    length: usize,
+}
+
+/// [`Decoder`] wrapper that watches incoming packets for configuration changes.
+///
+/// Pass every packet to [`Self::watch`] before decoding it with [`Self::decode_float`].
+/// With [`Self::set_auto_reconfigure`] enabled, a channel change replaces the wrapped
+/// decoder by one whose output layout follows the stream, so size the output buffer
+/// from [`Self::channels`] after each `watch`. The replacement keeps the gain, phase
+/// inversion and soft-clip settings; its statistics start afresh.
+pub struct StreamWatcher {
+    decoder: Decoder,
+    current: Option<StreamConfig>,
+    auto_reconfigure: Option<Duration>,
+    fade: Option<Fade>,
+    scratch: Vec<f32>,
+}
+
+impl StreamWatcher {
+    /// Watch the stream decoded by `decoder`, reporting changes only.
+    #[must_use]
+    pub const fn new(decoder: Decoder) -> Self {
+        Self {
+            decoder,
+            current: None,
+            auto_reconfigure: None,
+            fade: None,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Rebuild the decoder whenever the stream's channel count stops matching its output,
+    /// crossfading over `crossfade` from the old decoder's output, or stop doing so with
+    /// `None`. A zero crossfade switches abruptly.
+    pub const fn set_auto_reconfigure(&mut self, crossfade: Option<Duration>) {
+        self.auto_reconfigure = crossfade;
+    }
+
+    /// Crossfade used for automatic reconfiguration, if enabled.
+    #[must_use]
+    pub const fn auto_reconfigure(&self) -> Option<Duration> {
+        self.auto_reconfigure
+    }
+
+    /// Inspect `packet` and report whether its configuration differs from the stream's.
+    ///
+    /// The first packet only sets the baseline; with automatic reconfiguration it also
+    /// rebuilds the decoder, without a crossfade, if its layout does not match. Empty
+    /// packets (losses) are ignored.
+    ///
+    /// # Errors
+    /// Returns any error from creating or configuring a replacement decoder.
+    pub fn watch(&mut self, packet: &[u8]) -> Result<Option<StreamChange>> {
+        if packet.is_empty() {
+            return Ok(None);
+        }
+        let current = StreamConfig::of(packet)?;
+        let change = match self.current.replace(current) {
+            Some(previous) if previous != current => Some(StreamChange { previous, current }),
+            _ => None,
+        };
+        if let Some(crossfade) = self.auto_reconfigure
+            && self.decoder.channels() != current.channels
+        {
+            let crossfade = if change.is_some() {
+                crossfade
+            } else {
+                Duration::ZERO
+            };
+            self.reconfigure(current.channels, crossfade)?;
+        }
+        Ok(change)
+    }
+
+    /// Decode a packet into interleaved `f32` PCM with [`Self::channels`] channels, as
+    /// [`Decoder::decode_float`] does, blending in the replaced decoder's output while a
+    /// crossfade is running.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::decode_float`].
+    pub fn decode_float(&mut self, packet: &[u8], out: &mut [f32], fec: bool) -> Result<usize> {
+        let decoded = self.decoder.decode_float(packet, out, fec)?;
+        let Some(fade) = self.fade.as_mut() else {
+            return Ok(decoded);
+        };
+        let old_channels = fade.decoder.channels().as_usize();
+        self.scratch.resize(decoded * old_channels, 0.0);
+        fade.decoder.decode_float(packet, &mut self.scratch, fec)?;
+
+        let channels = self.decoder.channels().as_usize();
+        let mixed = decoded.min(fade.length - fade.position);
+        #[allow(clippy::cast_precision_loss)]
+        for (i, frame) in out.chunks_exact_mut(channels).take(mixed).enumerate() {
+            let weight = (fade.position + i + 1) as f32 / fade.length as f32;
+            let old = &self.scratch[i * old_channels..(i + 1) * old_channels];
+            for (c, sample) in frame.iter_mut().enumerate() {
+                let previous = match (old_channels, channels) {
+                    (2, 1) => 0.5 * (old[0] + old[1]),
+                    (1, _) => old[0],
+                    _ => old[c],
+                };
+                *sample = previous + weight * (*sample - previous);
+            }
+        }
+        fade.position += mixed;
+        if fade.position >= fade.length {
+            self.fade = None;
+        }
+        Ok(decoded)
+    }
+
+    /// Output channels of the wrapped decoder.
+    #[must_use]
+    pub const fn channels(&self) -> Channels {
+        self.decoder.channels()
+    }
+
+    /// Configuration of the last packet watched, or `None` before the first one.
+    #[must_use]
+    pub const fn current(&self) -> Option<StreamConfig> {
+        self.current
+    }
+
+    /// Wrapped decoder.
+    #[must_use]
+    pub const fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Wrapped decoder, for CTLs.
+    pub const fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Consume the watcher, returning the wrapped decoder.
+    #[must_use]
+    pub fn into_inner(self) -> Decoder {
+        self.decoder
+    }
+
+    /// Reset the decoder and forget the stream configuration.
+    ///
+    /// # Errors
+    /// Same as [`Decoder::reset`].
+    pub fn reset(&mut self) -> Result<()> {
+        self.decoder.reset()?;
+        self.current = None;
+        self.fade = None;
+        Ok(())
+    }
+
+    fn reconfigure(&mut self, channels: Channels, crossfade: Duration) -> Result<()> {
+        let sample_rate = self.decoder.sample_rate();
+        let mut next = Decoder::new(sample_rate, channels)?;
+        next.set_gain(self.decoder.gain()?)?;
+        next.set_phase_inversion_disabled(self.decoder.phase_inversion_disabled()?)?;
+        next.set_soft_clip(self.decoder.soft_clip());
+        let previous = mem::replace(&mut self.decoder, next);
+        let length = usize::try_from(crossfade.as_micros() * sample_rate as u128 / 1_000_000)
+            .unwrap_or(usize::MAX);
+        self.fade = (length > 0).then_some(Fade {
+            decoder: previous,
+            position: 0,
+            length,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_PACKET_SIZE;
+    use crate::encoder::Encoder;
+    use crate::testsignal::sine;
+    use crate::types::{Application, SampleRate};
+
+    #[test]
+    fn watcher_reports_changes_and_follows_channel_switch() {
+        let sr = SampleRate::Hz48000;
+        let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+        let mut watcher = StreamWatcher::new(Decoder::new(sr, Channels::Mono).unwrap());
+        watcher.set_auto_reconfigure(Some(Duration::from_millis(30)));
+        let pcm: Vec<f32> = sine(sr, Channels::Stereo, 440.0, 0.5, 960);
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let mut out = vec![0f32; 2 * 960];
+
+        let mut changes = Vec::new();
+        for i in 0..12 {
+            let force = if i < 4 {
+                Channels::Mono
+            } else {
+                Channels::Stereo
+            };
+            encoder.set_force_channels(Some(force)).unwrap();
+            if i == 8 {
+                encoder.set_bandwidth(Bandwidth::Wideband).unwrap();
+            }
+            let len = encoder.encode_float(&pcm, &mut packet).unwrap();
+            if let Some(change) = watcher.watch(&packet[..len]).unwrap() {
+                changes.push((i, change));
+            }
+            let channels = watcher.channels().as_usize();
+            let n = watcher
+                .decode_float(&packet[..len], &mut out[..960 * channels], false)
+                .unwrap();
+            assert_eq!(n, 960);
+            assert!(out[..960 * channels].iter().all(|s| s.is_finite()));
+            assert_eq!(watcher.channels(), force);
+            // A 30 ms fade spans the switching packet and the next one.
+            assert_eq!(watcher.fade.is_some(), i == 4);
+        }
+
+        assert_eq!(changes.len(), 2);
+        let (at, switch) = changes[0];
+        assert_eq!(at, 4);
+        assert!(switch.requires_reconfigure());
+        assert_eq!(switch.current.channels, Channels::Stereo);
+        let (at, narrowed) = changes[1];
+        assert_eq!(at, 8);
+        assert!(narrowed.bandwidth_changed() && !narrowed.requires_reconfigure());
+        assert_eq!(watcher.current().unwrap().bandwidth, Bandwidth::Wideband);
+
+        assert_eq!(watcher.watch(&[]).unwrap(), None);
+        watcher.reset().unwrap();
+        assert_eq!(watcher.current(), None);
+    }
+}
@@ -1,4 +1,9 @@
-use opus_codec::{Channels, Decoder, SampleRate};
+use opus_codec::{
+    Application, Bandwidth, Channels, Complexity, DecodeProcessing, Decoder, Encoder, FecRecovery,
+    FrameLevel, LastPacketInfo, MSDecoder, MSEncoder, Mapping, PacketLossPerc, Q8Gain, SampleRate,
+    StreamDecoder, max_multistream_packet_size, packet_bandwidth,
+};
+use std::time::Duration;
 
 #[test]
 fn decoder_control_roundtrip() {
@@ -8,6 +13,12 @@ fn decoder_control_roundtrip() {
     decoder.set_gain(256).expect("set gain");
     assert_eq!(decoder.gain().expect("get gain"), 256);
 
+    assert_eq!(decoder.complexity().expect("get complexity").value(), 0);
+    decoder
+        .set_complexity(Complexity::new(7))
+        .expect("set complexity");
+    assert_eq!(decoder.complexity().expect("get complexity").value(), 7);
+
     decoder
         .set_phase_inversion_disabled(true)
         .expect("disable phase inversion");
@@ -36,3 +47,529 @@ fn decoder_control_roundtrip() {
         0
     );
 }
+
+#[test]
+fn decoder_soft_clip_limits_float_output() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let pcm = opus_codec::testsignal::sine::<f32>(sr, Channels::Stereo, 440.0, 0.9, 960 * 10);
+    let packets: Vec<Vec<u8>> = pcm
+        .chunks_exact(1920)
+        .map(|frame| {
+            let mut packet = [0u8; 1275];
+            let len = encoder.encode_float(frame, &mut packet).expect("encode");
+            packet[..len].to_vec()
+        })
+        .collect();
+
+    let peak = |soft_clip: bool| {
+        let mut decoder = Decoder::new(sr, Channels::Stereo).expect("create decoder");
+        // +6 dB pushes the decoded sine well past full scale.
+        decoder.set_gain(1536).expect("set gain");
+        decoder.set_soft_clip(soft_clip);
+        assert_eq!(decoder.soft_clip(), soft_clip);
+        let mut out = [0f32; 1920];
+        packets
+            .iter()
+            .map(|packet| {
+                let n = decoder
+                    .decode_float(packet, &mut out, false)
+                    .expect("decode");
+                out[..n * 2].iter().fold(0f32, |m, s| m.max(s.abs()))
+            })
+            .fold(0f32, f32::max)
+    };
+    assert!(peak(false) > 1.2);
+    assert!(peak(true) <= 1.0);
+}
+
+#[test]
+fn multistream_soft_clip_keeps_state_per_channel() {
+    let sr = SampleRate::Hz48000;
+    let mapping = Mapping::SURROUND_51;
+    let (mut encoder, _) = MSEncoder::new_surround(sr, 6, 1, Application::Audio).expect("create");
+    // Only the front pair is loud enough to clip once the gain is applied.
+    let mono = opus_codec::testsignal::sine::<f32>(sr, Channels::Mono, 440.0, 0.9, 960 * 10);
+    let pcm: Vec<f32> = mono
+        .iter()
+        .flat_map(|&s| [s, s, s / 4.0, s / 4.0, s / 4.0, s / 4.0])
+        .collect();
+    let mut packet =
+        vec![0u8; max_multistream_packet_size(encoder.streams(), Duration::from_millis(20))];
+    let packets: Vec<Vec<u8>> = pcm
+        .chunks_exact(960 * 6)
+        .map(|frame| {
+            let len = encoder
+                .encode_float(frame, 960, &mut packet)
+                .expect("encode");
+            packet[..len].to_vec()
+        })
+        .collect();
+
+    let mut raw = MSDecoder::new(sr, mapping).expect("create decoder");
+    let mut clipped = MSDecoder::new(sr, mapping).expect("create decoder");
+    for decoder in [&mut raw, &mut clipped] {
+        decoder.set_gain(1536).expect("set gain");
+    }
+    clipped.set_soft_clip(true);
+    assert!(clipped.soft_clip() && !raw.soft_clip());
+    let mut expected = vec![0f32; 960 * 6];
+    let mut out = vec![0f32; 960 * 6];
+    let mut mem = [0f32; 6];
+    let mut peak = 0f32;
+    for packet in &packets {
+        raw.decode_float(packet, &mut expected, 960, false)
+            .expect("decode");
+        clipped
+            .decode_float(packet, &mut out, 960, false)
+            .expect("decode");
+        peak = expected.iter().fold(peak, |m, s| m.max(s.abs()));
+        // Channels that never reach full scale pass through untouched.
+        for frame in out.chunks_exact(6).zip(expected.chunks_exact(6)) {
+            assert_eq!(frame.0[2..], frame.1[2..]);
+        }
+        opus_codec::packet::soft_clip(&mut expected, 960, 6, &mut mem).expect("clip");
+        assert_eq!(out, expected);
+        assert!(out.iter().all(|s| s.abs() <= 1.0));
+    }
+    assert!(peak > 1.2);
+}
+
+#[test]
+fn strided_decode_fills_selected_device_channels() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let pcm = opus_codec::testsignal::sine::<f32>(sr, Channels::Stereo, 440.0, 0.5, 960);
+    let mut packet = [0u8; 1275];
+    let len = encoder.encode_float(&pcm, &mut packet).expect("encode");
+    let packet = &packet[..len];
+
+    let mut reference = Decoder::new(sr, Channels::Stereo).expect("create decoder");
+    let mut contiguous = [0f32; 1920];
+    let mut contiguous_i16 = [0i16; 1920];
+    reference
+        .decode_float(packet, &mut contiguous, false)
+        .expect("decode");
+    reference.reset().expect("reset");
+    reference
+        .decode(packet, &mut contiguous_i16, false)
+        .expect("decode");
+
+    let mut decoder = Decoder::new(sr, Channels::Stereo).expect("create decoder");
+    // Two extra frames of room: the decoder must not touch them.
+    let mut device = vec![9.0f32; 8 * 962];
+    let n = decoder
+        .decode_float_strided(packet, &mut device, 8, 4, false)
+        .expect("strided decode");
+    assert_eq!(n, 960);
+    for (frame, expected) in device.chunks_exact(8).zip(contiguous.chunks_exact(2)) {
+        assert_eq!(&frame[4..6], expected);
+        assert!(frame[..4].iter().chain(&frame[6..]).all(|&s| s == 9.0));
+    }
+    assert!(device[8 * 960..].iter().all(|&s| s == 9.0));
+
+    decoder.reset().expect("reset");
+    let mut device = vec![7i16; 3 * 960];
+    decoder
+        .decode_strided(packet, &mut device, 3, 1, false)
+        .expect("strided decode");
+    for (frame, expected) in device.chunks_exact(3).zip(contiguous_i16.chunks_exact(2)) {
+        assert_eq!(frame[0], 7);
+        assert_eq!(&frame[1..], expected);
+    }
+
+    assert!(
+        decoder
+            .decode_float_strided(packet, &mut [0.0; 64], 8, 7, false)
+            .is_err()
+    );
+    assert!(
+        decoder
+            .decode_float_strided(packet, &mut [0.0; 4], 8, 0, false)
+            .is_err()
+    );
+}
+
+#[test]
+fn last_packet_info_reports_decode_and_concealment() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("encoder");
+    let mut decoder = Decoder::new(sr, Channels::Stereo).expect("decoder");
+    assert_eq!(decoder.last_packet_info(), None);
+
+    let mut packet = vec![0u8; 1500];
+    let len = encoder.encode(&[0; 2 * 960], &mut packet).expect("encode");
+    let mut pcm = vec![0i16; 2 * 960];
+    decoder
+        .decode(&packet[..len], &mut pcm, false)
+        .expect("decode");
+    let info = decoder.last_packet_info().expect("info");
+    assert_eq!(info.samples, 960);
+    assert_eq!(info.duration, Duration::from_millis(20));
+    assert_eq!(
+        info.bandwidth,
+        opus_codec::packet_bandwidth(&packet[..len]).ok()
+    );
+    assert_eq!(info.stream_channels.len(), 1);
+    assert!(!info.concealed);
+
+    let mut lost = vec![0f32; 2 * 480];
+    decoder.decode_float(&[], &mut lost, false).expect("plc");
+    assert_eq!(
+        decoder.last_packet_info(),
+        Some(LastPacketInfo {
+            samples: 480,
+            duration: Duration::from_millis(10),
+            bandwidth: None,
+            stream_channels: Vec::new(),
+            concealed: true,
+        })
+    );
+    decoder.reset().expect("reset");
+    assert_eq!(decoder.last_packet_info(), None);
+
+    let mapping = Mapping {
+        channels: 3,
+        streams: 2,
+        coupled_streams: 1,
+        mapping: &[0, 1, 2],
+    };
+    let mut ms_encoder = MSEncoder::new(sr, Application::Audio, mapping).expect("ms encoder");
+    ms_encoder
+        .set_max_bandwidth(Bandwidth::Wideband)
+        .expect("max bandwidth");
+    let mut ms_decoder = MSDecoder::new(sr, mapping).expect("ms decoder");
+    let mut packet = vec![0u8; max_multistream_packet_size(2, Duration::from_millis(20))];
+    let pcm: Vec<i16> = (0..3 * 960).map(|i| ((i * 37) % 2000) as i16).collect();
+    let len = ms_encoder
+        .encode(&pcm, 960, &mut packet)
+        .expect("ms encode");
+    let mut out = vec![0i16; 3 * 960];
+    ms_decoder
+        .decode(&packet[..len], &mut out, 960, false)
+        .expect("ms decode");
+    let info = ms_decoder.last_packet_info().expect("ms info");
+    assert_eq!(info.samples, 960);
+    assert_eq!(info.duration, Duration::from_millis(20));
+    assert_eq!(info.stream_channels, [Channels::Stereo, Channels::Mono]);
+    assert!(
+        !info
+            .bandwidth
+            .expect("bandwidth")
+            .is_wider_than(Bandwidth::Wideband)
+    );
+    assert!(!info.concealed);
+
+    ms_decoder
+        .decode(&[], &mut out, 960, false)
+        .expect("ms plc");
+    let info = ms_decoder.last_packet_info().expect("ms plc info");
+    assert!(info.concealed);
+    assert!(info.stream_channels.is_empty());
+    ms_decoder.reset().expect("reset");
+    assert_eq!(ms_decoder.last_packet_info(), None);
+}
+
+#[test]
+fn metering_reports_decoded_frame_levels() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("encoder");
+    let mut decoder = Decoder::new(sr, Channels::Mono).expect("decoder");
+    assert_eq!(decoder.frame_level(), None);
+    decoder.set_metering(true);
+    assert_eq!(decoder.frame_level(), Some(FrameLevel::default()));
+
+    let tone: Vec<f32> = (0..960)
+        .map(|i| 0.5 * (i as f32 * std::f32::consts::TAU / 48.0).sin())
+        .collect();
+    let mut packet = vec![0u8; 1500];
+    let mut pcm = vec![0f32; 960];
+    for _ in 0..5 {
+        let len = encoder.encode_float(&tone, &mut packet).expect("encode");
+        decoder
+            .decode_float(&packet[..len], &mut pcm, false)
+            .expect("decode");
+    }
+    let level = decoder.frame_level().expect("metering");
+    assert_eq!(level, FrameLevel::measure(&pcm));
+    assert!((level.rms_dbfs() + 9.0).abs() < 1.5, "{level:?}");
+    assert!(level.peak >= level.rms);
+
+    decoder.reset().expect("reset");
+    assert_eq!(decoder.frame_level(), Some(FrameLevel::default()));
+    decoder.set_metering(false);
+    assert_eq!(decoder.frame_level(), None);
+}
+
+#[test]
+fn decode_vec_sizes_output_from_packet() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).expect("encoder");
+    let mut decoder = Decoder::new(SampleRate::Hz24000, Channels::Stereo).expect("decoder");
+    assert_eq!(
+        decoder.decode_vec(&[], false),
+        Err(opus_codec::Error::BadArg)
+    );
+
+    for frame in [480, 960, 2880] {
+        let packet = encoder.encode_vec(&vec![0; frame * 2]).expect("encode");
+        assert_eq!(decoder.required_output_len(&packet), Ok(frame));
+        let pcm = decoder.decode_vec(&packet, false).expect("decode");
+        assert_eq!(pcm.len(), frame);
+        let pcm = decoder
+            .decode_float_vec(&packet, false)
+            .expect("decode float");
+        assert_eq!(pcm.len(), frame);
+    }
+    assert_eq!(decoder.decode_vec(&[], false).expect("conceal").len(), 2880);
+}
+
+#[test]
+fn conceal_follows_last_packet_duration() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz16000, Channels::Mono, Application::Voip).expect("encoder");
+    let mut decoder = Decoder::new(SampleRate::Hz16000, Channels::Mono).expect("decoder");
+    let mut out = [0i16; 1920];
+    assert_eq!(decoder.conceal(&mut out), Err(opus_codec::Error::BadArg));
+
+    let packet = encoder.encode_vec(&[0; 640]).expect("encode");
+    decoder.decode_vec(&packet, false).expect("decode");
+    assert_eq!(decoder.conceal(&mut out), Ok(640));
+    assert_eq!(
+        decoder.conceal(&mut out[..320]),
+        Err(opus_codec::Error::BadArg)
+    );
+    assert_eq!(decoder.concealment_stats().concealed, 1);
+
+    assert_eq!(decoder.conceal_ms(7), Err(opus_codec::Error::BadArg));
+    assert_eq!(decoder.conceal_ms(0), Err(opus_codec::Error::BadArg));
+    assert_eq!(decoder.conceal_ms(25).expect("conceal").len(), 400);
+    assert_eq!(decoder.conceal_ms(300).expect("conceal").len(), 4800);
+}
+
+/// `count` 20 ms packets of a mono tone, each carrying in-band FEC for the one before.
+fn fec_packets(sr: SampleRate, count: usize) -> Vec<Vec<u8>> {
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("encoder");
+    encoder.set_inband_fec(true).expect("fec");
+    encoder
+        .set_packet_loss_perc(PacketLossPerc::new(20))
+        .expect("loss");
+    let frame = sr.as_i32() as usize / 50;
+    let tone = opus_codec::testsignal::sine::<i16>(sr, Channels::Mono, 200.0, 0.25, frame * count);
+    tone.chunks_exact(frame)
+        .map(|frame| encoder.encode_vec(frame).expect("encode"))
+        .collect()
+}
+
+#[test]
+fn recover_with_fec_reports_recovered_samples() {
+    let sr = SampleRate::Hz16000;
+    let packets = fec_packets(sr, 6);
+    assert!(opus_codec::packet_has_lbrr(&packets[5]).expect("lbrr"));
+
+    let mut decoder = Decoder::new(sr, Channels::Mono).expect("decoder");
+    for packet in &packets[..4] {
+        decoder.decode_vec(packet, false).expect("decode");
+    }
+    let mut out = vec![0i16; 640];
+    let recovery = decoder
+        .recover_with_fec(&packets[5], 640, &mut out)
+        .expect("recover");
+    assert_eq!(
+        recovery,
+        FecRecovery {
+            samples: 640,
+            fec_samples: 320
+        }
+    );
+    assert_eq!(decoder.concealment_stats().fec_recovered, 1);
+
+    let recovery = decoder
+        .recover_with_fec(&packets[5], 160, &mut out)
+        .expect("conceal");
+    assert_eq!(recovery.fec_samples, 0);
+    assert_eq!(
+        decoder.recover_with_fec(&packets[5], 100, &mut out),
+        Err(opus_codec::Error::BadArg)
+    );
+    assert_eq!(
+        decoder.recover_with_fec(&packets[5], 640, &mut out[..320]),
+        Err(opus_codec::Error::BadArg)
+    );
+}
+
+#[test]
+fn last_bandwidth_reads_decoder_state() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Audio).expect("encoder");
+    encoder
+        .set_max_bandwidth(Bandwidth::Wideband)
+        .expect("max bandwidth");
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).expect("decoder");
+    assert_eq!(decoder.last_bandwidth().expect("bandwidth"), None);
+
+    let packet = encoder.encode_vec(&[0; 960]).expect("encode");
+    decoder.decode_vec(&packet, false).expect("decode");
+    let bandwidth = decoder.last_bandwidth().expect("bandwidth");
+    assert_eq!(bandwidth, Some(packet_bandwidth(&packet).expect("toc")));
+    decoder.conceal_ms(20).expect("conceal");
+    assert_eq!(decoder.last_bandwidth().expect("bandwidth"), bandwidth);
+}
+
+#[test]
+fn stream_decoder_fills_gaps_in_sequence() {
+    let sr = SampleRate::Hz16000;
+    let packets = fec_packets(sr, 12);
+
+    let mut stream = StreamDecoder::new(Decoder::new(sr, Channels::Mono).expect("decoder"));
+    let mut pcm = Vec::new();
+    assert_eq!(stream.latency(), Duration::ZERO);
+    assert_eq!(stream.push(65_533, None, &mut pcm), Ok(0));
+    let seq = |i: u16| 65_534u16.wrapping_add(i);
+    for i in 0..4 {
+        let packet = Some(packets[usize::from(i)].as_slice());
+        assert_eq!(stream.push(seq(i), packet, &mut pcm), Ok(320));
+    }
+    // Packets 4 and 5 lost: 4 is concealed, 5 recovered from the FEC in 6.
+    assert_eq!(stream.push(seq(6), Some(&packets[6]), &mut pcm), Ok(960));
+    let stats = stream.decoder().concealment_stats();
+    assert_eq!((stats.concealed, stats.fec_recovered), (1, 1));
+    // Duplicate and late packets are dropped.
+    assert_eq!(stream.push(seq(6), Some(&packets[6]), &mut pcm), Ok(0));
+    assert_eq!(stream.push(seq(5), Some(&packets[5]), &mut pcm), Ok(0));
+    // Packet 7 missed its deadline: concealed at once, FEC no longer applies.
+    assert_eq!(stream.push(seq(7), None, &mut pcm), Ok(320));
+    assert_eq!(stream.push(seq(8), Some(&packets[8]), &mut pcm), Ok(320));
+    assert_eq!(pcm.len(), 320 * 9);
+
+    stream.set_max_concealed(1);
+    assert_eq!(stream.push(seq(11), Some(&packets[11]), &mut pcm), Ok(320));
+    // The restart skipped the gap but kept the counts from before it.
+    let stats = stream.into_inner().concealment_stats();
+    assert_eq!((stats.concealed, stats.fec_recovered), (2, 1));
+}
+
+#[test]
+fn gain_in_decibels_round_trips_through_q8() {
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Stereo).expect("decoder");
+    decoder.set_gain_db(-6.0).expect("set gain");
+    assert_eq!(decoder.gain().expect("gain"), -1_536);
+    assert!((decoder.gain_db().expect("gain db") + 6.0).abs() < f32::EPSILON);
+    assert_eq!(decoder.set_gain_db(200.0), Err(opus_codec::Error::BadArg));
+    assert_eq!(decoder.gain().expect("gain"), -1_536);
+
+    let mapping = Mapping {
+        channels: 2,
+        streams: 1,
+        coupled_streams: 1,
+        mapping: &[0, 1],
+    };
+    let mut ms = MSDecoder::new(SampleRate::Hz48000, mapping).expect("ms decoder");
+    ms.set_gain_db(2.5).expect("set gain");
+    assert_eq!(
+        ms.gain().expect("gain"),
+        i32::from(Q8Gain::new(640).value())
+    );
+    assert!((ms.gain_db().expect("gain db") - 2.5).abs() < f32::EPSILON);
+}
+
+#[test]
+fn decode_float_processed_scales_then_clips() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("encoder");
+    let tone: Vec<f32> = (0..960)
+        .map(|i| 0.9 * (i as f32 * std::f32::consts::TAU / 96.0).sin())
+        .collect();
+    let packets: Vec<Vec<u8>> = (0..4)
+        .map(|_| encoder.encode_float_vec(&tone).expect("encode"))
+        .collect();
+
+    let mut plain = Decoder::new(sr, Channels::Mono).expect("decoder");
+    let mut processed = Decoder::new(sr, Channels::Mono).expect("decoder");
+    let mut clipped = Decoder::new(sr, Channels::Mono).expect("decoder");
+    clipped.set_soft_clip(true);
+    let boost = DecodeProcessing {
+        gain: 2.0,
+        ..DecodeProcessing::default()
+    };
+    let limit = DecodeProcessing {
+        soft_clip: true,
+        ..boost
+    };
+    let mut expected = vec![0f32; 960];
+    let mut out = vec![0f32; 960];
+    for packet in &packets {
+        plain
+            .decode_float(packet, &mut expected, false)
+            .expect("decode");
+        processed
+            .decode_float_processed(packet, &mut out, &boost)
+            .expect("decode");
+        assert!(out.iter().zip(&expected).all(|(o, e)| *o == e * 2.0));
+        assert!(out.iter().any(|s| s.abs() > 1.0));
+
+        clipped
+            .decode_float_processed(packet, &mut out, &limit)
+            .expect("decode");
+        assert!(out.iter().all(|s| s.abs() <= 1.0));
+    }
+    assert!(clipped.soft_clip());
+
+    // With soft clipping enabled, boosted and plain frames share one clip state.
+    let mut plain = Decoder::new(sr, Channels::Mono).expect("decoder");
+    let mut mixed = Decoder::new(sr, Channels::Mono).expect("decoder");
+    mixed.set_soft_clip(true);
+    let mut mem = [0f32; 1];
+    for (i, packet) in packets.iter().enumerate() {
+        plain
+            .decode_float(packet, &mut expected, false)
+            .expect("decode");
+        if i % 2 == 0 {
+            expected.iter_mut().for_each(|s| *s *= 2.0);
+            mixed
+                .decode_float_processed(packet, &mut out, &boost)
+                .expect("decode");
+        } else {
+            mixed.decode_float(packet, &mut out, false).expect("decode");
+        }
+        opus_codec::packet::soft_clip(&mut expected, 960, 1, &mut mem).expect("clip");
+        assert_eq!(out, expected, "packet {i}");
+    }
+}
+
+#[test]
+fn decoder_follows_packet_layout() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("encoder");
+    let pcm = opus_codec::testsignal::pink_noise::<i16>(Channels::Stereo, 0.3, 960 * 30, 3);
+    let mut decoder = Decoder::new(sr, Channels::Stereo).expect("decoder");
+    let mut mono_out = Decoder::new(sr, Channels::Mono).expect("decoder");
+    let (mut out, mut mono_pcm) = (vec![0i16; 1920], vec![0f32; 960]);
+    let mut layouts = Vec::new();
+    for (i, frame) in pcm.chunks_exact(1920).enumerate() {
+        // A stereo encoder sends mono packets at low bitrates.
+        let bitrate = if (10..20).contains(&i) {
+            12_000
+        } else {
+            64_000
+        };
+        encoder
+            .set_bitrate(opus_codec::Bitrate::Custom(bitrate))
+            .expect("set bitrate");
+        let packet = encoder.encode_vec(frame).expect("encode");
+        assert_eq!(decoder.decode(&packet, &mut out, false), Ok(960));
+        assert_eq!(
+            mono_out.decode_float(&packet, &mut mono_pcm, false),
+            Ok(960)
+        );
+        let info = decoder.last_packet_info().expect("decoded");
+        layouts.push(info.coded_channels());
+    }
+    assert_eq!(layouts[0], Some(Channels::Stereo));
+    assert_eq!(layouts[15], Some(Channels::Mono));
+    assert_eq!(layouts[29], Some(Channels::Stereo));
+
+    assert_eq!(decoder.decode(&[], &mut out, false), Ok(960));
+    let info = decoder.last_packet_info().expect("concealed");
+    assert_eq!(info.coded_channels(), None);
+}
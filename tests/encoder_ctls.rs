@@ -1,6 +1,10 @@
 use opus_codec::{
-    Application, Bandwidth, Bitrate, Channels, Complexity, Encoder, SampleRate, Signal,
+    Application, Bandwidth, Bitrate, Channels, CodecMode, Complexity, Decoder, EncodeOverrides,
+    EncodeResult, Encoder, EncoderBuildError, EncoderSettings, Error, ExpertFrameDuration,
+    FrameSize, InbandFec, MSEncoder, Mapping, PacketLossPerc, Profile, SampleRate, Signal,
 };
+use std::mem::MaybeUninit;
+use std::time::Duration;
 
 #[test]
 fn encoder_control_roundtrip() {
@@ -31,9 +35,27 @@ fn encoder_control_roundtrip() {
 
     encoder.set_inband_fec(true).expect("enable fec");
     assert!(encoder.inband_fec().expect("get fec"));
+    assert_eq!(encoder.inband_fec_mode().unwrap(), InbandFec::On);
+    encoder
+        .set_inband_fec_mode(InbandFec::OnNoBitrateAdjust)
+        .expect("fec without bitrate adjustment");
+    assert_eq!(
+        encoder.inband_fec_mode().unwrap(),
+        InbandFec::OnNoBitrateAdjust
+    );
+    assert!(encoder.inband_fec().unwrap());
 
-    encoder.set_packet_loss_perc(15).expect("set packet loss");
-    assert_eq!(encoder.packet_loss_perc().expect("get packet loss"), 15);
+    encoder
+        .set_packet_loss_perc(PacketLossPerc::new(15))
+        .expect("set packet loss");
+    let loss = encoder.packet_loss_perc().expect("get packet loss");
+    assert_eq!(loss.value(), 15);
+    assert_eq!(loss.to_string(), "15%");
+    assert_eq!(PacketLossPerc::from(250), PacketLossPerc::new(100));
+    assert_eq!(
+        PacketLossPerc::from_fraction(0.126),
+        PacketLossPerc::new(13)
+    );
 
     encoder.set_signal(Signal::Music).expect("set signal");
     assert_eq!(encoder.signal().expect("get signal"), Signal::Music);
@@ -59,3 +81,985 @@ fn encoder_control_roundtrip() {
         .expect("clear force channels");
     assert_eq!(encoder.force_channels().expect("get forced channels"), None);
 }
+
+#[test]
+fn encoder_high_pass_prefilter() {
+    let sr = SampleRate::Hz16000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("create encoder");
+    assert_eq!(encoder.high_pass(), None);
+    assert!(encoder.set_high_pass(Some(9_000.0)).is_err());
+
+    encoder.set_high_pass(Some(80.0)).expect("enable high-pass");
+    assert_eq!(encoder.high_pass(), Some(80.0));
+
+    let offset = vec![4_000i16; 320];
+    let mut packet = [0u8; 1275];
+    for _ in 0..5 {
+        assert!(encoder.encode(&offset, &mut packet).expect("encode i16") > 0);
+    }
+    let offset = vec![0.1f32; 320];
+    assert!(
+        encoder
+            .encode_float(&offset, &mut packet)
+            .expect("encode f32")
+            > 0
+    );
+    encoder.reset().expect("reset");
+
+    encoder.set_high_pass(None).expect("disable high-pass");
+    assert_eq!(encoder.high_pass(), None);
+}
+
+#[test]
+fn encoder_input_gain_scales_and_soft_clips() {
+    let sr = SampleRate::Hz48000;
+    let tone = opus_codec::testsignal::sine::<f32>(sr, Channels::Mono, 440.0, 0.1, 960 * 25);
+    // Peak of the decoded output after the encoder has settled.
+    let peak = |gain: Option<f32>| {
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("create");
+        encoder
+            .set_bitrate(Bitrate::Custom(96_000))
+            .expect("bitrate");
+        encoder.set_input_gain(gain).expect("set gain");
+        assert_eq!(encoder.input_gain(), gain);
+        let mut decoder = Decoder::new(sr, Channels::Mono).expect("create decoder");
+        let mut packet = [0u8; 1275];
+        let mut out = [0f32; 960];
+        let mut peak = 0f32;
+        for (i, frame) in tone.chunks_exact(960).enumerate() {
+            let len = encoder.encode_float(frame, &mut packet).expect("encode");
+            let n = decoder
+                .decode_float(&packet[..len], &mut out, false)
+                .expect("decode");
+            if i >= 5 {
+                peak = out[..n].iter().fold(peak, |m, s| m.max(s.abs()));
+            }
+        }
+        peak
+    };
+    let unity = peak(None);
+    let boosted = peak(Some(12.0));
+    assert!((boosted / unity - 3.98).abs() < 0.3, "{unity} -> {boosted}");
+    // +26 dB drives the 0.1 sine to 2.0; the soft clipper keeps it within full scale.
+    assert!(peak(Some(26.0)) < 1.05);
+
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("create");
+    assert!(encoder.set_input_gain(Some(f32::NAN)).is_err());
+    encoder.set_high_pass(Some(80.0)).expect("enable high-pass");
+    encoder.set_input_gain(Some(-6.0)).expect("set gain");
+    encoder.set_high_pass(None).expect("disable high-pass");
+    assert_eq!(encoder.input_gain(), Some(-6.0));
+    let mut packet = [0u8; 1275];
+    assert!(
+        encoder
+            .encode(&[1_000i16; 960], &mut packet)
+            .expect("encode i16")
+            > 0
+    );
+    encoder.set_input_gain(None).expect("disable gain");
+    assert_eq!(encoder.input_gain(), None);
+}
+
+#[test]
+fn encoder_latency_matches_lookahead() {
+    for sr in [SampleRate::Hz16000, SampleRate::Hz48000] {
+        let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+        let lookahead = encoder.lookahead().expect("lookahead");
+        let latency = encoder.latency().expect("latency");
+        // 2.5 ms CELT overlap plus 4 ms delay compensation.
+        assert_eq!(latency, Duration::from_micros(6_500), "{sr:?}");
+        assert_eq!(latency, sr.duration_of(lookahead as usize));
+    }
+    let decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).expect("create decoder");
+    assert_eq!(decoder.latency(), Duration::ZERO);
+}
+
+#[test]
+fn encoder_bitrate_stats_track_target() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("create");
+    assert_eq!(encoder.bitrate_stats().expect("stats"), None);
+    assert!(encoder.set_bitrate_stats(Some(Duration::ZERO)).is_err());
+
+    encoder
+        .set_bitrate(Bitrate::Custom(32_000))
+        .expect("set bitrate");
+    encoder.set_vbr(false).expect("cbr");
+    encoder
+        .set_bitrate_stats(Some(Duration::from_secs(1)))
+        .expect("enable stats");
+    let pcm = opus_codec::testsignal::sine::<i16>(sr, Channels::Mono, 440.0, 0.5, 960 * 100);
+    let mut packet = [0u8; 1275];
+    for frame in pcm.chunks_exact(960) {
+        encoder.encode(frame, &mut packet).expect("encode");
+    }
+
+    let stats = encoder.bitrate_stats().expect("stats").expect("enabled");
+    assert_eq!(stats.packets, 50);
+    assert_eq!(stats.duration, Duration::from_secs(1));
+    assert_eq!(stats.target_bps, 32_000);
+    assert!(
+        stats.deviation_ratio().expect("target").abs() < 0.05,
+        "{stats:?}"
+    );
+    assert!(stats.peak_bps >= stats.average_bps);
+
+    encoder.reset().expect("reset");
+    assert_eq!(encoder.bitrate_stats().expect("stats"), None);
+    // The window is part of the encoder stats, whose totals survive the reset.
+    assert_eq!(encoder.stats().expect("enabled").frames(), 100);
+    encoder.set_bitrate_stats(None).expect("disable window");
+    assert!(encoder.stats().expect("enabled").window().is_none());
+}
+
+#[test]
+fn encoder_size_histogram() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("create");
+    assert!(encoder.stats().is_none());
+    encoder.set_stats(true);
+    let pcm = opus_codec::testsignal::pink_noise::<f32>(Channels::Mono, 0.3, 960 * 50, 3);
+    let mut packet = [0u8; 1275];
+    let mut largest = 0;
+    for frame in pcm.chunks_exact(960) {
+        largest = largest.max(encoder.encode_float(frame, &mut packet).expect("encode"));
+    }
+    let histogram = encoder.stats().expect("enabled").sizes();
+    assert_eq!(histogram.count(), 50);
+    assert_eq!(histogram.max(), Some(largest));
+    let (p50, p99) = (histogram.p50().expect("p50"), histogram.p99().expect("p99"));
+    assert!(p50 > 0 && p50 <= p99 && p99 <= largest);
+
+    encoder.set_stats(false);
+    assert!(encoder.stats().is_none());
+}
+
+#[test]
+fn encoder_stats_count_frames_bytes_and_dtx() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("create");
+    encoder
+        .set_bitrate(Bitrate::Custom(32_000))
+        .expect("bitrate");
+    encoder.set_dtx(true).expect("dtx");
+    assert!(encoder.stats().is_none());
+    encoder.set_stats(true);
+    let tone = opus_codec::testsignal::sine::<i16>(sr, Channels::Mono, 440.0, 0.5, 960);
+    let mut packet = [0u8; 1275];
+    let mut total = 0;
+    for frame in 0..50 {
+        let input = if frame < 25 { &tone[..] } else { &[0; 960][..] };
+        total += encoder.encode(input, &mut packet).expect("encode");
+    }
+
+    let stats = encoder.stats().expect("enabled");
+    assert_eq!(stats.frames(), 50);
+    assert_eq!(stats.bytes(), total as u64);
+    assert_eq!(stats.duration(), Duration::from_secs(1));
+    assert_eq!(stats.average_bitrate(), Some(total as f64 * 8.0));
+    assert!(stats.dtx_frames() > 0 && stats.dtx_frames() < 25);
+    assert_eq!(stats.sizes().count(), 50);
+    assert_eq!(stats.sizes().min(), Some(1));
+
+    encoder.reset().expect("reset");
+    assert_eq!(encoder.stats().expect("enabled").frames(), 50);
+    encoder.reset_stats();
+    let stats = encoder.stats().expect("enabled");
+    assert_eq!(stats.frames(), 0);
+    assert_eq!(stats.average_bitrate(), None);
+}
+
+#[cfg(feature = "timing")]
+#[test]
+fn encode_decode_timing_stats() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut decoder = Decoder::new(sr, Channels::Stereo).expect("create decoder");
+    assert!(encoder.timing_stats().is_none());
+    encoder
+        .set_complexity(Complexity::new(10))
+        .expect("complexity");
+
+    let pcm = opus_codec::testsignal::white_noise::<i16>(Channels::Stereo, 0.5, 960 * 20, 1);
+    let mut packet = [0u8; 1275];
+    let mut out = [0i16; 1920];
+    for frame in pcm.chunks_exact(1920) {
+        let len = encoder.encode(frame, &mut packet).expect("encode");
+        decoder
+            .decode(&packet[..len], &mut out, false)
+            .expect("decode");
+    }
+
+    for stats in [encoder.timing_stats(), decoder.timing_stats()] {
+        let stats = stats.expect("recorded");
+        assert_eq!(stats.calls, 20);
+        assert!(stats.min <= stats.avg && stats.avg <= stats.max);
+        assert!(stats.min <= stats.p99 && stats.p99 <= stats.max);
+    }
+    encoder.reset().expect("reset");
+    assert!(encoder.timing_stats().is_none());
+}
+
+#[test]
+fn encode_i32_matches_float_path_at_24_bits() {
+    let sr = SampleRate::Hz48000;
+    let pcm = opus_codec::testsignal::sine::<i16>(sr, Channels::Stereo, 440.0, 0.5, 960 * 5);
+    let wide: Vec<i32> = pcm.iter().map(|&s| i32::from(s) << 16).collect();
+    let float: Vec<f32> = pcm.iter().map(|&s| f32::from(s) / 32768.0).collect();
+
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut reference = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    reference.set_lsb_depth(24).expect("lsb depth");
+    let mut packet = [0u8; 1275];
+    let mut expected = [0u8; 1275];
+    for (frame, float_frame) in wide.chunks_exact(1920).zip(float.chunks_exact(1920)) {
+        let len = encoder.encode_i32(frame, &mut packet).expect("encode_i32");
+        let expected_len = reference
+            .encode_float(float_frame, &mut expected)
+            .expect("encode_float");
+        assert_eq!(packet[..len], expected[..expected_len]);
+    }
+    assert_eq!(encoder.lsb_depth().expect("lsb depth"), 24);
+
+    // An explicit depth is kept.
+    let mut explicit = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    explicit.set_lsb_depth(16).expect("lsb depth");
+    explicit
+        .encode_i32(&wide[..1920], &mut packet)
+        .expect("encode_i32");
+    assert_eq!(explicit.lsb_depth().expect("lsb depth"), 16);
+}
+
+#[test]
+fn planar_input_matches_interleaved() {
+    let sr = SampleRate::Hz48000;
+    let pcm = opus_codec::testsignal::sine::<f32>(sr, Channels::Stereo, 440.0, 0.5, 960);
+    let left: Vec<f32> = pcm.iter().step_by(2).copied().collect();
+    let right: Vec<f32> = pcm.iter().skip(1).step_by(2).copied().collect();
+
+    let mut planar = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut interleaved = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut packet = [0u8; 1275];
+    let mut expected = [0u8; 1275];
+    let len = planar
+        .encode_float_planar(&[&left, &right], &mut packet)
+        .expect("encode planar");
+    let expected_len = interleaved
+        .encode_float(&pcm, &mut expected)
+        .expect("encode interleaved");
+    assert_eq!(packet[..len], expected[..expected_len]);
+
+    let silence = [0i16; 960];
+    assert!(
+        planar
+            .encode_planar(&[&silence, &silence], &mut packet)
+            .is_ok()
+    );
+    assert_eq!(
+        planar.encode_planar(&[&silence], &mut packet),
+        Err(Error::BadArg)
+    );
+    assert_eq!(
+        planar.encode_planar(&[&silence, &silence[..480]], &mut packet),
+        Err(Error::BadArg)
+    );
+}
+
+#[test]
+fn encode_frame_checks_exact_duration() {
+    let sr = SampleRate::Hz16000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Voip).expect("create");
+    let mut packet = [0u8; 1275];
+    assert!(
+        encoder
+            .encode_frame(&[0; 2 * 320], FrameSize::Ms20, &mut packet)
+            .is_ok()
+    );
+    // 10 ms is a legal Opus frame, but not the 20 ms the caller meant to send.
+    assert_eq!(
+        encoder.encode_frame(&[0; 2 * 160], FrameSize::Ms20, &mut packet),
+        Err(Error::FrameLengthMismatch {
+            expected: 2 * 320,
+            actual: 2 * 160
+        })
+    );
+    let err = encoder
+        .encode_float_frame(&[0.0; 320], FrameSize::Ms20, &mut packet)
+        .unwrap_err();
+    assert_eq!(err.kind(), Error::BadArg);
+    assert_eq!(
+        err.to_string(),
+        "Frame holds 320 interleaved samples, 640 expected for its duration"
+    );
+    assert!(
+        encoder
+            .encode_float_frame(&[0.0; 2 * 40], FrameSize::Ms2_5, &mut packet)
+            .is_ok()
+    );
+}
+
+#[test]
+fn encode_s24_matches_i32_path() {
+    let sr = SampleRate::Hz48000;
+    let pcm: Vec<i32> = (0..960)
+        .map(|i| (i * 7_919 % 16_000_000) - 8_000_000)
+        .collect();
+    let packed: Vec<u8> = pcm
+        .iter()
+        .flat_map(|&s| s.to_le_bytes().into_iter().take(3))
+        .collect();
+    let wide: Vec<i32> = pcm.iter().map(|&s| s << 8).collect();
+
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("create");
+    let mut reference = Encoder::new(sr, Channels::Mono, Application::Audio).expect("create");
+    let mut packet = [0u8; 1275];
+    let mut expected = [0u8; 1275];
+    let len = encoder
+        .encode_s24(&packed, &mut packet)
+        .expect("encode_s24");
+    let expected_len = reference
+        .encode_i32(&wide, &mut expected)
+        .expect("encode_i32");
+    assert_eq!(packet[..len], expected[..expected_len]);
+    assert_eq!(encoder.lsb_depth().expect("lsb depth"), 24);
+    assert_eq!(
+        encoder.encode_s24(&packed[..4], &mut packet),
+        Err(Error::BadArg)
+    );
+}
+
+#[cfg(feature = "ctl-audit")]
+#[test]
+fn ctl_audit_records_mutations() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).expect("create");
+    assert!(encoder.ctl_audit().is_empty());
+    encoder
+        .set_bitrate(Bitrate::Custom(24_000))
+        .expect("bitrate");
+    encoder.set_dtx(true).expect("dtx");
+    encoder
+        .set_bitrate(Bitrate::Custom(6_000))
+        .expect("bitrate");
+    assert!(encoder.set_bandwidth(Bandwidth::Fullband).is_ok());
+    assert!(encoder.bitrate().is_ok());
+
+    let log = encoder.ctl_audit();
+    assert_eq!(log.len(), 4);
+    let last = log.last("OPUS_SET_BITRATE").expect("bitrate entry");
+    assert_eq!(last.value, 6_000);
+    assert!(last.succeeded());
+    let names: Vec<_> = log.entries().map(|e| e.request_name()).collect();
+    assert_eq!(
+        names,
+        [
+            "OPUS_SET_BITRATE",
+            "OPUS_SET_DTX",
+            "OPUS_SET_BITRATE",
+            "OPUS_SET_BANDWIDTH"
+        ]
+    );
+
+    let mut decoder = Decoder::new(sr, Channels::Mono).expect("create decoder");
+    assert!(decoder.set_gain(40_000).is_err());
+    let entry = decoder
+        .ctl_audit()
+        .last("OPUS_SET_GAIN")
+        .expect("gain entry");
+    assert!(!entry.succeeded());
+    assert_eq!(entry.value, 40_000);
+}
+
+#[test]
+fn strided_encode_matches_gathered_input() {
+    let sr = SampleRate::Hz48000;
+    let stereo = opus_codec::testsignal::sine::<f32>(sr, Channels::Stereo, 330.0, 0.4, 960 * 3);
+    // An 8-channel capture buffer carrying the stereo pair on channels 5 and 6.
+    let mut device = vec![0.9f32; 8 * 960 * 3];
+    for (frame, pair) in device.chunks_exact_mut(8).zip(stereo.chunks_exact(2)) {
+        frame[5..7].copy_from_slice(pair);
+    }
+    let device_i16: Vec<i16> = device.iter().map(|&s| (s * 32_767.0) as i16).collect();
+    let stereo_i16: Vec<i16> = stereo.iter().map(|&s| (s * 32_767.0) as i16).collect();
+
+    let mut strided = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut contiguous = Encoder::new(sr, Channels::Stereo, Application::Audio).expect("create");
+    let mut a = [0u8; 1275];
+    let mut b = [0u8; 1275];
+    for (block, pcm) in device.chunks_exact(8 * 960).zip(stereo.chunks_exact(1920)) {
+        let len = strided
+            .encode_float_strided(block, 8, 5, &mut a)
+            .expect("strided encode");
+        let expected = contiguous.encode_float(pcm, &mut b).expect("encode");
+        assert_eq!(a[..len], b[..expected]);
+    }
+    for (block, pcm) in device_i16
+        .chunks_exact(8 * 960)
+        .zip(stereo_i16.chunks_exact(1920))
+    {
+        let len = strided
+            .encode_strided(block, 8, 5, &mut a)
+            .expect("strided encode");
+        let expected = contiguous.encode(pcm, &mut b).expect("encode");
+        assert_eq!(a[..len], b[..expected]);
+    }
+
+    assert!(strided.encode_float_strided(&device, 8, 7, &mut a).is_err());
+    // 961 frames is not a valid Opus frame size.
+    assert!(
+        strided
+            .encode_float_strided(&device[..8 * 961], 8, 0, &mut a)
+            .is_err()
+    );
+}
+
+#[test]
+fn encoder_limits_bandwidth_to_input_rate() {
+    let rates = [
+        (SampleRate::Hz8000, Bandwidth::Narrowband),
+        (SampleRate::Hz12000, Bandwidth::Mediumband),
+        (SampleRate::Hz16000, Bandwidth::Wideband),
+        (SampleRate::Hz24000, Bandwidth::SuperWideband),
+        (SampleRate::Hz48000, Bandwidth::Fullband),
+    ];
+    for (sr, widest) in rates {
+        assert_eq!(Bandwidth::for_rate(sr), widest);
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).expect("create");
+        assert_eq!(encoder.max_bandwidth().expect("max bandwidth"), widest);
+        encoder
+            .set_bandwidth(widest)
+            .expect("representable bandwidth");
+        encoder.reset().expect("reset");
+        assert_eq!(encoder.max_bandwidth().expect("max bandwidth"), widest);
+    }
+
+    let mut encoder =
+        Encoder::new(SampleRate::Hz16000, Channels::Mono, Application::Voip).expect("create");
+    assert!(Bandwidth::SuperWideband.is_wider_than(Bandwidth::Wideband));
+    assert_eq!(
+        encoder.set_bandwidth(Bandwidth::SuperWideband),
+        Err(opus_codec::Error::BadArg)
+    );
+    encoder
+        .set_bandwidth(Bandwidth::Narrowband)
+        .expect("narrower bandwidth");
+}
+
+#[test]
+fn range_chains_match_end_to_end() {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+    encoder.set_dtx(true).unwrap();
+    encoder.set_range_chain(true);
+    decoder.set_range_chain(true);
+
+    let tone: Vec<i16> = (0..960)
+        .map(|i| if i % 48 < 24 { 6_000 } else { -6_000 })
+        .collect();
+    let silence = vec![0i16; 960];
+    let mut packets = Vec::new();
+    let mut buf = [0u8; 1500];
+    for frame in 0..40 {
+        let input = if frame < 20 { &tone } else { &silence };
+        let len = encoder.encode(input, &mut buf).unwrap();
+        packets.push(buf[..len].to_vec());
+    }
+    let mut pcm = vec![0i16; 960];
+    for packet in &packets {
+        decoder.decode(packet, &mut pcm, false).unwrap();
+    }
+    let sent = encoder.range_chain().unwrap();
+    assert_eq!(sent.packets(), 40);
+    assert_eq!(decoder.range_chain(), Some(sent));
+
+    // Concealment is not chained, so a lost packet leaves the chains apart.
+    decoder.set_range_chain(true);
+    for (i, packet) in packets.iter().enumerate() {
+        let input: &[u8] = if i == 5 { &[] } else { packet };
+        decoder.decode(input, &mut pcm, false).unwrap();
+    }
+    let received = decoder.range_chain().unwrap();
+    assert_eq!(received.packets(), 39);
+    assert_ne!(received.digest(), sent.digest());
+
+    encoder.set_range_chain(false);
+    assert_eq!(encoder.range_chain(), None);
+}
+
+#[test]
+fn variable_frame_sizes_follow_expert_duration() {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+    assert_eq!(
+        encoder.expert_frame_duration().unwrap(),
+        ExpertFrameDuration::FromArgument
+    );
+    let mut packet = [0u8; 1275];
+    for frame in [480, 960, 480, 960] {
+        let len = encoder.encode(&vec![0; frame], &mut packet).unwrap();
+        assert_eq!(
+            opus_codec::packet_nb_samples(&packet[..len], SampleRate::Hz48000).unwrap(),
+            frame
+        );
+    }
+
+    encoder
+        .set_expert_frame_duration(ExpertFrameDuration::Ms20)
+        .unwrap();
+    assert_eq!(
+        ExpertFrameDuration::Ms20.samples(SampleRate::Hz48000),
+        Some(960)
+    );
+    assert_eq!(
+        encoder.encode(&[0; 480], &mut packet),
+        Err(Error::InvalidFrameSize { samples: 480 })
+    );
+    assert_eq!(
+        encoder.encode(&[0; 1920], &mut packet),
+        Err(Error::InvalidFrameSize { samples: 1920 })
+    );
+    assert!(encoder.encode(&[0; 960], &mut packet).is_ok());
+
+    encoder
+        .set_expert_frame_duration(ExpertFrameDuration::FromArgument)
+        .unwrap();
+    assert_eq!(
+        encoder.expert_frame_duration().unwrap(),
+        ExpertFrameDuration::FromArgument
+    );
+    assert!(encoder.encode(&[0; 480], &mut packet).is_ok());
+}
+
+#[test]
+fn builder_applies_settings_and_names_failures() {
+    let mut encoder = Encoder::builder(SampleRate::Hz48000, Channels::Stereo, Application::Audio)
+        .bitrate(Bitrate::Custom(48_000))
+        .complexity(Complexity::new(3))
+        .vbr(false)
+        .inband_fec(InbandFec::On)
+        .packet_loss_perc(PacketLossPerc::new(10))
+        .dtx(true)
+        .signal(Signal::Music)
+        .max_bandwidth(Bandwidth::Wideband)
+        .lsb_depth(16)
+        .expert_frame_duration(ExpertFrameDuration::Ms10)
+        .build()
+        .expect("build encoder");
+    assert_eq!(encoder.bitrate().unwrap(), Bitrate::Custom(48_000));
+    assert_eq!(encoder.complexity().unwrap().value(), 3);
+    assert!(!encoder.vbr().unwrap());
+    assert_eq!(encoder.inband_fec_mode().unwrap(), InbandFec::On);
+    assert_eq!(encoder.packet_loss_perc().unwrap().value(), 10);
+    assert!(encoder.dtx().unwrap());
+    assert_eq!(encoder.signal().unwrap(), Signal::Music);
+    assert_eq!(encoder.max_bandwidth().unwrap(), Bandwidth::Wideband);
+    assert_eq!(encoder.lsb_depth().unwrap(), 16);
+    assert_eq!(
+        encoder.expert_frame_duration().unwrap(),
+        ExpertFrameDuration::Ms10
+    );
+
+    let err: EncoderBuildError =
+        Encoder::builder(SampleRate::Hz48000, Channels::Mono, Application::Voip)
+            .complexity(Complexity::new(5))
+            .lsb_depth(4)
+            .build()
+            .err()
+            .expect("lsb depth 4 is rejected");
+    assert_eq!(err.setting(), "lsb_depth");
+    assert_eq!(*err.error(), Error::BadArg);
+    assert!(err.to_string().contains("lsb_depth"));
+    assert_eq!(Error::from(err), Error::BadArg);
+}
+
+#[test]
+fn encode_vec_sizes_packets_automatically() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::Max).unwrap();
+    encoder.set_vbr(false).unwrap();
+    let mut decoder = Decoder::new(sr, Channels::Stereo).unwrap();
+    let mut pcm = vec![0f32; 2 * 960];
+    let mut out = vec![0f32; 2 * 5760];
+
+    let packet = encoder.encode_vec(&[0; 2 * 960]).unwrap();
+    assert!(!packet.is_empty());
+    assert_eq!(
+        decoder.decode(&packet, &mut vec![0i16; 2 * 960], false),
+        Ok(960)
+    );
+
+    encoder
+        .set_expert_frame_duration(ExpertFrameDuration::Ms120)
+        .unwrap();
+    pcm.resize(2 * 5760, 0.0);
+    for (i, sample) in pcm.iter_mut().enumerate() {
+        *sample = if (i * 7919) % 13 < 6 { 0.9 } else { -0.9 };
+    }
+    let packet = encoder.encode_float_vec(&pcm).unwrap();
+    assert!(packet.len() > 4000);
+    assert_eq!(decoder.decode_float(&packet, &mut out, false), Ok(5760));
+
+    assert_eq!(
+        encoder.encode_float_vec(&pcm[..7]),
+        Err(Error::UnevenChannels {
+            len: 7,
+            channels: 2
+        })
+    );
+}
+
+#[test]
+fn application_switches_before_first_packet_or_after_reset() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+    assert_eq!(encoder.application().unwrap(), Application::Voip);
+    encoder.set_application(Application::Audio).unwrap();
+    assert_eq!(encoder.application().unwrap(), Application::Audio);
+
+    encoder.set_bitrate(Bitrate::Custom(32_000)).unwrap();
+    let mut packet = vec![0u8; 4000];
+    encoder.encode(&[0; 960], &mut packet).unwrap();
+    assert_eq!(
+        encoder.set_application(Application::Voip),
+        Err(Error::BadArg)
+    );
+    encoder.set_application(Application::Audio).unwrap();
+
+    encoder.reset().unwrap();
+    encoder.set_application(Application::Voip).unwrap();
+    assert_eq!(encoder.application().unwrap(), Application::Voip);
+    assert_eq!(encoder.bitrate().unwrap(), Bitrate::Custom(32_000));
+    encoder.encode(&[0; 960], &mut packet).unwrap();
+}
+
+#[test]
+fn batch_encoding_matches_frame_by_frame() {
+    let sr = SampleRate::Hz48000;
+    let pcm: Vec<i16> = (0..960 * 5)
+        .map(|i| ((i * 37) % 2000) as i16 - 1000)
+        .collect();
+    let frames: Vec<&[i16]> = pcm.chunks(960).collect();
+
+    let mut single = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+    let expected: Vec<Vec<u8>> = frames
+        .iter()
+        .map(|frame| single.encode_vec(frame).unwrap())
+        .collect();
+
+    let mut batched = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+    let mut packets = Vec::new();
+    batched.encode_batch(&frames, &mut packets).unwrap();
+    assert_eq!(packets, expected);
+
+    let floats: Vec<f32> = pcm.iter().map(|&s| f32::from(s) / 32768.0).collect();
+    let mut float_frames: Vec<&[f32]> = floats.chunks(960).collect();
+    float_frames.insert(2, &floats[..7]);
+    packets.clear();
+    assert_eq!(
+        batched.encode_float_batch(&float_frames, &mut packets),
+        Err(Error::BadArg)
+    );
+    assert_eq!(packets.len(), 2);
+}
+
+#[test]
+fn profiles_apply_their_settings() {
+    let sr = SampleRate::Hz48000;
+    let mut voip = Encoder::with_profile(sr, Channels::Mono, Profile::VoipLossy).unwrap();
+    assert_eq!(voip.application().unwrap(), Application::Voip);
+    assert_eq!(voip.bitrate().unwrap(), Bitrate::Custom(32_000));
+    assert_eq!(voip.inband_fec_mode().unwrap(), InbandFec::On);
+    assert_eq!(voip.packet_loss_perc().unwrap().value(), 10);
+    assert!(voip.dtx().unwrap());
+    assert_eq!(voip.max_bandwidth().unwrap(), Bandwidth::Wideband);
+
+    let mut narrow =
+        Encoder::with_profile(SampleRate::Hz8000, Channels::Mono, Profile::VoipLowLatency).unwrap();
+    assert_eq!(narrow.max_bandwidth().unwrap(), Bandwidth::Narrowband);
+    let frame = Profile::VoipLowLatency
+        .frame_size()
+        .samples(SampleRate::Hz8000);
+    assert_eq!(frame, 80);
+    assert!(narrow.encode_vec(&vec![0; frame]).is_ok());
+
+    let mut music = Encoder::profile_builder(sr, Channels::Stereo, Profile::MusicArchive)
+        .complexity(Complexity::new(8))
+        .build()
+        .unwrap();
+    assert_eq!(music.application().unwrap(), Application::Audio);
+    assert_eq!(music.bitrate().unwrap(), Bitrate::Custom(192_000));
+    assert_eq!(music.signal().unwrap(), Signal::Music);
+    assert!(!music.vbr_constraint().unwrap());
+    assert_eq!(music.complexity().unwrap().value(), 8);
+    assert_eq!(
+        music.expert_frame_duration().unwrap(),
+        ExpertFrameDuration::Ms20
+    );
+}
+
+#[test]
+fn settings_snapshot_restores_on_new_encoder() {
+    let sr = SampleRate::Hz48000;
+    let mut fresh = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+    let defaults = fresh.settings().unwrap();
+    assert_eq!(defaults.signal, None);
+    assert_eq!(defaults.bandwidth, None);
+
+    let mut encoder = Encoder::profile_builder(sr, Channels::Stereo, Profile::VoipLossy)
+        .bandwidth(Bandwidth::Wideband)
+        .force_channels(Channels::Mono)
+        .lsb_depth(16)
+        .phase_inversion_disabled(true)
+        .build()
+        .unwrap();
+    let settings: EncoderSettings = encoder.settings().unwrap();
+    assert_eq!(settings.signal, Some(Signal::Voice));
+    assert_eq!(settings.bandwidth, Some(Bandwidth::Wideband));
+    assert_eq!(settings.force_channels, Some(Channels::Mono));
+
+    let mut renegotiated = Encoder::new(sr, Channels::Stereo, Application::Voip).unwrap();
+    renegotiated.apply_settings(&settings).unwrap();
+    assert_eq!(renegotiated.settings().unwrap(), settings);
+
+    renegotiated.apply_settings(&defaults).unwrap();
+    assert_eq!(renegotiated.settings().unwrap(), defaults);
+
+    let mut narrow = Encoder::new(SampleRate::Hz8000, Channels::Stereo, Application::Voip).unwrap();
+    assert_eq!(narrow.apply_settings(&settings), Err(Error::BadArg));
+}
+
+#[test]
+fn encode_with_applies_only_changed_overrides() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+    let pcm = opus_codec::testsignal::sine::<f32>(sr, Channels::Mono, 440.0, 0.5, 960);
+    let mut packet = vec![0u8; 1500];
+    let overrides = EncodeOverrides {
+        bitrate: Some(Bitrate::Custom(16_000)),
+        packet_loss_perc: Some(PacketLossPerc::new(10)),
+        inband_fec: Some(InbandFec::On),
+    };
+    for _ in 0..3 {
+        encoder
+            .encode_float_with(&pcm, &mut packet, &overrides)
+            .unwrap();
+    }
+    assert_eq!(encoder.bitrate().unwrap(), Bitrate::Custom(16_000));
+    assert_eq!(encoder.packet_loss_perc().unwrap(), PacketLossPerc::new(10));
+    assert_eq!(encoder.inband_fec_mode().unwrap(), InbandFec::On);
+
+    let lower = EncodeOverrides {
+        bitrate: Some(Bitrate::Custom(12_000)),
+        ..EncodeOverrides::default()
+    };
+    encoder.encode_with(&[0; 960], &mut packet, &lower).unwrap();
+    assert_eq!(encoder.bitrate().unwrap(), Bitrate::Custom(12_000));
+    assert_eq!(encoder.packet_loss_perc().unwrap(), PacketLossPerc::new(10));
+    #[cfg(feature = "ctl-audit")]
+    assert_eq!(encoder.ctl_audit().len(), 4);
+
+    let invalid = EncodeOverrides {
+        bitrate: Some(Bitrate::Custom(0)),
+        ..overrides
+    };
+    assert_eq!(
+        encoder.encode_float_with(&pcm, &mut packet, &invalid),
+        Err(Error::BadArg)
+    );
+}
+
+#[test]
+fn encode_ex_reports_dtx_and_final_range() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+    let mut decoder = Decoder::new(sr, Channels::Mono).unwrap();
+    encoder.set_dtx(true).unwrap();
+    let tone = opus_codec::testsignal::sine::<f32>(sr, Channels::Mono, 440.0, 0.5, 960);
+    let mut packet = [0u8; 1500];
+    let mut pcm = [0f32; 960];
+
+    let result: EncodeResult = encoder.encode_float_ex(&tone, &mut packet).unwrap();
+    assert!(!result.dtx);
+    assert!(result.bytes > 2);
+    decoder
+        .decode_float(&packet[..result.bytes], &mut pcm, false)
+        .unwrap();
+    assert_eq!(decoder.final_range().unwrap(), result.final_range);
+
+    let results: Vec<EncodeResult> = (0..30)
+        .map(|_| encoder.encode_ex(&[0; 960], &mut packet).unwrap())
+        .collect();
+    assert!(results.iter().any(|r| r.dtx));
+    assert!(results.iter().filter(|r| r.dtx).all(|r| r.bytes <= 3));
+    assert!(results.iter().filter(|r| r.bytes == 1).all(|r| r.dtx));
+}
+
+#[test]
+fn force_mode_pins_coding_mode() {
+    let sr = SampleRate::Hz48000;
+    let pcm = opus_codec::testsignal::pink_noise::<f32>(Channels::Mono, 0.3, 960 * 10, 5);
+    let mut packet = [0u8; 1500];
+    for mode in [CodecMode::SilkOnly, CodecMode::Hybrid, CodecMode::CeltOnly] {
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+        encoder.set_bitrate(Bitrate::Custom(48_000)).unwrap();
+        encoder.force_mode(Some(mode)).unwrap();
+        assert_eq!(encoder.forced_mode(), Some(mode));
+        for frame in pcm.chunks_exact(960) {
+            let len = encoder.encode_float(frame, &mut packet).unwrap();
+            assert_eq!(opus_codec::Toc::of(&packet[..len]).unwrap().mode(), mode);
+        }
+    }
+
+    // The bandwidth a mode needs is released when the mode changes or is cleared.
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::Custom(48_000)).unwrap();
+    let mut toc = |encoder: &mut Encoder| {
+        let mut last = None;
+        for frame in pcm.chunks_exact(960) {
+            let len = encoder.encode_float(frame, &mut packet).unwrap();
+            last = Some(opus_codec::Toc::of(&packet[..len]).unwrap());
+        }
+        last.unwrap()
+    };
+    encoder.force_mode(Some(CodecMode::SilkOnly)).unwrap();
+    assert_eq!(toc(&mut encoder).bandwidth(), Bandwidth::Wideband);
+    encoder.force_mode(Some(CodecMode::Hybrid)).unwrap();
+    let hybrid = toc(&mut encoder);
+    assert_eq!(
+        (hybrid.mode(), hybrid.bandwidth()),
+        (CodecMode::Hybrid, Bandwidth::Fullband)
+    );
+    encoder.force_mode(Some(CodecMode::SilkOnly)).unwrap();
+    assert_eq!(toc(&mut encoder).bandwidth(), Bandwidth::Wideband);
+    encoder.force_mode(None).unwrap();
+    assert_eq!(toc(&mut encoder).bandwidth(), Bandwidth::Fullband);
+    assert_eq!(encoder.settings().unwrap().bandwidth, None);
+
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+    encoder.set_bandwidth(Bandwidth::Fullband).unwrap();
+    assert_eq!(
+        encoder.force_mode(Some(CodecMode::SilkOnly)),
+        Err(Error::BadArg)
+    );
+    encoder.force_mode(Some(CodecMode::Hybrid)).unwrap();
+    encoder.force_mode(None).unwrap();
+    assert_eq!(encoder.forced_mode(), None);
+    // A bandwidth the caller forced stays after the mode is cleared.
+    assert_eq!(
+        encoder.settings().unwrap().bandwidth,
+        Some(Bandwidth::Fullband)
+    );
+
+    let mut wideband =
+        Encoder::new(SampleRate::Hz16000, Channels::Mono, Application::Voip).unwrap();
+    assert_eq!(
+        wideband.force_mode(Some(CodecMode::Hybrid)),
+        Err(Error::BadArg)
+    );
+    let mut low_delay = Encoder::new(sr, Channels::Mono, Application::RestrictedLowDelay).unwrap();
+    assert_eq!(
+        low_delay.force_mode(Some(CodecMode::SilkOnly)),
+        Err(Error::BadArg)
+    );
+    low_delay.force_mode(Some(CodecMode::CeltOnly)).unwrap();
+}
+
+#[test]
+fn checked_encode_reports_required_size() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+    let pcm = opus_codec::testsignal::sine::<f32>(sr, Channels::Stereo, 440.0, 0.5, 2_880);
+    let required = encoder.encode_required_size(pcm.len());
+    assert_eq!(
+        required,
+        opus_codec::constants::max_packet_size(Duration::from_millis(60))
+    );
+
+    let mut small = vec![0u8; 200];
+    let err = encoder
+        .encode_float_checked(&pcm, &mut small)
+        .expect_err("buffer below the worst case");
+    assert_eq!(err, Error::OutputTooSmall { required });
+    assert_eq!(err.kind(), Error::BufferTooSmall);
+
+    small.resize(required, 0);
+    assert!(encoder.encode_float_checked(&pcm, &mut small).unwrap() > 0);
+    let mut packet = vec![0u8; encoder.encode_required_size(960 * 2)];
+    assert!(encoder.encode_checked(&[0; 960 * 2], &mut packet).is_ok());
+}
+
+#[test]
+fn bitrate_range_is_validated() {
+    let sr = SampleRate::Hz48000;
+    let mut mono = Encoder::new(sr, Channels::Mono, Application::Audio).unwrap();
+    assert_eq!(mono.set_bitrate(Bitrate::Custom(499)), Err(Error::BadArg));
+    assert_eq!(mono.set_bitrate_kbps(513), Err(Error::BadArg));
+    mono.set_bitrate_kbps(32).unwrap();
+    assert_eq!(mono.bitrate().unwrap(), Bitrate::Custom(32_000));
+    // Within range, but above libopus' 300 kbps per channel cap.
+    mono.set_bitrate_kbps(400).unwrap();
+    assert_eq!(mono.bitrate().unwrap(), Bitrate::Custom(300_000));
+
+    let mut stereo = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+    stereo.set_bitrate_kbps(512).unwrap();
+    assert_eq!(stereo.bitrate().unwrap(), Bitrate::kbps(512));
+}
+
+#[test]
+fn position_tracking_counts_encoded_samples() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz16000, Channels::Stereo, Application::Voip).unwrap();
+    assert_eq!(encoder.samples_consumed(), None);
+    assert_eq!(encoder.granule_position(), None);
+
+    let mut packet = [0u8; 1275];
+    encoder.encode(&[0; 640], &mut packet).unwrap();
+    encoder.set_position_tracking(true);
+    assert_eq!(encoder.samples_consumed(), Some(0));
+    for frame in [320, 160, 320] {
+        encoder.encode(&vec![0; frame * 2], &mut packet).unwrap();
+    }
+    assert!(encoder.encode(&[0; 3], &mut packet).is_err());
+    assert_eq!(encoder.samples_consumed(), Some(800));
+    assert_eq!(encoder.granule_position(), Some(2_400));
+
+    encoder.reset().unwrap();
+    assert_eq!(encoder.samples_consumed(), Some(800));
+    encoder.set_position_tracking(true);
+    assert_eq!(encoder.samples_consumed(), Some(0));
+    encoder.set_position_tracking(false);
+    assert_eq!(encoder.granule_position(), None);
+}
+
+#[test]
+fn encode_into_uninit_matches_encode() {
+    let pcm: Vec<i16> = (0..1920)
+        .map(|i| ((i % 96) * 300 - 14_000) as i16)
+        .collect();
+    let mut reference =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut expected = [0u8; 1275];
+    let mut buf = [MaybeUninit::<u8>::uninit(); 1275];
+    for _ in 0..3 {
+        let len = reference.encode(&pcm, &mut expected).unwrap();
+        let packet = encoder.encode_into_uninit(&pcm, &mut buf).unwrap();
+        assert_eq!(packet, &expected[..len]);
+    }
+    assert_eq!(
+        encoder.encode_into_uninit(&pcm, &mut []),
+        Err(Error::BadArg)
+    );
+
+    let mapping = Mapping {
+        channels: 2,
+        streams: 1,
+        coupled_streams: 1,
+        mapping: &[0, 1],
+    };
+    let mut ms = MSEncoder::new(SampleRate::Hz48000, Application::Audio, mapping).unwrap();
+    let float: Vec<f32> = pcm.iter().map(|&s| f32::from(s) / 32_768.0).collect();
+    let packet = ms.encode_float_into_uninit(&float, 960, &mut buf).unwrap();
+    assert!(!packet.is_empty());
+}
@@ -4,280 +4,13 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::NamedTempFile;
 
-use opus_codec::{Application, Channels, Decoder, Encoder, SampleRate};
-use opus_codec::{MSDecoder, MSEncoder, Mapping};
+use opus_codec::testsignal::sine;
+use opus_codec::{Application, Channels, Decoder, Encoder, MAX_PACKET_SIZE, SampleRate};
 
 fn ffmpeg_available() -> bool {
     Command::new("ffmpeg").arg("-version").output().is_ok()
 }
 
-fn gen_sine_pcm_s16le(sample_rate: i32, channels: i32, duration_sec: f32) -> Vec<i16> {
-    // Generate raw s16le PCM to stdout and capture it
-    let sr = sample_rate.to_string();
-    let ch = channels.to_string();
-    let dur = duration_sec.to_string();
-    let args = [
-        "-hide_banner",
-        "-loglevel",
-        "error",
-        "-f",
-        "lavfi",
-        "-i",
-        &format!("sine=frequency=440:duration={}:sample_rate={}", dur, sr),
-        "-ac",
-        &ch,
-        "-f",
-        "s16le",
-        "-ar",
-        &sr,
-        "pipe:1",
-    ];
-    let out = Command::new("ffmpeg")
-        .args(args)
-        .output()
-        .expect("failed to run ffmpeg");
-    assert!(out.status.success(), "ffmpeg generation failed: {:?}", out);
-    // Convert bytes to i16
-    let bytes = out.stdout;
-    assert_eq!(bytes.len() % 2, 0);
-    let mut pcm = Vec::with_capacity(bytes.len() / 2);
-    for chunk in bytes.chunks_exact(2) {
-        let v = i16::from_le_bytes([chunk[0], chunk[1]]);
-        pcm.push(v);
-    }
-    pcm
-}
-
-fn gen_noise_pcm_s16le(
-    sample_rate: i32,
-    channels: i32,
-    duration_sec: f32,
-    color: &str,
-) -> Vec<i16> {
-    // Generate raw s16le noise
-    let sr = sample_rate.to_string();
-    let ch = channels.to_string();
-    let dur = duration_sec.to_string();
-    let args = [
-        "-hide_banner",
-        "-loglevel",
-        "error",
-        "-f",
-        "lavfi",
-        "-i",
-        &format!(
-            "anoisesrc=color={}:duration={}:sample_rate={}",
-            color, dur, sr
-        ),
-        "-ac",
-        &ch,
-        "-f",
-        "s16le",
-        "-ar",
-        &sr,
-        "pipe:1",
-    ];
-    let out = Command::new("ffmpeg")
-        .args(args)
-        .output()
-        .expect("failed to run ffmpeg");
-    assert!(out.status.success(), "ffmpeg generation failed: {:?}", out);
-    let bytes = out.stdout;
-    assert_eq!(bytes.len() % 2, 0);
-    let mut pcm = Vec::with_capacity(bytes.len() / 2);
-    for chunk in bytes.chunks_exact(2) {
-        let v = i16::from_le_bytes([chunk[0], chunk[1]]);
-        pcm.push(v);
-    }
-    pcm
-}
-
-fn snr_db_aligned(orig: &[f32], recon: &[f32]) -> f32 {
-    // Align signals by searching small shift around 0 to account for codec delay
-    let max_shift: isize = 2000; // ~40 ms at 48k covers lookahead
-    let mut best_snr = f32::NEG_INFINITY;
-    for shift in -max_shift..=max_shift {
-        let (start_o, start_r): (usize, usize) = if shift >= 0 {
-            (shift as usize, 0)
-        } else {
-            (0, (-shift) as usize)
-        };
-        if start_o >= orig.len() || start_r >= recon.len() {
-            continue;
-        }
-        let n = orig
-            .len()
-            .saturating_sub(start_o)
-            .min(recon.len().saturating_sub(start_r));
-        if n < 256 {
-            continue;
-        }
-        let (mut sig2, mut err2) = (0.0f64, 0.0f64);
-        for i in 0..n {
-            let s = orig[start_o + i] as f64;
-            let r = recon[start_r + i] as f64;
-            sig2 += s * s;
-            let e = s - r;
-            err2 += e * e;
-        }
-        if err2 <= 1e-12 {
-            return 100.0;
-        }
-        let snr = 10.0 * ((sig2 / err2).log10() as f32);
-        if snr > best_snr {
-            best_snr = snr;
-        }
-    }
-    best_snr
-}
-
-#[test]
-fn test_ffmpeg_sine_roundtrip_i16() {
-    assert!(ffmpeg_available(), "ffmpeg not found in PATH");
-    let sr = SampleRate::Hz48000;
-    let ch = Channels::Mono;
-    let pcm = gen_sine_pcm_s16le(sr.as_i32(), ch.as_i32(), 0.5);
-    let frame = 960usize; // 20ms @ 48kHz
-
-    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
-    enc.set_bitrate(opus_codec::Bitrate::Custom(64_000))
-        .unwrap();
-    let mut dec = Decoder::new(sr, ch).unwrap();
-
-    let mut recon = Vec::<i16>::with_capacity(pcm.len());
-    let mut tmp_pkt = vec![0u8; 4000];
-    let mut tmp_out = vec![0i16; frame * ch.as_usize()];
-    for chunk in pcm.chunks_exact(frame * ch.as_usize()) {
-        let nbytes = enc.encode(chunk, &mut tmp_pkt).unwrap();
-        assert!(nbytes > 0);
-        let nsamp = dec.decode(&tmp_pkt[..nbytes], &mut tmp_out, false).unwrap();
-        assert_eq!(nsamp, frame);
-        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
-    }
-    // Handle remainder by zero-padding the final frame and truncate to original length
-    let rem = pcm.len() % (frame * ch.as_usize());
-    if rem != 0 {
-        let mut padded = vec![0i16; frame * ch.as_usize()];
-        padded[..rem].copy_from_slice(&pcm[pcm.len() - rem..]);
-        let nbytes = enc.encode(&padded, &mut tmp_pkt).unwrap();
-        assert!(nbytes > 0);
-        let nsamp = dec.decode(&tmp_pkt[..nbytes], &mut tmp_out, false).unwrap();
-        assert_eq!(nsamp, frame);
-        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
-    }
-    recon.truncate(pcm.len());
-
-    // Compute SNR in float domain
-    let orig_f: Vec<f32> = pcm.iter().map(|&x| x as f32).collect();
-    let rec_f: Vec<f32> = recon.iter().map(|&x| x as f32).collect();
-    let snr = snr_db_aligned(&orig_f, &rec_f);
-    assert!(snr > 18.0, "SNR too low: {:.2} dB", snr);
-}
-
-#[test]
-fn test_multistream_basic_stereo_roundtrip() {
-    let sr = SampleRate::Hz48000;
-    let channels = 2u8;
-    // Stereo is typically 1 coupled stream, 0 uncoupled streams, mapping [0,1]
-    let mapping = Mapping {
-        channels,
-        streams: 1,
-        coupled_streams: 1,
-        mapping: &[0, 1],
-    };
-    let mut enc = MSEncoder::new(sr, Application::Audio, mapping).expect("ms encoder");
-    let mapping_dec = Mapping {
-        channels,
-        streams: 1,
-        coupled_streams: 1,
-        mapping: &[0, 1],
-    };
-    let mut dec = MSDecoder::new(sr, mapping_dec).expect("ms decoder");
-
-    // Generate 20 ms stereo sine
-    let frame = 960usize; // per channel
-    let n = frame * channels as usize;
-    let mut pcm = vec![0i16; n];
-    for i in 0..frame {
-        let t = i as f32 / 48000.0;
-        let s0 = (2.0 * std::f32::consts::PI * 440.0 * t).sin();
-        let s1 = (2.0 * std::f32::consts::PI * 660.0 * t).sin();
-        pcm[2 * i] = (s0 * 2000.0) as i16;
-        pcm[2 * i + 1] = (s1 * 2000.0) as i16;
-    }
-
-    let mut pkt = vec![0u8; 4000];
-    let nbytes = enc.encode(&pcm, frame, &mut pkt).expect("encode");
-    assert!(nbytes > 0);
-    let mut out = vec![0i16; n];
-    let ns = dec
-        .decode(&pkt[..nbytes], &mut out, frame, false)
-        .expect("decode");
-    assert_eq!(ns, frame);
-}
-
-#[test]
-fn test_ffmpeg_sine_roundtrip_f32_encode() {
-    assert!(ffmpeg_available(), "ffmpeg not found in PATH");
-    let sr = SampleRate::Hz48000;
-    let ch = Channels::Mono;
-    let pcm = gen_sine_pcm_s16le(sr.as_i32(), ch.as_i32(), 0.5);
-    let pcm_f: Vec<f32> = pcm.iter().map(|&x| x as f32 / 32768.0).collect();
-    let frame = 960usize;
-
-    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
-    enc.set_bitrate(opus_codec::Bitrate::Custom(64_000))
-        .unwrap();
-    let mut dec = Decoder::new(sr, ch).unwrap();
-
-    let mut recon = Vec::<f32>::with_capacity(pcm_f.len());
-    let mut tmp_pkt = vec![0u8; 4000];
-    let mut tmp_out = vec![0f32; frame * ch.as_usize()];
-    for chunk in pcm_f.chunks_exact(frame * ch.as_usize()) {
-        let nbytes = enc.encode_float(chunk, &mut tmp_pkt).unwrap();
-        assert!(nbytes > 0);
-        let nsamp = dec
-            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
-            .unwrap();
-        assert_eq!(nsamp, frame);
-        // Sanity: decoded samples must be finite and within [-1.05, 1.05]
-        for &v in &tmp_out[..frame * ch.as_usize()] {
-            assert!(v.is_finite(), "decoded NaN/Inf encountered");
-            assert!(
-                (-1.05..=1.05).contains(&v),
-                "decoded sample out of range: {}",
-                v
-            );
-        }
-        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
-    }
-    let rem = pcm_f.len() % (frame * ch.as_usize());
-    if rem != 0 {
-        let mut padded = vec![0f32; frame * ch.as_usize()];
-        padded[..rem].copy_from_slice(&pcm_f[pcm_f.len() - rem..]);
-        let nbytes = enc.encode_float(&padded, &mut tmp_pkt).unwrap();
-        assert!(nbytes > 0);
-        let nsamp = dec
-            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
-            .unwrap();
-        assert_eq!(nsamp, frame);
-        for &v in &tmp_out[..frame * ch.as_usize()] {
-            assert!(v.is_finite(), "decoded NaN/Inf encountered");
-            assert!(
-                (-1.05..=1.05).contains(&v),
-                "decoded sample out of range: {}",
-                v
-            );
-        }
-        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
-    }
-    recon.truncate(pcm_f.len());
-
-    // Compare to original float (normalized)
-    let snr = snr_db_aligned(&pcm_f, &recon);
-    assert!(snr > 18.0, "SNR too low (f32 path): {:.2} dB", snr);
-}
-
 fn tmp_path(_name: &str, ext: &str) -> PathBuf {
     let file = NamedTempFile::new().expect("tmp file");
     let mut p = file.into_temp_path().to_path_buf();
@@ -380,13 +113,19 @@ fn test_ffmpeg_bitrate_target_compare() {
     assert!(ffmpeg_available(), "ffmpeg not found in PATH");
     let sr = SampleRate::Hz48000;
     let ch = Channels::Mono;
-    let dur = 8.0;
-    let pcm = gen_sine_pcm_s16le(sr.as_i32(), ch.as_i32(), dur);
+    let dur: f32 = 8.0;
+    let pcm: Vec<i16> = sine(
+        sr,
+        ch,
+        440.0,
+        0.125,
+        (f64::from(dur) * f64::from(sr.as_i32())) as usize,
+    );
     let frame = 960usize;
 
     // Our encoder at two bitrates
     let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
-    let mut pkt = vec![0u8; 4000];
+    let mut pkt = vec![0u8; MAX_PACKET_SIZE];
     let mut total24 = 0usize;
     enc.set_bitrate(opus_codec::Bitrate::Custom(24_000))
         .unwrap();
@@ -458,7 +197,7 @@ fn test_ffmpeg_sample_rate_check_from_wav() {
     let chans = [Channels::Mono, Channels::Stereo];
     for &sr in &rates {
         for &ch in &chans {
-            let pcm = gen_sine_pcm_s16le(sr.as_i32(), ch.as_i32(), 1.0);
+            let pcm: Vec<i16> = sine(sr, ch, 440.0, 0.125, sr.as_i32() as usize);
             let frame = (sr.as_i32() as usize / 50) * ch.as_usize(); // 20ms
 
             // Round-trip through our codec
@@ -466,7 +205,7 @@ fn test_ffmpeg_sample_rate_check_from_wav() {
             enc.set_bitrate(opus_codec::Bitrate::Custom(48_000))
                 .unwrap();
             let mut dec = Decoder::new(sr, ch).unwrap();
-            let mut pkt = vec![0u8; 4000];
+            let mut pkt = vec![0u8; MAX_PACKET_SIZE];
             let mut out = vec![0i16; frame];
             let mut recon = Vec::<i16>::with_capacity(pcm.len());
             for chunk in pcm.chunks_exact(frame) {
@@ -494,62 +233,3 @@ fn test_ffmpeg_sample_rate_check_from_wav() {
         }
     }
 }
-
-#[test]
-fn test_ffmpeg_pink_noise_sanity_f32() {
-    assert!(ffmpeg_available(), "ffmpeg not found in PATH");
-    let sr = SampleRate::Hz48000;
-    let ch = Channels::Stereo;
-    let dur = 1.0;
-    let pcm = gen_noise_pcm_s16le(sr.as_i32(), ch.as_i32(), dur, "pink");
-    let pcm_f: Vec<f32> = pcm.iter().map(|&x| x as f32 / 32768.0).collect();
-    let frame = (sr.as_i32() as usize / 50) * ch.as_usize(); // 20ms
-
-    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
-    enc.set_bitrate(opus_codec::Bitrate::Custom(96_000))
-        .unwrap();
-    let mut dec = Decoder::new(sr, ch).unwrap();
-    let mut recon = Vec::<f32>::with_capacity(pcm_f.len());
-    let mut tmp_pkt = vec![0u8; 4000];
-    let mut tmp_out = vec![0f32; frame];
-    for chunk in pcm_f.chunks_exact(frame) {
-        let nbytes = enc.encode_float(chunk, &mut tmp_pkt).unwrap();
-        let nsamp = dec
-            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
-            .unwrap();
-        assert_eq!(nsamp, frame / ch.as_usize());
-        for &v in &tmp_out[..frame] {
-            assert!(v.is_finite(), "decoded NaN/Inf encountered");
-            assert!(
-                (-1.05..=1.05).contains(&v),
-                "decoded sample out of range: {}",
-                v
-            );
-        }
-        recon.extend_from_slice(&tmp_out[..frame]);
-    }
-    let rem = pcm_f.len() % frame;
-    if rem != 0 {
-        let mut padded = vec![0f32; frame];
-        padded[..rem].copy_from_slice(&pcm_f[pcm_f.len() - rem..]);
-        let nbytes = enc.encode_float(&padded, &mut tmp_pkt).unwrap();
-        let nsamp = dec
-            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
-            .unwrap();
-        assert_eq!(nsamp, frame / ch.as_usize());
-        for &v in &tmp_out[..frame] {
-            assert!(v.is_finite(), "decoded NaN/Inf encountered");
-            assert!(
-                (-1.05..=1.05).contains(&v),
-                "decoded sample out of range: {}",
-                v
-            );
-        }
-        recon.extend_from_slice(&tmp_out[..frame]);
-    }
-    recon.truncate(pcm_f.len());
-
-    // Ensure reconstructed audio is sane
-    let snr = snr_db_aligned(&pcm_f, &recon);
-    assert!(snr > 5.0, "SNR too low on noise: {:.2} dB", snr);
-}
@@ -0,0 +1,503 @@
+use opus_codec::ogg::{
+    OpusFile, OpusHead, OpusTags, OpusWriter, PacketReader, Page, SeekIndex, Violation,
+    ViolationKind, check_stream, cut_stream, estimate_duration, retag, retag_stream,
+};
+use opus_codec::stream::StreamEncoder;
+use opus_codec::testsignal::pink_noise;
+use opus_codec::{Application, Channels, Error, FrameSize, SampleRate};
+use std::io::Cursor;
+use std::time::Duration;
+
+/// Encode `seconds` of stereo noise into an in-memory `.opus` file.
+fn encode_file(tags: &OpusTags, seconds: usize) -> Vec<u8> {
+    let sr = SampleRate::Hz48000;
+    let mut stream =
+        StreamEncoder::new(sr, Channels::Stereo, Application::Audio, FrameSize::Ms20).unwrap();
+    let pre_skip = stream.pre_skip().unwrap();
+    let head = OpusHead::new(2, pre_skip, 48_000);
+    let mut writer = OpusWriter::new(Vec::new(), 0x1234, &head, tags).unwrap();
+
+    let pcm = pink_noise::<i16>(Channels::Stereo, 0.3, 48_000 * seconds, 5);
+    let packets = stream.push(&pcm).unwrap();
+    let end = stream.finish().unwrap();
+    let end_granule = end.end_granule(sr);
+    let mut packets = packets.into_iter().chain(end.packets).peekable();
+    let mut granule = 0;
+    while let Some(packet) = packets.next() {
+        granule += 960;
+        if packets.peek().is_none() {
+            return writer.finish(&packet, end_granule).unwrap();
+        }
+        writer.write_packet(&packet, granule).unwrap();
+    }
+    unreachable!("a non-empty stream ends with at least one packet")
+}
+
+fn packets(file: &[u8]) -> Vec<Vec<u8>> {
+    PacketReader::new(file)
+        .map(|packet| packet.unwrap().data)
+        .collect()
+}
+
+#[test]
+fn retag_replaces_only_the_comment_header() {
+    let mut tags = OpusTags::new("opus-codec");
+    tags.add("TITLE", "Before");
+    let original = encode_file(&tags, 1);
+    let before = packets(&original);
+
+    let mut retagged = OpusTags::new("opus-codec");
+    retagged.add("TITLE", "After");
+    // Large enough to span several pages.
+    retagged.add("COMMENT", &"x".repeat(200_000));
+    let mut out = Vec::new();
+    retag_stream(&mut original.as_slice(), &mut out, &retagged).unwrap();
+
+    let after = packets(&out);
+    assert_eq!(after.len(), before.len());
+    assert_eq!(after[0], before[0]);
+    assert_eq!(OpusTags::parse(&after[1]).unwrap(), retagged);
+    assert_eq!(after[2..], before[2..]);
+
+    // Page sequence numbers stay contiguous and the last page keeps its granule.
+    let mut reader = out.as_slice();
+    let mut sequence = 0;
+    let mut last = None;
+    while let Some(page) = Page::read(&mut reader).unwrap() {
+        assert_eq!(page.sequence, sequence);
+        sequence += 1;
+        last = Some(page);
+    }
+    let last = last.unwrap();
+    assert!(last.is_eos());
+    assert_eq!(last.granule(), Some(312 + 48_000));
+
+    // Shrinking back restores the original layout exactly.
+    let mut restored = Vec::new();
+    retag_stream(&mut out.as_slice(), &mut restored, &tags).unwrap();
+    assert_eq!(restored, original);
+}
+
+#[test]
+fn retag_file_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.opus");
+    std::fs::write(&path, encode_file(&OpusTags::new("opus-codec"), 1)).unwrap();
+
+    let mut tags = OpusTags::new("opus-codec");
+    tags.add("ARTIST", "Someone");
+    retag(&path, &tags).unwrap();
+    let file = std::fs::read(&path).unwrap();
+    let parsed = OpusTags::parse(&packets(&file)[1]).unwrap();
+    assert_eq!(parsed.get("artist").collect::<Vec<_>>(), ["Someone"]);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    assert!(retag(dir.path().join("missing.opus"), &tags).is_err());
+}
+
+#[test]
+fn seek_index_finds_pages_in_constant_time() {
+    let file = encode_file(&OpusTags::new("opus-codec"), 10);
+    let index = SeekIndex::build(file.as_slice(), Duration::from_millis(100)).unwrap();
+    assert_eq!(index.interval(), 4_800);
+    assert_eq!(index.stream_len(), file.len() as u64);
+    assert!(index.entries().len() > 100);
+
+    let mut reader = PacketReader::new(Cursor::new(&file));
+    for target in [0u64, 5_000, 48_000 * 3 + 17, 48_000 * 9] {
+        let entry = index.lookup(target).unwrap();
+        assert!(entry.granule <= target);
+        reader.seek_to(entry.offset).unwrap();
+        // Packets read from the entry are 20 ms each; the first complete page tells us
+        // where they started.
+        let mut count = 0u64;
+        let granule = loop {
+            let packet = reader.next_packet().unwrap().unwrap();
+            count += 1;
+            if let Some(granule) = packet.granule_position {
+                break granule;
+            }
+        };
+        // The first audio page follows the header page (granule 0), so its packets may
+        // start after the entry's granule, never before.
+        assert!(granule - count * 960 >= entry.granule);
+        // No later page starts at or before the target's interval boundary.
+        assert!(granule > target / index.interval() * index.interval());
+    }
+    assert_eq!(index.lookup(u64::MAX), index.entries().last().copied());
+
+    let mut bytes = index.to_bytes().unwrap();
+    let restored = SeekIndex::from_bytes(&bytes).unwrap();
+    assert_eq!(restored, index);
+    assert!(SeekIndex::from_bytes(&bytes[..20]).is_err());
+    // A zero interval would make every lookup divide by zero.
+    bytes[9..17].fill(0);
+    assert!(SeekIndex::from_bytes(&bytes).is_err());
+    assert!(SeekIndex::build(file.as_slice(), Duration::ZERO).is_err());
+}
+
+#[test]
+fn conformance_check_reports_page_offsets() {
+    let mut tags = OpusTags::new("opus-codec");
+    let original = encode_file(&tags, 2);
+    assert_eq!(check_stream(original.as_slice()).unwrap(), []);
+    tags.add("COMMENT", &"x".repeat(100_000));
+    let mut retagged = Vec::new();
+    retag_stream(&mut original.as_slice(), &mut retagged, &tags).unwrap();
+    assert_eq!(check_stream(retagged.as_slice()).unwrap(), []);
+
+    let mut reader = original.as_slice();
+    let mut pages = Vec::new();
+    while let Some(page) = Page::read(&mut reader).unwrap() {
+        pages.push(page);
+    }
+    assert!(pages.len() > 5);
+    // Shift one page's granule and cut off the end-of-stream page.
+    pages[3].granule_position += 960;
+    pages.pop();
+    let last = pages.len() - 1;
+    let offsets: Vec<u64> = pages
+        .iter()
+        .scan(0, |offset, page| {
+            let start = *offset;
+            *offset += page.len() as u64;
+            Some(start)
+        })
+        .collect();
+    let broken: Vec<u8> = pages.iter().flat_map(Page::to_bytes).collect();
+
+    let granule = pages[3].granule_position;
+    let violations = check_stream(broken.as_slice()).unwrap();
+    assert_eq!(
+        violations,
+        [
+            Violation {
+                page_offset: offsets[3],
+                packet: None,
+                kind: ViolationKind::GranuleMismatch {
+                    expected: granule - 960,
+                    found: granule,
+                },
+            },
+            Violation {
+                page_offset: offsets[4],
+                packet: None,
+                kind: ViolationKind::GranuleMismatch {
+                    expected: pages[4].granule_position + 960,
+                    found: pages[4].granule_position,
+                },
+            },
+            Violation {
+                page_offset: offsets[last],
+                packet: None,
+                kind: ViolationKind::MissingEos,
+            },
+        ]
+    );
+    assert_eq!(
+        violations[2].to_string(),
+        format!(
+            "page at byte {}: last page lacks the EOS flag",
+            offsets[last]
+        )
+    );
+
+    // A flipped byte fails the page CRC and stops the check there.
+    let mut corrupt = original.clone();
+    let at = usize::try_from(offsets[2]).unwrap() + 40;
+    corrupt[at] ^= 0xFF;
+    let violations = check_stream(corrupt.as_slice()).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].kind, ViolationKind::CorruptPage);
+    assert_eq!(violations[0].page_offset, offsets[2]);
+}
+
+#[test]
+fn estimate_duration_reads_granules_of_chained_links() {
+    let tags = OpusTags::new("opus-codec");
+    let first = encode_file(&tags, 2);
+    assert_eq!(estimate_duration(Cursor::new(&first)).unwrap(), 2_000);
+
+    // Chain a second link with its own serial.
+    let mut reader = encode_file(&tags, 1);
+    let mut chained = first.clone();
+    let mut pages = reader.as_slice();
+    while let Some(mut page) = Page::read(&mut pages).unwrap() {
+        page.serial = 0x5678;
+        chained.extend(page.to_bytes());
+    }
+    assert_eq!(estimate_duration(Cursor::new(&chained)).unwrap(), 3_000);
+    assert_eq!(check_stream(chained.as_slice()).unwrap(), []);
+
+    reader.truncate(10);
+    assert!(estimate_duration(Cursor::new(&reader)).is_err());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_transcode_stitches_segments() {
+    use opus_codec::transcode::{TranscodeConfig, transcode};
+    use opus_codec::{Bitrate, ogg::OggPacket};
+
+    let mut tags = OpusTags::new("opus-codec");
+    tags.add("TITLE", "Archive");
+    let input = encode_file(&tags, 3);
+    let config = TranscodeConfig {
+        bitrate: Bitrate::Custom(32_000),
+        segment: Duration::from_secs(1),
+        overlap: Duration::from_millis(100),
+        threads: 2,
+        ..TranscodeConfig::default()
+    };
+    let mut output = Vec::new();
+    let report = transcode(input.as_slice(), &mut output, &config).unwrap();
+    assert_eq!(report.samples, 3 * 48_000);
+    // 312 samples of pre-skip push the stream one frame past three seconds.
+    assert_eq!(report.packets, 151);
+    assert_eq!(report.segments, 4);
+
+    assert_eq!(check_stream(output.as_slice()).unwrap(), []);
+    assert_eq!(estimate_duration(Cursor::new(&output)).unwrap(), 3_000);
+    let packets: Vec<OggPacket> = PacketReader::new(output.as_slice())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(OpusTags::parse(&packets[1].data).unwrap(), tags);
+    assert_eq!(packets.len(), 2 + report.packets);
+    assert!(packets[2..].iter().all(|p| p.data.len() < 200));
+
+    let zero = TranscodeConfig {
+        segment: Duration::ZERO,
+        ..config
+    };
+    assert!(transcode(input.as_slice(), Vec::new(), &zero).is_err());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_encode_keeps_frame_order() {
+    use opus_codec::transcode::{TranscodeConfig, parallel_encode};
+
+    let pcm: Vec<f32> = pink_noise(Channels::Stereo, 0.3, 48_000, 7);
+    let frames: Vec<&[f32]> = pcm.chunks(2 * 960).collect();
+    let config = TranscodeConfig {
+        segment: Duration::from_millis(200),
+        threads: 1,
+        ..TranscodeConfig::default()
+    };
+    let serial = parallel_encode(&frames, Channels::Stereo, &config).unwrap();
+    assert_eq!(serial.len(), 50);
+    let threaded = TranscodeConfig {
+        threads: 4,
+        ..config
+    };
+    assert_eq!(
+        parallel_encode(&frames, Channels::Stereo, &threaded).unwrap(),
+        serial
+    );
+    assert_eq!(
+        parallel_encode(&frames[..0], Channels::Stereo, &config).unwrap(),
+        Vec::<Vec<u8>>::new()
+    );
+    assert_eq!(
+        parallel_encode(&frames, Channels::Mono, &config),
+        Err(Error::BadArg)
+    );
+}
+
+#[test]
+fn opus_file_reads_trimmed_pcm_and_seeks() {
+    let tags = OpusTags::new("opus-codec");
+    let bytes = encode_file(&tags, 3);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.opus");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let mut file = OpusFile::open(&path).unwrap();
+    assert_eq!(file.channels(), Channels::Stereo);
+    assert_eq!(file.sample_rate(), SampleRate::Hz48000);
+    assert_eq!(file.input_sample_rate(), 48_000);
+    assert_eq!(file.head().pre_skip, 312);
+    let total = file.pcm_total().unwrap();
+    assert_eq!(total, 3 * 48_000);
+
+    // Odd buffer sizes straddle packet boundaries.
+    let mut all = Vec::new();
+    let mut buf = vec![0.0f32; 2 * 701];
+    loop {
+        let n = file.read_f32(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        all.extend_from_slice(&buf[..2 * n]);
+        assert_eq!(file.tell(), all.len() as u64 / 2);
+    }
+    assert_eq!(all.len() as u64 / 2, total);
+
+    let target = 100_000;
+    file.pcm_seek(target).unwrap();
+    assert_eq!(file.tell(), target);
+    let mut seeked = vec![0.0f32; 2 * 4_800];
+    let mut filled = 0;
+    while filled < seeked.len() {
+        filled += 2 * file.read_f32(&mut seeked[filled..]).unwrap();
+    }
+    assert_eq!(file.tell(), target + 4_800);
+    let reference = &all[2 * target as usize..][..seeked.len()];
+    let error: f32 = seeked
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum();
+    let energy: f32 = reference.iter().map(|r| r * r).sum();
+    assert!(error < energy * 0.01, "error {error}, energy {energy}");
+
+    file.pcm_seek(0).unwrap();
+    let n = file.read_f32(&mut buf).unwrap();
+    assert_eq!(buf[..2 * n], all[..2 * n]);
+    assert!(file.pcm_seek(total + 1).is_err());
+}
+
+/// Decode every sample of an in-memory `.opus` file.
+fn decode_all(bytes: &[u8]) -> Vec<f32> {
+    let mut file = OpusFile::new(Cursor::new(bytes)).unwrap();
+    let mut all = Vec::new();
+    let mut buf = vec![0.0f32; 2 * 960];
+    loop {
+        let n = file.read_f32(&mut buf).unwrap();
+        if n == 0 {
+            return all;
+        }
+        all.extend_from_slice(&buf[..2 * n]);
+    }
+}
+
+#[test]
+fn cut_keeps_exactly_the_requested_span() {
+    let tags = OpusTags::new("opus-codec");
+    let bytes = encode_file(&tags, 3);
+    let reference = decode_all(&bytes);
+
+    let mut out = Vec::new();
+    let start = Duration::from_millis(510);
+    let samples = cut_stream(
+        bytes.as_slice(),
+        &mut out,
+        start,
+        Duration::from_millis(1_755),
+    )
+    .unwrap();
+    assert_eq!(samples, 59_760);
+    assert!(check_stream(out.as_slice()).unwrap().is_empty());
+    assert!(out.len() < bytes.len() / 2);
+
+    let file = OpusFile::new(Cursor::new(&out)).unwrap();
+    assert_eq!(file.pcm_total(), Some(samples));
+    assert_eq!(file.tags(), &tags);
+    let head = file.head();
+    assert!(head.pre_skip >= 3_840 && head.pre_skip < 3_840 + 960);
+
+    let cut = decode_all(&out);
+    assert_eq!(cut.len() as u64, 2 * samples);
+    let reference = &reference[2 * 24_480..][..cut.len()];
+    let error: f32 = cut
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum();
+    let energy: f32 = reference.iter().map(|r| r * r).sum();
+    assert!(error < energy * 0.01, "error {error}, energy {energy}");
+
+    // An end past the stream is clamped; a start past it is rejected.
+    let mut tail = Vec::new();
+    let kept = cut_stream(
+        bytes.as_slice(),
+        &mut tail,
+        Duration::from_secs(2),
+        Duration::from_secs(9),
+    )
+    .unwrap();
+    assert_eq!(kept, 48_000);
+    assert_eq!(decode_all(&tail).len(), 2 * 48_000);
+    let past = cut_stream(
+        bytes.as_slice(),
+        Vec::new(),
+        Duration::from_secs(3),
+        Duration::from_secs(4),
+    );
+    assert_eq!(past, Err(Error::BadArg));
+    let empty = cut_stream(bytes.as_slice(), Vec::new(), start, start);
+    assert_eq!(empty, Err(Error::BadArg));
+}
+
+/// Async reader handing out a few bytes at a time and stalling on every other poll.
+#[cfg(feature = "tokio")]
+struct Trickle<'a> {
+    data: &'a [u8],
+    stall: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for Trickle<'_> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        self.stall = !self.stall;
+        if self.stall {
+            cx.waker().wake_by_ref();
+            return std::task::Poll::Pending;
+        }
+        let len = self.data.len().min(buf.remaining()).min(333);
+        buf.put_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_reader_survives_cancellation() {
+    use opus_codec::ogg::AsyncOpusReader;
+
+    let mut tags = OpusTags::new("opus-codec");
+    tags.add("TITLE", "Streamed");
+    let bytes = encode_file(&tags, 2);
+    let mut expected = Vec::new();
+    let mut file = OpusFile::new(Cursor::new(&bytes)).unwrap();
+    let mut buf = vec![0.0f32; 2 * 1_000];
+    loop {
+        let n = file.read_f32(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        expected.extend_from_slice(&buf[..2 * n]);
+    }
+
+    let trickle = Trickle {
+        data: &bytes,
+        stall: false,
+    };
+    let mut reader = AsyncOpusReader::new(trickle).await.unwrap();
+    assert_eq!(reader.tags(), &tags);
+    assert_eq!(reader.channels(), Channels::Stereo);
+    let mut decoded = Vec::new();
+    let mut cancelled = 0;
+    loop {
+        // Drop the read whenever it would wait on I/O.
+        tokio::select! {
+            biased;
+            n = reader.read_f32(&mut buf) => {
+                let n = n.unwrap();
+                if n == 0 {
+                    break;
+                }
+                decoded.extend_from_slice(&buf[..2 * n]);
+            }
+            () = std::future::ready(()) => cancelled += 1,
+        }
+    }
+    assert!(cancelled > 0);
+    assert_eq!(reader.tell(), 2 * 48_000);
+    assert_eq!(decoded, expected);
+}
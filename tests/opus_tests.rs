@@ -1,12 +1,18 @@
+use opus_codec::constants::max_multistream_packet_size;
 use opus_codec::decoder::Decoder;
 use opus_codec::encoder::Encoder;
 use opus_codec::error::Error;
+use opus_codec::lbrr::{multistream_packet_lbrr, packet_lbrr};
 use opus_codec::multistream::{MSDecoder, MSEncoder, Mapping};
 use opus_codec::packet::{
-    packet_bandwidth, packet_channels, packet_nb_frames, packet_nb_samples, packet_parse, soft_clip,
+    Toc, multistream_packet_pad_vec, multistream_packet_unpad_vec, packet_bandwidth,
+    packet_channels, packet_has_lbrr, packet_nb_frames, packet_nb_samples, packet_pad_vec,
+    packet_padding, packet_padding_payload, packet_parse, packet_samples_per_frame,
+    packet_set_padding_payload, packet_unpad_vec, soft_clip,
 };
 use opus_codec::repacketizer::Repacketizer;
-use opus_codec::types::{Application, Bandwidth, Channels, SampleRate};
+use opus_codec::types::{Application, Bandwidth, Channels, InbandFec, PacketLossPerc, SampleRate};
+use std::time::Duration;
 
 #[test]
 fn test_packet_analysis() {
@@ -134,6 +140,121 @@ fn test_repacketizer() {
 
     // Verify the merged packet has 2 frames
     assert_eq!(packet_nb_frames(&merged[..merged_len]).unwrap(), 2);
+
+    // Same merge, padded to a fixed MTU-sized payload
+    let mut padded = [0u8; 500];
+    assert_eq!(rp.out_padded(&mut padded, 400).unwrap(), 400);
+    assert_eq!(packet_nb_frames(&padded[..400]).unwrap(), 2);
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+    let mut pcm_out = vec![0i16; frame_size * 2];
+    assert_eq!(
+        decoder.decode(&padded[..400], &mut pcm_out, false).unwrap(),
+        frame_size * 2
+    );
+    assert_eq!(
+        rp.out_padded(&mut padded, merged_len - 1),
+        Err(Error::BufferTooSmall)
+    );
+    assert_eq!(rp.out_padded(&mut padded, 501), Err(Error::BadArg));
+
+    // Owned output is sized from the queued frames and matches the slice API.
+    assert_eq!(rp.out_to_vec().unwrap(), &merged[..merged_len]);
+    let mut second = [0u8; 500];
+    let second_len = rp.out_range(1, 2, &mut second).unwrap();
+    assert_eq!(rp.out_range_to_vec(1, 2).unwrap(), &second[..second_len]);
+    assert_eq!(rp.out_range_to_vec(1, 3), Err(Error::BadArg));
+    rp.reset();
+    assert_eq!(rp.out_to_vec(), Err(Error::BadArg));
+}
+
+#[test]
+fn test_packet_pad_vec_round_trip() {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+    let mut buf = [0u8; 1275];
+    let len = encoder.encode(&[0; 960], &mut buf).unwrap();
+    let original = buf[..len].to_vec();
+
+    let mut packet = original.clone();
+    packet_pad_vec(&mut packet, len + 100).unwrap();
+    assert_eq!(packet.len(), len + 100);
+    assert_eq!(packet_nb_frames(&packet).unwrap(), 1);
+    assert_eq!(packet_pad_vec(&mut packet, len), Err(Error::BadArg));
+    assert_eq!(packet.len(), len + 100);
+
+    packet_unpad_vec(&mut packet).unwrap();
+    assert_eq!(packet, original);
+
+    let mut bogus = vec![0xFF];
+    assert!(packet_pad_vec(&mut bogus, 10).is_err());
+    assert_eq!(bogus, [0xFF]);
+}
+
+#[test]
+fn test_padding_payload_round_trip() {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).unwrap();
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+    let mut buf = [0u8; 1275];
+    let len = encoder.encode(&[0; 960], &mut buf).unwrap();
+    let original = buf[..len].to_vec();
+    assert_eq!(packet_padding(&original).unwrap(), &[] as &[u8]);
+    assert_eq!(packet_padding_payload(&original, 100).unwrap(), None);
+
+    // Large enough that the padding length needs two bytes.
+    let stamp: Vec<u8> = (0..300u16).map(|i| i as u8).collect();
+    let mut packet = original.clone();
+    packet_set_padding_payload(&mut packet, 100, &stamp).unwrap();
+    assert_eq!(packet_padding(&packet).unwrap().len(), stamp.len() + 1);
+    assert_eq!(
+        packet_padding_payload(&packet, 100).unwrap(),
+        Some(&stamp[..])
+    );
+    assert_eq!(packet_padding_payload(&packet, 101).unwrap(), None);
+
+    // The payload survives re-padding and does not disturb decoding.
+    packet_pad_vec(&mut packet, len + 400).unwrap();
+    assert_eq!(
+        packet_padding_payload(&packet, 100).unwrap(),
+        Some(&stamp[..])
+    );
+    let mut pcm = [0i16; 960];
+    assert_eq!(decoder.decode(&packet, &mut pcm, false).unwrap(), 960);
+
+    packet_set_padding_payload(&mut packet, 100, b"ts").unwrap();
+    assert_eq!(
+        packet_padding_payload(&packet, 100).unwrap(),
+        Some(&b"ts"[..])
+    );
+    packet_unpad_vec(&mut packet).unwrap();
+    assert_eq!(packet, original);
+    assert_eq!(
+        packet_set_padding_payload(&mut packet, 31, b"x"),
+        Err(Error::BadArg)
+    );
+}
+
+#[test]
+fn test_multistream_packet_pad_vec_to_wire_size() {
+    let sr = SampleRate::Hz48000;
+    let (mut encoder, _) = MSEncoder::new_surround(sr, 6, 1, Application::Audio).unwrap();
+    let streams = i32::from(encoder.streams());
+    let mut buf =
+        vec![0u8; max_multistream_packet_size(encoder.streams(), Duration::from_millis(20))];
+    let len = encoder.encode(&[0i16; 960 * 6], 960, &mut buf).unwrap();
+    let original = buf[..len].to_vec();
+
+    let mut packet = original.clone();
+    multistream_packet_pad_vec(&mut packet, 1200, streams).unwrap();
+    assert_eq!(packet.len(), 1200);
+    let mut decoder = MSDecoder::new(sr, Mapping::SURROUND_51).unwrap();
+    let mut pcm = vec![0i16; 960 * 6];
+    assert_eq!(decoder.decode(&packet, &mut pcm, 960, false).unwrap(), 960);
+    assert_eq!(
+        multistream_packet_pad_vec(&mut packet, 10, streams),
+        Err(Error::BadArg)
+    );
+
+    multistream_packet_unpad_vec(&mut packet, streams).unwrap();
+    assert_eq!(packet, original);
 }
 
 #[test]
@@ -210,3 +331,305 @@ fn test_soft_clip_validations() {
     let mut pcm = vec![1.5f32; 4];
     assert_eq!(soft_clip(&mut pcm, 2, -1, &mut state), Err(Error::BadArg));
 }
+
+#[test]
+fn test_fixed_frame_arrays() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Stereo).unwrap();
+
+    let pcm = [0i16; 960 * 2];
+    let mut packet = [0u8; 1275];
+    let len = encoder.encode_array(&pcm, &mut packet).unwrap();
+
+    let out: [i16; 960 * 2] = decoder.decode_frame(&packet[..len], false).unwrap();
+    assert!(out.iter().all(|&s| s.abs() < 64));
+
+    // A 20 ms packet cannot fill a 10 ms array.
+    let err = decoder.decode_frame::<{ 480 * 2 }>(&packet[..len], false);
+    assert!(err.is_err());
+
+    let fpcm = [0f32; 480 * 2];
+    let len = encoder.encode_float_array(&fpcm, &mut packet).unwrap();
+    let out = decoder
+        .decode_frame_float::<{ 480 * 2 }>(&packet[..len], false)
+        .unwrap();
+    assert_eq!(out.len(), 960);
+}
+
+#[test]
+fn test_pcm_frame_roundtrip_checks_layout() {
+    use opus_codec::frame::PcmFrame;
+    use opus_codec::types::FrameSize;
+
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Stereo).unwrap();
+    let mut packet = [0u8; 1500];
+
+    let frame = PcmFrame::<i16>::silence(SampleRate::Hz48000, Channels::Stereo, FrameSize::Ms20);
+    let len = encoder.encode_pcm_frame(&frame, &mut packet).unwrap();
+    let decoded = decoder.decode_pcm_frame(&packet[..len], false).unwrap();
+    assert_eq!(decoded.samples_per_channel(), 960);
+    assert_eq!(decoded.channels(), Channels::Stereo);
+    assert_eq!(decoded.sample_rate(), SampleRate::Hz48000);
+
+    let concealed = decoder.decode_pcm_frame_float(&[], false).unwrap();
+    assert_eq!(concealed.samples_per_channel(), 960);
+
+    let mono = PcmFrame::<i16>::silence(SampleRate::Hz48000, Channels::Mono, FrameSize::Ms20);
+    assert_eq!(
+        encoder.encode_pcm_frame(&mono, &mut packet),
+        Err(Error::BadArg)
+    );
+    let wrong_rate =
+        PcmFrame::<f32>::silence(SampleRate::Hz16000, Channels::Stereo, FrameSize::Ms20);
+    assert_eq!(
+        encoder.encode_pcm_frame_float(&wrong_rate, &mut packet),
+        Err(Error::BadArg)
+    );
+}
+
+#[test]
+fn test_toc_matches_libopus() {
+    for byte in 0..=u8::MAX {
+        let toc = Toc::from_byte(byte);
+        let packet = [byte, 0, 0];
+        assert_eq!(Toc::of(&packet).unwrap(), toc);
+        assert_eq!(toc.byte(), byte);
+        assert_eq!(toc.bandwidth(), packet_bandwidth(&packet).unwrap());
+        assert_eq!(toc.channels(), packet_channels(&packet).unwrap());
+        assert_eq!(
+            toc.samples_per_frame(SampleRate::Hz48000),
+            packet_samples_per_frame(&packet, SampleRate::Hz48000).unwrap()
+        );
+        assert_eq!(
+            Toc::new(toc.config(), toc.is_stereo(), toc.frame_code()).unwrap(),
+            toc
+        );
+    }
+    assert!(Toc::new(32, false, 0).is_err());
+    assert!(Toc::of(&[]).is_err());
+}
+
+#[test]
+fn test_packet_lbrr_frames() {
+    let sr = SampleRate::Hz16000;
+    let pcm = opus_codec::testsignal::pink_noise::<i16>(Channels::Mono, 0.3, sr as usize, 5);
+    for (frame_ms, silk_frames) in [(20, 1u32), (40, 2), (60, 3)] {
+        let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+        encoder.set_inband_fec(true).unwrap();
+        encoder
+            .set_packet_loss_perc(PacketLossPerc::new(25))
+            .unwrap();
+        encoder
+            .set_bitrate(opus_codec::Bitrate::Custom(32_000))
+            .unwrap();
+        let frame_len = 16 * frame_ms;
+        let mut packet = [0u8; 1500];
+        let infos: Vec<_> = pcm
+            .chunks_exact(frame_len)
+            .map(|chunk| {
+                let len = encoder.encode(chunk, &mut packet).unwrap();
+                let info = packet_lbrr(&packet[..len]).unwrap();
+                assert_eq!(
+                    info.frames()[0] != 0,
+                    packet_has_lbrr(&packet[..len]).unwrap(),
+                    "{frame_ms} ms"
+                );
+                info
+            })
+            .collect();
+        // Stationary noise eventually reads as inactive, so look at the best-protected packet.
+        let info = infos.iter().max_by_key(|info| info.count()).unwrap();
+        assert_eq!(info.silk_frame_samples(), 960);
+        assert_eq!(info.count(), silk_frames as usize, "{frame_ms} ms");
+        assert_eq!(info.recoverable_samples(), 48 * frame_ms);
+        assert_eq!(
+            info.recoverable_duration(),
+            Duration::from_millis(frame_ms as u64)
+        );
+    }
+
+    // A code-3 packet whose first frame is empty (DTX): FEC only reads the first frame,
+    // so the redundancy in the second one restores nothing.
+    let mut encoder = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+    encoder.set_inband_fec(true).unwrap();
+    encoder
+        .set_packet_loss_perc(PacketLossPerc::new(25))
+        .unwrap();
+    let mut packet = [0u8; 1500];
+    let protected = pcm
+        .chunks_exact(320)
+        .find_map(|chunk| {
+            let len = encoder.encode(chunk, &mut packet).unwrap();
+            (packet[0] & 0x03 == 0 && packet_lbrr(&packet[..len]).unwrap().count() > 0)
+                .then(|| packet[..len].to_vec())
+        })
+        .unwrap();
+    let mut code3 = vec![protected[0] | 0x03, 0x80 | 2, 0];
+    code3.extend_from_slice(&protected[1..]);
+    let info = packet_lbrr(&code3).unwrap();
+    assert_eq!(info.frames(), [0, 1]);
+    assert_eq!(info.count(), 1);
+    assert_eq!(info.recoverable_samples(), 0);
+
+    let mut celt = Encoder::new(
+        SampleRate::Hz48000,
+        Channels::Stereo,
+        Application::RestrictedLowDelay,
+    )
+    .unwrap();
+    let mut packet = [0u8; 1500];
+    let len = celt.encode(&[0i16; 960], &mut packet).unwrap();
+    let info = packet_lbrr(&packet[..len]).unwrap();
+    assert_eq!((info.count(), info.recoverable_samples()), (0, 0));
+}
+
+#[test]
+fn test_multistream_packet_lbrr() {
+    let sr = SampleRate::Hz16000;
+    let channels = 6u8;
+    let (mut encoder, _) = MSEncoder::new_surround(sr, channels, 1, Application::Voip).unwrap();
+    encoder
+        .set_inband_fec_mode(InbandFec::OnNoBitrateAdjust)
+        .unwrap();
+    assert_eq!(
+        encoder.inband_fec_mode().unwrap(),
+        InbandFec::OnNoBitrateAdjust
+    );
+    encoder.set_inband_fec(true).unwrap();
+    assert_eq!(encoder.inband_fec_mode().unwrap(), InbandFec::On);
+    encoder
+        .set_packet_loss_perc(PacketLossPerc::new(25))
+        .unwrap();
+    encoder
+        .set_bitrate(opus_codec::Bitrate::Custom(128_000))
+        .unwrap();
+    let streams = encoder.streams();
+
+    let per_channel: Vec<Vec<i16>> = (0..u64::from(channels))
+        .map(|seed| opus_codec::testsignal::pink_noise(Channels::Mono, 0.3, sr as usize / 2, seed))
+        .collect();
+    let pcm: Vec<i16> = (0..per_channel[0].len())
+        .flat_map(|i| per_channel.iter().map(move |ch| ch[i]))
+        .collect();
+
+    let mut packet = vec![0u8; max_multistream_packet_size(streams, Duration::from_millis(20))];
+    let mut protected = vec![0usize; usize::from(streams)];
+    for chunk in pcm.chunks_exact(320 * usize::from(channels)) {
+        let len = encoder.encode(chunk, 320, &mut packet).unwrap();
+        let report = multistream_packet_lbrr(&packet[..len], streams).unwrap();
+        assert_eq!(report.len(), usize::from(streams));
+        for (total, info) in protected.iter_mut().zip(&report) {
+            *total += info.count();
+        }
+        assert!(multistream_packet_lbrr(&packet[..len], streams + 1).is_err());
+    }
+    // Only SILK-coded streams carry redundancy; the LFE stream is always CELT-only.
+    let (lfe, rest) = protected.split_last().unwrap();
+    assert_eq!(*lfe, 0);
+    assert!(rest.iter().any(|&n| n > 0), "{protected:?}");
+
+    let mut mono = Encoder::new(sr, Channels::Mono, Application::Voip).unwrap();
+    let len = mono.encode(&pcm[..320], &mut packet).unwrap();
+    assert_eq!(
+        multistream_packet_lbrr(&packet[..len], 1).unwrap(),
+        vec![packet_lbrr(&packet[..len]).unwrap()]
+    );
+}
+
+#[test]
+fn test_codecs_in_caller_memory() {
+    let sr = SampleRate::Hz48000;
+    let words = |size: usize| vec![0u64; size.div_ceil(8)];
+    let bytes = |arena: &mut Vec<u64>| unsafe {
+        std::slice::from_raw_parts_mut(arena.as_mut_ptr().cast::<u8>(), arena.len() * 8)
+    };
+
+    let mut enc_arena = words(Encoder::size(Channels::Stereo).unwrap());
+    let mut dec_arena = words(Decoder::size(Channels::Stereo).unwrap());
+    assert!(Encoder::size(Channels::Mono).unwrap() < Encoder::size(Channels::Stereo).unwrap());
+    let mut encoder = unsafe {
+        Encoder::init_in(
+            bytes(&mut enc_arena),
+            sr,
+            Channels::Stereo,
+            Application::Audio,
+        )
+    }
+    .unwrap();
+    let mut decoder =
+        unsafe { Decoder::init_in(bytes(&mut dec_arena), sr, Channels::Stereo) }.unwrap();
+
+    let mut packet = vec![0u8; 1500];
+    let mut pcm = vec![0i16; 2 * 960];
+    for _ in 0..3 {
+        let len = encoder.encode(&[0; 2 * 960], &mut packet).unwrap();
+        assert_eq!(decoder.decode(&packet[..len], &mut pcm, false), Ok(960));
+    }
+    drop((encoder, decoder));
+
+    let mut small = words(Decoder::size(Channels::Stereo).unwrap() / 2);
+    let err = unsafe { Decoder::init_in(bytes(&mut small), sr, Channels::Stereo) };
+    assert_eq!(err.err(), Some(Error::BadArg));
+    let misaligned = &mut bytes(&mut enc_arena)[1..];
+    let err = unsafe { Encoder::init_in(misaligned, sr, Channels::Mono, Application::Voip) };
+    assert_eq!(err.err(), Some(Error::BadArg));
+}
+
+#[test]
+fn test_reinit_reuses_codecs() {
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio).unwrap();
+    let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Stereo).unwrap();
+    encoder.set_dtx(true).unwrap();
+    let mut packet = vec![0u8; 1500];
+    let len = encoder.encode(&[0; 2 * 960], &mut packet).unwrap();
+    decoder
+        .decode(&packet[..len], &mut [0i16; 2 * 960], false)
+        .unwrap();
+
+    encoder
+        .reinit(SampleRate::Hz16000, Channels::Mono, Application::Voip)
+        .unwrap();
+    decoder.reinit(SampleRate::Hz16000, Channels::Mono).unwrap();
+    assert_eq!(
+        (encoder.sample_rate(), encoder.channels()),
+        (SampleRate::Hz16000, Channels::Mono)
+    );
+    assert!(!encoder.dtx().unwrap());
+    assert_eq!(encoder.application().unwrap(), Application::Voip);
+    assert_eq!(encoder.max_bandwidth().unwrap(), Bandwidth::Wideband);
+    assert_eq!(decoder.concealment_stats().decoded, 0);
+    let len = encoder.encode(&[0; 320], &mut packet).unwrap();
+    assert_eq!(
+        decoder.decode(&packet[..len], &mut [0i16; 320], false),
+        Ok(320)
+    );
+
+    // Growing a libopus-allocated mono state to stereo reallocates it.
+    encoder
+        .reinit(SampleRate::Hz48000, Channels::Stereo, Application::Audio)
+        .unwrap();
+    decoder
+        .reinit(SampleRate::Hz48000, Channels::Stereo)
+        .unwrap();
+    let len = encoder.encode(&[0; 2 * 960], &mut packet).unwrap();
+    assert_eq!(
+        decoder.decode(&packet[..len], &mut [0i16; 2 * 960], false),
+        Ok(960)
+    );
+
+    // A caller-provided mono state cannot grow.
+    let mut arena = vec![0u64; Decoder::size(Channels::Mono).unwrap().div_ceil(8)];
+    let buf =
+        unsafe { std::slice::from_raw_parts_mut(arena.as_mut_ptr().cast::<u8>(), arena.len() * 8) };
+    let mut mono = unsafe { Decoder::init_in(buf, SampleRate::Hz48000, Channels::Mono) }.unwrap();
+    assert_eq!(
+        mono.reinit(SampleRate::Hz48000, Channels::Stereo),
+        Err(Error::BadArg)
+    );
+    mono.reinit(SampleRate::Hz8000, Channels::Mono).unwrap();
+    assert_eq!(mono.decode(&[], &mut [0i16; 160], false), Ok(160));
+}
@@ -1,7 +1,8 @@
 use opus_codec::{
-    Application, Bitrate, SampleRate,
-    projection::{ProjectionDecoder, ProjectionEncoder},
+    Application, Bitrate, SampleRate, StereoRenderer, max_multistream_packet_size,
+    projection::{AmbisonicsLayout, ProjectionDecoder, ProjectionEncoder, SessionParams},
 };
+use std::time::Duration;
 
 const FRAME: usize = 960; // 20 ms @ 48 kHz
 const MAPPING_FAMILY: i32 = 3;
@@ -40,7 +41,8 @@ fn projection_roundtrip_basic() {
         *sample = (((i as i32 * 47) % 30_000) - 15_000) as i16;
     }
 
-    let mut packet = vec![0u8; 4000];
+    let mut packet =
+        vec![0u8; max_multistream_packet_size(encoder.streams(), Duration::from_millis(20))];
     let bytes = encoder
         .encode(&pcm, FRAME, &mut packet)
         .expect("encode projection");
@@ -95,3 +97,107 @@ fn projection_demixing_matrix_ctl_consistency() {
     )
     .expect("projection decoder from CTLs");
 }
+
+#[test]
+fn projection_session_params_roundtrip() {
+    let sr = SampleRate::Hz48000;
+    let mut encoder = match ProjectionEncoder::new(sr, CHANNELS, MAPPING_FAMILY, Application::Audio)
+    {
+        Ok(enc) => enc,
+        Err(opus_codec::Error::Unimplemented) => return,
+        Err(err) => panic!("failed to create projection encoder: {err:?}"),
+    };
+
+    let params = encoder.session_params().expect("session params");
+    assert_eq!(params.channels, CHANNELS);
+    assert_eq!(params.matrix.len(), params.expected_matrix_len());
+
+    let blob = params.to_bytes();
+    let parsed = SessionParams::from_bytes(&blob).expect("parse session params");
+    assert_eq!(parsed, params);
+    assert!(SessionParams::from_bytes(&blob[..blob.len() - 1]).is_err());
+
+    let mut decoder = ProjectionDecoder::from_params(sr, &parsed).expect("decoder from params");
+    assert_eq!(decoder.streams(), encoder.streams());
+    decoder.set_gain_db(-3.0).expect("set gain");
+    assert_eq!(decoder.gain().expect("gain"), -768);
+    assert!((decoder.gain_db().expect("gain db") + 3.0).abs() < f32::EPSILON);
+
+    let pcm = vec![0i16; FRAME * CHANNELS as usize];
+    let mut packet =
+        vec![0u8; max_multistream_packet_size(encoder.streams(), Duration::from_millis(20))];
+    let bytes = encoder
+        .encode(&pcm, FRAME, &mut packet)
+        .expect("encode projection");
+    let mut out = vec![0i16; FRAME * CHANNELS as usize];
+    let decoded = decoder
+        .decode(&packet[..bytes], &mut out, FRAME, false)
+        .expect("decode projection");
+    assert_eq!(decoded, FRAME);
+}
+
+#[test]
+fn projection_head_locked_stereo_layout() {
+    assert!(AmbisonicsLayout::from_channels(5).is_err());
+    assert!(AmbisonicsLayout::from_channels(1).is_err());
+    assert!(AmbisonicsLayout::new(6, false).is_err());
+
+    let layout = AmbisonicsLayout::new(1, true).expect("foa + stereo");
+    assert_eq!(layout.channels(), 6);
+    assert_eq!(layout.non_diegetic_channels(), Some((4, 5)));
+    assert_eq!(AmbisonicsLayout::from_channels(6).unwrap(), layout);
+
+    let sr = SampleRate::Hz48000;
+    let mut encoder = match ProjectionEncoder::new_ambisonics(sr, layout, Application::Audio) {
+        Ok(enc) => enc,
+        Err(opus_codec::Error::Unimplemented) => return,
+        Err(err) => panic!("failed to create projection encoder: {err:?}"),
+    };
+    assert!(encoder.has_non_diegetic_stereo());
+    assert!(ProjectionEncoder::new(sr, 7, MAPPING_FAMILY, Application::Audio).is_err());
+
+    let params = encoder.session_params().expect("session params");
+    assert_eq!(params.layout().unwrap(), layout);
+    let decoder = ProjectionDecoder::from_params(sr, &params).expect("decoder from params");
+    assert!(decoder.has_non_diegetic_stereo());
+    assert_eq!(decoder.layout(), Some(layout));
+}
+
+#[test]
+fn projection_decode_rendered_to_stereo() {
+    let sr = SampleRate::Hz48000;
+    let layout = AmbisonicsLayout::new(1, false).expect("foa");
+    let mut encoder = match ProjectionEncoder::new_ambisonics(sr, layout, Application::Audio) {
+        Ok(enc) => enc,
+        Err(opus_codec::Error::Unimplemented) => return,
+        Err(err) => panic!("failed to create projection encoder: {err:?}"),
+    };
+    let params = encoder.session_params().expect("session params");
+    let mut decoder = ProjectionDecoder::from_params(sr, &params).expect("decoder from params");
+
+    let channels = usize::from(layout.channels());
+    let mut pcm = vec![0f32; FRAME * channels];
+    for (i, frame) in pcm.chunks_exact_mut(channels).enumerate() {
+        let s = (i as f32 * 0.05).sin() * 0.3;
+        frame[0] = s; // W
+        frame[1] = s; // Y: source on the left
+    }
+    let mut packet =
+        vec![0u8; max_multistream_packet_size(encoder.streams(), Duration::from_millis(20))];
+    let mut stereo = vec![0f32; FRAME * 2];
+    let mut renderer = StereoRenderer::default();
+    let mut left = 0f32;
+    let mut right = 0f32;
+    for _ in 0..5 {
+        let bytes = encoder
+            .encode_float(&pcm, FRAME, &mut packet)
+            .expect("encode projection");
+        let rendered = decoder
+            .decode_rendered(&packet[..bytes], &mut renderer, &mut stereo, FRAME, false)
+            .expect("decode rendered");
+        assert_eq!(rendered, FRAME);
+        left = stereo.iter().step_by(2).map(|s| s * s).sum();
+        right = stereo.iter().skip(1).step_by(2).map(|s| s * s).sum();
+    }
+    assert!(left > right * 4.0, "left {left} right {right}");
+}
@@ -0,0 +1,277 @@
+use opus_codec::testsignal::{pink_noise, sine};
+use opus_codec::{Application, Channels, Decoder, Encoder, SampleRate};
+use opus_codec::{MAX_PACKET_SIZE, MSDecoder, MSEncoder, Mapping, max_multistream_packet_size};
+use std::time::Duration;
+
+fn snr_db_aligned(orig: &[f32], recon: &[f32]) -> f32 {
+    // Align signals by searching small shift around 0 to account for codec delay
+    let max_shift: isize = 2000; // ~40 ms at 48k covers lookahead
+    let mut best_snr = f32::NEG_INFINITY;
+    for shift in -max_shift..=max_shift {
+        let (start_o, start_r): (usize, usize) = if shift >= 0 {
+            (shift as usize, 0)
+        } else {
+            (0, (-shift) as usize)
+        };
+        if start_o >= orig.len() || start_r >= recon.len() {
+            continue;
+        }
+        let n = orig
+            .len()
+            .saturating_sub(start_o)
+            .min(recon.len().saturating_sub(start_r));
+        if n < 256 {
+            continue;
+        }
+        let (mut sig2, mut err2) = (0.0f64, 0.0f64);
+        for i in 0..n {
+            let s = orig[start_o + i] as f64;
+            let r = recon[start_r + i] as f64;
+            sig2 += s * s;
+            let e = s - r;
+            err2 += e * e;
+        }
+        if err2 <= 1e-12 {
+            return 100.0;
+        }
+        let snr = 10.0 * ((sig2 / err2).log10() as f32);
+        if snr > best_snr {
+            best_snr = snr;
+        }
+    }
+    best_snr
+}
+
+#[test]
+fn test_sine_roundtrip_i16() {
+    let sr = SampleRate::Hz48000;
+    let ch = Channels::Mono;
+    let pcm: Vec<i16> = sine(sr, ch, 440.0, 0.125, sr.as_i32() as usize / 2);
+    let frame = 960usize; // 20ms @ 48kHz
+
+    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
+    enc.set_bitrate(opus_codec::Bitrate::Custom(64_000))
+        .unwrap();
+    let mut dec = Decoder::new(sr, ch).unwrap();
+
+    let mut recon = Vec::<i16>::with_capacity(pcm.len());
+    let mut tmp_pkt = vec![0u8; MAX_PACKET_SIZE];
+    let mut tmp_out = vec![0i16; frame * ch.as_usize()];
+    for chunk in pcm.chunks_exact(frame * ch.as_usize()) {
+        let nbytes = enc.encode(chunk, &mut tmp_pkt).unwrap();
+        assert!(nbytes > 0);
+        let nsamp = dec.decode(&tmp_pkt[..nbytes], &mut tmp_out, false).unwrap();
+        assert_eq!(nsamp, frame);
+        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
+    }
+    // Handle remainder by zero-padding the final frame and truncate to original length
+    let rem = pcm.len() % (frame * ch.as_usize());
+    if rem != 0 {
+        let mut padded = vec![0i16; frame * ch.as_usize()];
+        padded[..rem].copy_from_slice(&pcm[pcm.len() - rem..]);
+        let nbytes = enc.encode(&padded, &mut tmp_pkt).unwrap();
+        assert!(nbytes > 0);
+        let nsamp = dec.decode(&tmp_pkt[..nbytes], &mut tmp_out, false).unwrap();
+        assert_eq!(nsamp, frame);
+        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
+    }
+    recon.truncate(pcm.len());
+
+    // Compute SNR in float domain
+    let orig_f: Vec<f32> = pcm.iter().map(|&x| x as f32).collect();
+    let rec_f: Vec<f32> = recon.iter().map(|&x| x as f32).collect();
+    let snr = snr_db_aligned(&orig_f, &rec_f);
+    assert!(snr > 18.0, "SNR too low: {:.2} dB", snr);
+}
+
+#[test]
+fn test_multistream_basic_stereo_roundtrip() {
+    let sr = SampleRate::Hz48000;
+    let channels = 2u8;
+    // Stereo is typically 1 coupled stream, 0 uncoupled streams, mapping [0,1]
+    let mapping = Mapping {
+        channels,
+        streams: 1,
+        coupled_streams: 1,
+        mapping: &[0, 1],
+    };
+    let mut enc = MSEncoder::new(sr, Application::Audio, mapping).expect("ms encoder");
+    let mapping_dec = Mapping {
+        channels,
+        streams: 1,
+        coupled_streams: 1,
+        mapping: &[0, 1],
+    };
+    let mut dec = MSDecoder::new(sr, mapping_dec).expect("ms decoder");
+
+    // Generate 20 ms stereo sine
+    let frame = 960usize; // per channel
+    let n = frame * channels as usize;
+    let mut pcm = vec![0i16; n];
+    for i in 0..frame {
+        let t = i as f32 / 48000.0;
+        let s0 = (2.0 * std::f32::consts::PI * 440.0 * t).sin();
+        let s1 = (2.0 * std::f32::consts::PI * 660.0 * t).sin();
+        pcm[2 * i] = (s0 * 2000.0) as i16;
+        pcm[2 * i + 1] = (s1 * 2000.0) as i16;
+    }
+
+    let mut pkt = vec![0u8; max_multistream_packet_size(enc.streams(), Duration::from_millis(20))];
+    let nbytes = enc.encode(&pcm, frame, &mut pkt).expect("encode");
+    assert!(nbytes > 0);
+    let mut out = vec![0i16; n];
+    let ns = dec
+        .decode(&pkt[..nbytes], &mut out, frame, false)
+        .expect("decode");
+    assert_eq!(ns, frame);
+}
+
+#[test]
+fn test_sine_roundtrip_f32_encode() {
+    let sr = SampleRate::Hz48000;
+    let ch = Channels::Mono;
+    let pcm: Vec<i16> = sine(sr, ch, 440.0, 0.125, sr.as_i32() as usize / 2);
+    let pcm_f: Vec<f32> = pcm.iter().map(|&x| x as f32 / 32768.0).collect();
+    let frame = 960usize;
+
+    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
+    enc.set_bitrate(opus_codec::Bitrate::Custom(64_000))
+        .unwrap();
+    let mut dec = Decoder::new(sr, ch).unwrap();
+
+    let mut recon = Vec::<f32>::with_capacity(pcm_f.len());
+    let mut tmp_pkt = vec![0u8; MAX_PACKET_SIZE];
+    let mut tmp_out = vec![0f32; frame * ch.as_usize()];
+    for chunk in pcm_f.chunks_exact(frame * ch.as_usize()) {
+        let nbytes = enc.encode_float(chunk, &mut tmp_pkt).unwrap();
+        assert!(nbytes > 0);
+        let nsamp = dec
+            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
+            .unwrap();
+        assert_eq!(nsamp, frame);
+        // Sanity: decoded samples must be finite and within [-1.05, 1.05]
+        for &v in &tmp_out[..frame * ch.as_usize()] {
+            assert!(v.is_finite(), "decoded NaN/Inf encountered");
+            assert!(
+                (-1.05..=1.05).contains(&v),
+                "decoded sample out of range: {}",
+                v
+            );
+        }
+        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
+    }
+    let rem = pcm_f.len() % (frame * ch.as_usize());
+    if rem != 0 {
+        let mut padded = vec![0f32; frame * ch.as_usize()];
+        padded[..rem].copy_from_slice(&pcm_f[pcm_f.len() - rem..]);
+        let nbytes = enc.encode_float(&padded, &mut tmp_pkt).unwrap();
+        assert!(nbytes > 0);
+        let nsamp = dec
+            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
+            .unwrap();
+        assert_eq!(nsamp, frame);
+        for &v in &tmp_out[..frame * ch.as_usize()] {
+            assert!(v.is_finite(), "decoded NaN/Inf encountered");
+            assert!(
+                (-1.05..=1.05).contains(&v),
+                "decoded sample out of range: {}",
+                v
+            );
+        }
+        recon.extend_from_slice(&tmp_out[..frame * ch.as_usize()]);
+    }
+    recon.truncate(pcm_f.len());
+
+    // Compare to original float (normalized)
+    let snr = snr_db_aligned(&pcm_f, &recon);
+    assert!(snr > 18.0, "SNR too low (f32 path): {:.2} dB", snr);
+}
+
+#[test]
+fn test_pink_noise_sanity_f32() {
+    let sr = SampleRate::Hz48000;
+    let ch = Channels::Stereo;
+    let pcm: Vec<i16> = pink_noise(ch, 0.5, sr.as_i32() as usize, 0x5eed);
+    let pcm_f: Vec<f32> = pcm.iter().map(|&x| x as f32 / 32768.0).collect();
+    let frame = (sr.as_i32() as usize / 50) * ch.as_usize(); // 20ms
+
+    let mut enc = Encoder::new(sr, ch, Application::Audio).unwrap();
+    enc.set_bitrate(opus_codec::Bitrate::Custom(96_000))
+        .unwrap();
+    let mut dec = Decoder::new(sr, ch).unwrap();
+    let mut recon = Vec::<f32>::with_capacity(pcm_f.len());
+    let mut tmp_pkt = vec![0u8; MAX_PACKET_SIZE];
+    let mut tmp_out = vec![0f32; frame];
+    for chunk in pcm_f.chunks_exact(frame) {
+        let nbytes = enc.encode_float(chunk, &mut tmp_pkt).unwrap();
+        let nsamp = dec
+            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
+            .unwrap();
+        assert_eq!(nsamp, frame / ch.as_usize());
+        for &v in &tmp_out[..frame] {
+            assert!(v.is_finite(), "decoded NaN/Inf encountered");
+            assert!(
+                (-1.05..=1.05).contains(&v),
+                "decoded sample out of range: {}",
+                v
+            );
+        }
+        recon.extend_from_slice(&tmp_out[..frame]);
+    }
+    let rem = pcm_f.len() % frame;
+    if rem != 0 {
+        let mut padded = vec![0f32; frame];
+        padded[..rem].copy_from_slice(&pcm_f[pcm_f.len() - rem..]);
+        let nbytes = enc.encode_float(&padded, &mut tmp_pkt).unwrap();
+        let nsamp = dec
+            .decode_float(&tmp_pkt[..nbytes], &mut tmp_out, false)
+            .unwrap();
+        assert_eq!(nsamp, frame / ch.as_usize());
+        for &v in &tmp_out[..frame] {
+            assert!(v.is_finite(), "decoded NaN/Inf encountered");
+            assert!(
+                (-1.05..=1.05).contains(&v),
+                "decoded sample out of range: {}",
+                v
+            );
+        }
+        recon.extend_from_slice(&tmp_out[..frame]);
+    }
+    recon.truncate(pcm_f.len());
+
+    // Ensure reconstructed audio is sane
+    let snr = snr_db_aligned(&pcm_f, &recon);
+    assert!(snr > 5.0, "SNR too low on noise: {:.2} dB", snr);
+}
+
+#[cfg(feature = "resample")]
+#[test]
+fn test_resampling_decoder_44k1() {
+    use opus_codec::ResamplingDecoder;
+
+    let sr = SampleRate::Hz48000;
+    let mut encoder = Encoder::new(sr, Channels::Stereo, Application::Audio).unwrap();
+    let pcm = sine::<f32>(sr, Channels::Stereo, 440.0, 0.5, 48_000);
+    let mut decoder = ResamplingDecoder::new(44_100, Channels::Stereo).unwrap();
+    assert!(decoder.latency() > Duration::ZERO);
+
+    let mut packet = [0u8; 1500];
+    let mut out = Vec::new();
+    let mut frame = vec![0i16; 2 * 900];
+    for chunk in pcm.chunks_exact(1920) {
+        let len = encoder.encode_float(chunk, &mut packet).unwrap();
+        assert!(decoder.max_output_len(&packet[..len]).unwrap() <= frame.len());
+        let n = decoder.decode(&packet[..len], &mut frame, false).unwrap();
+        assert!((881..=883).contains(&n) || out.is_empty(), "{n}");
+        out.extend_from_slice(&frame[..2 * n]);
+    }
+    assert!((44_000..=44_100).contains(&(out.len() / 2)));
+    assert!(out.iter().any(|&s| s.unsigned_abs() > 10_000));
+
+    let len = encoder.encode_float(&pcm[..1920], &mut packet).unwrap();
+    assert_eq!(
+        decoder.decode(&packet[..len], &mut frame[..100], false),
+        Err(opus_codec::Error::BufferTooSmall)
+    );
+    assert!(ResamplingDecoder::new(0, Channels::Mono).is_err());
+}